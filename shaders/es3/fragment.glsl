@@ -0,0 +1,146 @@
+#version 300 es
+precision mediump float;
+
+struct SunLight {
+    vec3 direction;
+    vec3 color;
+};
+
+// Data that's the same for every draw in a frame, which the renderer uploads once per frame
+layout(std140) uniform Frame {
+    mat4 view;
+    mat4 projection;
+    SunLight sun;
+    vec3 ambient;
+};
+
+const float minRoughness = 0.04;
+const float pi = 3.1415926535897932384626433832795;
+
+struct MaterialInfo {
+    vec4 baseColor;
+    float metalFactor;
+    float roughness;
+    bool hasBaseColorTexture;
+    // Roughness is multiplied by the green channel, and the metal factor by the blue channel
+    bool hasMetallicRoughnessTexture;
+    bool hasNormalTexture;
+    float normalScale;
+    // Ambient light is darkened by the red channel
+    bool hasOcclusionTexture;
+    float occlusionStrength;
+    vec3 emissive;
+    bool hasEmissiveTexture;
+    bool alphaMask;
+    float alphaCutoff;
+    bool alphaBlend;
+    bool doubleSided;
+};
+
+float fresnel(float metal, float viewDotHalf) {
+    return metal + (1.0 - metal) * pow(clamp(1.0 - viewDotHalf, 0.0, 1.0), 5.0);
+}
+
+float schlickOcclusion(float perceptualRoughness, float lightDotHalf, float normalDotHalf)
+{
+    float k = perceptualRoughness * sqrt(2.0 / pi);
+
+    float l = lightDotHalf / (lightDotHalf * (1.0 - k) + k);
+    float n = normalDotHalf / (normalDotHalf * (1.0 - k) + k);
+    return l * n;
+}
+
+float trowbridgeReitzMicrofacetDistribution(float alphaRoughness, float normalDotHalf)
+{
+    float roughnessSq = alphaRoughness * alphaRoughness;
+    float f = (normalDotHalf * roughnessSq - normalDotHalf) * normalDotHalf + 1.0;
+    return roughnessSq / (pi * f * f);
+}
+
+uniform MaterialInfo material;
+uniform sampler2D baseColorTexture;
+uniform sampler2D metallicRoughnessTexture;
+uniform sampler2D normalTexture;
+uniform sampler2D occlusionTexture;
+uniform sampler2D emissiveTexture;
+
+in vec3 viewPos;
+in vec3 viewNormal;
+// Multiplies the material's base color (for instanced draws)
+in vec4 tint;
+in vec2 uv;
+in vec4 viewTangent;
+
+out vec4 fragColor;
+
+void main() {
+    vec4 baseColor = material.baseColor * tint;
+    if (material.hasBaseColorTexture) {
+        baseColor *= texture(baseColorTexture, uv);
+    }
+    if (material.alphaMask) {
+        if (baseColor.w < material.alphaCutoff) {
+            discard;
+        }
+        baseColor.w = 1.0;
+    } else if (!material.alphaBlend) {
+        baseColor.w = 1.0;
+    }
+
+    vec3 normal = normalize(viewNormal);
+    // Geometry without tangents reads a zero tangent, and isn't normal mapped.
+    vec3 tangent = viewTangent.xyz;
+    bool hasTangent = dot(tangent, tangent) > 0.0;
+    if (material.doubleSided && !gl_FrontFacing) {
+        normal = -normal;
+        tangent = -tangent;
+    }
+    if (material.hasNormalTexture && hasTangent) {
+        tangent = normalize(tangent - normal * dot(normal, tangent));
+        vec3 bitangent = cross(normal, tangent) * viewTangent.w;
+        vec3 mapped = texture(normalTexture, uv).xyz * 2.0 - 1.0;
+        mapped.xy *= material.normalScale;
+        normal = normalize(mat3(tangent, bitangent, normal) * mapped);
+    }
+
+    float metalFactor = material.metalFactor;
+    float roughness = material.roughness;
+    if (material.hasMetallicRoughnessTexture) {
+        vec4 metallicRoughness = texture(metallicRoughnessTexture, uv);
+        roughness *= metallicRoughness.g;
+        metalFactor *= metallicRoughness.b;
+    }
+
+    vec3 sunVect = -(view * vec4(sun.direction, 0.0)).xyz;
+    vec3 viewVect = vec3(0.0, 0.0, 1.0); // Assumes an orthographic projection
+    vec3 halfVect = normalize(sunVect + viewVect);
+
+    float normalDotSun = clamp(dot(normal, sunVect), 0.001, 1.0);
+    float normalDotView = clamp(abs(dot(normal, viewVect)), 0.001, 1.0);
+    float normalDotHalf = clamp(dot(normal, halfVect), 0.0, 1.0);
+    float sunDotHalf = clamp(dot(sunVect, halfVect), 0.0, 1.0);
+    float viewDotHalf = clamp(dot(viewVect, halfVect), 0.0, 1.0);
+
+    float perceptualRoughness = clamp(roughness, minRoughness, 1.0);
+
+    float F = fresnel(metalFactor, viewDotHalf); // TODO: make Fresnel reflection white.
+    float G = schlickOcclusion(perceptualRoughness, sunDotHalf, normalDotHalf);
+    float D = trowbridgeReitzMicrofacetDistribution(perceptualRoughness * perceptualRoughness, normalDotHalf);
+    float specular = (F * G * D) / (4.0 * normalDotSun * normalDotView);
+    float diffuse = 1.0 / pi; // Standard Lambert diffuse term
+    vec3 reflectance = baseColor.xyz * (diffuse + specular);
+
+    vec3 reflectedSun = max(normalDotSun, 0.0) * sun.color * reflectance;
+    vec3 reflectedAmbient = ambient * baseColor.xyz;
+    if (material.hasOcclusionTexture) {
+        float occlusion = texture(occlusionTexture, uv).r;
+        reflectedAmbient *= 1.0 + material.occlusionStrength * (occlusion - 1.0);
+    }
+
+    vec3 emitted = material.emissive;
+    if (material.hasEmissiveTexture) {
+        emitted *= texture(emissiveTexture, uv).rgb;
+    }
+
+    fragColor = vec4(reflectedSun + reflectedAmbient + emitted, baseColor.w);
+}
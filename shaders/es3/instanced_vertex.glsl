@@ -0,0 +1,44 @@
+#version 300 es
+precision mediump float;
+
+struct SunLight {
+    vec3 direction;
+    vec3 color;
+};
+
+// Data that's the same for every draw in a frame, which the renderer uploads once per frame
+layout(std140) uniform Frame {
+    mat4 view;
+    mat4 projection;
+    SunLight sun;
+    vec3 ambient;
+};
+
+in vec3 position;
+in vec3 normal;
+in vec2 texCoord;
+// The xyz tangent, and in w the sign of the bitangent (cross(normal, tangent) * w)
+in vec4 tangent;
+
+// Each instance's model transform, one column at a time
+in vec4 model0;
+in vec4 model1;
+in vec4 model2;
+in vec4 model3;
+in vec4 color;
+
+out vec3 viewPos;
+out vec3 viewNormal;
+out vec4 tint;
+out vec2 uv;
+out vec4 viewTangent;
+
+void main() {
+    mat4 modelView = view * mat4(model0, model1, model2, model3);
+    viewPos = (modelView * vec4(position, 1.0)).xyz;
+    viewNormal = (modelView * vec4(normal, 0.0)).xyz; // Assumes that modelView is orthogonal
+    tint = color;
+    uv = texCoord;
+    viewTangent = vec4((modelView * vec4(tangent.xyz, 0.0)).xyz, tangent.w);
+    gl_Position = projection * vec4(viewPos, 1.0);
+}
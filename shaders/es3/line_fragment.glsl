@@ -0,0 +1,26 @@
+#version 300 es
+precision mediump float;
+
+struct SunLight {
+    vec3 direction;
+    vec3 color;
+};
+
+// Data that's the same for every draw in a frame, which the renderer uploads once per frame
+layout(std140) uniform Frame {
+    mat4 view;
+    mat4 projection;
+    SunLight sun;
+    vec3 ambient;
+};
+
+uniform vec3 color;
+uniform float opacity;
+
+in float vertex_alpha;
+
+out vec4 fragColor;
+
+void main() {
+    fragColor = vec4(color, vertex_alpha * opacity);
+}
@@ -0,0 +1,25 @@
+#version 300 es
+precision mediump float;
+
+struct SunLight {
+    vec3 direction;
+    vec3 color;
+};
+
+// Data that's the same for every draw in a frame, which the renderer uploads once per frame
+layout(std140) uniform Frame {
+    mat4 view;
+    mat4 projection;
+    SunLight sun;
+    vec3 ambient;
+};
+
+in vec3 position;
+in float alpha;
+
+out float vertex_alpha;
+
+void main() {
+    gl_Position = projection * (view * vec4(position, 1.0));
+    vertex_alpha = alpha;
+}
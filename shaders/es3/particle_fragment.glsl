@@ -0,0 +1,29 @@
+#version 300 es
+precision mediump float;
+
+struct SunLight {
+    vec3 direction;
+    vec3 color;
+};
+
+// Data that's the same for every draw in a frame, which the renderer uploads once per frame
+layout(std140) uniform Frame {
+    mat4 view;
+    mat4 projection;
+    SunLight sun;
+    vec3 ambient;
+};
+
+in vec3 particle_color;
+in float particle_alpha;
+
+out vec4 fragColor;
+
+void main() {
+    // Draw each point as a disc that's brightest in the middle.
+    float radius = length(gl_PointCoord - vec2(0.5)) * 2.0;
+    if (radius > 1.0) {
+        discard;
+    }
+    fragColor = vec4(particle_color, particle_alpha * (1.0 - radius * radius));
+}
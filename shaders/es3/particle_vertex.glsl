@@ -0,0 +1,34 @@
+#version 300 es
+precision mediump float;
+
+struct SunLight {
+    vec3 direction;
+    vec3 color;
+};
+
+// Data that's the same for every draw in a frame, which the renderer uploads once per frame
+layout(std140) uniform Frame {
+    mat4 view;
+    mat4 projection;
+    SunLight sun;
+    vec3 ambient;
+};
+
+in vec3 position;
+// Diameter in world units
+in float size;
+in vec3 color;
+in float alpha;
+
+uniform vec2 viewport;
+
+out vec3 particle_color;
+out float particle_alpha;
+
+void main() {
+    gl_Position = projection * (view * vec4(position, 1.0));
+    // Convert the size from world units to pixels.
+    gl_PointSize = max(size * projection[1][1] * viewport.y * 0.5 / gl_Position.w, 1.0);
+    particle_color = color;
+    particle_alpha = alpha;
+}
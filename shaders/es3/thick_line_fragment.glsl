@@ -0,0 +1,36 @@
+#version 300 es
+precision mediump float;
+
+struct SunLight {
+    vec3 direction;
+    vec3 color;
+};
+
+// Data that's the same for every draw in a frame, which the renderer uploads once per frame
+layout(std140) uniform Frame {
+    mat4 view;
+    mat4 projection;
+    SunLight sun;
+    vec3 ambient;
+};
+
+uniform vec3 color;
+uniform float opacity;
+uniform float width;
+// Lengths (in pixels) of each dash and the gap after it; dashes are off if the gap is zero
+uniform float dash_length;
+uniform float dash_gap;
+
+in float vertex_alpha;
+in float edge_distance;
+in float line_distance;
+
+out vec4 fragColor;
+
+void main() {
+    if (dash_gap > 0.0 && mod(line_distance, dash_length + dash_gap) > dash_length) {
+        discard;
+    }
+    float coverage = clamp(width * 0.5 + 0.5 - abs(edge_distance), 0.0, 1.0);
+    fragColor = vec4(color, vertex_alpha * opacity * coverage);
+}
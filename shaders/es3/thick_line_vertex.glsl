@@ -0,0 +1,79 @@
+#version 300 es
+precision mediump float;
+
+struct SunLight {
+    vec3 direction;
+    vec3 color;
+};
+
+// Data that's the same for every draw in a frame, which the renderer uploads once per frame
+layout(std140) uniform Frame {
+    mat4 view;
+    mat4 projection;
+    SunLight sun;
+    vec3 ambient;
+};
+
+// Each point of the line appears twice, once on each side (-1 or 1).
+in vec3 position;
+in vec3 previous;
+in vec3 next;
+in float side;
+in float alpha;
+in float arc_length;
+
+uniform mat4 model;
+uniform vec2 viewport;
+uniform float width;
+
+out float vertex_alpha;
+out float edge_distance;
+out float line_distance;
+
+// Extra width (in pixels) on each side of the line for anti-aliasing
+const float FEATHER = 1.0;
+// Shortest length of a miter, relative to the line's width, before it's cut off
+const float MIN_MITER_SCALE = 0.25;
+
+vec2 to_screen(vec4 clip_position) {
+    return clip_position.xy / clip_position.w * viewport * 0.5;
+}
+
+void main() {
+    mat4 transform = projection * view * model;
+    vec4 clip_position = transform * vec4(position, 1.0);
+    vec2 screen = to_screen(clip_position);
+    vec2 to_previous = screen - to_screen(transform * vec4(previous, 1.0));
+    vec2 to_next = to_screen(transform * vec4(next, 1.0)) - screen;
+
+    // The first and last points repeat themselves as their previous and next points.
+    bool is_start = length(to_previous) < 0.0001;
+    bool is_end = length(to_next) < 0.0001;
+    vec2 direction_in = is_start ? vec2(0.0) : normalize(to_previous);
+    vec2 direction_out = is_end ? direction_in : normalize(to_next);
+    if (is_start) {
+        direction_in = direction_out;
+    }
+
+    vec2 tangent = direction_in + direction_out;
+    tangent = length(tangent) < 0.0001 ? direction_in : normalize(tangent);
+    vec2 normal = vec2(-tangent.y, tangent.x);
+    float half_width = width * 0.5 + FEATHER;
+    float miter_scale = max(dot(normal, vec2(-direction_in.y, direction_in.x)), MIN_MITER_SCALE);
+    vec2 offset = normal * side * half_width / miter_scale;
+    // Square caps
+    if (is_start) {
+        offset -= direction_out * half_width;
+    }
+    if (is_end) {
+        offset += direction_in * half_width;
+    }
+
+    clip_position.xy += offset / (viewport * 0.5) * clip_position.w;
+    gl_Position = clip_position;
+
+    vertex_alpha = alpha;
+    edge_distance = side * half_width;
+    // Orthographic projections have a uniform number of pixels per world unit.
+    line_distance = arc_length * projection[1][1] * viewport.y * 0.5;
+}
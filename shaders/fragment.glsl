@@ -7,6 +7,20 @@ struct MaterialInfo {
     vec4 baseColor;
     float metalFactor;
     float roughness;
+    bool hasBaseColorTexture;
+    // Roughness is multiplied by the green channel, and the metal factor by the blue channel
+    bool hasMetallicRoughnessTexture;
+    bool hasNormalTexture;
+    float normalScale;
+    // Ambient light is darkened by the red channel
+    bool hasOcclusionTexture;
+    float occlusionStrength;
+    vec3 emissive;
+    bool hasEmissiveTexture;
+    bool alphaMask;
+    float alphaCutoff;
+    bool alphaBlend;
+    bool doubleSided;
 };
 
 struct SunLight {
@@ -37,14 +51,59 @@ float trowbridgeReitzMicrofacetDistribution(float alphaRoughness, float normalDo
 uniform mat4 view;
 
 uniform MaterialInfo material;
+uniform sampler2D baseColorTexture;
+uniform sampler2D metallicRoughnessTexture;
+uniform sampler2D normalTexture;
+uniform sampler2D occlusionTexture;
+uniform sampler2D emissiveTexture;
 uniform SunLight sun;
 uniform vec3 ambient;
 
 varying vec3 viewPos;
 varying vec3 viewNormal;
+// Multiplies the material's base color (for instanced draws)
+varying vec4 tint;
+varying vec2 uv;
+varying vec4 viewTangent;
 
 void main() {
+    vec4 baseColor = material.baseColor * tint;
+    if (material.hasBaseColorTexture) {
+        baseColor *= texture2D(baseColorTexture, uv);
+    }
+    if (material.alphaMask) {
+        if (baseColor.w < material.alphaCutoff) {
+            discard;
+        }
+        baseColor.w = 1.0;
+    } else if (!material.alphaBlend) {
+        baseColor.w = 1.0;
+    }
+
     vec3 normal = normalize(viewNormal);
+    // Geometry without tangents reads a zero tangent, and isn't normal mapped.
+    vec3 tangent = viewTangent.xyz;
+    bool hasTangent = dot(tangent, tangent) > 0.0;
+    if (material.doubleSided && !gl_FrontFacing) {
+        normal = -normal;
+        tangent = -tangent;
+    }
+    if (material.hasNormalTexture && hasTangent) {
+        tangent = normalize(tangent - normal * dot(normal, tangent));
+        vec3 bitangent = cross(normal, tangent) * viewTangent.w;
+        vec3 mapped = texture2D(normalTexture, uv).xyz * 2.0 - 1.0;
+        mapped.xy *= material.normalScale;
+        normal = normalize(mat3(tangent, bitangent, normal) * mapped);
+    }
+
+    float metalFactor = material.metalFactor;
+    float roughness = material.roughness;
+    if (material.hasMetallicRoughnessTexture) {
+        vec4 metallicRoughness = texture2D(metallicRoughnessTexture, uv);
+        roughness *= metallicRoughness.g;
+        metalFactor *= metallicRoughness.b;
+    }
+
     vec3 sunVect = -(view * vec4(sun.direction, 0.0)).xyz;
     vec3 viewVect = vec3(0.0, 0.0, 1.0); // Assumes an orthographic projection
     vec3 halfVect = normalize(sunVect + viewVect);
@@ -55,17 +114,26 @@ void main() {
     float sunDotHalf = clamp(dot(sunVect, halfVect), 0.0, 1.0);
     float viewDotHalf = clamp(dot(viewVect, halfVect), 0.0, 1.0);
 
-    float perceptualRoughness = clamp(material.roughness, minRoughness, 1.0);
+    float perceptualRoughness = clamp(roughness, minRoughness, 1.0);
 
-    float F = fresnel(material.metalFactor, viewDotHalf); // TODO: make Fresnel reflection white.
+    float F = fresnel(metalFactor, viewDotHalf); // TODO: make Fresnel reflection white.
     float G = schlickOcclusion(perceptualRoughness, sunDotHalf, normalDotHalf);
     float D = trowbridgeReitzMicrofacetDistribution(perceptualRoughness * perceptualRoughness, normalDotHalf);
     float specular = (F * G * D) / (4.0 * normalDotSun * normalDotView);
     float diffuse = 1.0 / pi; // Standard Lambert diffuse term
-    vec3 reflectance = material.baseColor.xyz * (diffuse + specular);
+    vec3 reflectance = baseColor.xyz * (diffuse + specular);
 
     vec3 reflectedSun = max(normalDotSun, 0.0) * sun.color * reflectance;
-    vec3 reflectedAmbient = ambient * material.baseColor.xyz;
-
-    gl_FragColor = vec4(reflectedSun + reflectedAmbient, material.baseColor.w);
+    vec3 reflectedAmbient = ambient * baseColor.xyz;
+    if (material.hasOcclusionTexture) {
+        float occlusion = texture2D(occlusionTexture, uv).r;
+        reflectedAmbient *= 1.0 + material.occlusionStrength * (occlusion - 1.0);
+    }
+
+    vec3 emitted = material.emissive;
+    if (material.hasEmissiveTexture) {
+        emitted *= texture2D(emissiveTexture, uv).rgb;
+    }
+
+    gl_FragColor = vec4(reflectedSun + reflectedAmbient + emitted, baseColor.w);
 }
@@ -0,0 +1,33 @@
+precision mediump float;
+
+attribute vec3 position;
+attribute vec3 normal;
+attribute vec2 texCoord;
+// The xyz tangent, and in w the sign of the bitangent (cross(normal, tangent) * w)
+attribute vec4 tangent;
+
+// Each instance's model transform, one column at a time
+attribute vec4 model0;
+attribute vec4 model1;
+attribute vec4 model2;
+attribute vec4 model3;
+attribute vec4 color;
+
+uniform mat4 view;
+uniform mat4 projection;
+
+varying vec3 viewPos;
+varying vec3 viewNormal;
+varying vec4 tint;
+varying vec2 uv;
+varying vec4 viewTangent;
+
+void main() {
+    mat4 modelView = view * mat4(model0, model1, model2, model3);
+    viewPos = (modelView * vec4(position, 1.0)).xyz;
+    viewNormal = (modelView * vec4(normal, 0.0)).xyz; // Assumes that modelView is orthogonal
+    tint = color;
+    uv = texCoord;
+    viewTangent = vec4((modelView * vec4(tangent.xyz, 0.0)).xyz, tangent.w);
+    gl_Position = projection * vec4(viewPos, 1.0);
+}
@@ -1,7 +1,10 @@
 precision mediump float;
 
 uniform vec3 color;
+uniform float opacity;
+
+varying float vertex_alpha;
 
 void main() {
-    gl_FragColor = vec4(color, 1.0);
+    gl_FragColor = vec4(color, vertex_alpha * opacity);
 }
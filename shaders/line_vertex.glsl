@@ -1,10 +1,14 @@
 precision mediump float;
 
 attribute vec3 position;
+attribute float alpha;
 
 uniform mat4 view;
 uniform mat4 projection;
 
+varying float vertex_alpha;
+
 void main() {
     gl_Position = projection * (view * vec4(position, 1.0));
+    vertex_alpha = alpha;
 }
@@ -0,0 +1,13 @@
+precision mediump float;
+
+varying vec3 particle_color;
+varying float particle_alpha;
+
+void main() {
+    // Draw each point as a disc that's brightest in the middle.
+    float radius = length(gl_PointCoord - vec2(0.5)) * 2.0;
+    if (radius > 1.0) {
+        discard;
+    }
+    gl_FragColor = vec4(particle_color, particle_alpha * (1.0 - radius * radius));
+}
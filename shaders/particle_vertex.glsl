@@ -0,0 +1,22 @@
+precision mediump float;
+
+attribute vec3 position;
+// Diameter in world units
+attribute float size;
+attribute vec3 color;
+attribute float alpha;
+
+uniform mat4 view;
+uniform mat4 projection;
+uniform vec2 viewport;
+
+varying vec3 particle_color;
+varying float particle_alpha;
+
+void main() {
+    gl_Position = projection * (view * vec4(position, 1.0));
+    // Convert the size from world units to pixels.
+    gl_PointSize = max(size * projection[1][1] * viewport.y * 0.5 / gl_Position.w, 1.0);
+    particle_color = color;
+    particle_alpha = alpha;
+}
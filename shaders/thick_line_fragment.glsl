@@ -0,0 +1,20 @@
+precision mediump float;
+
+uniform vec3 color;
+uniform float opacity;
+uniform float width;
+// Lengths (in pixels) of each dash and the gap after it; dashes are off if the gap is zero
+uniform float dash_length;
+uniform float dash_gap;
+
+varying float vertex_alpha;
+varying float edge_distance;
+varying float line_distance;
+
+void main() {
+    if (dash_gap > 0.0 && mod(line_distance, dash_length + dash_gap) > dash_length) {
+        discard;
+    }
+    float coverage = clamp(width * 0.5 + 0.5 - abs(edge_distance), 0.0, 1.0);
+    gl_FragColor = vec4(color, vertex_alpha * opacity * coverage);
+}
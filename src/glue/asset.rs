@@ -12,6 +12,8 @@ use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::JsFuture;
 use web_sys::Response;
 
+use crate::rendering::mesh::gltf::{external_uris, parse_gltf};
+
 #[derive(Clone, Debug)]
 pub enum FetchErrorType {
     NotFound,
@@ -102,11 +104,30 @@ impl AssetLoaderData {
         }
     }
 
-    fn load(loader: &Rc<RefCell<AssetLoaderData>>, uri: &str) {
+    /// Starts fetching an asset, unless it's already being fetched
+    ///
+    /// If `is_gltf` is set, the files that the asset refers to are fetched along with it.
+    fn load(loader: &Rc<RefCell<AssetLoaderData>>, uri: &str, is_gltf: bool) {
+        {
+            let borrowed = loader.borrow();
+            if borrowed.pending.contains_key(uri) || borrowed.resolved.contains_key(uri) {
+                return;
+            }
+        }
         let saved_uri: Box<str> = uri.into();
         let saved_loader = Rc::clone(&loader);
         let future = async move {
             let result = do_fetch(&saved_uri).await;
+            if let Some(data) = result.as_ref().ok().filter(|_| is_gltf) {
+                // Queue the other files before this one is resolved, so that loading doesn't
+                // look complete in between.
+                if let Ok(gltf) = parse_gltf(data) {
+                    for dependency in external_uris(&gltf) {
+                        let path = relative_asset_path(&saved_uri, dependency);
+                        AssetLoaderData::load(&saved_loader, &path, false);
+                    }
+                }
+            }
             let mut borrowed = saved_loader.borrow_mut();
             borrowed.process_response(&saved_uri, result);
             Ok(JsValue::null())
@@ -132,7 +153,13 @@ impl AssetLoader {
     }
 
     pub fn load(&self, uri: &str) {
-        AssetLoaderData::load(&self.data, uri);
+        AssetLoaderData::load(&self.data, uri, false);
+    }
+
+    /// Loads a `.gltf` or `.glb` file, along with the buffers and images that it refers to
+    #[wasm_bindgen(js_name = "loadGltf")]
+    pub fn load_gltf(&self, uri: &str) {
+        AssetLoaderData::load(&self.data, uri, true);
     }
 
     #[wasm_bindgen(js_name = "then")]
@@ -164,6 +191,20 @@ impl AssetData {
     }
 }
 
+/// Returns the path of a file that another asset refers to with a URI
+///
+/// Relative URIs are relative to the directory of the referring asset. Absolute ones are
+/// returned unchanged.
+pub fn relative_asset_path(base: &str, uri: &str) -> String {
+    if uri.contains("://") || uri.starts_with('/') {
+        return String::from(uri);
+    }
+    match base.rfind('/') {
+        Some(slash) => format!("{}{}", &base[..=slash], uri),
+        None => String::from(uri),
+    }
+}
+
 fn error_to_message(error: JsValue) -> Option<String> {
     match error.dyn_into::<js_sys::Error>() {
         Ok(error) => Some(error.message().into()),
@@ -7,9 +7,25 @@ use wasm_bindgen::prelude::*;
 use web_sys::HtmlCanvasElement;
 
 use crate::glue::callback::Callback;
-use crate::glue::webgl::game_renderer::WebGlRenderer;
+use crate::state::editor::EditorEvent;
 use crate::state::event::{InputEvent, MissileParams};
-use crate::state::{GamePhase, GameState, Turn, TurnState};
+use crate::state::rules::GameRules;
+use crate::state::{GamePhase, GameState, OverlayRenderer, Turn, TurnState};
+use crate::state_renderer::GravityOverlayMode;
+
+/// A renderer that draws onto the page's canvas, whichever rendering context it uses
+pub trait CanvasRenderer {
+    fn canvas(&self) -> &HtmlCanvasElement;
+    /// Returns the size of the drawing buffer in device pixels
+    fn drawing_buffer_size(&self) -> (f32, f32);
+    fn device_pixel_ratio(&self) -> f32;
+    fn gravity_overlay(self: Rc<Self>, mode: GravityOverlayMode) -> Rc<dyn OverlayRenderer>;
+
+    fn aspect_ratio(&self) -> f32 {
+        let (width, height) = self.drawing_buffer_size();
+        width / height
+    }
+}
 
 /// Main interface between JavaScript and Rust
 ///
@@ -17,14 +33,14 @@ use crate::state::{GamePhase, GameState, Turn, TurnState};
 #[wasm_bindgen]
 pub struct GameHandle {
     game_state: Rc<RefCell<GameState>>,
-    renderer: Rc<WebGlRenderer>,
+    renderer: Rc<dyn CanvasRenderer>,
     input_queue: Rc<RefCell<VecDeque<InputEvent>>>,
     interface: Rc<RefCell<Option<GameInterface>>>,
     callbacks: Vec<Box<dyn Callback>>,
 }
 
 impl GameHandle {
-    pub fn new(game_state: Rc<RefCell<GameState>>, renderer: Rc<WebGlRenderer>) -> GameHandle {
+    pub fn new(game_state: Rc<RefCell<GameState>>, renderer: Rc<dyn CanvasRenderer>) -> GameHandle {
         GameHandle {
             game_state,
             renderer,
@@ -46,6 +62,24 @@ impl GameHandle {
         &self.interface
     }
 
+    /// Converts a position on the canvas (in CSS pixels from the top left) to world coordinates
+    fn canvas_to_world(&self, x: f32, y: f32) -> Vector2<f32> {
+        let pixel_ratio = self.renderer.device_pixel_ratio();
+        let (width, height) = self.renderer.drawing_buffer_size();
+        let width = width / pixel_ratio;
+        let height = height / pixel_ratio;
+        let ndc = Vector2::new(2.0 * x / width - 1.0, 1.0 - 2.0 * y / height);
+        let camera = &mut self.game_state.borrow_mut().camera;
+        camera.aspect_ratio = self.renderer.aspect_ratio();
+        camera.unproject(&ndc)
+    }
+
+    fn push_edit(&self, event: EditorEvent) {
+        self.input_queue
+            .borrow_mut()
+            .push_back(InputEvent::Edit(event));
+    }
+
     /// Adds a callback to the internal list so it won't get dropped while the handle exists
     pub fn add_callback(&mut self, callback: Box<dyn Callback>) {
         self.callbacks.push(callback);
@@ -55,7 +89,7 @@ impl GameHandle {
 #[wasm_bindgen]
 impl GameHandle {
     pub fn canvas(&self) -> HtmlCanvasElement {
-        self.renderer.context().canvas().clone()
+        self.renderer.canvas().clone()
     }
 
     #[wasm_bindgen(js_name = isAiming)]
@@ -111,10 +145,10 @@ impl GameHandle {
     #[wasm_bindgen(js_name = onPan)]
     pub fn on_pan(&mut self, x: f32, y: f32) {
         let camera = &mut self.game_state.borrow_mut().camera;
-        camera.aspect_ratio = self.renderer.context().aspect_ratio();
+        camera.aspect_ratio = self.renderer.aspect_ratio();
         let projection = camera.projection();
-        let canvas_height = self.renderer.context().height() as f32
-            / self.renderer.context().device_pixel_ratio() as f32;
+        let canvas_height =
+            self.renderer.drawing_buffer_size().1 / self.renderer.device_pixel_ratio();
         let pan_factor = (projection.top() - projection.bottom()) / canvas_height;
         let delta = Vector2::new(x * pan_factor, y * pan_factor);
         self.input_queue
@@ -135,6 +169,118 @@ impl GameHandle {
             .borrow_mut()
             .push_back(InputEvent::FireMissile(MissileParams { angle, speed }));
     }
+
+    /// Updates the tentative aim shown by the trajectory preview without firing
+    #[wasm_bindgen(js_name = onAim)]
+    pub fn on_aim(&mut self, angle: f32, speed: f32) {
+        self.input_queue
+            .borrow_mut()
+            .push_back(InputEvent::Aim(MissileParams { angle, speed }));
+    }
+
+    /// Switches between practice rules (with aiming aids) and competitive rules
+    #[wasm_bindgen(js_name = setPracticeMode)]
+    pub fn set_practice_mode(&mut self, practice: bool) {
+        self.game_state.borrow_mut().rules = if practice {
+            GameRules::practice()
+        } else {
+            GameRules::competitive()
+        };
+    }
+
+    #[wasm_bindgen(js_name = isPracticeMode)]
+    pub fn is_practice_mode(&self) -> bool {
+        self.game_state.borrow().rules.trajectory_preview
+    }
+
+    /// Shows the gravity overlay in the given mode (`"contours"` or `"vectors"`), or hides it
+    ///
+    /// Returns false if the mode isn't recognized.
+    #[wasm_bindgen(js_name = setGravityOverlay)]
+    pub fn set_gravity_overlay(&mut self, mode: Option<String>) -> bool {
+        let mode = match mode.as_deref() {
+            Some("contours") => Some(GravityOverlayMode::Contours),
+            Some("vectors") => Some(GravityOverlayMode::Vectors),
+            Some(_) => return false,
+            None => None,
+        };
+        self.game_state.borrow_mut().overlay =
+            mode.map(|mode| Rc::clone(&self.renderer).gravity_overlay(mode));
+        true
+    }
+
+    #[wasm_bindgen(js_name = isEditing)]
+    pub fn is_editing(&self) -> bool {
+        self.game_state.borrow().editor().is_some()
+    }
+
+    /// Selects the entity at the given canvas position (in pixels)
+    #[wasm_bindgen(js_name = editorSelect)]
+    pub fn editor_select(&mut self, x: f32, y: f32) {
+        let position = self.canvas_to_world(x, y);
+        self.push_edit(EditorEvent::Select(position));
+    }
+
+    /// Adds a planet at the given canvas position (in pixels)
+    #[wasm_bindgen(js_name = editorAddPlanet)]
+    pub fn editor_add_planet(&mut self, x: f32, y: f32, radius: f32, density: f32) {
+        let position = self.canvas_to_world(x, y);
+        self.push_edit(EditorEvent::AddPlanet {
+            position,
+            radius,
+            density,
+        });
+    }
+
+    /// Moves the selected entity to the given canvas position (in pixels)
+    #[wasm_bindgen(js_name = editorMoveSelected)]
+    pub fn editor_move_selected(&mut self, x: f32, y: f32) {
+        let position = self.canvas_to_world(x, y);
+        self.push_edit(EditorEvent::MoveSelected(position));
+    }
+
+    #[wasm_bindgen(js_name = editorResizeSelected)]
+    pub fn editor_resize_selected(&mut self, radius: f32) {
+        self.push_edit(EditorEvent::ResizeSelected(radius));
+    }
+
+    #[wasm_bindgen(js_name = editorSetDensity)]
+    pub fn editor_set_density(&mut self, density: f32) {
+        self.push_edit(EditorEvent::SetSelectedDensity(density));
+    }
+
+    #[wasm_bindgen(js_name = editorDeleteSelected)]
+    pub fn editor_delete_selected(&mut self) {
+        self.push_edit(EditorEvent::DeleteSelected);
+    }
+
+    /// Places a player's spawn point at the given canvas position (in pixels)
+    #[wasm_bindgen(js_name = editorPlaceSpawn)]
+    pub fn editor_place_spawn(&mut self, player: u32, x: f32, y: f32) {
+        let position = self.canvas_to_world(x, y);
+        self.push_edit(EditorEvent::PlaceSpawn {
+            player: player as usize,
+            position,
+        });
+    }
+
+    /// Returns the selected planet's radius and density
+    #[wasm_bindgen(js_name = editorSelectedPlanet)]
+    pub fn editor_selected_planet(&self) -> Option<Vec<f32>> {
+        let state = self.game_state.borrow();
+        let planet = state.editor()?.selected_planet()?;
+        Some(vec![planet.radius, planet.density])
+    }
+
+    /// Returns the edited map as JSON, or throws an error if the editor isn't active or the map
+    /// isn't valid
+    #[wasm_bindgen(js_name = exportMap)]
+    pub fn export_map(&self) -> Result<String, JsValue> {
+        match self.game_state.borrow().export_map() {
+            Ok(map) => Ok(map.to_json()),
+            Err(err) => Err(JsValue::from_str(&err.to_string())),
+        }
+    }
 }
 
 #[wasm_bindgen(module = "/src/glue/game_interface.js")]
@@ -57,6 +57,12 @@ export class GameInterface {
             this.sendFireEvent();
         });
 
+        for (let input of [this.controls.angleInput, this.controls.powerInput]) {
+            input.addEventListener('input', () => {
+                this.sendAimEvent();
+            });
+        }
+
         let canvas = this.gameHandle.canvas();
         canvas.addEventListener('mousemove', (event) => {
             if (!(event.buttons & PRIMARY_BUTTON)) return;
@@ -69,10 +75,19 @@ export class GameInterface {
         this.gameHandle.onInterfaceReady(this);
     }
 
-    sendFireEvent() {
+    readAim() {
         let angle = parseFloat(this.controls.angleInput.value) * Math.PI / 180.0;
         let power = parseFloat(this.controls.powerInput.value);
+        return [angle, power];
+    }
 
+    sendAimEvent() {
+        let [angle, power] = this.readAim();
+        this.gameHandle.onAim(angle, power);
+    }
+
+    sendFireEvent() {
+        let [angle, power] = this.readAim();
         this.gameHandle.onFire(angle, power);
     }
 
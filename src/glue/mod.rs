@@ -1,6 +1,5 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 
-use std::io::Cursor;
 use std::panic;
 use std::rc::Rc;
 use std::str;
@@ -9,35 +8,75 @@ use log::error;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys;
-use web_sys::WebGlRenderingContext;
 use web_sys::{Element, HtmlCanvasElement};
+use web_sys::{WebGl2RenderingContext, WebGlRenderingContext};
 
-use gltf::Gltf;
-
-use crate::glue::asset::{AssetData, AssetLoader};
+use crate::glue::asset::{relative_asset_path, AssetData, AssetLoader};
 use crate::glue::callback::{AnimationFrameCallback, IntervalCallback};
-use crate::glue::game_handle::GameHandle;
+use crate::glue::game_handle::{CanvasRenderer, GameHandle};
+use crate::glue::shaders::BuiltinShaders;
 use crate::glue::webgl::game_renderer::WebGlRenderer;
-use crate::glue::webgl::shader::{Shader, ShaderProgram};
 use crate::glue::webgl::WebGlContext;
+use crate::glue::webgl2::game_renderer::WebGl2Renderer;
+use crate::glue::webgl2::WebGl2Context;
 use crate::rendering::context::RenderingContext;
-use crate::rendering::line::LineShader;
-use crate::rendering::material::MaterialShader;
-use crate::rendering::mesh::gltf::GltfLoader;
-use crate::rendering::shader::ShaderType;
-use crate::rendering::Rgb;
+use crate::rendering::mesh::gltf::{parse_gltf, GltfCache, GltfLoader};
 use crate::state::constants::TICK_INTERVAL;
-use crate::state::mapgen::{self, MapgenParams};
+use crate::state::editor::MeshEditorFactory;
+use crate::state::mapfile::{MapFile, MapLoader};
+use crate::state::mapgen::{self, ExoticFrequencies, MapgenParams, SphereRendererFactory};
+use crate::state::placement::PlacementConstraints;
+use crate::state::symmetry::MapSymmetry;
 use crate::state::{EntityRenderer, GameState, Player};
-use crate::state_renderer::{GameRenderer, MissileTrailRenderer};
+use crate::state_renderer::{
+    GameRenderer, MissileTrailRenderer, ParticleRenderer, TrajectoryPreviewRenderer,
+};
 
 pub mod asset;
 pub mod callback;
 pub mod game_handle;
+pub mod recording;
+pub mod shaders;
+pub mod software;
 pub mod webgl;
+pub mod webgl2;
 
 const DEFAULT_MAP_WIDTH: f32 = 150.0;
 const DEFAULT_MAP_HEIGHT: f32 = 100.0;
+const DEFAULT_NUM_SYSTEMS: usize = 1;
+const DEFAULT_NUM_ASTEROID_BELTS: usize = 1;
+const DEFAULT_MAP_SYMMETRY: MapSymmetry = MapSymmetry::Rotational;
+const DEFAULT_EDITOR_MAP_NAME: &str = "Untitled";
+const SHIP_MODEL: &str = "assets/meshes/ship.glb";
+
+/// Hand-made maps that are loaded along with the other assets
+pub const CLASSIC_MAPS: &[&str] = &[
+    "maps/duel.json",
+    "maps/binary.json",
+    "maps/gauntlet.json",
+    "maps/triangle.json",
+];
+
+thread_local! {
+    /// The context that the last game rendered with, which later games on the same canvas reuse
+    static SAVED_CONTEXT: RefCell<Option<SavedContext>> = const { RefCell::new(None) };
+}
+
+/// A rendering context, along with the glTF resources that have been uploaded to it
+#[derive(Clone)]
+enum SavedContext {
+    WebGl(Rc<GltfCache<WebGlContext>>),
+    WebGl2(Rc<GltfCache<WebGl2Context>>),
+}
+
+impl SavedContext {
+    fn canvas(&self) -> &HtmlCanvasElement {
+        match self {
+            SavedContext::WebGl(cache) => cache.context().canvas(),
+            SavedContext::WebGl2(cache) => cache.context().canvas(),
+        }
+    }
+}
 
 #[wasm_bindgen(start)]
 pub fn do_init() {
@@ -54,6 +93,22 @@ pub fn get_canvas() -> Option<(Element, HtmlCanvasElement)> {
     Some((canvas_element, canvas))
 }
 
+/// Gets a WebGL 2 context without antialiasing
+///
+/// The WebGL 2 renderer does its own multisampling, and multisampled framebuffers can't be
+/// resolved onto a canvas that is multisampled too.
+pub fn get_webgl2_context(canvas: &HtmlCanvasElement) -> Result<WebGl2RenderingContext, String> {
+    let options = js_sys::Object::new();
+    js_sys::Reflect::set(&options, &"antialias".into(), &JsValue::FALSE)
+        .map_err(|_| String::from("Error setting context options"))?;
+    canvas
+        .get_context_with_context_options("webgl2", &options)
+        .map_err(|_| String::from("Error retrieving context"))?
+        .ok_or_else(|| String::from("Context is null"))?
+        .dyn_into::<WebGl2RenderingContext>()
+        .map_err(|obj| obj.to_string().into())
+}
+
 pub fn get_webgl_context(canvas: &HtmlCanvasElement) -> Result<WebGlRenderingContext, String> {
     canvas
         .get_context("webgl")
@@ -67,49 +122,86 @@ pub fn get_webgl_context(canvas: &HtmlCanvasElement) -> Result<WebGlRenderingCon
 pub fn load_assets() -> AssetLoader {
     let assets = AssetLoader::new();
     assets.load("shaders/vertex.glsl");
+    assets.load("shaders/instanced_vertex.glsl");
     assets.load("shaders/fragment.glsl");
     assets.load("shaders/line_vertex.glsl");
     assets.load("shaders/line_fragment.glsl");
-    assets.load("assets/meshes/ship.glb");
+    assets.load("shaders/thick_line_vertex.glsl");
+    assets.load("shaders/thick_line_fragment.glsl");
+    assets.load("shaders/particle_vertex.glsl");
+    assets.load("shaders/particle_fragment.glsl");
+    assets.load("shaders/es3/vertex.glsl");
+    assets.load("shaders/es3/instanced_vertex.glsl");
+    assets.load("shaders/es3/fragment.glsl");
+    assets.load("shaders/es3/line_vertex.glsl");
+    assets.load("shaders/es3/line_fragment.glsl");
+    assets.load("shaders/es3/thick_line_vertex.glsl");
+    assets.load("shaders/es3/thick_line_fragment.glsl");
+    assets.load("shaders/es3/particle_vertex.glsl");
+    assets.load("shaders/es3/particle_fragment.glsl");
+    assets.load_gltf(SHIP_MODEL);
+    for map in CLASSIC_MAPS {
+        assets.load(map);
+    }
 
     assets
 }
 
-fn compile_shader_from_asset(
-    url: &str,
-    assets: &AssetData,
-    context: &WebGlContext,
-    shader_type: ShaderType,
-) -> Result<Shader, String> {
-    match assets.get(url) {
-        Ok(ref data) => {
-            let text = str::from_utf8(data).unwrap_or("<UTF-8 decoding error>");
-            context
-                .compile_shader(shader_type, text)
-                .map_err(|e| format!("{}: {}", url, e))
-        }
-        Err(error) => Err(format!("Unable to load asset {}: {}", url, error)),
-    }
+/// Returns the source of a shader asset
+fn shader_asset<'a>(assets: &'a AssetData, url: &str) -> Result<&'a str, String> {
+    let data = assets
+        .get(url)
+        .map_err(|e| format!("Unable to load asset {}: {}", url, e))?;
+    str::from_utf8(data).map_err(|_| format!("Shader {} is not valid UTF-8", url))
 }
 
-fn load_program_from_assets(
-    vert_url: &str,
-    frag_url: &str,
+/// Loads the built-in shaders from the shader assets
+fn load_shaders_from_assets<Context: RenderingContext>(
     assets: &AssetData,
-    context: &WebGlContext,
-) -> Result<ShaderProgram, String> {
-    let vertex_shader = compile_shader_from_asset(vert_url, assets, context, ShaderType::Vertex)?;
-    let fragment_shader =
-        compile_shader_from_asset(frag_url, assets, context, ShaderType::Fragment)?;
-    context
-        .link_shader_program([vertex_shader, fragment_shader].iter())
-        .map_err(|e| format!("{}", e))
+    context: &Context,
+) -> Result<BuiltinShaders<Context>, String> {
+    BuiltinShaders::load_with(context, |url| shader_asset(assets, url))
 }
 
+/// Returns the URLs of the hand-made maps that can be passed to `startGameWithMap`
+#[wasm_bindgen(js_name = "classicMaps")]
+pub fn classic_maps() -> Box<[JsValue]> {
+    CLASSIC_MAPS
+        .iter()
+        .map(|url| JsValue::from_str(url))
+        .collect()
+}
+
+/// Starts a game on a randomly generated map
 #[wasm_bindgen(js_name = "startGame")]
 pub fn start_game(assets: &AssetData) -> JsValue {
+    start_game_from(assets, GameSource::Generated)
+}
+
+/// Starts a game on a map loaded from a map file
+#[wasm_bindgen(js_name = "startGameWithMap")]
+pub fn start_game_with_map(assets: &AssetData, map_url: &str) -> JsValue {
+    start_game_from(assets, GameSource::MapFile(map_url))
+}
+
+/// Opens the map editor, starting from a map file or from an empty map
+#[wasm_bindgen(js_name = "startEditor")]
+pub fn start_editor(assets: &AssetData, map_url: Option<String>) -> JsValue {
+    start_game_from(assets, GameSource::Editor(map_url.as_deref()))
+}
+
+/// Where the initial game state comes from
+#[derive(Clone, Copy, Debug)]
+enum GameSource<'a> {
+    Generated,
+    MapFile(&'a str),
+    /// The map editor, with an optional map file to edit
+    Editor(Option<&'a str>),
+}
+
+fn start_game_from(assets: &AssetData, source: GameSource) -> JsValue {
     panic::set_hook(Box::new(console_error_panic_hook::hook)); // TODO: make this happen earlier.
-    match try_start_game(assets) {
+    match try_start_game(assets, source) {
         Ok(handle) => JsValue::from(handle),
         Err(err) => {
             error!("Error starting game: {}", err);
@@ -118,86 +210,196 @@ pub fn start_game(assets: &AssetData) -> JsValue {
     }
 }
 
-fn try_start_game(assets: &AssetData) -> Result<GameHandle, String> {
+fn load_map_file(assets: &AssetData, url: &str) -> Result<MapFile, String> {
+    let data = assets
+        .get(url)
+        .map_err(|e| format!("Unable to load map {}: {}", url, e))?;
+    let text = str::from_utf8(data).map_err(|_| format!("Map {} is not valid UTF-8", url))?;
+    MapFile::from_json(text).map_err(|e| format!("{}: {}", url, e))
+}
+
+fn try_start_game(assets: &AssetData, source: GameSource) -> Result<GameHandle, String> {
     let (canvas_element, canvas) =
         get_canvas().ok_or_else(|| String::from("Unable to find canvas"))?;
-    let gl_context = get_webgl_context(&canvas)?;
-    let context = Rc::new(WebGlContext::new(canvas_element, canvas, gl_context));
-
-    let mat_program = load_program_from_assets(
-        "shaders/vertex.glsl",
-        "shaders/fragment.glsl",
-        assets,
-        &context,
-    )?;
-    let line_program = load_program_from_assets(
-        "shaders/line_vertex.glsl",
-        "shaders/line_fragment.glsl",
-        assets,
-        &context,
-    )?;
-    let mat_shader = MaterialShader::new(mat_program).map_err(|e| format!("{:?}", e))?;
-    let line_shader = LineShader::new(line_program).map_err(|e| format!("{:?}", e))?;
-
-    let renderer = Rc::new(WebGlRenderer::new(
-        Rc::clone(&context),
-        mat_shader,
-        line_shader,
-    ));
-    renderer.configure_context();
+    let saved = SAVED_CONTEXT.with(|saved| saved.borrow().clone());
+    let saved = match saved.filter(|saved| *saved.canvas() == canvas) {
+        Some(saved) => saved,
+        None => {
+            let saved = make_context(canvas_element, canvas)?;
+            SAVED_CONTEXT.with(|cell| cell.replace(Some(saved.clone())));
+            saved
+        }
+    };
+    match saved {
+        SavedContext::WebGl2(gltf_cache) => {
+            let context = Rc::clone(gltf_cache.context());
+            let shaders =
+                BuiltinShaders::load_es3_with(context.as_ref(), |url| shader_asset(assets, url))?;
+            let renderer = Rc::new(WebGl2Renderer::new(context, shaders)?);
+            renderer.configure_context();
+            start_game_with_renderer(assets, source, gltf_cache, renderer)
+        }
+        SavedContext::WebGl(gltf_cache) => {
+            let context = Rc::clone(gltf_cache.context());
+            let shaders = load_shaders_from_assets(assets, context.as_ref())?;
+            let renderer = Rc::new(WebGlRenderer::new(context, shaders));
+            renderer.configure_context();
+            start_game_with_renderer(assets, source, gltf_cache, renderer)
+        }
+    }
+}
 
-    let renderer_clone = Rc::clone(&renderer) as Rc<dyn GameRenderer<Context = WebGlContext>>;
-    let make_missile_trail = move || {
-        Some(Rc::new(
-            MissileTrailRenderer::new(Rc::clone(&renderer_clone), Rgb::new(1.0, 1.0, 1.0)).ok()?,
-        ) as Rc<dyn EntityRenderer>)
+fn make_context(
+    canvas_element: Element,
+    canvas: HtmlCanvasElement,
+) -> Result<SavedContext, String> {
+    // Once a canvas has a WebGL 2 context, it can't provide a WebGL 1 context, so there's no
+    // falling back after this succeeds.
+    match get_webgl2_context(&canvas) {
+        Ok(gl_context) => Ok(SavedContext::WebGl2(Rc::new(GltfCache::new(Rc::new(
+            WebGl2Context::new(canvas_element, canvas, gl_context),
+        ))))),
+        Err(err) => {
+            log::info!("WebGL 2 is unavailable ({}); falling back to WebGL 1", err);
+            let gl_context = get_webgl_context(&canvas)?;
+            Ok(SavedContext::WebGl(Rc::new(GltfCache::new(Rc::new(
+                WebGlContext::new(canvas_element, canvas, gl_context),
+            )))))
+        }
+    }
+}
+
+fn start_game_with_renderer<Context, R>(
+    assets: &AssetData,
+    source: GameSource,
+    gltf_cache: Rc<GltfCache<Context>>,
+    renderer: Rc<R>,
+) -> Result<GameHandle, String>
+where
+    Context: RenderingContext + 'static,
+    R: GameRenderer<Context = Context> + CanvasRenderer + 'static,
+{
+    let renderer_clone = Rc::clone(&renderer) as Rc<dyn GameRenderer<Context = Context>>;
+    let make_missile_trail = move |player: &Player| {
+        Some(
+            Rc::new(MissileTrailRenderer::new(Rc::clone(&renderer_clone), player.color).ok()?)
+                as Rc<dyn EntityRenderer>,
+        )
     };
     let mut state = GameState::new(Box::new(make_missile_trail));
+    state.preview_renderer = Some(Rc::new(TrajectoryPreviewRenderer::new(
+        Rc::clone(&renderer) as Rc<dyn GameRenderer<Context = Context>>,
+    )));
+    let particle_renderer =
+        ParticleRenderer::new(Rc::clone(&renderer) as Rc<dyn GameRenderer<Context = Context>>)
+            .map_err(|_| String::from("Unable to create particle renderer"))?;
+    state.particle_renderer = Some(Rc::new(particle_renderer));
 
     let raw_gltf = assets
-        .get("assets/meshes/ship.glb")
+        .get(SHIP_MODEL)
         .map_err(|_| String::from("Unable to retrieve mesh asset"))?;
-    let gltf = Gltf::from_reader(Cursor::new(raw_gltf)).map_err(|e| format!("{:?}", e))?;
-    let mut loader = GltfLoader::new(Rc::clone(renderer.context()), &gltf);
-    let first_mesh = gltf
-        .meshes()
-        .next()
-        .ok_or_else(|| String::from("Unable to find mesh"))?;
-    let ship_mesh = Rc::new(
+    let gltf = parse_gltf(raw_gltf).map_err(|e| format!("{:?}", e))?;
+    let resolve = |uri: &str| {
+        assets
+            .get(&relative_asset_path(SHIP_MODEL, uri))
+            .ok()
+            .map(<[u8]>::to_vec)
+    };
+    let mut loader = GltfLoader::with_cache(gltf_cache, SHIP_MODEL, &gltf, Some(&resolve));
+    let ship_scene = Rc::new(
         loader
-            .load_mesh(&first_mesh)
-            .map_err(|e| format!("Unable to load mesh: {:?}", e))?,
+            .load_default_scene()
+            .ok_or_else(|| String::from("Unable to find scene"))?
+            .map_err(|e| format!("Unable to load scene: {:?}", e))?,
     );
 
     let renderer_clone = Rc::clone(&renderer);
-    let ship_mesh_clone = Rc::clone(&ship_mesh);
+    let ship_scene_clone = Rc::clone(&ship_scene);
     let make_ship_renderer = move |player: &Player| {
-        Ok(mapgen::make_ship_mesh_renderer(
-            Rc::clone(&renderer_clone) as Rc<dyn GameRenderer<Context = WebGlContext>>,
-            &ship_mesh_clone,
+        Ok(mapgen::make_ship_renderer(
+            Rc::clone(&renderer_clone) as Rc<dyn GameRenderer<Context = Context>>,
+            &ship_scene_clone,
             &player.color,
         ))
     };
-    let mut mapgen_params = MapgenParams {
-        game_state: &mut state,
-        width: DEFAULT_MAP_WIDTH,
-        height: DEFAULT_MAP_HEIGHT,
-        num_players: 2,
-        game_renderer: Rc::clone(&renderer) as Rc<dyn GameRenderer<Context = WebGlContext>>,
-        ship_mesh: &ship_mesh,
-        make_ship_renderer: Box::new(make_ship_renderer),
-    };
-    mapgen_params
-        .generate_map()
-        .map_err(|e| format!("Unable to create map: {:?}", e))?;
-    state.start_game();
+    let game_renderer = Rc::clone(&renderer) as Rc<dyn GameRenderer<Context = Context>>;
+    match source {
+        GameSource::MapFile(url) => {
+            let map = load_map_file(assets, url)?;
+            let mut loader = MapLoader {
+                game_state: &mut state,
+                game_renderer,
+                ship_scene: &ship_scene,
+                make_ship_renderer: Box::new(make_ship_renderer),
+            };
+            loader
+                .load_map(&map)
+                .map_err(|e| format!("Unable to load map {}: {}", url, e))?;
+            state.start_game();
+        }
+        GameSource::Generated => {
+            let mut mapgen_params = MapgenParams {
+                game_state: &mut state,
+                width: DEFAULT_MAP_WIDTH,
+                height: DEFAULT_MAP_HEIGHT,
+                num_players: 2,
+                num_systems: DEFAULT_NUM_SYSTEMS,
+                exotic_frequencies: ExoticFrequencies::default(),
+                num_asteroid_belts: DEFAULT_NUM_ASTEROID_BELTS,
+                placement: PlacementConstraints::default(),
+                symmetry: DEFAULT_MAP_SYMMETRY,
+                validate_fairness: true,
+                game_renderer,
+                ship_scene: &ship_scene,
+                make_ship_renderer: Box::new(make_ship_renderer),
+            };
+            mapgen_params
+                .generate_map()
+                .map_err(|e| format!("Unable to create map: {:?}", e))?;
+            state.start_game();
+        }
+        GameSource::Editor(url) => {
+            let map = match url {
+                Some(url) => load_map_file(assets, url)?,
+                None => MapFile {
+                    name: String::from(DEFAULT_EDITOR_MAP_NAME),
+                    width: DEFAULT_MAP_WIDTH,
+                    height: DEFAULT_MAP_HEIGHT,
+                    planets: Vec::new(),
+                    spawns: Vec::new(),
+                    lighting: None,
+                },
+            };
+            let planet_renderers = SphereRendererFactory::new(&game_renderer)
+                .map_err(|_| String::from("Unable to create planet renderer"))?;
+            let factory = MeshEditorFactory {
+                game_renderer,
+                planet_renderers,
+                ship_scene: Rc::clone(&ship_scene),
+                make_ship_renderer: Box::new(make_ship_renderer),
+            };
+            state
+                .start_editor(&map, Box::new(factory))
+                .map_err(|e| format!("Unable to start map editor: {}", e))?;
+        }
+    }
 
-    let mut game_handle = GameHandle::new(Rc::new(RefCell::new(state)), Rc::clone(&renderer));
+    let mut game_handle =
+        GameHandle::new(Rc::new(RefCell::new(state)), Rc::clone(&renderer) as Rc<_>);
 
     let render_state = Rc::clone(game_handle.game_state());
-    let render_frame = move |_milliseconds: f64| {
+    // The particle tick count at the last frame, and when that tick was first drawn
+    let last_tick = Cell::new((0, 0.0));
+    let render_frame = move |milliseconds: f64| {
+        let mut state = render_state.borrow_mut();
+        let ticks = state.particles.ticks();
+        if ticks != last_tick.get().0 {
+            last_tick.set((ticks, milliseconds));
+        }
+        let tick_elapsed = (milliseconds - last_tick.get().1) as f32 / (TICK_INTERVAL * 1000.0);
+        state.particles.interpolation = tick_elapsed.min(1.0);
         renderer
-            .render(&mut render_state.borrow_mut())
+            .render(&mut state)
             .unwrap_or_else(|e| error!("{}", e.to_string()));
     };
 
@@ -212,7 +414,10 @@ fn try_start_game(assets: &AssetData) -> Result<GameHandle, String> {
                 error!("{}", e.to_string());
             }
         }
+        update_state.borrow_mut().update_bodies();
+        update_state.borrow_mut().update_particles();
         update_state.borrow_mut().update_missiles();
+        update_state.borrow_mut().update_trajectory_preview();
         if let Some(ref interface) = *update_interface.borrow() {
             if let Err(err) = interface.update_ui() {
                 log::error!("UI update error: {:?}", err);
@@ -0,0 +1,86 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::glue::recording::{Command, Recorder, RecordingContext};
+use crate::rendering;
+use crate::rendering::buffer::VertexAttributeBinding;
+
+#[derive(Debug)]
+pub struct AttributeBuffer {
+    id: usize,
+    recorder: Rc<RefCell<Recorder>>,
+}
+
+impl AttributeBuffer {
+    pub fn new(recorder: Rc<RefCell<Recorder>>) -> Self {
+        let id = {
+            let mut log = recorder.borrow_mut();
+            let id = log.next_id();
+            log.record(Command::CreateAttributeBuffer(id));
+            id
+        };
+        AttributeBuffer { id, recorder }
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+}
+
+impl rendering::buffer::Buffer for AttributeBuffer {
+    type RenderingContext = RecordingContext;
+
+    fn set_data(&self, data: &[u8]) {
+        self.recorder.borrow_mut().record(Command::SetBufferData {
+            buffer: self.id,
+            data: data.to_vec(),
+        });
+    }
+}
+
+impl rendering::buffer::AttributeBuffer for AttributeBuffer {
+    fn bind_to_attribute(&self, index: usize, binding: &VertexAttributeBinding) {
+        let mut recorder = self.recorder.borrow_mut();
+        recorder.attributes.insert(index, (self.id, *binding));
+        recorder.record(Command::BindAttribute {
+            buffer: self.id,
+            index,
+            binding: *binding,
+        });
+    }
+}
+
+#[derive(Debug)]
+pub struct IndexBuffer {
+    id: usize,
+    recorder: Rc<RefCell<Recorder>>,
+}
+
+impl IndexBuffer {
+    pub fn new(recorder: Rc<RefCell<Recorder>>) -> Self {
+        let id = {
+            let mut log = recorder.borrow_mut();
+            let id = log.next_id();
+            log.record(Command::CreateIndexBuffer(id));
+            id
+        };
+        IndexBuffer { id, recorder }
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+}
+
+impl rendering::buffer::Buffer for IndexBuffer {
+    type RenderingContext = RecordingContext;
+
+    fn set_data(&self, data: &[u8]) {
+        self.recorder.borrow_mut().record(Command::SetBufferData {
+            buffer: self.id,
+            data: data.to_vec(),
+        });
+    }
+}
+
+impl rendering::buffer::IndexBuffer for IndexBuffer {}
@@ -0,0 +1,70 @@
+use std::rc::Rc;
+
+use crate::glue::recording::RecordingContext;
+use crate::glue::shaders::BuiltinShaders;
+use crate::rendering::line::{LineShader, ThickLineShader};
+use crate::rendering::material::MaterialShader;
+use crate::rendering::particle::ParticleShader;
+use crate::rendering::queue::RenderQueue;
+use crate::state::GameState;
+use crate::state_renderer::GameRenderer;
+
+/// Records the commands that rendering the game would issue
+#[derive(Debug)]
+pub struct RecordingRenderer {
+    context: Rc<RecordingContext>,
+    shaders: BuiltinShaders<RecordingContext>,
+    render_queue: RenderQueue<RecordingContext>,
+}
+
+impl RecordingRenderer {
+    /// Creates a renderer using the game's built-in shaders
+    pub fn new(context: Rc<RecordingContext>) -> Result<RecordingRenderer, String> {
+        let shaders = BuiltinShaders::load(context.as_ref())?;
+        Ok(RecordingRenderer {
+            context,
+            shaders,
+            render_queue: RenderQueue::new(),
+        })
+    }
+
+    pub fn context(&self) -> &Rc<RecordingContext> {
+        &self.context
+    }
+}
+
+impl GameRenderer for RecordingRenderer {
+    type Context = RecordingContext;
+
+    fn context(&self) -> &Self::Context {
+        &self.context
+    }
+
+    fn material_shader(&self) -> &MaterialShader<RecordingContext> {
+        &self.shaders.material
+    }
+
+    fn instanced_material_shader(&self) -> Option<&MaterialShader<RecordingContext>> {
+        self.shaders.instanced_material.as_ref()
+    }
+
+    fn line_shader(&self) -> &LineShader<RecordingContext> {
+        &self.shaders.line
+    }
+
+    fn thick_line_shader(&self) -> &ThickLineShader<RecordingContext> {
+        &self.shaders.thick_line
+    }
+
+    fn particle_shader(&self) -> &ParticleShader<RecordingContext> {
+        &self.shaders.particle
+    }
+
+    fn render_queue(&self) -> &RenderQueue<RecordingContext> {
+        &self.render_queue
+    }
+
+    fn begin_frame(&self, state: &mut GameState) {
+        state.camera.aspect_ratio = self.context.aspect_ratio();
+    }
+}
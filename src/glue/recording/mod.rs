@@ -0,0 +1,257 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+use crate::glue::recording::shader::{RecordingBoundShader, ShaderProgram};
+use crate::rendering::buffer::{ElementBinding, VertexAttributeBinding};
+use crate::rendering::context::RenderingContext;
+use crate::rendering::glsl::UniformValue;
+use crate::rendering::shader::{PrimitiveType, ShaderBindError, ShaderType};
+use crate::rendering::texture::{Sampler, TextureFormat};
+
+pub mod buffer;
+pub mod game_renderer;
+pub mod shader;
+pub mod texture;
+
+#[cfg(test)]
+mod tests;
+
+/// A call made on a recording context or on an object that it created
+///
+/// Buffers, textures, shaders and programs are referred to by the id that they were given when
+/// they were created. Ids are unique within a context.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    CreateAttributeBuffer(usize),
+    CreateIndexBuffer(usize),
+    CreateTexture(usize),
+    SetBufferData {
+        buffer: usize,
+        data: Vec<u8>,
+    },
+    BindAttribute {
+        buffer: usize,
+        index: usize,
+        binding: VertexAttributeBinding,
+    },
+    DisableAttribute(usize),
+    SetTextureData {
+        texture: usize,
+        format: TextureFormat,
+        width: u32,
+        height: u32,
+    },
+    SetTextureSubData {
+        texture: usize,
+        x: u32,
+        y: u32,
+        format: TextureFormat,
+        width: u32,
+        height: u32,
+    },
+    GenerateMipmaps(usize),
+    SetSampler {
+        texture: usize,
+        sampler: Sampler,
+    },
+    CompileShader {
+        shader: usize,
+        shader_type: ShaderType,
+    },
+    LinkProgram {
+        program: usize,
+        shaders: Vec<usize>,
+    },
+    BindShader(usize),
+    BindTexture {
+        unit: usize,
+        texture: usize,
+    },
+    SetUniform {
+        program: usize,
+        name: String,
+        value: UniformValue,
+    },
+    SetDoubleSided(bool),
+    Draw(DrawCall),
+}
+
+/// A draw call, along with the state that it drew with
+#[derive(Clone, Debug, PartialEq)]
+pub struct DrawCall {
+    pub program: usize,
+    pub primitive: PrimitiveType,
+    /// The number of vertices drawn
+    pub count: usize,
+    /// The number of instances drawn, which is 1 for draws that aren't instanced
+    pub instance_count: usize,
+    /// The index buffer's id and binding, for indexed draws
+    pub indices: Option<(usize, ElementBinding)>,
+    /// The buffer id and binding of each of the program's attributes that was bound
+    pub attributes: BTreeMap<String, (usize, VertexAttributeBinding)>,
+    /// The value of each of the program's uniforms
+    pub uniforms: BTreeMap<String, UniformValue>,
+    /// The id of the texture bound to each texture unit
+    pub textures: BTreeMap<usize, usize>,
+    /// Whether triangles that face away from the camera were drawn
+    pub double_sided: bool,
+}
+
+impl DrawCall {
+    pub fn attribute(&self, name: &str) -> Option<&(usize, VertexAttributeBinding)> {
+        self.attributes.get(name)
+    }
+
+    pub fn uniform(&self, name: &str) -> Option<&UniformValue> {
+        self.uniforms.get(name)
+    }
+
+    /// Returns the id of the texture that a sampler uniform read from
+    pub fn texture(&self, sampler: &str) -> Option<usize> {
+        match self.uniform(sampler)? {
+            UniformValue::Int(unit) => self.textures.get(&(*unit as usize)).copied(),
+            _ => None,
+        }
+    }
+}
+
+/// The command log and attribute and texture bindings shared by a context and the objects that it creates
+#[derive(Debug, Default)]
+pub struct Recorder {
+    next_id: usize,
+    commands: Vec<Command>,
+    attributes: HashMap<usize, (usize, VertexAttributeBinding)>,
+    textures: HashMap<usize, usize>,
+}
+
+impl Recorder {
+    fn next_id(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn record(&mut self, command: Command) {
+        self.commands.push(command);
+    }
+}
+
+/// A rendering context that records what is done with it instead of drawing
+///
+/// Every buffer upload, shader bind, uniform set and draw call is appended to a command log,
+/// which can be inspected to check what a renderer did. Shader sources are read for their
+/// attribute and uniform declarations, so any of the game's shader programs can be linked.
+#[derive(Debug)]
+pub struct RecordingContext {
+    recorder: Rc<RefCell<Recorder>>,
+    viewport_size: (u32, u32),
+    pixel_ratio: f32,
+}
+
+impl RecordingContext {
+    pub fn new(width: u32, height: u32) -> RecordingContext {
+        RecordingContext {
+            recorder: Rc::new(RefCell::new(Recorder::default())),
+            viewport_size: (width, height),
+            pixel_ratio: 1.0,
+        }
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        let (width, height) = self.viewport_size;
+        width as f32 / height.max(1) as f32
+    }
+
+    /// Sets the number of pixels per CSS pixel, which scales line widths
+    pub fn set_pixel_ratio(&mut self, pixel_ratio: f32) {
+        self.pixel_ratio = pixel_ratio;
+    }
+
+    /// Returns a copy of every command recorded since the log was last cleared
+    pub fn commands(&self) -> Vec<Command> {
+        self.recorder.borrow().commands.clone()
+    }
+
+    /// Returns the draw calls recorded since the log was last cleared
+    pub fn draw_calls(&self) -> Vec<DrawCall> {
+        self.recorder
+            .borrow()
+            .commands
+            .iter()
+            .filter_map(|command| match command {
+                Command::Draw(draw) => Some(draw.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Empties the command log
+    ///
+    /// Attribute bindings and uniform values are kept, as they would be on a GPU.
+    pub fn clear_commands(&self) {
+        self.recorder.borrow_mut().commands.clear();
+    }
+}
+
+impl RenderingContext for RecordingContext {
+    type AttributeBuffer = buffer::AttributeBuffer;
+    type IndexBuffer = buffer::IndexBuffer;
+    type Shader = shader::Shader;
+    type ShaderCreationError = shader::ShaderCreationError;
+    type ShaderProgram = ShaderProgram;
+    type ShaderLinkError = shader::ShaderLinkError;
+    type BoundShader = RecordingBoundShader;
+    type Texture = texture::Texture;
+
+    fn make_attribute_buffer(&self) -> Result<Self::AttributeBuffer, ()> {
+        Ok(Self::AttributeBuffer::new(Rc::clone(&self.recorder)))
+    }
+
+    fn make_index_buffer(&self) -> Result<Self::IndexBuffer, ()> {
+        Ok(Self::IndexBuffer::new(Rc::clone(&self.recorder)))
+    }
+
+    fn make_texture(&self) -> Result<Self::Texture, ()> {
+        Ok(Self::Texture::new(Rc::clone(&self.recorder)))
+    }
+
+    fn compile_shader(
+        &self,
+        shader_type: ShaderType,
+        source: &str,
+    ) -> Result<Self::Shader, Self::ShaderCreationError> {
+        Self::Shader::compile(Rc::clone(&self.recorder), shader_type, source)
+    }
+
+    fn link_shader_program<'a, T: Iterator<Item = &'a Self::Shader>>(
+        &self,
+        shaders: T,
+    ) -> Result<Self::ShaderProgram, Self::ShaderLinkError> {
+        Self::ShaderProgram::link(Rc::clone(&self.recorder), shaders)
+    }
+
+    fn viewport_size(&self) -> (u32, u32) {
+        self.viewport_size
+    }
+
+    fn pixel_ratio(&self) -> f32 {
+        self.pixel_ratio
+    }
+
+    fn supports_instancing(&self) -> bool {
+        true
+    }
+
+    fn bind_shader(
+        &self,
+        shader: Rc<Self::ShaderProgram>,
+    ) -> Result<Self::BoundShader, ShaderBindError> {
+        if !shader.is_same_context(&self.recorder) {
+            return Err(ShaderBindError::InvalidContextForShader);
+        }
+        self.recorder
+            .borrow_mut()
+            .record(Command::BindShader(shader.id()));
+        Ok(RecordingBoundShader::new(Rc::clone(&self.recorder), shader))
+    }
+}
@@ -0,0 +1,339 @@
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Display;
+use std::rc::Rc;
+
+use nalgebra::{Matrix4, Vector2, Vector3, Vector4};
+
+use crate::glue::recording::texture::Texture;
+use crate::glue::recording::{Command, DrawCall, Recorder, RecordingContext};
+use crate::rendering::buffer::ElementBinding;
+use crate::rendering::glsl::{Declarations, UniformValue};
+use crate::rendering::mesh::ElementIndices;
+use crate::rendering::shader;
+use crate::rendering::shader::{BoundShader, PrimitiveType, ShaderParamInfo, ShaderType};
+use crate::rendering::texture::TextureUnits;
+
+pub use crate::rendering::glsl::ShaderCreationError;
+
+#[derive(Debug)]
+pub struct Shader {
+    id: usize,
+    shader_type: ShaderType,
+    declarations: Declarations,
+}
+
+impl Shader {
+    /// Records a shader's compilation and reads its declarations
+    pub fn compile(
+        recorder: Rc<RefCell<Recorder>>,
+        shader_type: ShaderType,
+        source: &str,
+    ) -> Result<Shader, ShaderCreationError> {
+        let declarations = Declarations::parse(source)?;
+        let mut recorder = recorder.borrow_mut();
+        let id = recorder.next_id();
+        recorder.record(Command::CompileShader {
+            shader: id,
+            shader_type,
+        });
+        Ok(Shader {
+            id,
+            shader_type,
+            declarations,
+        })
+    }
+}
+
+impl shader::Shader for Shader {
+    type RenderingContext = RecordingContext;
+
+    fn shader_type(&self) -> ShaderType {
+        self.shader_type
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum ShaderLinkError {
+    MissingShader(ShaderType),
+}
+
+impl Display for ShaderLinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ShaderLinkError::MissingShader(shader_type) => {
+                write!(f, "Shader program has no {:?} shader", shader_type)
+            }
+        }
+    }
+}
+
+impl Error for ShaderLinkError {}
+
+#[derive(Debug)]
+pub struct ShaderProgram {
+    id: usize,
+    recorder: Rc<RefCell<Recorder>>,
+    attributes: Vec<String>,
+    uniform_names: Vec<String>,
+    uniforms: RefCell<Vec<UniformValue>>,
+}
+
+impl ShaderProgram {
+    pub fn link<'a, T: Iterator<Item = &'a Shader>>(
+        recorder: Rc<RefCell<Recorder>>,
+        shaders: T,
+    ) -> Result<ShaderProgram, ShaderLinkError> {
+        let shaders: Vec<&Shader> = shaders.collect();
+        let find = |shader_type| {
+            shaders
+                .iter()
+                .find(|s| s.shader_type == shader_type)
+                .ok_or(ShaderLinkError::MissingShader(shader_type))
+        };
+        let vertex = find(ShaderType::Vertex)?;
+        let fragment = find(ShaderType::Fragment)?;
+
+        // Uniforms that are declared in both shaders are shared.
+        let mut uniform_names = Vec::new();
+        let mut uniforms = Vec::new();
+        let declared = vertex
+            .declarations
+            .uniforms
+            .iter()
+            .chain(fragment.declarations.uniforms.iter());
+        for (name, uniform_type) in declared {
+            if !uniform_names.contains(name) {
+                uniform_names.push(name.clone());
+                uniforms.push(UniformValue::zero(*uniform_type));
+            }
+        }
+
+        let id = {
+            let mut log = recorder.borrow_mut();
+            let id = log.next_id();
+            log.record(Command::LinkProgram {
+                program: id,
+                shaders: shaders.iter().map(|s| s.id).collect(),
+            });
+            id
+        };
+        Ok(ShaderProgram {
+            id,
+            recorder,
+            attributes: vertex.declarations.attributes.clone(),
+            uniform_names,
+            uniforms: RefCell::new(uniforms),
+        })
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn is_same_context(&self, recorder: &Rc<RefCell<Recorder>>) -> bool {
+        Rc::ptr_eq(&self.recorder, recorder)
+    }
+
+    /// Returns the current value of each of the program's uniforms
+    pub fn uniforms(&self) -> BTreeMap<String, UniformValue> {
+        self.uniform_names
+            .iter()
+            .cloned()
+            .zip(self.uniforms.borrow().iter().copied())
+            .collect()
+    }
+}
+
+impl shader::ShaderProgram for ShaderProgram {
+    type RenderingContext = RecordingContext;
+
+    fn attribute_names(&self) -> Vec<String> {
+        self.attributes.clone()
+    }
+
+    fn uniform_names(&self) -> Vec<String> {
+        self.uniform_names.clone()
+    }
+
+    fn attribute(&self, name: &str) -> Option<ShaderParamInfo> {
+        self.attributes
+            .iter()
+            .position(|a| a == name)
+            .map(|index| ShaderParamInfo { index })
+    }
+
+    fn uniform(&self, name: &str) -> Option<ShaderParamInfo> {
+        self.uniform_names
+            .iter()
+            .position(|u| u == name)
+            .map(|index| ShaderParamInfo { index })
+    }
+}
+
+// The minimum that WebGL 2 guarantees
+const MAX_TEXTURE_UNITS: usize = 16;
+
+pub struct RecordingBoundShader {
+    recorder: Rc<RefCell<Recorder>>,
+    shader: Rc<ShaderProgram>,
+    texture_units: TextureUnits,
+    double_sided: Cell<bool>,
+}
+
+impl RecordingBoundShader {
+    pub fn new(recorder: Rc<RefCell<Recorder>>, shader: Rc<ShaderProgram>) -> RecordingBoundShader {
+        RecordingBoundShader {
+            recorder,
+            shader,
+            texture_units: TextureUnits::new(MAX_TEXTURE_UNITS),
+            double_sided: Cell::new(false),
+        }
+    }
+
+    fn draw(
+        &self,
+        primitive: PrimitiveType,
+        count: usize,
+        instance_count: usize,
+        indices: Option<(usize, ElementBinding)>,
+    ) {
+        let mut recorder = self.recorder.borrow_mut();
+        let attributes = self
+            .shader
+            .attributes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, name)| Some((name.clone(), *recorder.attributes.get(&index)?)))
+            .collect();
+        let draw = DrawCall {
+            program: self.shader.id,
+            primitive,
+            count,
+            instance_count,
+            indices,
+            attributes,
+            uniforms: self.shader.uniforms(),
+            textures: recorder
+                .textures
+                .iter()
+                .map(|(&unit, &texture)| (unit, texture))
+                .collect(),
+            double_sided: self.double_sided.get(),
+        };
+        recorder.record(Command::Draw(draw));
+    }
+
+    fn set_uniform(&self, index: usize, value: UniformValue) {
+        if let Some(uniform) = self.shader.uniforms.borrow_mut().get_mut(index) {
+            *uniform = value;
+            self.recorder.borrow_mut().record(Command::SetUniform {
+                program: self.shader.id,
+                name: self.shader.uniform_names[index].clone(),
+                value,
+            });
+        }
+    }
+}
+
+impl BoundShader<RecordingContext> for RecordingBoundShader {
+    fn program(&self) -> &ShaderProgram {
+        &self.shader
+    }
+
+    fn draw_triangles(&self, count: usize) {
+        self.draw(PrimitiveType::Triangles, count, 1, None);
+    }
+
+    fn draw_indexed_triangles(&self, indices: &ElementIndices<RecordingContext>) {
+        let binding = indices.binding;
+        self.draw(
+            PrimitiveType::Triangles,
+            binding.count,
+            1,
+            Some((indices.buffer.id(), binding)),
+        );
+    }
+
+    fn draw_triangles_instanced(&self, count: usize, instance_count: usize) {
+        self.draw(PrimitiveType::Triangles, count, instance_count, None);
+    }
+
+    fn draw_indexed_triangles_instanced(
+        &self,
+        indices: &ElementIndices<RecordingContext>,
+        instance_count: usize,
+    ) {
+        let binding = indices.binding;
+        self.draw(
+            PrimitiveType::Triangles,
+            binding.count,
+            instance_count,
+            Some((indices.buffer.id(), binding)),
+        );
+    }
+
+    fn draw_polyline(&self, num_vertices: usize) {
+        self.draw(PrimitiveType::LineStrip, num_vertices, 1, None);
+    }
+
+    fn draw_triangle_strip(&self, num_vertices: usize) {
+        self.draw(PrimitiveType::TriangleStrip, num_vertices, 1, None);
+    }
+
+    fn draw_points(&self, num_vertices: usize) {
+        self.draw(PrimitiveType::Points, num_vertices, 1, None);
+    }
+
+    fn set_double_sided(&self, double_sided: bool) {
+        self.double_sided.set(double_sided);
+        self.recorder
+            .borrow_mut()
+            .record(Command::SetDoubleSided(double_sided));
+    }
+
+    fn disable_attribute(&self, index: usize) {
+        let mut recorder = self.recorder.borrow_mut();
+        recorder.attributes.remove(&index);
+        recorder.record(Command::DisableAttribute(index));
+    }
+
+    fn set_uniform_bool(&self, index: usize, value: bool) {
+        self.set_uniform(index, UniformValue::Int(value as i32));
+    }
+
+    fn set_uniform_f32(&self, index: usize, value: f32) {
+        self.set_uniform(index, UniformValue::Float(value));
+    }
+
+    fn set_uniform_mat4(&self, index: usize, value: Matrix4<f32>) {
+        self.set_uniform(index, UniformValue::Mat4(value));
+    }
+
+    fn set_uniform_vec2(&self, index: usize, value: Vector2<f32>) {
+        self.set_uniform(index, UniformValue::Vec2(value));
+    }
+
+    fn set_uniform_vec3(&self, index: usize, value: Vector3<f32>) {
+        self.set_uniform(index, UniformValue::Vec3(value));
+    }
+
+    fn set_uniform_vec4(&self, index: usize, value: Vector4<f32>) {
+        self.set_uniform(index, UniformValue::Vec4(value));
+    }
+
+    fn set_uniform_texture(&self, index: usize, texture: &Texture) {
+        if let Some(unit) = self.texture_units.unit_for(index) {
+            {
+                let mut recorder = self.recorder.borrow_mut();
+                recorder.textures.insert(unit, texture.id());
+                recorder.record(Command::BindTexture {
+                    unit,
+                    texture: texture.id(),
+                });
+            }
+            self.set_uniform(index, UniformValue::Int(unit as i32));
+        }
+    }
+}
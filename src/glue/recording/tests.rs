@@ -0,0 +1,240 @@
+use std::rc::Rc;
+
+use nalgebra::{Matrix4, Vector3, Vector4};
+
+use crate::glue::recording::game_renderer::RecordingRenderer;
+use crate::glue::recording::{Command, DrawCall, RecordingContext};
+use crate::meshgen;
+use crate::rendering::glsl::UniformValue;
+use crate::rendering::line::{BoundLineShader, PolyLine};
+use crate::rendering::material::{BoundMaterialShader, Material};
+use crate::rendering::mesh::Mesh;
+use crate::rendering::shader::PrimitiveType;
+use crate::rendering::{Rgb, Rgba};
+use crate::state::mapgen::SphereRendererFactory;
+use crate::state::{Entity, GameState, MissileTrail};
+use crate::state_renderer::{GameRenderer, MeshRenderer, MissileTrailRenderer, RingRenderer};
+
+fn setup() -> (Rc<RecordingContext>, Rc<RecordingRenderer>, GameState) {
+    let context = Rc::new(RecordingContext::new(400, 300));
+    let renderer = Rc::new(RecordingRenderer::new(Rc::clone(&context)).unwrap());
+    context.clear_commands();
+    (context, renderer, GameState::new(Box::new(|_| None)))
+}
+
+fn make_sphere(context: &RecordingContext) -> Mesh<RecordingContext> {
+    let material = Material::from_color(Rgba::new(0.2, 0.4, 1.0, 1.0), 0.0, 1.0);
+    meshgen::gen_sphere(1.0, 8, context, material).unwrap()
+}
+
+// Returns the buffers that received data
+fn uploads(context: &RecordingContext) -> Vec<usize> {
+    context
+        .commands()
+        .iter()
+        .filter_map(|command| match command {
+            Command::SetBufferData { buffer, .. } => Some(*buffer),
+            _ => None,
+        })
+        .collect()
+}
+
+fn mat4(draw: &DrawCall, name: &str) -> Matrix4<f32> {
+    match draw.uniform(name) {
+        Some(UniformValue::Mat4(value)) => *value,
+        value => panic!("{} is {:?}", name, value),
+    }
+}
+
+#[test]
+fn mesh_draw_binds_material_and_geometry() {
+    let (context, renderer, state) = setup();
+    let mesh = make_sphere(&context);
+    let shader = renderer.material_shader();
+    let bound = BoundMaterialShader::new(context.as_ref(), shader, &state).unwrap();
+    mesh.draw(&bound);
+
+    let draws = context.draw_calls();
+    assert_eq!(draws.len(), mesh.primitives.len());
+    let draw = &draws[0];
+    assert_eq!(draw.program, shader.program.id());
+    assert_eq!(draw.primitive, PrimitiveType::Triangles);
+    assert_eq!(draw.instance_count, 1);
+    assert!(draw.indices.is_some());
+    assert!(draw.attribute("position").is_some());
+    assert!(draw.attribute("normal").is_some());
+    assert_eq!(
+        draw.uniform("material.baseColor"),
+        Some(&UniformValue::Vec4(Vector4::new(0.2, 0.4, 1.0, 1.0)))
+    );
+    assert_eq!(mat4(draw, "view"), state.camera.view().to_homogeneous());
+}
+
+#[test]
+fn polyline_draw_binds_vertices_and_color() {
+    let (context, renderer, state) = setup();
+    let mut line = PolyLine::new(context.as_ref(), Rgb::new(1.0, 0.5, 0.0)).unwrap();
+    let points = [
+        Vector3::new(0.0, 0.0, 0.0),
+        Vector3::new(1.0, 0.0, 0.0),
+        Vector3::new(1.0, 1.0, 0.0),
+    ];
+    line.set_vertices(&points, &[1.0, 0.5]);
+    line.opacity = 0.25;
+    let bound = BoundLineShader::new(context.as_ref(), renderer.line_shader(), &state).unwrap();
+    line.draw(&bound);
+
+    let draws = context.draw_calls();
+    assert_eq!(draws.len(), 1);
+    let draw = &draws[0];
+    assert_eq!(draw.primitive, PrimitiveType::LineStrip);
+    // Extra positions are dropped.
+    assert_eq!(draw.count, 2);
+    let (position_buffer, _) = draw.attribute("position").unwrap();
+    let (alpha_buffer, _) = draw.attribute("alpha").unwrap();
+    assert_eq!(uploads(&context), vec![*position_buffer, *alpha_buffer]);
+    assert_eq!(
+        draw.uniform("color"),
+        Some(&UniformValue::Vec3(Vector3::new(1.0, 0.5, 0.0)))
+    );
+    assert_eq!(draw.uniform("opacity"), Some(&UniformValue::Float(0.25)));
+}
+
+#[test]
+fn mesh_renderer_draws_with_entity_transform() {
+    let (context, renderer, mut state) = setup();
+    let mesh = make_sphere(&context);
+    let num_primitives = mesh.primitives.len();
+    let mut entity = Entity::new(Vector3::new(3.0, -4.0, 0.0));
+    entity.transform.scale = 2.0;
+    let expected = entity.transform.to_similarity().to_homogeneous();
+    entity.renderer = Some(Rc::new(MeshRenderer::new(renderer.clone(), mesh)));
+    state.add_entity(entity);
+    context.clear_commands();
+    renderer.render(&mut state).unwrap();
+
+    let draws = context.draw_calls();
+    assert_eq!(draws.len(), num_primitives);
+    for draw in draws.iter() {
+        assert_eq!(draw.program, renderer.material_shader().program.id());
+        assert_eq!(mat4(draw, "model"), expected);
+    }
+    // The mesh was uploaded when it was created.
+    assert!(uploads(&context).is_empty());
+}
+
+#[test]
+fn missile_trail_renderer_draws_each_segment() {
+    let (context, renderer, mut state) = setup();
+    let mut trail = MissileTrail::new(0, Vector3::zeros(), Vector3::new(1.0, 0.0, 0.0));
+    trail.add_position(Vector3::new(1.0, 0.0, 0.0));
+    trail.teleport(Vector3::new(5.0, 5.0, 0.0));
+    trail.add_position(Vector3::new(6.0, 5.0, 0.0));
+    trail.add_position(Vector3::new(7.0, 5.0, 0.0));
+    let trail_renderer = MissileTrailRenderer::new(renderer.clone(), Rgb::new(1.0, 0.0, 0.0));
+    let mut missile = Entity::new(Vector3::new(7.0, 5.0, 0.0));
+    missile.missile_trail = Some(trail);
+    missile.renderer = Some(Rc::new(trail_renderer.unwrap()));
+    state.add_entity(missile);
+
+    context.clear_commands();
+    renderer.render(&mut state).unwrap();
+    let draws = context.draw_calls();
+    assert_eq!(draws.len(), 2);
+    // Each point of a thick line is drawn as two vertices.
+    let counts: Vec<usize> = draws.iter().map(|draw| draw.count).collect();
+    assert_eq!(counts, vec![4, 6]);
+    for draw in draws.iter() {
+        assert_eq!(draw.primitive, PrimitiveType::TriangleStrip);
+        assert_eq!(draw.program, renderer.thick_line_shader().program.id());
+    }
+    let first_uploads = uploads(&context);
+    assert!(!first_uploads.is_empty());
+
+    // The lines are only rebuilt when the trail changes.
+    context.clear_commands();
+    renderer.render(&mut state).unwrap();
+    assert_eq!(context.draw_calls().len(), 2);
+    assert!(uploads(&context).is_empty());
+
+    if let Some(ref mut trail) = state.entities[0].missile_trail {
+        trail.add_position(Vector3::new(8.0, 5.0, 0.0));
+    }
+    context.clear_commands();
+    renderer.render(&mut state).unwrap();
+    assert_eq!(context.draw_calls()[1].count, 8);
+    assert!(!uploads(&context).is_empty());
+}
+
+#[test]
+fn shared_ring_renderer_draws_around_each_entity() {
+    let (context, renderer, mut state) = setup();
+    let ring = RingRenderer::new(renderer.clone(), 3.0, Rgb::new(1.0, 1.0, 1.0)).unwrap();
+    let ring_uploads = uploads(&context);
+    let ring: Rc<RingRenderer<RecordingContext>> = Rc::new(ring);
+    for x in [-10.0, 10.0] {
+        let mut entity = Entity::new(Vector3::new(x, 0.0, 0.0));
+        entity.renderer = Some(ring.clone());
+        state.add_entity(entity);
+    }
+    context.clear_commands();
+    renderer.render(&mut state).unwrap();
+
+    // Both rings use the geometry that was uploaded once, around the origin.
+    assert_eq!(ring_uploads.len(), 1);
+    assert!(uploads(&context).is_empty());
+    let draws = context.draw_calls();
+    assert_eq!(draws.len(), 2);
+    for draw in draws.iter() {
+        assert_eq!(draw.attribute("position").unwrap().0, ring_uploads[0]);
+    }
+    let mut offsets: Vec<f32> = draws
+        .iter()
+        .map(|draw| mat4(draw, "model").column(3).x)
+        .collect();
+    offsets.sort_by(f32::total_cmp);
+    assert_eq!(offsets, vec![-10.0, 10.0]);
+}
+
+#[test]
+fn spheres_with_different_colors_are_instanced() {
+    let (context, renderer, mut state) = setup();
+    let game_renderer: Rc<dyn GameRenderer<Context = RecordingContext>> = renderer.clone();
+    let spheres = SphereRendererFactory::new(&game_renderer).unwrap();
+    let colors = [Rgba::new(1.0, 0.0, 0.0, 1.0), Rgba::new(0.0, 1.0, 0.0, 1.0)];
+    for (i, color) in colors.iter().enumerate() {
+        let mut planet = Entity::new(Vector3::new(i as f32 * 10.0, 0.0, 0.0));
+        planet.renderer = Some(spheres.make_renderer(*color));
+        state.add_entity(planet);
+    }
+    context.clear_commands();
+    renderer.render(&mut state).unwrap();
+
+    let draws = context.draw_calls();
+    assert_eq!(draws.len(), 1);
+    let draw = &draws[0];
+    assert_eq!(draw.instance_count, 2);
+    let instanced_shader = renderer.instanced_material_shader().unwrap();
+    assert_eq!(draw.program, instanced_shader.program.id());
+    let (instance_buffer, _) = draw.attribute("color").unwrap();
+    let data = context
+        .commands()
+        .iter()
+        .find_map(|command| match command {
+            Command::SetBufferData { buffer, data } if buffer == instance_buffer => {
+                Some(data.clone())
+            }
+            _ => None,
+        })
+        .unwrap();
+    let floats: Vec<f32> = data
+        .chunks(4)
+        .map(|bytes| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect();
+    // Each instance's color follows its model transform.
+    let instance_colors: Vec<&[f32]> = floats.chunks(20).map(|instance| &instance[16..]).collect();
+    assert_eq!(instance_colors.len(), 2);
+    for color in colors.iter() {
+        assert!(instance_colors.contains(&&[color.r, color.g, color.b, color.a][..]));
+    }
+}
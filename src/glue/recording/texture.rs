@@ -0,0 +1,69 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::glue::recording::{Command, Recorder, RecordingContext};
+use crate::rendering::texture;
+use crate::rendering::texture::{Sampler, SetTextureDataError, TextureData};
+
+#[derive(Debug)]
+pub struct Texture {
+    id: usize,
+    recorder: Rc<RefCell<Recorder>>,
+}
+
+impl Texture {
+    pub fn new(recorder: Rc<RefCell<Recorder>>) -> Texture {
+        let id = {
+            let mut log = recorder.borrow_mut();
+            let id = log.next_id();
+            log.record(Command::CreateTexture(id));
+            id
+        };
+        Texture { id, recorder }
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+}
+
+impl texture::Texture for Texture {
+    type RenderingContext = RecordingContext;
+
+    fn set_data(&self, data: TextureData) -> Result<(), SetTextureDataError> {
+        self.recorder.borrow_mut().record(Command::SetTextureData {
+            texture: self.id,
+            format: data.format(),
+            width: data.width(),
+            height: data.height(),
+        });
+        Ok(())
+    }
+
+    fn set_sub_data(&self, x: u32, y: u32, data: TextureData) -> Result<(), SetTextureDataError> {
+        self.recorder
+            .borrow_mut()
+            .record(Command::SetTextureSubData {
+                texture: self.id,
+                x,
+                y,
+                format: data.format(),
+                width: data.width(),
+                height: data.height(),
+            });
+        Ok(())
+    }
+
+    fn generate_mipmaps(&self) {
+        self.recorder
+            .borrow_mut()
+            .record(Command::GenerateMipmaps(self.id));
+    }
+
+    fn set_sampler(&self, sampler: &Sampler) {
+        self.recorder.borrow_mut().record(Command::SetSampler {
+            texture: self.id,
+            sampler: *sampler,
+        });
+    }
+}
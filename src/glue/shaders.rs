@@ -0,0 +1,136 @@
+//! The game's shader programs, built into the binary for renderers that don't load assets
+
+use crate::rendering::context::RenderingContext;
+use crate::rendering::line::{LineShader, ThickLineShader};
+use crate::rendering::material::MaterialShader;
+use crate::rendering::particle::ParticleShader;
+use crate::rendering::shader::ShaderType;
+
+pub const VERTEX: &str = include_str!("../../shaders/vertex.glsl");
+pub const INSTANCED_VERTEX: &str = include_str!("../../shaders/instanced_vertex.glsl");
+pub const FRAGMENT: &str = include_str!("../../shaders/fragment.glsl");
+pub const LINE_VERTEX: &str = include_str!("../../shaders/line_vertex.glsl");
+pub const LINE_FRAGMENT: &str = include_str!("../../shaders/line_fragment.glsl");
+pub const THICK_LINE_VERTEX: &str = include_str!("../../shaders/thick_line_vertex.glsl");
+pub const THICK_LINE_FRAGMENT: &str = include_str!("../../shaders/thick_line_fragment.glsl");
+pub const PARTICLE_VERTEX: &str = include_str!("../../shaders/particle_vertex.glsl");
+pub const PARTICLE_FRAGMENT: &str = include_str!("../../shaders/particle_fragment.glsl");
+
+/// GLSL ES 3.00 versions of the shaders for WebGL 2, which share a `Frame` uniform block
+pub mod es3 {
+    pub const VERTEX: &str = include_str!("../../shaders/es3/vertex.glsl");
+    pub const INSTANCED_VERTEX: &str = include_str!("../../shaders/es3/instanced_vertex.glsl");
+    pub const FRAGMENT: &str = include_str!("../../shaders/es3/fragment.glsl");
+    pub const LINE_VERTEX: &str = include_str!("../../shaders/es3/line_vertex.glsl");
+    pub const LINE_FRAGMENT: &str = include_str!("../../shaders/es3/line_fragment.glsl");
+    pub const THICK_LINE_VERTEX: &str = include_str!("../../shaders/es3/thick_line_vertex.glsl");
+    pub const THICK_LINE_FRAGMENT: &str =
+        include_str!("../../shaders/es3/thick_line_fragment.glsl");
+    pub const PARTICLE_VERTEX: &str = include_str!("../../shaders/es3/particle_vertex.glsl");
+    pub const PARTICLE_FRAGMENT: &str = include_str!("../../shaders/es3/particle_fragment.glsl");
+}
+
+pub fn load_program<Context: RenderingContext>(
+    context: &Context,
+    vertex_source: &str,
+    fragment_source: &str,
+) -> Result<Context::ShaderProgram, String> {
+    let vertex = context
+        .compile_shader(ShaderType::Vertex, vertex_source)
+        .map_err(|e| format!("{}", e))?;
+    let fragment = context
+        .compile_shader(ShaderType::Fragment, fragment_source)
+        .map_err(|e| format!("{}", e))?;
+    context
+        .link_shader_program([vertex, fragment].iter())
+        .map_err(|e| format!("{}", e))
+}
+
+/// The shaders that a `GameRenderer` needs
+#[derive(Debug)]
+pub struct BuiltinShaders<Context: RenderingContext> {
+    pub material: MaterialShader<Context>,
+    /// Only loaded if the context supports instancing
+    pub instanced_material: Option<MaterialShader<Context>>,
+    pub line: LineShader<Context>,
+    pub thick_line: ThickLineShader<Context>,
+    pub particle: ParticleShader<Context>,
+}
+
+impl<Context: RenderingContext> BuiltinShaders<Context> {
+    /// Loads the shaders from the sources that are built into the binary
+    pub fn load(context: &Context) -> Result<Self, String> {
+        Self::load_with(context, builtin_source)
+    }
+
+    /// Loads the GLSL ES 3.00 shaders, getting the source of each one from its path under
+    /// `shaders/es3/`
+    pub fn load_es3_with<'a, F>(context: &Context, source: F) -> Result<Self, String>
+    where
+        F: Fn(&str) -> Result<&'a str, String>,
+    {
+        Self::load_with(context, |path| match path.strip_prefix("shaders/") {
+            Some(name) => source(&format!("shaders/es3/{}", name)),
+            None => source(path),
+        })
+    }
+
+    /// Loads the shaders, getting the source of each one from its path under `shaders/`
+    pub fn load_with<'a, F>(context: &Context, source: F) -> Result<Self, String>
+    where
+        F: Fn(&str) -> Result<&'a str, String>,
+    {
+        let load = |vertex: &str, fragment: &str| {
+            load_program(context, source(vertex)?, source(fragment)?)
+                .map_err(|e| format!("{}/{}: {}", vertex, fragment, e))
+        };
+        let material = load("shaders/vertex.glsl", "shaders/fragment.glsl")?;
+        let instanced_material = if context.supports_instancing() {
+            let program = load("shaders/instanced_vertex.glsl", "shaders/fragment.glsl")?;
+            Some(MaterialShader::new(program).map_err(|e| format!("{:?}", e))?)
+        } else {
+            None
+        };
+        let line = load("shaders/line_vertex.glsl", "shaders/line_fragment.glsl")?;
+        let thick_line = load(
+            "shaders/thick_line_vertex.glsl",
+            "shaders/thick_line_fragment.glsl",
+        )?;
+        let particle = load(
+            "shaders/particle_vertex.glsl",
+            "shaders/particle_fragment.glsl",
+        )?;
+        Ok(BuiltinShaders {
+            material: MaterialShader::new(material).map_err(|e| format!("{:?}", e))?,
+            instanced_material,
+            line: LineShader::new(line).map_err(|e| format!("{:?}", e))?,
+            thick_line: ThickLineShader::new(thick_line).map_err(|e| format!("{:?}", e))?,
+            particle: ParticleShader::new(particle).map_err(|e| format!("{:?}", e))?,
+        })
+    }
+}
+
+/// Returns the built-in source of the shader at the given path
+pub fn builtin_source(path: &str) -> Result<&'static str, String> {
+    match path {
+        "shaders/vertex.glsl" => Ok(VERTEX),
+        "shaders/instanced_vertex.glsl" => Ok(INSTANCED_VERTEX),
+        "shaders/fragment.glsl" => Ok(FRAGMENT),
+        "shaders/line_vertex.glsl" => Ok(LINE_VERTEX),
+        "shaders/line_fragment.glsl" => Ok(LINE_FRAGMENT),
+        "shaders/thick_line_vertex.glsl" => Ok(THICK_LINE_VERTEX),
+        "shaders/thick_line_fragment.glsl" => Ok(THICK_LINE_FRAGMENT),
+        "shaders/particle_vertex.glsl" => Ok(PARTICLE_VERTEX),
+        "shaders/particle_fragment.glsl" => Ok(PARTICLE_FRAGMENT),
+        "shaders/es3/vertex.glsl" => Ok(es3::VERTEX),
+        "shaders/es3/instanced_vertex.glsl" => Ok(es3::INSTANCED_VERTEX),
+        "shaders/es3/fragment.glsl" => Ok(es3::FRAGMENT),
+        "shaders/es3/line_vertex.glsl" => Ok(es3::LINE_VERTEX),
+        "shaders/es3/line_fragment.glsl" => Ok(es3::LINE_FRAGMENT),
+        "shaders/es3/thick_line_vertex.glsl" => Ok(es3::THICK_LINE_VERTEX),
+        "shaders/es3/thick_line_fragment.glsl" => Ok(es3::THICK_LINE_FRAGMENT),
+        "shaders/es3/particle_vertex.glsl" => Ok(es3::PARTICLE_VERTEX),
+        "shaders/es3/particle_fragment.glsl" => Ok(es3::PARTICLE_FRAGMENT),
+        _ => Err(format!("No built-in shader at {}", path)),
+    }
+}
@@ -0,0 +1,171 @@
+use std::cell::RefCell;
+use std::convert::TryInto;
+use std::rc::Rc;
+
+use nalgebra::Vector4;
+
+use crate::glue::software::{ContextState, SoftwareContext};
+use crate::rendering;
+use crate::rendering::buffer::{AttributeType, ElementBinding, IndexType, VertexAttributeBinding};
+
+fn attr_type_size(attr_type: AttributeType) -> usize {
+    match attr_type {
+        AttributeType::Byte | AttributeType::UnsignedByte => 1,
+        AttributeType::Short | AttributeType::UnsignedShort => 2,
+        AttributeType::Int | AttributeType::UnsignedInt | AttributeType::Float => 4,
+    }
+}
+
+fn index_type_size(index_type: IndexType) -> usize {
+    match index_type {
+        IndexType::UnsignedByte => 1,
+        IndexType::UnsignedShort => 2,
+        IndexType::UnsignedInt => 4,
+    }
+}
+
+/// Reads one component, returning `None` if it's past the end of the data
+fn read_component(
+    data: &[u8],
+    offset: usize,
+    attr_type: AttributeType,
+    normalized: bool,
+) -> Option<f32> {
+    let bytes = data.get(offset..offset + attr_type_size(attr_type))?;
+    // Normalized integers are mapped to [0, 1] (unsigned) or [-1, 1] (signed).
+    let (value, scale) = match attr_type {
+        AttributeType::Byte => (bytes[0] as i8 as f32, i8::MAX as f32),
+        AttributeType::UnsignedByte => (bytes[0] as f32, u8::MAX as f32),
+        AttributeType::Short => (
+            i16::from_ne_bytes(bytes.try_into().ok()?) as f32,
+            i16::MAX as f32,
+        ),
+        AttributeType::UnsignedShort => (
+            u16::from_ne_bytes(bytes.try_into().ok()?) as f32,
+            u16::MAX as f32,
+        ),
+        AttributeType::Int => (
+            i32::from_ne_bytes(bytes.try_into().ok()?) as f32,
+            i32::MAX as f32,
+        ),
+        AttributeType::UnsignedInt => (
+            u32::from_ne_bytes(bytes.try_into().ok()?) as f32,
+            u32::MAX as f32,
+        ),
+        AttributeType::Float => return Some(f32::from_ne_bytes(bytes.try_into().ok()?)),
+    };
+    Some(if normalized {
+        (value / scale).max(-1.0)
+    } else {
+        value
+    })
+}
+
+/// A buffer and the layout of an attribute's data within it
+#[derive(Debug)]
+pub struct AttributeSource {
+    data: Rc<RefCell<Vec<u8>>>,
+    binding: VertexAttributeBinding,
+}
+
+impl AttributeSource {
+    /// Reads the attribute's value for a vertex of an instance
+    ///
+    /// Missing components are filled in from (0, 0, 0, 1), as in OpenGL. Returns `None` if the
+    /// value is outside the buffer.
+    pub fn fetch(&self, vertex: usize, instance: usize) -> Option<Vector4<f32>> {
+        let binding = &self.binding;
+        let element = match binding.divisor {
+            0 => vertex,
+            divisor => instance / divisor,
+        };
+        let component_size = attr_type_size(binding.attr_type);
+        let stride = match binding.stride {
+            0 => component_size * binding.num_components,
+            stride => stride,
+        };
+        let start = binding.offset + element * stride;
+        let data = self.data.borrow();
+        let mut value = Vector4::new(0.0, 0.0, 0.0, 1.0);
+        for i in 0..binding.num_components.min(4) {
+            value[i] = read_component(
+                &data,
+                start + i * component_size,
+                binding.attr_type,
+                binding.normalized,
+            )?;
+        }
+        Some(value)
+    }
+}
+
+#[derive(Debug)]
+pub struct AttributeBuffer {
+    data: Rc<RefCell<Vec<u8>>>,
+    state: Rc<RefCell<ContextState>>,
+}
+
+impl AttributeBuffer {
+    pub fn new(state: Rc<RefCell<ContextState>>) -> Self {
+        AttributeBuffer {
+            data: Rc::new(RefCell::new(Vec::new())),
+            state,
+        }
+    }
+}
+
+impl rendering::buffer::Buffer for AttributeBuffer {
+    type RenderingContext = SoftwareContext;
+
+    fn set_data(&self, data: &[u8]) {
+        *self.data.borrow_mut() = data.to_vec();
+    }
+}
+
+impl rendering::buffer::AttributeBuffer for AttributeBuffer {
+    fn bind_to_attribute(&self, index: usize, binding: &VertexAttributeBinding) {
+        let source = AttributeSource {
+            data: Rc::clone(&self.data),
+            binding: *binding,
+        };
+        self.state.borrow_mut().attributes.insert(index, source);
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct IndexBuffer {
+    data: RefCell<Vec<u8>>,
+}
+
+impl IndexBuffer {
+    pub fn new() -> Self {
+        IndexBuffer::default()
+    }
+
+    /// Reads the vertex indices selected by a binding, stopping at the end of the buffer
+    pub fn indices(&self, binding: &ElementBinding) -> Vec<usize> {
+        let size = index_type_size(binding.index_type);
+        let data = self.data.borrow();
+        (0..binding.count)
+            .map_while(|i| {
+                let offset = binding.offset + i * size;
+                let bytes = data.get(offset..offset + size)?;
+                Some(match binding.index_type {
+                    IndexType::UnsignedByte => bytes[0] as usize,
+                    IndexType::UnsignedShort => u16::from_ne_bytes(bytes.try_into().ok()?) as usize,
+                    IndexType::UnsignedInt => u32::from_ne_bytes(bytes.try_into().ok()?) as usize,
+                })
+            })
+            .collect()
+    }
+}
+
+impl rendering::buffer::Buffer for IndexBuffer {
+    type RenderingContext = SoftwareContext;
+
+    fn set_data(&self, data: &[u8]) {
+        *self.data.borrow_mut() = data.to_vec();
+    }
+}
+
+impl rendering::buffer::IndexBuffer for IndexBuffer {}
@@ -0,0 +1,72 @@
+use std::rc::Rc;
+
+use crate::glue::shaders::BuiltinShaders;
+use crate::glue::software::SoftwareContext;
+use crate::rendering::line::{LineShader, ThickLineShader};
+use crate::rendering::material::MaterialShader;
+use crate::rendering::particle::ParticleShader;
+use crate::rendering::queue::RenderQueue;
+use crate::rendering::Rgba;
+use crate::state::GameState;
+use crate::state_renderer::GameRenderer;
+
+/// Renders the game into an image without a GPU
+#[derive(Debug)]
+pub struct SoftwareRenderer {
+    context: Rc<SoftwareContext>,
+    shaders: BuiltinShaders<SoftwareContext>,
+    render_queue: RenderQueue<SoftwareContext>,
+}
+
+impl SoftwareRenderer {
+    /// Creates a renderer using the game's built-in shaders
+    pub fn new(context: Rc<SoftwareContext>) -> Result<SoftwareRenderer, String> {
+        let shaders = BuiltinShaders::load(context.as_ref())?;
+        Ok(SoftwareRenderer {
+            context,
+            shaders,
+            render_queue: RenderQueue::new(),
+        })
+    }
+
+    pub fn context(&self) -> &Rc<SoftwareContext> {
+        &self.context
+    }
+}
+
+impl GameRenderer for SoftwareRenderer {
+    type Context = SoftwareContext;
+
+    fn context(&self) -> &Self::Context {
+        &self.context
+    }
+
+    fn material_shader(&self) -> &MaterialShader<SoftwareContext> {
+        &self.shaders.material
+    }
+
+    fn instanced_material_shader(&self) -> Option<&MaterialShader<SoftwareContext>> {
+        self.shaders.instanced_material.as_ref()
+    }
+
+    fn line_shader(&self) -> &LineShader<SoftwareContext> {
+        &self.shaders.line
+    }
+
+    fn thick_line_shader(&self) -> &ThickLineShader<SoftwareContext> {
+        &self.shaders.thick_line
+    }
+
+    fn particle_shader(&self) -> &ParticleShader<SoftwareContext> {
+        &self.shaders.particle
+    }
+
+    fn render_queue(&self) -> &RenderQueue<SoftwareContext> {
+        &self.render_queue
+    }
+
+    fn begin_frame(&self, state: &mut GameState) {
+        state.camera.aspect_ratio = self.context.aspect_ratio();
+        self.context.clear(&Rgba::new(0.0, 0.0, 0.0, 1.0));
+    }
+}
@@ -0,0 +1,176 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::rc::Rc;
+
+use image::{Rgba, RgbaImage};
+use nalgebra::Vector4;
+
+use crate::glue::software::buffer::AttributeSource;
+use crate::glue::software::shader::{ShaderProgram, SoftwareBoundShader};
+use crate::glue::software::texture::BoundTexture;
+use crate::rendering;
+use crate::rendering::context::RenderingContext;
+use crate::rendering::shader::{ShaderBindError, ShaderType};
+
+pub mod buffer;
+pub mod game_renderer;
+pub mod pipeline;
+pub mod raster;
+pub mod shader;
+pub mod texture;
+
+/// The drawing state shared by a context and the buffers and shaders that it creates
+pub struct ContextState {
+    color: RgbaImage,
+    depth: Vec<f32>,
+    attributes: HashMap<usize, AttributeSource>,
+    /// The texture bound to each texture unit
+    textures: HashMap<usize, BoundTexture>,
+}
+
+impl ContextState {
+    fn new(width: u32, height: u32) -> ContextState {
+        ContextState {
+            color: RgbaImage::new(width, height),
+            depth: vec![1.0; (width * height) as usize],
+            attributes: HashMap::new(),
+            textures: HashMap::new(),
+        }
+    }
+}
+
+impl Debug for ContextState {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "ContextState({}x{})",
+            self.color.width(),
+            self.color.height()
+        )
+    }
+}
+
+/// A rendering context that draws into an image on the CPU
+///
+/// This doesn't need a GPU or a browser, so it can render frames in tests and native tools. It
+/// only runs the game's own shader programs (see `pipeline`), which are recognized by the
+/// attributes that they declare. Triangles are culled if they face away from the camera, and
+/// fragments are depth tested and alpha blended, matching the WebGL renderer's configuration.
+#[derive(Debug)]
+pub struct SoftwareContext {
+    state: Rc<RefCell<ContextState>>,
+    pixel_ratio: f32,
+}
+
+impl SoftwareContext {
+    pub fn new(width: u32, height: u32) -> SoftwareContext {
+        SoftwareContext {
+            state: Rc::new(RefCell::new(ContextState::new(width, height))),
+            pixel_ratio: 1.0,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.state.borrow().color.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.state.borrow().color.height()
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        self.width() as f32 / self.height().max(1) as f32
+    }
+
+    /// Sets the number of pixels per CSS pixel, which scales line widths
+    pub fn set_pixel_ratio(&mut self, pixel_ratio: f32) {
+        self.pixel_ratio = pixel_ratio;
+    }
+
+    /// Changes the size of the image, clearing its contents
+    pub fn resize(&self, width: u32, height: u32) {
+        let mut state = self.state.borrow_mut();
+        state.color = RgbaImage::new(width, height);
+        state.depth = vec![1.0; (width * height) as usize];
+    }
+
+    /// Fills the image with a color and resets the depth buffer
+    pub fn clear(&self, color: &rendering::Rgba) {
+        let mut state = self.state.borrow_mut();
+        let pixel = Rgba(raster::to_bytes(&Vector4::new(
+            color.r, color.g, color.b, color.a,
+        )));
+        for p in state.color.pixels_mut() {
+            *p = pixel;
+        }
+        for depth in state.depth.iter_mut() {
+            *depth = 1.0;
+        }
+    }
+
+    /// Returns a copy of the rendered image
+    pub fn image(&self) -> RgbaImage {
+        self.state.borrow().color.clone()
+    }
+}
+
+impl RenderingContext for SoftwareContext {
+    type AttributeBuffer = buffer::AttributeBuffer;
+    type IndexBuffer = buffer::IndexBuffer;
+    type Shader = shader::Shader;
+    type ShaderCreationError = shader::ShaderCreationError;
+    type ShaderProgram = ShaderProgram;
+    type ShaderLinkError = shader::ShaderLinkError;
+    type BoundShader = SoftwareBoundShader;
+    type Texture = texture::Texture;
+
+    fn make_attribute_buffer(&self) -> Result<Self::AttributeBuffer, ()> {
+        Ok(Self::AttributeBuffer::new(Rc::clone(&self.state)))
+    }
+
+    fn make_index_buffer(&self) -> Result<Self::IndexBuffer, ()> {
+        Ok(Self::IndexBuffer::new())
+    }
+
+    fn make_texture(&self) -> Result<Self::Texture, ()> {
+        Ok(Self::Texture::new())
+    }
+
+    fn compile_shader(
+        &self,
+        shader_type: ShaderType,
+        source: &str,
+    ) -> Result<Self::Shader, Self::ShaderCreationError> {
+        Self::Shader::compile(shader_type, source)
+    }
+
+    fn link_shader_program<'a, T: Iterator<Item = &'a Self::Shader>>(
+        &self,
+        shaders: T,
+    ) -> Result<Self::ShaderProgram, Self::ShaderLinkError> {
+        Self::ShaderProgram::link(Rc::clone(&self.state), shaders)
+    }
+
+    fn viewport_size(&self) -> (u32, u32) {
+        (self.width(), self.height())
+    }
+
+    fn pixel_ratio(&self) -> f32 {
+        self.pixel_ratio
+    }
+
+    fn supports_instancing(&self) -> bool {
+        true
+    }
+
+    fn bind_shader(
+        &self,
+        shader: Rc<Self::ShaderProgram>,
+    ) -> Result<Self::BoundShader, ShaderBindError> {
+        if !shader.is_same_context(&self.state) {
+            return Err(ShaderBindError::InvalidContextForShader);
+        }
+        Ok(SoftwareBoundShader::new(Rc::clone(&self.state), shader))
+    }
+}
@@ -0,0 +1,564 @@
+//! Rust versions of the game's shader programs
+//!
+//! Each pipeline matches the GLSL shaders in `shaders/` as closely as possible, using the same
+//! names for attributes and uniforms.
+
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+use nalgebra::{Matrix4, Vector2, Vector3, Vector4};
+
+use crate::glue::software::shader::Uniforms;
+use crate::glue::software::texture::BoundTexture;
+
+/// Maximum number of floats passed from a vertex to its fragments
+pub const MAX_VARYINGS: usize = 16;
+
+pub type Varyings = [f32; MAX_VARYINGS];
+
+#[derive(Clone, Copy, Debug)]
+pub struct VertexOutput {
+    /// Position in clip space (`gl_Position`)
+    pub position: Vector4<f32>,
+    /// Diameter of the vertex in pixels when it's drawn as a point (`gl_PointSize`)
+    pub point_size: f32,
+    pub varyings: Varyings,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Fragment {
+    pub varyings: Varyings,
+    /// Position within a point, from (0, 0) at the top left to (1, 1) (`gl_PointCoord`)
+    pub point_coord: Vector2<f32>,
+    /// Whether the fragment is on a triangle that faces the camera, or isn't on a triangle
+    /// (`gl_FrontFacing`)
+    pub front_facing: bool,
+}
+
+/// Runs the vertex and fragment stages of a program with a fixed set of uniform values
+pub trait Stages {
+    /// Transforms a vertex, reading its attributes by name
+    fn vertex(&self, attribute: &dyn Fn(&str) -> Vector4<f32>) -> VertexOutput;
+    /// Returns the color of a fragment, or `None` to discard it
+    fn fragment(&self, fragment: &Fragment) -> Option<Vector4<f32>>;
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Pipeline {
+    /// `vertex.glsl` and `fragment.glsl`
+    Material,
+    /// `instanced_vertex.glsl` and `fragment.glsl`
+    InstancedMaterial,
+    /// `line_vertex.glsl` and `line_fragment.glsl`
+    Line,
+    /// `thick_line_vertex.glsl` and `thick_line_fragment.glsl`
+    ThickLine,
+    /// `particle_vertex.glsl` and `particle_fragment.glsl`
+    Particle,
+}
+
+impl Pipeline {
+    /// Finds the pipeline for a program from its vertex attributes
+    pub fn identify(attributes: &[String]) -> Option<Pipeline> {
+        let has = |name: &str| attributes.iter().any(|a| a == name);
+        if !has("position") {
+            None
+        } else if has("normal") && has("model0") {
+            Some(Pipeline::InstancedMaterial)
+        } else if has("normal") {
+            Some(Pipeline::Material)
+        } else if has("side") && has("previous") && has("next") {
+            Some(Pipeline::ThickLine)
+        } else if has("size") && has("color") {
+            Some(Pipeline::Particle)
+        } else if has("alpha") {
+            Some(Pipeline::Line)
+        } else {
+            None
+        }
+    }
+
+    /// Creates the stages for the current uniform values and the texture bound to each unit
+    pub fn stages(
+        &self,
+        uniforms: &Uniforms,
+        textures: &HashMap<usize, BoundTexture>,
+    ) -> Box<dyn Stages> {
+        match self {
+            Pipeline::Material => Box::new(MaterialStages::new(uniforms, textures, false)),
+            Pipeline::InstancedMaterial => Box::new(MaterialStages::new(uniforms, textures, true)),
+            Pipeline::Line => Box::new(LineStages::new(uniforms)),
+            Pipeline::ThickLine => Box::new(ThickLineStages::new(uniforms)),
+            Pipeline::Particle => Box::new(ParticleStages::new(uniforms)),
+        }
+    }
+}
+
+fn varyings(values: &[f32]) -> Varyings {
+    let mut varyings = [0.0; MAX_VARYINGS];
+    varyings[..values.len()].copy_from_slice(values);
+    varyings
+}
+
+fn point(v: &Vector4<f32>) -> Vector4<f32> {
+    Vector4::new(v.x, v.y, v.z, 1.0)
+}
+
+const MIN_ROUGHNESS: f32 = 0.04;
+
+struct MaterialStages {
+    /// Whether the model transform and tint are read from attributes
+    instanced: bool,
+    view: Matrix4<f32>,
+    model_view: Matrix4<f32>,
+    projection: Matrix4<f32>,
+    base_color: Vector4<f32>,
+    metal_factor: f32,
+    roughness: f32,
+    base_color_texture: Option<BoundTexture>,
+    metallic_roughness_texture: Option<BoundTexture>,
+    normal_texture: Option<BoundTexture>,
+    normal_scale: f32,
+    occlusion_texture: Option<BoundTexture>,
+    occlusion_strength: f32,
+    emissive: Vector3<f32>,
+    emissive_texture: Option<BoundTexture>,
+    alpha_mask: bool,
+    alpha_cutoff: f32,
+    alpha_blend: bool,
+    double_sided: bool,
+    sun_vect: Vector3<f32>,
+    sun_color: Vector3<f32>,
+    ambient: Vector3<f32>,
+}
+
+impl MaterialStages {
+    fn new(uniforms: &Uniforms, textures: &HashMap<usize, BoundTexture>, instanced: bool) -> Self {
+        let view = uniforms.mat4("view");
+        let sun_direction = uniforms.vec3("sun.direction");
+        let texture = |sampler: &str, is_set: &str| {
+            if uniforms.bool(is_set) {
+                let unit = uniforms.int(sampler) as usize;
+                textures.get(&unit).cloned()
+            } else {
+                None
+            }
+        };
+        MaterialStages {
+            instanced,
+            view,
+            model_view: view * uniforms.mat4("model"),
+            projection: uniforms.mat4("projection"),
+            base_color: uniforms.vec4("material.baseColor"),
+            metal_factor: uniforms.f32("material.metalFactor"),
+            roughness: uniforms.f32("material.roughness"),
+            base_color_texture: texture("baseColorTexture", "material.hasBaseColorTexture"),
+            metallic_roughness_texture: texture(
+                "metallicRoughnessTexture",
+                "material.hasMetallicRoughnessTexture",
+            ),
+            normal_texture: texture("normalTexture", "material.hasNormalTexture"),
+            normal_scale: uniforms.f32("material.normalScale"),
+            occlusion_texture: texture("occlusionTexture", "material.hasOcclusionTexture"),
+            occlusion_strength: uniforms.f32("material.occlusionStrength"),
+            emissive: uniforms.vec3("material.emissive"),
+            emissive_texture: texture("emissiveTexture", "material.hasEmissiveTexture"),
+            alpha_mask: uniforms.bool("material.alphaMask"),
+            alpha_cutoff: uniforms.f32("material.alphaCutoff"),
+            alpha_blend: uniforms.bool("material.alphaBlend"),
+            double_sided: uniforms.bool("material.doubleSided"),
+            sun_vect: -(view * sun_direction.to_homogeneous()).xyz(),
+            sun_color: uniforms.vec3("sun.color"),
+            ambient: uniforms.vec3("ambient"),
+        }
+    }
+}
+
+fn fresnel(metal: f32, view_dot_half: f32) -> f32 {
+    metal + (1.0 - metal) * (1.0 - view_dot_half).clamp(0.0, 1.0).powi(5)
+}
+
+fn schlick_occlusion(perceptual_roughness: f32, light_dot_half: f32, normal_dot_half: f32) -> f32 {
+    let k = perceptual_roughness * (2.0 / PI).sqrt();
+    let l = light_dot_half / (light_dot_half * (1.0 - k) + k);
+    let n = normal_dot_half / (normal_dot_half * (1.0 - k) + k);
+    l * n
+}
+
+fn trowbridge_reitz_microfacet_distribution(alpha_roughness: f32, normal_dot_half: f32) -> f32 {
+    let roughness_sq = alpha_roughness * alpha_roughness;
+    let f = (normal_dot_half * roughness_sq - normal_dot_half) * normal_dot_half + 1.0;
+    roughness_sq / (PI * f * f)
+}
+
+impl Stages for MaterialStages {
+    fn vertex(&self, attribute: &dyn Fn(&str) -> Vector4<f32>) -> VertexOutput {
+        let (model_view, tint) = if self.instanced {
+            let model = Matrix4::from_columns(&[
+                attribute("model0"),
+                attribute("model1"),
+                attribute("model2"),
+                attribute("model3"),
+            ]);
+            (self.view * model, attribute("color"))
+        } else {
+            (self.model_view, Vector4::repeat(1.0))
+        };
+        let view_pos = model_view * point(&attribute("position"));
+        let normal = attribute("normal").xyz().to_homogeneous();
+        let view_normal = model_view * normal;
+        let uv = attribute("texCoord");
+        let tangent = attribute("tangent");
+        let view_tangent = model_view * tangent.xyz().to_homogeneous();
+        VertexOutput {
+            position: self.projection * Vector4::new(view_pos.x, view_pos.y, view_pos.z, 1.0),
+            point_size: 1.0,
+            varyings: varyings(&[
+                view_pos.x,
+                view_pos.y,
+                view_pos.z,
+                view_normal.x,
+                view_normal.y,
+                view_normal.z,
+                tint.x,
+                tint.y,
+                tint.z,
+                tint.w,
+                uv.x,
+                uv.y,
+                view_tangent.x,
+                view_tangent.y,
+                view_tangent.z,
+                tangent.w,
+            ]),
+        }
+    }
+
+    fn fragment(&self, fragment: &Fragment) -> Option<Vector4<f32>> {
+        let v = &fragment.varyings;
+        let mut base_color = self
+            .base_color
+            .component_mul(&Vector4::new(v[6], v[7], v[8], v[9]));
+        let uv = Vector2::new(v[10], v[11]);
+        if let Some(ref texture) = self.base_color_texture {
+            base_color = base_color.component_mul(&texture.sample(uv));
+        }
+        if self.alpha_mask {
+            if base_color.w < self.alpha_cutoff {
+                return None;
+            }
+            base_color.w = 1.0;
+        } else if !self.alpha_blend {
+            base_color.w = 1.0;
+        }
+
+        let mut normal = Vector3::new(v[3], v[4], v[5])
+            .try_normalize(0.0)
+            .unwrap_or_else(Vector3::z);
+        // Geometry without tangents reads a zero tangent, and isn't normal mapped.
+        let mut tangent = Vector3::new(v[12], v[13], v[14]);
+        let has_tangent = tangent.dot(&tangent) > 0.0;
+        if self.double_sided && !fragment.front_facing {
+            normal = -normal;
+            tangent = -tangent;
+        }
+        if let Some(texture) = self.normal_texture.as_ref().filter(|_| has_tangent) {
+            let tangent = (tangent - normal * normal.dot(&tangent))
+                .try_normalize(0.0)
+                .unwrap_or_else(Vector3::x);
+            let bitangent = normal.cross(&tangent) * v[15];
+            let mut mapped = texture.sample(uv).xyz() * 2.0 - Vector3::repeat(1.0);
+            mapped.x *= self.normal_scale;
+            mapped.y *= self.normal_scale;
+            normal = (tangent * mapped.x + bitangent * mapped.y + normal * mapped.z)
+                .try_normalize(0.0)
+                .unwrap_or(normal);
+        }
+
+        let mut metal_factor = self.metal_factor;
+        let mut roughness = self.roughness;
+        if let Some(ref texture) = self.metallic_roughness_texture {
+            let metallic_roughness = texture.sample(uv);
+            roughness *= metallic_roughness.y;
+            metal_factor *= metallic_roughness.z;
+        }
+
+        let sun_vect = self.sun_vect;
+        // Assumes an orthographic projection
+        let view_vect = Vector3::z();
+        let half_vect = (sun_vect + view_vect)
+            .try_normalize(0.0)
+            .unwrap_or(view_vect);
+
+        let normal_dot_sun = normal.dot(&sun_vect).clamp(0.001, 1.0);
+        let normal_dot_view = normal.dot(&view_vect).abs().clamp(0.001, 1.0);
+        let normal_dot_half = normal.dot(&half_vect).clamp(0.0, 1.0);
+        let sun_dot_half = sun_vect.dot(&half_vect).clamp(0.0, 1.0);
+        let view_dot_half = view_vect.dot(&half_vect).clamp(0.0, 1.0);
+
+        let perceptual_roughness = roughness.clamp(MIN_ROUGHNESS, 1.0);
+
+        let f = fresnel(metal_factor, view_dot_half);
+        let g = schlick_occlusion(perceptual_roughness, sun_dot_half, normal_dot_half);
+        let d = trowbridge_reitz_microfacet_distribution(
+            perceptual_roughness * perceptual_roughness,
+            normal_dot_half,
+        );
+        let specular = (f * g * d) / (4.0 * normal_dot_sun * normal_dot_view);
+        let diffuse = 1.0 / PI;
+        let reflectance = base_color.xyz() * (diffuse + specular);
+
+        let reflected_sun = normal_dot_sun.max(0.0) * self.sun_color.component_mul(&reflectance);
+        let mut reflected_ambient = self.ambient.component_mul(&base_color.xyz());
+        if let Some(ref texture) = self.occlusion_texture {
+            let occlusion = texture.sample(uv).x;
+            reflected_ambient *= 1.0 + self.occlusion_strength * (occlusion - 1.0);
+        }
+
+        let mut emitted = self.emissive;
+        if let Some(ref texture) = self.emissive_texture {
+            emitted = emitted.component_mul(&texture.sample(uv).xyz());
+        }
+
+        let color = reflected_sun + reflected_ambient + emitted;
+        Some(Vector4::new(color.x, color.y, color.z, base_color.w))
+    }
+}
+
+struct LineStages {
+    transform: Matrix4<f32>,
+    color: Vector3<f32>,
+    opacity: f32,
+}
+
+impl LineStages {
+    fn new(uniforms: &Uniforms) -> Self {
+        LineStages {
+            transform: uniforms.mat4("projection") * uniforms.mat4("view"),
+            color: uniforms.vec3("color"),
+            opacity: uniforms.f32("opacity"),
+        }
+    }
+}
+
+impl Stages for LineStages {
+    fn vertex(&self, attribute: &dyn Fn(&str) -> Vector4<f32>) -> VertexOutput {
+        VertexOutput {
+            position: self.transform * point(&attribute("position")),
+            point_size: 1.0,
+            varyings: varyings(&[attribute("alpha").x]),
+        }
+    }
+
+    fn fragment(&self, fragment: &Fragment) -> Option<Vector4<f32>> {
+        let alpha = fragment.varyings[0] * self.opacity;
+        Some(Vector4::new(
+            self.color.x,
+            self.color.y,
+            self.color.z,
+            alpha,
+        ))
+    }
+}
+
+// Constants from thick_line_vertex.glsl
+const FEATHER: f32 = 1.0;
+const MIN_MITER_SCALE: f32 = 0.25;
+
+struct ThickLineStages {
+    transform: Matrix4<f32>,
+    projection: Matrix4<f32>,
+    viewport: Vector2<f32>,
+    width: f32,
+    color: Vector3<f32>,
+    opacity: f32,
+    dash_length: f32,
+    dash_gap: f32,
+}
+
+impl ThickLineStages {
+    fn new(uniforms: &Uniforms) -> Self {
+        let projection = uniforms.mat4("projection");
+        ThickLineStages {
+            transform: projection * uniforms.mat4("view") * uniforms.mat4("model"),
+            projection,
+            viewport: uniforms.vec2("viewport"),
+            width: uniforms.f32("width"),
+            color: uniforms.vec3("color"),
+            opacity: uniforms.f32("opacity"),
+            dash_length: uniforms.f32("dash_length"),
+            dash_gap: uniforms.f32("dash_gap"),
+        }
+    }
+
+    fn to_screen(&self, clip_position: &Vector4<f32>) -> Vector2<f32> {
+        (clip_position.xy() / clip_position.w).component_mul(&self.viewport) * 0.5
+    }
+}
+
+impl Stages for ThickLineStages {
+    fn vertex(&self, attribute: &dyn Fn(&str) -> Vector4<f32>) -> VertexOutput {
+        let side = attribute("side").x;
+        let mut clip_position = self.transform * point(&attribute("position"));
+        let screen = self.to_screen(&clip_position);
+        let to_previous =
+            screen - self.to_screen(&(self.transform * point(&attribute("previous"))));
+        let to_next = self.to_screen(&(self.transform * point(&attribute("next")))) - screen;
+
+        let is_start = to_previous.magnitude() < 0.0001;
+        let is_end = to_next.magnitude() < 0.0001;
+        let mut direction_in = if is_start {
+            Vector2::zeros()
+        } else {
+            to_previous.normalize()
+        };
+        let direction_out = if is_end {
+            direction_in
+        } else {
+            to_next.normalize()
+        };
+        if is_start {
+            direction_in = direction_out;
+        }
+
+        let tangent = direction_in + direction_out;
+        let tangent = if tangent.magnitude() < 0.0001 {
+            direction_in
+        } else {
+            tangent.normalize()
+        };
+        let normal = Vector2::new(-tangent.y, tangent.x);
+        let half_width = self.width * 0.5 + FEATHER;
+        let miter_scale = normal
+            .dot(&Vector2::new(-direction_in.y, direction_in.x))
+            .max(MIN_MITER_SCALE);
+        let mut offset = normal * side * half_width / miter_scale;
+        if is_start {
+            offset -= direction_out * half_width;
+        }
+        if is_end {
+            offset += direction_in * half_width;
+        }
+
+        let shift = offset.component_div(&(self.viewport * 0.5)) * clip_position.w;
+        clip_position.x += shift.x;
+        clip_position.y += shift.y;
+
+        let line_distance =
+            attribute("arc_length").x * self.projection[(1, 1)] * self.viewport.y * 0.5;
+        VertexOutput {
+            position: clip_position,
+            point_size: 1.0,
+            varyings: varyings(&[attribute("alpha").x, side * half_width, line_distance]),
+        }
+    }
+
+    fn fragment(&self, fragment: &Fragment) -> Option<Vector4<f32>> {
+        let (alpha, edge_distance, line_distance) = (
+            fragment.varyings[0],
+            fragment.varyings[1],
+            fragment.varyings[2],
+        );
+        if self.dash_gap > 0.0
+            && line_distance.rem_euclid(self.dash_length + self.dash_gap) > self.dash_length
+        {
+            return None;
+        }
+        let coverage = (self.width * 0.5 + 0.5 - edge_distance.abs()).clamp(0.0, 1.0);
+        let alpha = alpha * self.opacity * coverage;
+        Some(Vector4::new(
+            self.color.x,
+            self.color.y,
+            self.color.z,
+            alpha,
+        ))
+    }
+}
+
+struct ParticleStages {
+    transform: Matrix4<f32>,
+    projection: Matrix4<f32>,
+    viewport: Vector2<f32>,
+}
+
+impl ParticleStages {
+    fn new(uniforms: &Uniforms) -> Self {
+        let projection = uniforms.mat4("projection");
+        ParticleStages {
+            transform: projection * uniforms.mat4("view"),
+            projection,
+            viewport: uniforms.vec2("viewport"),
+        }
+    }
+}
+
+impl Stages for ParticleStages {
+    fn vertex(&self, attribute: &dyn Fn(&str) -> Vector4<f32>) -> VertexOutput {
+        let position = self.transform * point(&attribute("position"));
+        let size = attribute("size").x;
+        let point_size =
+            (size * self.projection[(1, 1)] * self.viewport.y * 0.5 / position.w).max(1.0);
+        let color = attribute("color");
+        VertexOutput {
+            position,
+            point_size,
+            varyings: varyings(&[color.x, color.y, color.z, attribute("alpha").x]),
+        }
+    }
+
+    fn fragment(&self, fragment: &Fragment) -> Option<Vector4<f32>> {
+        let radius = (fragment.point_coord - Vector2::new(0.5, 0.5)).magnitude() * 2.0;
+        if radius > 1.0 {
+            return None;
+        }
+        let v = &fragment.varyings;
+        Some(Vector4::new(
+            v[0],
+            v[1],
+            v[2],
+            v[3] * (1.0 - radius * radius),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::glue::shaders;
+
+    fn attributes(source: &str) -> Vec<String> {
+        source
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("attribute "))
+            .filter_map(|declaration| declaration.trim_end_matches(';').split_whitespace().last())
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn identifies_builtin_shaders() {
+        let identify = |source| Pipeline::identify(&attributes(source));
+        assert_eq!(identify(shaders::VERTEX), Some(Pipeline::Material));
+        assert_eq!(
+            identify(shaders::INSTANCED_VERTEX),
+            Some(Pipeline::InstancedMaterial)
+        );
+        assert_eq!(identify(shaders::LINE_VERTEX), Some(Pipeline::Line));
+        assert_eq!(
+            identify(shaders::THICK_LINE_VERTEX),
+            Some(Pipeline::ThickLine)
+        );
+        assert_eq!(identify(shaders::PARTICLE_VERTEX), Some(Pipeline::Particle));
+    }
+
+    #[test]
+    fn rejects_unknown_programs() {
+        let identify = |names: &[&str]| {
+            let names: Vec<String> = names.iter().map(|name| String::from(*name)).collect();
+            Pipeline::identify(&names)
+        };
+        assert_eq!(identify(&[]), None);
+        assert_eq!(identify(&["normal", "alpha"]), None);
+        assert_eq!(identify(&["position", "uv"]), None);
+    }
+}
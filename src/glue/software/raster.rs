@@ -0,0 +1,311 @@
+use std::cell::Cell;
+use std::ops::Range;
+
+use nalgebra::{Vector2, Vector4};
+
+use crate::glue::software::pipeline::{Fragment, Stages, Varyings, VertexOutput, MAX_VARYINGS};
+use crate::glue::software::shader::ShaderProgram;
+use crate::glue::software::ContextState;
+use crate::rendering::shader::{PrimitiveType, ShaderProgram as _};
+
+/// Converts a color with components from 0 to 1 into bytes
+pub fn to_bytes(color: &Vector4<f32>) -> [u8; 4] {
+    let byte = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    [byte(color.x), byte(color.y), byte(color.z), byte(color.w)]
+}
+
+/// A vertex after the vertex stage, in window coordinates
+///
+/// The window's origin is at the top left of the image, with y increasing downward.
+#[derive(Clone, Copy, Debug)]
+struct WindowVertex {
+    position: Vector2<f32>,
+    /// Depth from 0 (near) to 1 (far)
+    depth: f32,
+    /// Reciprocal of the clip space w, which is used for perspective-correct interpolation
+    inv_w: f32,
+    point_size: f32,
+    varyings: Varyings,
+}
+
+/// Draws primitives made from the given vertices with a shader program, once for each instance
+///
+/// Primitives with a vertex behind the camera (with a clip space w that isn't positive) are
+/// skipped rather than clipped. Fragments outside of the depth range are discarded. Triangles
+/// that face away from the camera are culled unless `double_sided` is set.
+pub fn draw(
+    state: &mut ContextState,
+    program: &ShaderProgram,
+    primitive: PrimitiveType,
+    vertices: &[usize],
+    instance_count: usize,
+    double_sided: bool,
+) {
+    let stages = program
+        .pipeline()
+        .stages(&program.uniforms(), &state.textures);
+    for instance in 0..instance_count {
+        draw_instance(
+            state,
+            program,
+            stages.as_ref(),
+            primitive,
+            vertices,
+            instance,
+            double_sided,
+        );
+    }
+}
+
+fn draw_instance(
+    state: &mut ContextState,
+    program: &ShaderProgram,
+    stages: &dyn Stages,
+    primitive: PrimitiveType,
+    vertices: &[usize],
+    instance: usize,
+    double_sided: bool,
+) {
+    let (width, height) = (state.color.width(), state.color.height());
+    let window_vertices: Vec<Option<WindowVertex>> = vertices
+        .iter()
+        .map(|&v| {
+            let output = run_vertex(state, program, stages, v, instance)?;
+            to_window(&output, width, height)
+        })
+        .collect();
+    let mut target = Target {
+        state,
+        stages,
+        double_sided,
+    };
+
+    match primitive {
+        PrimitiveType::Triangles => {
+            for triangle in window_vertices.chunks_exact(3) {
+                if let [Some(a), Some(b), Some(c)] = triangle {
+                    target.triangle(a, b, c);
+                }
+            }
+        }
+        PrimitiveType::TriangleStrip => {
+            for (i, triangle) in window_vertices.windows(3).enumerate() {
+                if let [Some(a), Some(b), Some(c)] = triangle {
+                    // Every other triangle is reversed so that they all have the same winding.
+                    if i % 2 == 0 {
+                        target.triangle(a, b, c);
+                    } else {
+                        target.triangle(b, a, c);
+                    }
+                }
+            }
+        }
+        PrimitiveType::LineStrip => {
+            for line in window_vertices.windows(2) {
+                if let [Some(a), Some(b)] = line {
+                    target.line(a, b);
+                }
+            }
+        }
+        PrimitiveType::Points => {
+            for vertex in window_vertices.iter().flatten() {
+                target.point(vertex);
+            }
+        }
+    }
+}
+
+/// Runs the vertex stage, returning `None` if the vertex is missing from a bound buffer
+fn run_vertex(
+    state: &ContextState,
+    program: &ShaderProgram,
+    stages: &dyn Stages,
+    vertex: usize,
+    instance: usize,
+) -> Option<VertexOutput> {
+    let in_bounds = Cell::new(true);
+    let output = stages.vertex(&|name| {
+        let source = program
+            .attribute(name)
+            .and_then(|info| state.attributes.get(&info.index));
+        match source {
+            Some(source) => source.fetch(vertex, instance).unwrap_or_else(|| {
+                in_bounds.set(false);
+                Vector4::new(0.0, 0.0, 0.0, 1.0)
+            }),
+            // Unbound attributes read as the default (0, 0, 0, 1).
+            None => Vector4::new(0.0, 0.0, 0.0, 1.0),
+        }
+    });
+    if in_bounds.get() {
+        Some(output)
+    } else {
+        None
+    }
+}
+
+fn to_window(output: &VertexOutput, width: u32, height: u32) -> Option<WindowVertex> {
+    let w = output.position.w;
+    if w <= 0.0 || !w.is_finite() {
+        return None;
+    }
+    let ndc = output.position.xyz() / w;
+    Some(WindowVertex {
+        position: Vector2::new(
+            (ndc.x + 1.0) * 0.5 * width as f32,
+            (1.0 - ndc.y) * 0.5 * height as f32,
+        ),
+        depth: ndc.z * 0.5 + 0.5,
+        inv_w: 1.0 / w,
+        point_size: output.point_size,
+        varyings: output.varyings,
+    })
+}
+
+struct Target<'a> {
+    state: &'a mut ContextState,
+    stages: &'a dyn Stages,
+    double_sided: bool,
+}
+
+impl<'a> Target<'a> {
+    /// Shades a fragment, then depth tests it and blends it into the image
+    fn fragment(&mut self, x: u32, y: u32, depth: f32, fragment: &Fragment) {
+        if !(0.0..=1.0).contains(&depth) {
+            return;
+        }
+        let index = (y * self.state.color.width() + x) as usize;
+        if depth >= self.state.depth[index] {
+            return;
+        }
+        let color = match self.stages.fragment(fragment) {
+            Some(color) => color,
+            None => return,
+        };
+        self.state.depth[index] = depth;
+
+        // Blend with (SRC_ALPHA, ONE_MINUS_SRC_ALPHA).
+        let pixel = self.state.color.get_pixel_mut(x, y);
+        let alpha = color.w.clamp(0.0, 1.0);
+        let mut blended = [0; 4];
+        let source = to_bytes(&color);
+        for i in 0..4 {
+            let (src, dst) = (source[i] as f32, pixel.0[i] as f32);
+            blended[i] = (src * alpha + dst * (1.0 - alpha)).round() as u8;
+        }
+        pixel.0 = blended;
+    }
+
+    fn triangle(&mut self, a: &WindowVertex, b: &WindowVertex, c: &WindowVertex) {
+        // With y pointing down, front (counter-clockwise) faces have a negative area.
+        let area = edge(&a.position, &b.position, &c.position);
+        let front_facing = area < 0.0;
+        if area == 0.0 || (!front_facing && !self.double_sided) {
+            return;
+        }
+        let (width, height) = (self.state.color.width(), self.state.color.height());
+        let mins = a.position.inf(&b.position).inf(&c.position);
+        let maxs = a.position.sup(&b.position).sup(&c.position);
+        let x_range = pixel_range(mins.x, maxs.x, width);
+        let y_range = pixel_range(mins.y, maxs.y, height);
+
+        for y in y_range {
+            for x in x_range.clone() {
+                let p = Vector2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let weights = [
+                    edge(&b.position, &c.position, &p) / area,
+                    edge(&c.position, &a.position, &p) / area,
+                    edge(&a.position, &b.position, &p) / area,
+                ];
+                if weights.iter().any(|&w| w < 0.0) {
+                    continue;
+                }
+                let depth = weights[0] * a.depth + weights[1] * b.depth + weights[2] * c.depth;
+                let fragment = Fragment {
+                    varyings: interpolate(&[a, b, c], &weights),
+                    point_coord: Vector2::zeros(),
+                    front_facing,
+                };
+                self.fragment(x, y, depth, &fragment);
+            }
+        }
+    }
+
+    /// Draws a one pixel wide line, including its first pixel but not its last
+    fn line(&mut self, a: &WindowVertex, b: &WindowVertex) {
+        let (width, height) = (self.state.color.width(), self.state.color.height());
+        let delta = b.position - a.position;
+        let steps = delta.x.abs().max(delta.y.abs()).ceil() as usize;
+        for step in 0..steps {
+            let t = (step as f32 + 0.5) / steps as f32;
+            let p = a.position + delta * t;
+            if p.x < 0.0 || p.y < 0.0 || p.x >= width as f32 || p.y >= height as f32 {
+                continue;
+            }
+            let weights = [1.0 - t, t];
+            let fragment = Fragment {
+                varyings: interpolate(&[a, b], &weights),
+                point_coord: Vector2::zeros(),
+                front_facing: true,
+            };
+            let depth = a.depth * (1.0 - t) + b.depth * t;
+            self.fragment(p.x as u32, p.y as u32, depth, &fragment);
+        }
+    }
+
+    fn point(&mut self, vertex: &WindowVertex) {
+        let (width, height) = (self.state.color.width(), self.state.color.height());
+        let size = vertex.point_size.max(1.0);
+        let corner = vertex.position - Vector2::new(size, size) * 0.5;
+        let x_range = pixel_range(corner.x, corner.x + size, width);
+        let y_range = pixel_range(corner.y, corner.y + size, height);
+        for y in y_range {
+            for x in x_range.clone() {
+                let p = Vector2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let point_coord = (p - corner) / size;
+                if point_coord.x > 1.0 || point_coord.y > 1.0 {
+                    continue;
+                }
+                let fragment = Fragment {
+                    varyings: vertex.varyings,
+                    point_coord,
+                    front_facing: true,
+                };
+                self.fragment(x, y, vertex.depth, &fragment);
+            }
+        }
+    }
+}
+
+/// Returns twice the signed area of the triangle (a, b, p)
+fn edge(a: &Vector2<f32>, b: &Vector2<f32>, p: &Vector2<f32>) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+/// Returns the pixels whose centers might be between `min` and `max`, clamped to the image
+fn pixel_range(min: f32, max: f32, size: u32) -> Range<u32> {
+    let start = (min - 0.5).ceil().max(0.0);
+    let end = ((max - 0.5).floor() + 1.0).min(size as f32);
+    if start < end {
+        start as u32..end as u32
+    } else {
+        0..0
+    }
+}
+
+/// Interpolates varyings with perspective correction, given screen space weights
+fn interpolate(vertices: &[&WindowVertex], weights: &[f32]) -> Varyings {
+    let inv_w: f32 = vertices
+        .iter()
+        .zip(weights)
+        .map(|(v, weight)| v.inv_w * weight)
+        .sum();
+    let mut varyings = [0.0; MAX_VARYINGS];
+    for (v, weight) in vertices.iter().zip(weights) {
+        let scale = v.inv_w * weight / inv_w;
+        for (out, value) in varyings.iter_mut().zip(v.varyings.iter()) {
+            *out += value * scale;
+        }
+    }
+    varyings
+}
@@ -0,0 +1,357 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Display;
+use std::rc::Rc;
+
+use nalgebra::{Matrix4, Vector2, Vector3, Vector4};
+
+use crate::glue::software::pipeline::Pipeline;
+use crate::glue::software::raster;
+use crate::glue::software::texture::Texture;
+use crate::glue::software::{ContextState, SoftwareContext};
+use crate::rendering::glsl::{Declarations, UniformValue};
+use crate::rendering::mesh::ElementIndices;
+use crate::rendering::shader;
+use crate::rendering::shader::{BoundShader, PrimitiveType, ShaderParamInfo, ShaderType};
+use crate::rendering::texture::TextureUnits;
+
+pub use crate::rendering::glsl::ShaderCreationError;
+
+#[derive(Debug)]
+pub struct Shader {
+    shader_type: ShaderType,
+    declarations: Declarations,
+}
+
+impl Shader {
+    /// "Compiles" a shader by reading its declarations
+    ///
+    /// The code itself is ignored; the program that the shader belongs to is run by one of the
+    /// built-in pipelines.
+    pub fn compile(shader_type: ShaderType, source: &str) -> Result<Shader, ShaderCreationError> {
+        Ok(Shader {
+            shader_type,
+            declarations: Declarations::parse(source)?,
+        })
+    }
+}
+
+impl shader::Shader for Shader {
+    type RenderingContext = SoftwareContext;
+
+    fn shader_type(&self) -> ShaderType {
+        self.shader_type
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ShaderLinkError {
+    MissingShader(ShaderType),
+    /// The program's attributes don't match any of the built-in pipelines.
+    UnsupportedProgram(Vec<String>),
+}
+
+impl Display for ShaderLinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ShaderLinkError::MissingShader(shader_type) => {
+                write!(f, "Shader program has no {:?} shader", shader_type)
+            }
+            ShaderLinkError::UnsupportedProgram(attributes) => write!(
+                f,
+                "No software pipeline for shader program with attributes {}",
+                attributes.join(", ")
+            ),
+        }
+    }
+}
+
+impl Error for ShaderLinkError {}
+
+/// The current uniform values of a shader program, which are read by name
+///
+/// Uniforms that don't exist or that have the wrong type read as zero.
+#[derive(Clone, Debug)]
+pub struct Uniforms {
+    names: HashMap<String, usize>,
+    values: Vec<UniformValue>,
+}
+
+impl Uniforms {
+    fn get(&self, name: &str) -> Option<&UniformValue> {
+        self.values.get(*self.names.get(name)?)
+    }
+
+    pub fn bool(&self, name: &str) -> bool {
+        self.int(name) != 0
+    }
+
+    pub fn int(&self, name: &str) -> i32 {
+        match self.get(name) {
+            Some(UniformValue::Int(value)) => *value,
+            _ => 0,
+        }
+    }
+
+    pub fn f32(&self, name: &str) -> f32 {
+        match self.get(name) {
+            Some(UniformValue::Float(value)) => *value,
+            _ => 0.0,
+        }
+    }
+
+    pub fn vec2(&self, name: &str) -> Vector2<f32> {
+        match self.get(name) {
+            Some(UniformValue::Vec2(value)) => *value,
+            _ => Vector2::zeros(),
+        }
+    }
+
+    pub fn vec3(&self, name: &str) -> Vector3<f32> {
+        match self.get(name) {
+            Some(UniformValue::Vec3(value)) => *value,
+            _ => Vector3::zeros(),
+        }
+    }
+
+    pub fn vec4(&self, name: &str) -> Vector4<f32> {
+        match self.get(name) {
+            Some(UniformValue::Vec4(value)) => *value,
+            _ => Vector4::zeros(),
+        }
+    }
+
+    pub fn mat4(&self, name: &str) -> Matrix4<f32> {
+        match self.get(name) {
+            Some(UniformValue::Mat4(value)) => *value,
+            _ => Matrix4::zeros(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ShaderProgram {
+    state: Rc<RefCell<ContextState>>,
+    pipeline: Pipeline,
+    attributes: Vec<String>,
+    uniform_names: Vec<String>,
+    uniforms: RefCell<Uniforms>,
+}
+
+impl ShaderProgram {
+    pub fn link<'a, T: Iterator<Item = &'a Shader>>(
+        state: Rc<RefCell<ContextState>>,
+        shaders: T,
+    ) -> Result<ShaderProgram, ShaderLinkError> {
+        let mut vertex = None;
+        let mut fragment = None;
+        for shader in shaders {
+            match shader.shader_type {
+                ShaderType::Vertex => vertex = Some(shader),
+                ShaderType::Fragment => fragment = Some(shader),
+            }
+        }
+        let vertex = vertex.ok_or(ShaderLinkError::MissingShader(ShaderType::Vertex))?;
+        let fragment = fragment.ok_or(ShaderLinkError::MissingShader(ShaderType::Fragment))?;
+
+        let attributes = vertex.declarations.attributes.clone();
+        let pipeline = Pipeline::identify(&attributes)
+            .ok_or_else(|| ShaderLinkError::UnsupportedProgram(attributes.clone()))?;
+
+        // Uniforms that are declared in both shaders are shared.
+        let mut names = HashMap::new();
+        let mut uniform_names = Vec::new();
+        let mut values = Vec::new();
+        let declared = vertex
+            .declarations
+            .uniforms
+            .iter()
+            .chain(fragment.declarations.uniforms.iter());
+        for (name, uniform_type) in declared {
+            if !names.contains_key(name) {
+                names.insert(name.clone(), values.len());
+                uniform_names.push(name.clone());
+                values.push(UniformValue::zero(*uniform_type));
+            }
+        }
+
+        Ok(ShaderProgram {
+            state,
+            pipeline,
+            attributes,
+            uniform_names,
+            uniforms: RefCell::new(Uniforms { names, values }),
+        })
+    }
+
+    pub fn is_same_context(&self, state: &Rc<RefCell<ContextState>>) -> bool {
+        Rc::ptr_eq(&self.state, state)
+    }
+
+    pub fn pipeline(&self) -> Pipeline {
+        self.pipeline
+    }
+
+    pub fn uniforms(&self) -> Uniforms {
+        self.uniforms.borrow().clone()
+    }
+
+    fn set_uniform(&self, index: usize, value: UniformValue) {
+        if let Some(uniform) = self.uniforms.borrow_mut().values.get_mut(index) {
+            *uniform = value;
+        }
+    }
+}
+
+impl shader::ShaderProgram for ShaderProgram {
+    type RenderingContext = SoftwareContext;
+
+    fn attribute_names(&self) -> Vec<String> {
+        self.attributes.clone()
+    }
+
+    fn uniform_names(&self) -> Vec<String> {
+        self.uniform_names.clone()
+    }
+
+    fn attribute(&self, name: &str) -> Option<ShaderParamInfo> {
+        self.attributes
+            .iter()
+            .position(|a| a == name)
+            .map(|index| ShaderParamInfo { index })
+    }
+
+    fn uniform(&self, name: &str) -> Option<ShaderParamInfo> {
+        self.uniform_names
+            .iter()
+            .position(|u| u == name)
+            .map(|index| ShaderParamInfo { index })
+    }
+}
+
+// The minimum that WebGL 2 guarantees
+const MAX_TEXTURE_UNITS: usize = 16;
+
+pub struct SoftwareBoundShader {
+    state: Rc<RefCell<ContextState>>,
+    shader: Rc<ShaderProgram>,
+    texture_units: TextureUnits,
+    double_sided: Cell<bool>,
+}
+
+impl SoftwareBoundShader {
+    pub fn new(state: Rc<RefCell<ContextState>>, shader: Rc<ShaderProgram>) -> SoftwareBoundShader {
+        SoftwareBoundShader {
+            state,
+            shader,
+            texture_units: TextureUnits::new(MAX_TEXTURE_UNITS),
+            double_sided: Cell::new(false),
+        }
+    }
+
+    fn draw(&self, primitive: PrimitiveType, vertices: &[usize]) {
+        self.draw_instanced(primitive, vertices, 1);
+    }
+
+    fn draw_instanced(&self, primitive: PrimitiveType, vertices: &[usize], instance_count: usize) {
+        raster::draw(
+            &mut self.state.borrow_mut(),
+            &self.shader,
+            primitive,
+            vertices,
+            instance_count,
+            self.double_sided.get(),
+        );
+    }
+}
+
+impl BoundShader<SoftwareContext> for SoftwareBoundShader {
+    fn program(&self) -> &ShaderProgram {
+        &self.shader
+    }
+
+    fn draw_triangles(&self, count: usize) {
+        let vertices: Vec<usize> = (0..count).collect();
+        self.draw(PrimitiveType::Triangles, &vertices);
+    }
+
+    fn draw_indexed_triangles(&self, indices: &ElementIndices<SoftwareContext>) {
+        let vertices = indices.buffer.indices(&indices.binding);
+        self.draw(PrimitiveType::Triangles, &vertices);
+    }
+
+    fn draw_triangles_instanced(&self, count: usize, instance_count: usize) {
+        let vertices: Vec<usize> = (0..count).collect();
+        self.draw_instanced(PrimitiveType::Triangles, &vertices, instance_count);
+    }
+
+    fn draw_indexed_triangles_instanced(
+        &self,
+        indices: &ElementIndices<SoftwareContext>,
+        instance_count: usize,
+    ) {
+        let vertices = indices.buffer.indices(&indices.binding);
+        self.draw_instanced(PrimitiveType::Triangles, &vertices, instance_count);
+    }
+
+    fn draw_polyline(&self, num_vertices: usize) {
+        let vertices: Vec<usize> = (0..num_vertices).collect();
+        self.draw(PrimitiveType::LineStrip, &vertices);
+    }
+
+    fn draw_triangle_strip(&self, num_vertices: usize) {
+        let vertices: Vec<usize> = (0..num_vertices).collect();
+        self.draw(PrimitiveType::TriangleStrip, &vertices);
+    }
+
+    fn draw_points(&self, num_vertices: usize) {
+        let vertices: Vec<usize> = (0..num_vertices).collect();
+        self.draw(PrimitiveType::Points, &vertices);
+    }
+
+    fn set_double_sided(&self, double_sided: bool) {
+        self.double_sided.set(double_sided);
+    }
+
+    fn disable_attribute(&self, index: usize) {
+        self.state.borrow_mut().attributes.remove(&index);
+    }
+
+    fn set_uniform_bool(&self, index: usize, value: bool) {
+        self.shader
+            .set_uniform(index, UniformValue::Int(value as i32));
+    }
+
+    fn set_uniform_f32(&self, index: usize, value: f32) {
+        self.shader.set_uniform(index, UniformValue::Float(value));
+    }
+
+    fn set_uniform_mat4(&self, index: usize, value: Matrix4<f32>) {
+        self.shader.set_uniform(index, UniformValue::Mat4(value));
+    }
+
+    fn set_uniform_vec2(&self, index: usize, value: Vector2<f32>) {
+        self.shader.set_uniform(index, UniformValue::Vec2(value));
+    }
+
+    fn set_uniform_vec3(&self, index: usize, value: Vector3<f32>) {
+        self.shader.set_uniform(index, UniformValue::Vec3(value));
+    }
+
+    fn set_uniform_vec4(&self, index: usize, value: Vector4<f32>) {
+        self.shader.set_uniform(index, UniformValue::Vec4(value));
+    }
+
+    fn set_uniform_texture(&self, index: usize, texture: &Texture) {
+        if let Some(unit) = self.texture_units.unit_for(index) {
+            let mut state = self.state.borrow_mut();
+            match texture.bound() {
+                Some(bound) => state.textures.insert(unit, bound),
+                None => state.textures.remove(&unit),
+            };
+            self.shader
+                .set_uniform(index, UniformValue::Int(unit as i32));
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use image::RgbaImage;
+use nalgebra::{Vector2, Vector4};
+
+use crate::glue::software::SoftwareContext;
+use crate::rendering::texture;
+use crate::rendering::texture::{
+    MagFilter, Sampler, SetTextureDataError, TextureData, TextureFormat, TextureShape, WrapMode,
+};
+
+/// A texture whose image is kept as RGBA, whatever format it was uploaded in
+///
+/// There are no mipmaps. Fragments don't know how far apart their texture coordinates are, so
+/// every sample is filtered with the sampler's `mag_filter`.
+#[derive(Debug, Default)]
+pub struct Texture {
+    image: RefCell<Option<Rc<RgbaImage>>>,
+    format: Cell<Option<TextureFormat>>,
+    sampler: Cell<Sampler>,
+}
+
+impl Texture {
+    pub fn new() -> Texture {
+        Texture::default()
+    }
+
+    /// Returns the texture's image, if it has been set
+    pub fn image(&self) -> Option<Rc<RgbaImage>> {
+        self.image.borrow().clone()
+    }
+
+    /// Captures the texture's current image and sampler for a draw
+    pub fn bound(&self) -> Option<BoundTexture> {
+        Some(BoundTexture {
+            image: self.image()?,
+            sampler: self.sampler.get(),
+        })
+    }
+
+    fn shape(&self) -> Option<TextureShape> {
+        let image = self.image.borrow();
+        let image = image.as_ref()?;
+        Some(TextureShape {
+            format: self.format.get()?,
+            width: image.width(),
+            height: image.height(),
+        })
+    }
+}
+
+impl texture::Texture for Texture {
+    type RenderingContext = SoftwareContext;
+
+    fn set_data(&self, data: TextureData) -> Result<(), SetTextureDataError> {
+        let mut image = RgbaImage::new(data.width(), data.height());
+        copy_into(&mut image, 0, 0, &data);
+        *self.image.borrow_mut() = Some(Rc::new(image));
+        self.format.set(Some(data.format()));
+        Ok(())
+    }
+
+    fn set_sub_data(&self, x: u32, y: u32, data: TextureData) -> Result<(), SetTextureDataError> {
+        self.shape()
+            .ok_or(SetTextureDataError::IncompatibleSubImage)?
+            .check_sub_image(x, y, &data)?;
+        if let Some(ref mut image) = *self.image.borrow_mut() {
+            // Draws that already captured the image keep the old copy.
+            copy_into(Rc::make_mut(image), x, y, &data);
+        }
+        Ok(())
+    }
+
+    fn generate_mipmaps(&self) {}
+
+    fn set_sampler(&self, sampler: &Sampler) {
+        self.sampler.set(*sampler);
+    }
+}
+
+/// Writes pixels into an RGBA image, expanding them the way that WebGL samples them
+fn copy_into(image: &mut RgbaImage, x: u32, y: u32, data: &TextureData) {
+    if data.width() == 0 {
+        return;
+    }
+    let channels = data.format().channels();
+    let rows = data.pixels().chunks_exact(data.width() as usize * channels);
+    for (row, dy) in rows.zip(0..) {
+        for (pixel, dx) in row.chunks_exact(channels).zip(0..) {
+            let rgba = match data.format() {
+                TextureFormat::Gray => [pixel[0], pixel[0], pixel[0], 255],
+                TextureFormat::GrayAlpha => [pixel[0], pixel[0], pixel[0], pixel[1]],
+                TextureFormat::Rgb => [pixel[0], pixel[1], pixel[2], 255],
+                TextureFormat::Rgba => [pixel[0], pixel[1], pixel[2], pixel[3]],
+            };
+            image.put_pixel(x + dx, y + dy, image::Rgba(rgba));
+        }
+    }
+}
+
+/// A texture's image and sampler, as they were when the texture was bound
+#[derive(Clone, Debug)]
+pub struct BoundTexture {
+    image: Rc<RgbaImage>,
+    sampler: Sampler,
+}
+
+impl BoundTexture {
+    /// Samples the image at texture coordinates that run from 0 to 1 (`texture2D`)
+    pub fn sample(&self, uv: Vector2<f32>) -> Vector4<f32> {
+        let (width, height) = self.image.dimensions();
+        if width == 0 || height == 0 {
+            return Vector4::new(0.0, 0.0, 0.0, 1.0);
+        }
+        let x = uv.x * width as f32;
+        let y = uv.y * height as f32;
+        match self.sampler.mag_filter {
+            MagFilter::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            MagFilter::Linear => {
+                // Blend the four texels whose centers surround the sample.
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let top = self.texel(x0, y0).lerp(&self.texel(x0 + 1, y0), fx);
+                let bottom = self.texel(x0, y0 + 1).lerp(&self.texel(x0 + 1, y0 + 1), fx);
+                top.lerp(&bottom, fy)
+            }
+        }
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Vector4<f32> {
+        let (width, height) = self.image.dimensions();
+        let x = wrap(x, width, self.sampler.wrap_s);
+        let y = wrap(y, height, self.sampler.wrap_t);
+        let pixel = self.image.get_pixel(x, y);
+        Vector4::new(
+            pixel[0] as f32 / 255.0,
+            pixel[1] as f32 / 255.0,
+            pixel[2] as f32 / 255.0,
+            pixel[3] as f32 / 255.0,
+        )
+    }
+}
+
+/// Maps a texel coordinate onto the image
+fn wrap(coordinate: i64, size: u32, mode: WrapMode) -> u32 {
+    let size = size as i64;
+    let wrapped = match mode {
+        WrapMode::Repeat => coordinate.rem_euclid(size),
+        WrapMode::MirroredRepeat => {
+            let period = coordinate.rem_euclid(2 * size);
+            if period < size {
+                period
+            } else {
+                2 * size - 1 - period
+            }
+        }
+        WrapMode::ClampToEdge => coordinate.clamp(0, size - 1),
+    };
+    wrapped as u32
+}
@@ -3,7 +3,7 @@ use std::fmt::{Debug, Formatter};
 use std::rc::Rc;
 use std::slice;
 
-use web_sys::{WebGlBuffer, WebGlRenderingContext};
+use web_sys::{AngleInstancedArrays, WebGlBuffer, WebGlRenderingContext};
 
 use crate::glue::webgl::WebGlContext;
 use crate::rendering;
@@ -84,12 +84,17 @@ impl Buffer {
 #[derive(Debug)]
 pub struct AttributeBuffer {
     buffer: Buffer,
+    instancing: Option<Rc<AngleInstancedArrays>>,
 }
 
 impl AttributeBuffer {
-    pub fn new(context: Rc<WebGlRenderingContext>) -> Option<Self> {
+    pub fn new(
+        context: Rc<WebGlRenderingContext>,
+        instancing: Option<Rc<AngleInstancedArrays>>,
+    ) -> Option<Self> {
         Some(AttributeBuffer {
             buffer: Buffer::new(context, BufferBinding::ArrayBuffer)?,
+            instancing,
         })
     }
 }
@@ -114,6 +119,11 @@ impl rendering::buffer::AttributeBuffer for AttributeBuffer {
             binding.offset as i32,
         );
         self.buffer.context.enable_vertex_attrib_array(index as u32);
+        // Divisors belong to the attribute location rather than the program, so they're always
+        // set to undo any earlier instanced binding.
+        if let Some(ref instancing) = self.instancing {
+            instancing.vertex_attrib_divisor_angle(index as u32, binding.divisor as u32);
+        }
     }
 }
 
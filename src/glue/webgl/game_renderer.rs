@@ -1,31 +1,30 @@
-use std::error::Error;
 use std::rc::Rc;
 
-use web_sys::WebGlRenderingContext;
+use web_sys::{HtmlCanvasElement, WebGlRenderingContext};
 
+use crate::glue::game_handle::CanvasRenderer;
+use crate::glue::shaders::BuiltinShaders;
 use crate::glue::webgl::WebGlContext;
-use crate::rendering::line::LineShader;
+use crate::rendering::line::{LineShader, ThickLineShader};
 use crate::rendering::material::MaterialShader;
-use crate::state::GameState;
-use crate::state_renderer::GameRenderer;
+use crate::rendering::particle::ParticleShader;
+use crate::rendering::queue::RenderQueue;
+use crate::state::{GameState, OverlayRenderer};
+use crate::state_renderer::{GameRenderer, GravityOverlayMode, GravityOverlayRenderer};
 
 #[derive(Debug)]
 pub struct WebGlRenderer {
     context: Rc<WebGlContext>,
-    material_shader: MaterialShader<WebGlContext>,
-    line_shader: LineShader<WebGlContext>,
+    shaders: BuiltinShaders<WebGlContext>,
+    render_queue: RenderQueue<WebGlContext>,
 }
 
 impl WebGlRenderer {
-    pub fn new(
-        context: Rc<WebGlContext>,
-        material_shader: MaterialShader<WebGlContext>,
-        line_shader: LineShader<WebGlContext>,
-    ) -> WebGlRenderer {
+    pub fn new(context: Rc<WebGlContext>, shaders: BuiltinShaders<WebGlContext>) -> WebGlRenderer {
         WebGlRenderer {
             context,
-            material_shader,
-            line_shader,
+            shaders,
+            render_queue: RenderQueue::new(),
         }
     }
 
@@ -34,6 +33,11 @@ impl WebGlRenderer {
         self.gl_context().cull_face(WebGlRenderingContext::BACK);
         self.gl_context().enable(WebGlRenderingContext::DEPTH_TEST);
         self.gl_context().depth_func(WebGlRenderingContext::LESS);
+        self.gl_context().enable(WebGlRenderingContext::BLEND);
+        self.gl_context().blend_func(
+            WebGlRenderingContext::SRC_ALPHA,
+            WebGlRenderingContext::ONE_MINUS_SRC_ALPHA,
+        );
     }
 
     pub fn context(&self) -> &Rc<WebGlContext> {
@@ -53,27 +57,56 @@ impl GameRenderer for WebGlRenderer {
     }
 
     fn material_shader(&self) -> &MaterialShader<WebGlContext> {
-        &self.material_shader
+        &self.shaders.material
+    }
+
+    fn instanced_material_shader(&self) -> Option<&MaterialShader<WebGlContext>> {
+        self.shaders.instanced_material.as_ref()
     }
 
     fn line_shader(&self) -> &LineShader<WebGlContext> {
-        &self.line_shader
+        &self.shaders.line
+    }
+
+    fn thick_line_shader(&self) -> &ThickLineShader<WebGlContext> {
+        &self.shaders.thick_line
+    }
+
+    fn particle_shader(&self) -> &ParticleShader<WebGlContext> {
+        &self.shaders.particle
+    }
+
+    fn render_queue(&self) -> &RenderQueue<WebGlContext> {
+        &self.render_queue
     }
 
-    fn render(&self, state: &mut GameState) -> Result<(), Box<dyn Error>> {
+    fn begin_frame(&self, state: &mut GameState) {
         self.context.set_viewport();
         state.camera.aspect_ratio = self.context.aspect_ratio();
         self.gl_context().clear_color(0.0, 0.0, 0.0, 1.0);
         self.gl_context().clear(
             WebGlRenderingContext::COLOR_BUFFER_BIT | WebGlRenderingContext::DEPTH_BUFFER_BIT,
         );
+    }
+}
 
-        for entity in state.iter_entities() {
-            if let Some(ref renderer) = entity.renderer {
-                renderer.render(entity, state);
-            }
-        }
+impl CanvasRenderer for WebGlRenderer {
+    fn canvas(&self) -> &HtmlCanvasElement {
+        self.context.canvas()
+    }
+
+    fn drawing_buffer_size(&self) -> (f32, f32) {
+        (self.context.width() as f32, self.context.height() as f32)
+    }
+
+    fn device_pixel_ratio(&self) -> f32 {
+        self.context.device_pixel_ratio() as f32
+    }
 
-        Ok(())
+    fn gravity_overlay(self: Rc<Self>, mode: GravityOverlayMode) -> Rc<dyn OverlayRenderer> {
+        Rc::new(GravityOverlayRenderer::new(
+            self as Rc<dyn GameRenderer<Context = WebGlContext>>,
+            mode,
+        ))
     }
 }
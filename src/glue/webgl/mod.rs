@@ -2,7 +2,8 @@ use std::cell::Cell;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
 
-use web_sys::WebGlRenderingContext;
+use wasm_bindgen::JsCast;
+use web_sys::{AngleInstancedArrays, WebGlRenderingContext};
 use web_sys::{Element, HtmlCanvasElement};
 
 use crate::glue::webgl::shader::{ShaderProgram, WebGlBoundShader};
@@ -19,6 +20,8 @@ pub struct WebGlContext {
     canvas_element: Element,
     canvas: HtmlCanvasElement,
     gl_context: Rc<WebGlRenderingContext>,
+    instancing: Option<Rc<AngleInstancedArrays>>,
+    max_texture_units: usize,
     shader_bound: Cell<bool>,
 }
 
@@ -30,10 +33,25 @@ impl WebGlContext {
         canvas: HtmlCanvasElement,
         gl_context: WebGlRenderingContext,
     ) -> WebGlContext {
+        // Extension objects aren't instances of their interfaces in every browser, so they can't
+        // be checked with dyn_into().
+        let instancing = gl_context
+            .get_extension("ANGLE_instanced_arrays")
+            .ok()
+            .flatten()
+            .map(|extension| Rc::new(extension.unchecked_into::<AngleInstancedArrays>()));
+        // Every WebGL implementation supports at least 8.
+        let max_texture_units = gl_context
+            .get_parameter(WebGlRenderingContext::MAX_TEXTURE_IMAGE_UNITS)
+            .ok()
+            .and_then(|units| units.as_f64())
+            .map_or(8, |units| units as usize);
         WebGlContext {
             canvas_element,
             canvas,
             gl_context: Rc::new(gl_context),
+            instancing,
+            max_texture_units,
             shader_bound: Cell::new(false),
         }
     }
@@ -65,31 +83,11 @@ impl WebGlContext {
      * Always returns a finite, positive value.
      */
     pub fn device_pixel_ratio(&self) -> f64 {
-        let ratio = web_sys::window().unwrap().device_pixel_ratio();
-        if !ratio.is_finite() || ratio <= 0.0 {
-            if !HAS_WARNED_ABOUT_PIXEL_RATIO.swap(true, Ordering::Relaxed) {
-                log::warn!("Invalid device pixel ratio");
-            }
-            1.0
-        } else {
-            ratio
-        }
+        device_pixel_ratio()
     }
 
     pub fn set_viewport(&self) {
-        use std::cmp::max;
-
-        let scale = self.device_pixel_ratio();
-        let width = (max(self.canvas_element.client_width(), 0) as f64 * scale) as u32;
-        let height = (max(self.canvas_element.client_height(), 0) as f64 * scale) as u32;
-
-        if self.canvas.width() != width {
-            self.canvas.set_width(width);
-        }
-        if self.canvas.height() != height {
-            self.canvas.set_height(height);
-        }
-
+        fit_canvas_to_element(&self.canvas_element, &self.canvas);
         self.gl_context.viewport(
             0,
             0,
@@ -99,6 +97,39 @@ impl WebGlContext {
     }
 }
 
+/**
+ * Returns the device pixel ratio.
+ *
+ * Always returns a finite, positive value.
+ */
+pub fn device_pixel_ratio() -> f64 {
+    let ratio = web_sys::window().unwrap().device_pixel_ratio();
+    if !ratio.is_finite() || ratio <= 0.0 {
+        if !HAS_WARNED_ABOUT_PIXEL_RATIO.swap(true, Ordering::Relaxed) {
+            log::warn!("Invalid device pixel ratio");
+        }
+        1.0
+    } else {
+        ratio
+    }
+}
+
+/// Resizes a canvas's drawing buffer to cover its element in device pixels
+pub fn fit_canvas_to_element(canvas_element: &Element, canvas: &HtmlCanvasElement) {
+    use std::cmp::max;
+
+    let scale = device_pixel_ratio();
+    let width = (max(canvas_element.client_width(), 0) as f64 * scale) as u32;
+    let height = (max(canvas_element.client_height(), 0) as f64 * scale) as u32;
+
+    if canvas.width() != width {
+        canvas.set_width(width);
+    }
+    if canvas.height() != height {
+        canvas.set_height(height);
+    }
+}
+
 impl RenderingContext for WebGlContext {
     type AttributeBuffer = buffer::AttributeBuffer;
     type IndexBuffer = buffer::IndexBuffer;
@@ -110,7 +141,7 @@ impl RenderingContext for WebGlContext {
     type Texture = texture::Texture;
 
     fn make_attribute_buffer(&self) -> Result<Self::AttributeBuffer, ()> {
-        Self::AttributeBuffer::new(Rc::clone(&self.gl_context)).ok_or(())
+        Self::AttributeBuffer::new(Rc::clone(&self.gl_context), self.instancing.clone()).ok_or(())
     }
 
     fn make_index_buffer(&self) -> Result<Self::IndexBuffer, ()> {
@@ -136,6 +167,18 @@ impl RenderingContext for WebGlContext {
         Self::ShaderProgram::link(Rc::clone(&self.gl_context), shaders)
     }
 
+    fn viewport_size(&self) -> (u32, u32) {
+        (self.width().max(0) as u32, self.height().max(0) as u32)
+    }
+
+    fn pixel_ratio(&self) -> f32 {
+        self.device_pixel_ratio() as f32
+    }
+
+    fn supports_instancing(&self) -> bool {
+        self.instancing.is_some()
+    }
+
     fn bind_shader(
         &self,
         shader: Rc<Self::ShaderProgram>,
@@ -148,6 +191,11 @@ impl RenderingContext for WebGlContext {
         }
 
         shader.bind();
-        Ok(WebGlBoundShader::new(Rc::clone(&self.gl_context), shader))
+        Ok(WebGlBoundShader::new(
+            Rc::clone(&self.gl_context),
+            self.instancing.clone(),
+            shader,
+            self.max_texture_units,
+        ))
     }
 }
@@ -1,16 +1,21 @@
+use std::cell::Cell;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt::Display;
 use std::rc::Rc;
 
-use nalgebra::{Matrix4, Vector3, Vector4};
-use web_sys::{WebGlProgram, WebGlRenderingContext, WebGlShader, WebGlUniformLocation};
+use nalgebra::{Matrix4, Vector2, Vector3, Vector4};
+use web_sys::{
+    AngleInstancedArrays, WebGlProgram, WebGlRenderingContext, WebGlShader, WebGlUniformLocation,
+};
 
+use crate::glue::webgl::texture::Texture;
 use crate::glue::webgl::WebGlContext;
 use crate::rendering::buffer::IndexType;
 use crate::rendering::mesh::ElementIndices;
 use crate::rendering::shader;
 use crate::rendering::shader::{BoundShader, ShaderParamInfo, ShaderType};
+use crate::rendering::texture::TextureUnits;
 
 #[derive(Clone, Debug)]
 pub enum ShaderCreationError {
@@ -235,12 +240,39 @@ impl shader::ShaderProgram for ShaderProgram {
 
 pub struct WebGlBoundShader {
     context: Rc<WebGlRenderingContext>,
+    instancing: Option<Rc<AngleInstancedArrays>>,
     shader: Rc<ShaderProgram>,
+    texture_units: TextureUnits,
+    double_sided: Cell<bool>,
 }
 
 impl WebGlBoundShader {
-    pub fn new(context: Rc<WebGlRenderingContext>, shader: Rc<ShaderProgram>) -> WebGlBoundShader {
-        WebGlBoundShader { context, shader }
+    pub fn new(
+        context: Rc<WebGlRenderingContext>,
+        instancing: Option<Rc<AngleInstancedArrays>>,
+        shader: Rc<ShaderProgram>,
+        max_texture_units: usize,
+    ) -> WebGlBoundShader {
+        WebGlBoundShader {
+            context,
+            instancing,
+            shader,
+            texture_units: TextureUnits::new(max_texture_units),
+            double_sided: Cell::new(false),
+        }
+    }
+
+    fn instancing(&self) -> Option<&AngleInstancedArrays> {
+        if self.instancing.is_none() {
+            log::error!("Instanced draw without ANGLE_instanced_arrays");
+        }
+        self.instancing.as_deref()
+    }
+}
+
+impl Drop for WebGlBoundShader {
+    fn drop(&mut self) {
+        self.set_double_sided(false);
     }
 }
 
@@ -264,11 +296,71 @@ impl BoundShader<WebGlContext> for WebGlBoundShader {
         );
     }
 
+    fn draw_triangles_instanced(&self, count: usize, instance_count: usize) {
+        if let Some(instancing) = self.instancing() {
+            instancing.draw_arrays_instanced_angle(
+                WebGlRenderingContext::TRIANGLES,
+                0,
+                count as i32,
+                instance_count as i32,
+            );
+        }
+    }
+
+    fn draw_indexed_triangles_instanced(
+        &self,
+        indices: &ElementIndices<WebGlContext>,
+        instance_count: usize,
+    ) {
+        if let Some(instancing) = self.instancing() {
+            indices.buffer.bind();
+            instancing.draw_elements_instanced_angle_with_i32(
+                WebGlRenderingContext::TRIANGLES,
+                indices.binding.count as i32,
+                to_gl_element_type(indices.binding.index_type),
+                indices.binding.offset as i32,
+                instance_count as i32,
+            );
+        }
+    }
+
     fn draw_polyline(&self, num_vertices: usize) {
         self.context
             .draw_arrays(WebGlRenderingContext::LINE_STRIP, 0, num_vertices as i32);
     }
 
+    fn draw_triangle_strip(&self, num_vertices: usize) {
+        self.context.draw_arrays(
+            WebGlRenderingContext::TRIANGLE_STRIP,
+            0,
+            num_vertices as i32,
+        );
+    }
+
+    fn draw_points(&self, num_vertices: usize) {
+        self.context
+            .draw_arrays(WebGlRenderingContext::POINTS, 0, num_vertices as i32);
+    }
+
+    fn set_double_sided(&self, double_sided: bool) {
+        if double_sided != self.double_sided.replace(double_sided) {
+            if double_sided {
+                self.context.disable(WebGlRenderingContext::CULL_FACE);
+            } else {
+                self.context.enable(WebGlRenderingContext::CULL_FACE);
+            }
+        }
+    }
+
+    fn disable_attribute(&self, index: usize) {
+        self.context.disable_vertex_attrib_array(index as u32);
+    }
+
+    fn set_uniform_bool(&self, index: usize, value: bool) {
+        self.context
+            .uniform1i(Some(&self.shader.uniforms[index].location), value as i32);
+    }
+
     fn set_uniform_f32(&self, index: usize, value: f32) {
         self.context
             .uniform1f(Some(&self.shader.uniforms[index].location), value);
@@ -283,6 +375,12 @@ impl BoundShader<WebGlContext> for WebGlBoundShader {
         );
     }
 
+    fn set_uniform_vec2(&self, index: usize, mut value: Vector2<f32>) {
+        let raw: &mut [f32; 2] = value.as_mut();
+        self.context
+            .uniform2fv_with_f32_array(Some(&self.shader.uniforms[index].location), raw);
+    }
+
     fn set_uniform_vec3(&self, index: usize, mut value: Vector3<f32>) {
         let raw: &mut [f32; 3] = value.as_mut();
         self.context
@@ -294,6 +392,16 @@ impl BoundShader<WebGlContext> for WebGlBoundShader {
         self.context
             .uniform4fv_with_f32_array(Some(&self.shader.uniforms[index].location), raw);
     }
+
+    fn set_uniform_texture(&self, index: usize, texture: &Texture) {
+        if let Some(unit) = self.texture_units.unit_for(index) {
+            self.context
+                .active_texture(WebGlRenderingContext::TEXTURE0 + unit as u32);
+            texture.bind();
+            self.context
+                .uniform1i(Some(&self.shader.uniforms[index].location), unit as i32);
+        }
+    }
 }
 
 fn to_gl_shader_type(shader_type: ShaderType) -> u32 {
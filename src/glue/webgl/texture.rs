@@ -1,29 +1,77 @@
+use std::cell::Cell;
 use std::fmt::{Debug, Formatter};
 use std::rc::Rc;
-use std::slice;
 
-use image::RgbImage;
 use web_sys::{WebGlRenderingContext, WebGlTexture};
 
 use crate::glue::webgl::WebGlContext;
 use crate::rendering::texture;
-use crate::rendering::texture::SetTextureDataError;
+use crate::rendering::texture::{
+    MagFilter, MinFilter, Sampler, SetTextureDataError, TextureData, TextureFormat, TextureShape,
+    WrapMode,
+};
 
 pub struct Texture {
     context: Rc<WebGlRenderingContext>,
     texture: WebGlTexture,
+    shape: Cell<Option<TextureShape>>,
+    sampler: Cell<Sampler>,
+    has_mipmaps: Cell<bool>,
 }
 
 impl Texture {
     pub fn new(context: Rc<WebGlRenderingContext>) -> Option<Texture> {
         let texture = context.create_texture()?;
-        Some(Texture { context, texture })
+        Some(Texture {
+            context,
+            texture,
+            shape: Cell::new(None),
+            sampler: Cell::new(Sampler::default()),
+            has_mipmaps: Cell::new(false),
+        })
     }
 
-    fn bind(&self) {
+    pub fn bind(&self) {
         self.context
             .bind_texture(WebGlRenderingContext::TEXTURE_2D, Some(&self.texture));
     }
+
+    /// Sets the texture parameters from the sampler, leaving out what the texture can't support
+    ///
+    /// WebGL 1 can only repeat and mipmap images whose sides are powers of two; sampling any
+    /// other texture with those settings reads black.
+    fn apply_sampler(&self) {
+        let mut sampler = self.sampler.get();
+        if matches!(self.shape.get(), Some(shape) if !shape.is_power_of_two()) {
+            sampler.wrap_s = WrapMode::ClampToEdge;
+            sampler.wrap_t = WrapMode::ClampToEdge;
+        }
+        if !self.has_mipmaps.get() {
+            sampler.min_filter = sampler.min_filter.without_mipmaps();
+        }
+
+        self.bind();
+        let set = |name, value| {
+            self.context
+                .tex_parameteri(WebGlRenderingContext::TEXTURE_2D, name, value as i32)
+        };
+        set(
+            WebGlRenderingContext::TEXTURE_WRAP_S,
+            to_gl_wrap(sampler.wrap_s),
+        );
+        set(
+            WebGlRenderingContext::TEXTURE_WRAP_T,
+            to_gl_wrap(sampler.wrap_t),
+        );
+        set(
+            WebGlRenderingContext::TEXTURE_MIN_FILTER,
+            to_gl_min_filter(sampler.min_filter),
+        );
+        set(
+            WebGlRenderingContext::TEXTURE_MAG_FILTER,
+            to_gl_mag_filter(sampler.mag_filter),
+        );
+    }
 }
 
 impl Debug for Texture {
@@ -32,25 +80,118 @@ impl Debug for Texture {
     }
 }
 
+impl Drop for Texture {
+    fn drop(&mut self) {
+        self.context.delete_texture(Some(&self.texture));
+    }
+}
+
 impl texture::Texture for Texture {
     type RenderingContext = WebGlContext;
 
-    fn set_image_data(&self, image: &RgbImage) -> Result<(), SetTextureDataError> {
+    fn set_data(&self, data: TextureData) -> Result<(), SetTextureDataError> {
         self.bind();
-        let data: &[u8] = &*image;
-        let data_mut = unsafe { slice::from_raw_parts_mut(data.as_ptr() as *mut u8, data.len()) };
+        // Rows are tightly packed, rather than padded to the default alignment of 4 bytes.
+        self.context
+            .pixel_storei(WebGlRenderingContext::UNPACK_ALIGNMENT, 1);
+        let format = to_gl_format(data.format());
         self.context
             .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
                 WebGlRenderingContext::TEXTURE_2D,
                 0,
-                WebGlRenderingContext::RGB as i32,
-                image.width() as i32,
-                image.height() as i32,
+                format as i32,
+                data.width() as i32,
+                data.height() as i32,
+                0,
+                format,
+                WebGlRenderingContext::UNSIGNED_BYTE,
+                Some(data.pixels()),
+            )
+            .map_err(|_| SetTextureDataError::UploadFailed)?;
+        self.shape.set(Some(data.shape()));
+        self.has_mipmaps.set(false);
+        self.apply_sampler();
+        Ok(())
+    }
+
+    fn set_sub_data(&self, x: u32, y: u32, data: TextureData) -> Result<(), SetTextureDataError> {
+        self.shape
+            .get()
+            .ok_or(SetTextureDataError::IncompatibleSubImage)?
+            .check_sub_image(x, y, &data)?;
+        self.bind();
+        self.context
+            .pixel_storei(WebGlRenderingContext::UNPACK_ALIGNMENT, 1);
+        self.context
+            .tex_sub_image_2d_with_i32_and_i32_and_u32_and_type_and_opt_u8_array(
+                WebGlRenderingContext::TEXTURE_2D,
                 0,
-                WebGlRenderingContext::RGB,
+                x as i32,
+                y as i32,
+                data.width() as i32,
+                data.height() as i32,
+                to_gl_format(data.format()),
                 WebGlRenderingContext::UNSIGNED_BYTE,
-                Some(data_mut),
+                Some(data.pixels()),
             )
-            .map_err(|_| SetTextureDataError)
+            .map_err(|_| SetTextureDataError::UploadFailed)
+    }
+
+    fn generate_mipmaps(&self) {
+        match self.shape.get() {
+            Some(shape) if shape.is_power_of_two() => {
+                self.bind();
+                self.context
+                    .generate_mipmap(WebGlRenderingContext::TEXTURE_2D);
+                self.has_mipmaps.set(true);
+                self.apply_sampler();
+            }
+            Some(shape) => log::warn!(
+                "Can't generate mipmaps for a {}x{} texture in WebGL 1",
+                shape.width,
+                shape.height
+            ),
+            None => log::warn!("Can't generate mipmaps for an empty texture"),
+        }
+    }
+
+    fn set_sampler(&self, sampler: &Sampler) {
+        self.sampler.set(*sampler);
+        self.apply_sampler();
+    }
+}
+
+fn to_gl_format(format: TextureFormat) -> u32 {
+    match format {
+        TextureFormat::Gray => WebGlRenderingContext::LUMINANCE,
+        TextureFormat::GrayAlpha => WebGlRenderingContext::LUMINANCE_ALPHA,
+        TextureFormat::Rgb => WebGlRenderingContext::RGB,
+        TextureFormat::Rgba => WebGlRenderingContext::RGBA,
+    }
+}
+
+fn to_gl_wrap(wrap: WrapMode) -> u32 {
+    match wrap {
+        WrapMode::Repeat => WebGlRenderingContext::REPEAT,
+        WrapMode::MirroredRepeat => WebGlRenderingContext::MIRRORED_REPEAT,
+        WrapMode::ClampToEdge => WebGlRenderingContext::CLAMP_TO_EDGE,
+    }
+}
+
+fn to_gl_min_filter(filter: MinFilter) -> u32 {
+    match filter {
+        MinFilter::Nearest => WebGlRenderingContext::NEAREST,
+        MinFilter::Linear => WebGlRenderingContext::LINEAR,
+        MinFilter::NearestMipmapNearest => WebGlRenderingContext::NEAREST_MIPMAP_NEAREST,
+        MinFilter::LinearMipmapNearest => WebGlRenderingContext::LINEAR_MIPMAP_NEAREST,
+        MinFilter::NearestMipmapLinear => WebGlRenderingContext::NEAREST_MIPMAP_LINEAR,
+        MinFilter::LinearMipmapLinear => WebGlRenderingContext::LINEAR_MIPMAP_LINEAR,
+    }
+}
+
+fn to_gl_mag_filter(filter: MagFilter) -> u32 {
+    match filter {
+        MagFilter::Nearest => WebGlRenderingContext::NEAREST,
+        MagFilter::Linear => WebGlRenderingContext::LINEAR,
     }
 }
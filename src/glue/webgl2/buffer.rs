@@ -0,0 +1,137 @@
+use std::fmt;
+use std::fmt::{Debug, Formatter};
+use std::rc::Rc;
+
+use web_sys::{WebGl2RenderingContext, WebGlBuffer};
+
+use crate::glue::webgl2::WebGl2Context;
+use crate::rendering;
+use crate::rendering::buffer::{AttributeType, VertexAttributeBinding};
+
+fn to_gl_attr_type(attr_type: AttributeType) -> u32 {
+    match attr_type {
+        AttributeType::Byte => WebGl2RenderingContext::BYTE,
+        AttributeType::UnsignedByte => WebGl2RenderingContext::UNSIGNED_BYTE,
+        AttributeType::Short => WebGl2RenderingContext::SHORT,
+        AttributeType::UnsignedShort => WebGl2RenderingContext::UNSIGNED_SHORT,
+        AttributeType::Int => WebGl2RenderingContext::INT,
+        AttributeType::UnsignedInt => WebGl2RenderingContext::UNSIGNED_INT,
+        AttributeType::Float => WebGl2RenderingContext::FLOAT,
+    }
+}
+
+#[repr(u32)]
+#[derive(Clone, Copy, Debug)]
+enum BufferBinding {
+    ArrayBuffer = WebGl2RenderingContext::ARRAY_BUFFER,
+    ElementArrayBuffer = WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER,
+}
+
+struct Buffer {
+    buffer: WebGlBuffer,
+    binding: BufferBinding,
+    context: Rc<WebGl2RenderingContext>,
+}
+
+impl Debug for Buffer {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Buffer")
+    }
+}
+
+impl Buffer {
+    fn new(context: Rc<WebGl2RenderingContext>, binding: BufferBinding) -> Option<Buffer> {
+        let buffer = context.create_buffer()?;
+        Some(Buffer {
+            buffer,
+            binding,
+            context,
+        })
+    }
+
+    fn bind(&self) {
+        self.context
+            .bind_buffer(self.binding as u32, Some(&self.buffer));
+    }
+
+    fn set_data(&self, data: &[u8]) {
+        self.bind();
+        // WebGL 2 takes immutable slices, so there's no need for the WebGL 1 backend's cast.
+        self.context.buffer_data_with_u8_array(
+            self.binding as u32,
+            data,
+            WebGl2RenderingContext::STATIC_DRAW,
+        );
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        self.context.delete_buffer(Some(&self.buffer));
+    }
+}
+
+#[derive(Debug)]
+pub struct AttributeBuffer {
+    buffer: Buffer,
+}
+
+impl AttributeBuffer {
+    pub fn new(context: Rc<WebGl2RenderingContext>) -> Option<Self> {
+        Some(AttributeBuffer {
+            buffer: Buffer::new(context, BufferBinding::ArrayBuffer)?,
+        })
+    }
+}
+
+impl rendering::buffer::Buffer for AttributeBuffer {
+    type RenderingContext = WebGl2Context;
+
+    fn set_data(&self, data: &[u8]) {
+        self.buffer.set_data(data);
+    }
+}
+
+impl rendering::buffer::AttributeBuffer for AttributeBuffer {
+    fn bind_to_attribute(&self, index: usize, binding: &VertexAttributeBinding) {
+        let context = &self.buffer.context;
+        self.buffer.bind();
+        context.vertex_attrib_pointer_with_i32(
+            index as u32,
+            binding.num_components as i32,
+            to_gl_attr_type(binding.attr_type),
+            binding.normalized,
+            binding.stride as i32,
+            binding.offset as i32,
+        );
+        context.enable_vertex_attrib_array(index as u32);
+        context.vertex_attrib_divisor(index as u32, binding.divisor as u32);
+    }
+}
+
+#[derive(Debug)]
+pub struct IndexBuffer {
+    buffer: Buffer,
+}
+
+impl IndexBuffer {
+    pub fn new(context: Rc<WebGl2RenderingContext>) -> Option<Self> {
+        Some(IndexBuffer {
+            buffer: Buffer::new(context, BufferBinding::ElementArrayBuffer)?,
+        })
+    }
+
+    pub fn bind(&self) {
+        self.buffer.bind();
+    }
+}
+
+impl rendering::buffer::Buffer for IndexBuffer {
+    type RenderingContext = WebGl2Context;
+
+    fn set_data(&self, data: &[u8]) {
+        self.buffer.set_data(data);
+    }
+}
+
+impl rendering::buffer::IndexBuffer for IndexBuffer {}
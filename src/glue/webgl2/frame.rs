@@ -0,0 +1,121 @@
+//! The uniform block that holds the camera and light, which every draw in a frame shares
+
+use std::rc::Rc;
+
+use web_sys::WebGl2RenderingContext;
+
+use crate::glue::webgl2::shader::UniformBlock;
+use crate::rendering::material::MaterialWorldContext;
+
+/// The name of the block in the GLSL ES 3.00 shaders
+pub const FRAME_BLOCK: &str = "Frame";
+/// The binding point that every program reads its frame block from
+pub const FRAME_BLOCK_BINDING: u32 = 0;
+
+// Where each member of the block starts, in bytes, under the std140 layout
+const VIEW_OFFSET: usize = 0;
+const PROJECTION_OFFSET: usize = 64;
+const SUN_DIRECTION_OFFSET: usize = 128;
+const SUN_COLOR_OFFSET: usize = 144;
+const AMBIENT_OFFSET: usize = 160;
+const FRAME_BLOCK_SIZE: usize = 176;
+
+/// Returns where a member of the frame block starts, in bytes
+pub fn member_offset(name: &str) -> Option<usize> {
+    match name {
+        "view" => Some(VIEW_OFFSET),
+        "projection" => Some(PROJECTION_OFFSET),
+        "sun.direction" => Some(SUN_DIRECTION_OFFSET),
+        "sun.color" => Some(SUN_COLOR_OFFSET),
+        "ambient" => Some(AMBIENT_OFFSET),
+        _ => None,
+    }
+}
+
+/// The buffer that every program's frame block reads from
+///
+/// Members of the frame block are only set here, so programs skip them when they're bound.
+#[derive(Debug)]
+pub struct FrameUniforms {
+    block: UniformBlock,
+}
+
+impl FrameUniforms {
+    pub fn new(context: Rc<WebGl2RenderingContext>) -> Option<FrameUniforms> {
+        let block = UniformBlock::new(context, FRAME_BLOCK_SIZE)?;
+        Some(FrameUniforms { block })
+    }
+
+    /// Uploads the camera and light for a frame, and binds them for every program
+    pub fn update(&self, world: &dyn MaterialWorldContext) {
+        let sun = world.sun();
+        let ambient = world.ambient();
+        self.block.write(VIEW_OFFSET, world.view().as_slice());
+        self.block
+            .write(PROJECTION_OFFSET, world.projection().as_slice());
+        self.block
+            .write(SUN_DIRECTION_OFFSET, sun.direction.as_slice());
+        self.block
+            .write(SUN_COLOR_OFFSET, &[sun.color.r, sun.color.g, sun.color.b]);
+        self.block
+            .write(AMBIENT_OFFSET, &[ambient.r, ambient.g, ambient.b]);
+        self.block.upload();
+        self.block.bind(FRAME_BLOCK_BINDING);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::glue::shaders::es3;
+
+    const SHADERS: &[&str] = &[
+        es3::VERTEX,
+        es3::INSTANCED_VERTEX,
+        es3::FRAGMENT,
+        es3::LINE_VERTEX,
+        es3::LINE_FRAGMENT,
+        es3::THICK_LINE_VERTEX,
+        es3::THICK_LINE_FRAGMENT,
+        es3::PARTICLE_VERTEX,
+        es3::PARTICLE_FRAGMENT,
+    ];
+
+    /// Returns the names of the frame block's members, with struct members expanded
+    fn frame_members(source: &str) -> Vec<String> {
+        let start = source.find("uniform Frame {").expect("No frame block");
+        let end = start + source[start..].find("};").unwrap();
+        source[start..end]
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.trim().strip_suffix(';'))
+            .flat_map(|member| {
+                let mut words = member.split_whitespace();
+                let (member_type, name) = (words.next().unwrap(), words.next().unwrap());
+                match member_type {
+                    "SunLight" => vec![format!("{}.direction", name), format!("{}.color", name)],
+                    _ => vec![String::from(name)],
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shaders_share_the_frame_block() {
+        let expected = frame_members(es3::VERTEX);
+        for source in SHADERS {
+            assert!(source.starts_with("#version 300 es\n"));
+            assert!(source.contains("layout(std140) uniform Frame {"));
+            assert_eq!(frame_members(source), expected);
+        }
+    }
+
+    #[test]
+    fn every_frame_member_has_an_offset() {
+        let members = frame_members(es3::VERTEX);
+        assert_eq!(members.len(), 5);
+        for member in members {
+            assert!(member_offset(&member).is_some(), "{} has no offset", member);
+        }
+    }
+}
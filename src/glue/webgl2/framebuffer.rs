@@ -0,0 +1,118 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use web_sys::{WebGl2RenderingContext, WebGlFramebuffer, WebGlRenderbuffer};
+
+// More samples than this cost fill rate without visibly smoothing the edges any further.
+const MAX_SAMPLES: i32 = 4;
+
+/// An offscreen, multisampled color and depth target that is resolved onto the canvas
+///
+/// Rendering into a framebuffer instead of the canvas leaves room for post-processing passes.
+#[derive(Debug)]
+pub struct MultisampleTarget {
+    context: Rc<WebGl2RenderingContext>,
+    framebuffer: WebGlFramebuffer,
+    color: WebGlRenderbuffer,
+    depth: WebGlRenderbuffer,
+    samples: i32,
+    size: Cell<(i32, i32)>,
+}
+
+impl MultisampleTarget {
+    /// Creates a target, or returns `None` if the context can't multisample
+    pub fn new(context: Rc<WebGl2RenderingContext>) -> Option<MultisampleTarget> {
+        let samples = context
+            .get_parameter(WebGl2RenderingContext::MAX_SAMPLES)
+            .ok()?
+            .as_f64()? as i32;
+        if samples < 2 {
+            return None;
+        }
+        let framebuffer = context.create_framebuffer()?;
+        let color = context.create_renderbuffer()?;
+        let depth = context.create_renderbuffer()?;
+        Some(MultisampleTarget {
+            context,
+            framebuffer,
+            color,
+            depth,
+            samples: samples.min(MAX_SAMPLES),
+            size: Cell::new((0, 0)),
+        })
+    }
+
+    /// Binds the target for drawing, resizing it to the given size if needed
+    ///
+    /// Returns false (and leaves the canvas bound) if the framebuffer can't be drawn to.
+    pub fn bind(&self, width: i32, height: i32) -> bool {
+        let context = &self.context;
+        context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, Some(&self.framebuffer));
+        if self.size.get() != (width, height) {
+            let allocate = |renderbuffer, format| {
+                context.bind_renderbuffer(WebGl2RenderingContext::RENDERBUFFER, Some(renderbuffer));
+                context.renderbuffer_storage_multisample(
+                    WebGl2RenderingContext::RENDERBUFFER,
+                    self.samples,
+                    format,
+                    width,
+                    height,
+                );
+            };
+            allocate(&self.color, WebGl2RenderingContext::RGBA8);
+            allocate(&self.depth, WebGl2RenderingContext::DEPTH_COMPONENT24);
+            context.framebuffer_renderbuffer(
+                WebGl2RenderingContext::FRAMEBUFFER,
+                WebGl2RenderingContext::COLOR_ATTACHMENT0,
+                WebGl2RenderingContext::RENDERBUFFER,
+                Some(&self.color),
+            );
+            context.framebuffer_renderbuffer(
+                WebGl2RenderingContext::FRAMEBUFFER,
+                WebGl2RenderingContext::DEPTH_ATTACHMENT,
+                WebGl2RenderingContext::RENDERBUFFER,
+                Some(&self.depth),
+            );
+            self.size.set((width, height));
+        }
+
+        let status = context.check_framebuffer_status(WebGl2RenderingContext::FRAMEBUFFER);
+        if status != WebGl2RenderingContext::FRAMEBUFFER_COMPLETE {
+            context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None);
+            return false;
+        }
+        true
+    }
+
+    /// Resolves the samples onto the canvas and leaves the canvas bound
+    pub fn resolve(&self) {
+        let context = &self.context;
+        let (width, height) = self.size.get();
+        context.bind_framebuffer(
+            WebGl2RenderingContext::READ_FRAMEBUFFER,
+            Some(&self.framebuffer),
+        );
+        context.bind_framebuffer(WebGl2RenderingContext::DRAW_FRAMEBUFFER, None);
+        context.blit_framebuffer(
+            0,
+            0,
+            width,
+            height,
+            0,
+            0,
+            width,
+            height,
+            WebGl2RenderingContext::COLOR_BUFFER_BIT,
+            WebGl2RenderingContext::NEAREST,
+        );
+        context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None);
+    }
+}
+
+impl Drop for MultisampleTarget {
+    fn drop(&mut self) {
+        self.context.delete_framebuffer(Some(&self.framebuffer));
+        self.context.delete_renderbuffer(Some(&self.color));
+        self.context.delete_renderbuffer(Some(&self.depth));
+    }
+}
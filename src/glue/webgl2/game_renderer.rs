@@ -0,0 +1,147 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use web_sys::{HtmlCanvasElement, WebGl2RenderingContext};
+
+use crate::glue::game_handle::CanvasRenderer;
+use crate::glue::shaders::BuiltinShaders;
+use crate::glue::webgl2::frame::FrameUniforms;
+use crate::glue::webgl2::framebuffer::MultisampleTarget;
+use crate::glue::webgl2::WebGl2Context;
+use crate::rendering::line::{LineShader, ThickLineShader};
+use crate::rendering::material::MaterialShader;
+use crate::rendering::particle::ParticleShader;
+use crate::rendering::queue::RenderQueue;
+use crate::state::{GameState, OverlayRenderer};
+use crate::state_renderer::{GameRenderer, GravityOverlayMode, GravityOverlayRenderer};
+
+#[derive(Debug)]
+pub struct WebGl2Renderer {
+    context: Rc<WebGl2Context>,
+    shaders: BuiltinShaders<WebGl2Context>,
+    render_queue: RenderQueue<WebGl2Context>,
+    /// The camera and light that the shaders' frame block reads
+    frame_uniforms: FrameUniforms,
+    /// Where frames are drawn before being resolved onto the canvas, if multisampling works
+    multisample: Option<MultisampleTarget>,
+    /// Whether the current frame is being drawn to the multisampled target
+    multisampled: Cell<bool>,
+}
+
+impl WebGl2Renderer {
+    pub fn new(
+        context: Rc<WebGl2Context>,
+        shaders: BuiltinShaders<WebGl2Context>,
+    ) -> Result<WebGl2Renderer, String> {
+        let frame_uniforms = FrameUniforms::new(Rc::clone(context.gl_context()))
+            .ok_or_else(|| String::from("Unable to create the frame uniform buffer"))?;
+        let multisample = MultisampleTarget::new(Rc::clone(context.gl_context()));
+        Ok(WebGl2Renderer {
+            context,
+            shaders,
+            render_queue: RenderQueue::new(),
+            frame_uniforms,
+            multisample,
+            multisampled: Cell::new(false),
+        })
+    }
+
+    pub fn configure_context(&self) {
+        self.gl_context().enable(WebGl2RenderingContext::CULL_FACE);
+        self.gl_context().cull_face(WebGl2RenderingContext::BACK);
+        self.gl_context().enable(WebGl2RenderingContext::DEPTH_TEST);
+        self.gl_context().depth_func(WebGl2RenderingContext::LESS);
+        self.gl_context().enable(WebGl2RenderingContext::BLEND);
+        self.gl_context().blend_func(
+            WebGl2RenderingContext::SRC_ALPHA,
+            WebGl2RenderingContext::ONE_MINUS_SRC_ALPHA,
+        );
+    }
+
+    pub fn context(&self) -> &Rc<WebGl2Context> {
+        &self.context
+    }
+
+    pub fn gl_context(&self) -> &Rc<WebGl2RenderingContext> {
+        self.context.gl_context()
+    }
+}
+
+impl GameRenderer for WebGl2Renderer {
+    type Context = WebGl2Context;
+
+    fn context(&self) -> &Self::Context {
+        &self.context
+    }
+
+    fn material_shader(&self) -> &MaterialShader<WebGl2Context> {
+        &self.shaders.material
+    }
+
+    fn instanced_material_shader(&self) -> Option<&MaterialShader<WebGl2Context>> {
+        self.shaders.instanced_material.as_ref()
+    }
+
+    fn line_shader(&self) -> &LineShader<WebGl2Context> {
+        &self.shaders.line
+    }
+
+    fn thick_line_shader(&self) -> &ThickLineShader<WebGl2Context> {
+        &self.shaders.thick_line
+    }
+
+    fn particle_shader(&self) -> &ParticleShader<WebGl2Context> {
+        &self.shaders.particle
+    }
+
+    fn render_queue(&self) -> &RenderQueue<WebGl2Context> {
+        &self.render_queue
+    }
+
+    fn begin_frame(&self, state: &mut GameState) {
+        self.context.set_viewport();
+        state.camera.aspect_ratio = self.context.aspect_ratio();
+
+        let target = self
+            .multisample
+            .as_ref()
+            .filter(|target| target.bind(self.context.width(), self.context.height()));
+        self.multisampled.set(target.is_some());
+        self.gl_context().clear_color(0.0, 0.0, 0.0, 1.0);
+        self.gl_context().clear(
+            WebGl2RenderingContext::COLOR_BUFFER_BIT | WebGl2RenderingContext::DEPTH_BUFFER_BIT,
+        );
+        self.frame_uniforms.update(state);
+    }
+
+    fn end_frame(&self) {
+        if let Some(target) = self
+            .multisample
+            .as_ref()
+            .filter(|_| self.multisampled.get())
+        {
+            target.resolve();
+        }
+    }
+}
+
+impl CanvasRenderer for WebGl2Renderer {
+    fn canvas(&self) -> &HtmlCanvasElement {
+        self.context.canvas()
+    }
+
+    fn drawing_buffer_size(&self) -> (f32, f32) {
+        (self.context.width() as f32, self.context.height() as f32)
+    }
+
+    fn device_pixel_ratio(&self) -> f32 {
+        self.context.device_pixel_ratio() as f32
+    }
+
+    fn gravity_overlay(self: Rc<Self>, mode: GravityOverlayMode) -> Rc<dyn OverlayRenderer> {
+        Rc::new(GravityOverlayRenderer::new(
+            self as Rc<dyn GameRenderer<Context = WebGl2Context>>,
+            mode,
+        ))
+    }
+}
@@ -0,0 +1,155 @@
+use std::rc::Rc;
+
+use web_sys::WebGl2RenderingContext;
+use web_sys::{Element, HtmlCanvasElement};
+
+use crate::glue::webgl;
+use crate::glue::webgl2::shader::{ShaderProgram, WebGl2BoundShader};
+use crate::rendering::context::RenderingContext;
+use crate::rendering::shader::{ShaderBindError, ShaderType};
+
+pub mod buffer;
+pub mod frame;
+pub mod framebuffer;
+pub mod game_renderer;
+pub mod shader;
+pub mod texture;
+
+/// A rendering context that uses WebGL 2
+///
+/// Unlike WebGL 1, instancing and 32-bit indices are always available, and multisampled
+/// framebuffers can be rendered to.
+#[derive(Debug)]
+pub struct WebGl2Context {
+    canvas_element: Element,
+    canvas: HtmlCanvasElement,
+    gl_context: Rc<WebGl2RenderingContext>,
+    max_texture_units: usize,
+}
+
+impl WebGl2Context {
+    pub fn new(
+        canvas_element: Element,
+        canvas: HtmlCanvasElement,
+        gl_context: WebGl2RenderingContext,
+    ) -> WebGl2Context {
+        // Every WebGL 2 implementation supports at least 16.
+        let max_texture_units = gl_context
+            .get_parameter(WebGl2RenderingContext::MAX_TEXTURE_IMAGE_UNITS)
+            .ok()
+            .and_then(|units| units.as_f64())
+            .map_or(16, |units| units as usize);
+        WebGl2Context {
+            canvas_element,
+            canvas,
+            gl_context: Rc::new(gl_context),
+            max_texture_units,
+        }
+    }
+
+    pub fn canvas(&self) -> &HtmlCanvasElement {
+        &self.canvas
+    }
+
+    pub fn gl_context(&self) -> &Rc<WebGl2RenderingContext> {
+        &self.gl_context
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        self.gl_context.drawing_buffer_width() as f32
+            / self.gl_context.drawing_buffer_height() as f32
+    }
+
+    pub fn width(&self) -> i32 {
+        self.gl_context.drawing_buffer_width()
+    }
+
+    pub fn height(&self) -> i32 {
+        self.gl_context.drawing_buffer_height()
+    }
+
+    /**
+     * Returns the device pixel ratio.
+     *
+     * Always returns a finite, positive value.
+     */
+    pub fn device_pixel_ratio(&self) -> f64 {
+        webgl::device_pixel_ratio()
+    }
+
+    pub fn set_viewport(&self) {
+        webgl::fit_canvas_to_element(&self.canvas_element, &self.canvas);
+        self.gl_context.viewport(
+            0,
+            0,
+            self.gl_context.drawing_buffer_width(),
+            self.gl_context.drawing_buffer_height(),
+        );
+    }
+}
+
+impl RenderingContext for WebGl2Context {
+    type AttributeBuffer = buffer::AttributeBuffer;
+    type IndexBuffer = buffer::IndexBuffer;
+    type Shader = shader::Shader;
+    type ShaderCreationError = shader::ShaderCreationError;
+    type ShaderProgram = ShaderProgram;
+    type ShaderLinkError = shader::ShaderLinkError;
+    type BoundShader = shader::WebGl2BoundShader;
+    type Texture = texture::Texture;
+
+    fn make_attribute_buffer(&self) -> Result<Self::AttributeBuffer, ()> {
+        Self::AttributeBuffer::new(Rc::clone(&self.gl_context)).ok_or(())
+    }
+
+    fn make_index_buffer(&self) -> Result<Self::IndexBuffer, ()> {
+        Self::IndexBuffer::new(Rc::clone(&self.gl_context)).ok_or(())
+    }
+
+    fn make_texture(&self) -> Result<Self::Texture, ()> {
+        Self::Texture::new(Rc::clone(&self.gl_context)).ok_or(())
+    }
+
+    fn compile_shader(
+        &self,
+        shader_type: ShaderType,
+        source: &str,
+    ) -> Result<Self::Shader, Self::ShaderCreationError> {
+        Self::Shader::compile(Rc::clone(&self.gl_context), shader_type, source)
+    }
+
+    fn link_shader_program<'a, T: Iterator<Item = &'a Self::Shader>>(
+        &self,
+        shaders: T,
+    ) -> Result<Self::ShaderProgram, Self::ShaderLinkError> {
+        Self::ShaderProgram::link(Rc::clone(&self.gl_context), shaders)
+    }
+
+    fn viewport_size(&self) -> (u32, u32) {
+        (self.width().max(0) as u32, self.height().max(0) as u32)
+    }
+
+    fn pixel_ratio(&self) -> f32 {
+        self.device_pixel_ratio() as f32
+    }
+
+    fn supports_instancing(&self) -> bool {
+        true
+    }
+
+    fn bind_shader(
+        &self,
+        shader: Rc<Self::ShaderProgram>,
+    ) -> Result<Self::BoundShader, ShaderBindError> {
+        if !shader.is_same_context(&self.gl_context) {
+            return Err(ShaderBindError::InvalidContextForShader);
+        }
+
+        shader.bind();
+        Ok(WebGl2BoundShader::new(
+            Rc::clone(&self.gl_context),
+            shader,
+            self.max_texture_units,
+        ))
+    }
+}
@@ -0,0 +1,637 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Display;
+use std::rc::Rc;
+
+use nalgebra::{Matrix4, Vector2, Vector3, Vector4};
+use web_sys::{
+    WebGl2RenderingContext, WebGlBuffer, WebGlProgram, WebGlShader, WebGlUniformLocation,
+    WebGlVertexArrayObject,
+};
+
+use crate::glue::webgl2::frame;
+use crate::glue::webgl2::texture::Texture;
+use crate::glue::webgl2::WebGl2Context;
+use crate::rendering::buffer::IndexType;
+use crate::rendering::mesh::ElementIndices;
+use crate::rendering::shader;
+use crate::rendering::shader::{BoundShader, ShaderParamInfo, ShaderType};
+use crate::rendering::texture::TextureUnits;
+
+#[derive(Clone, Debug)]
+pub enum ShaderCreationError {
+    FailedToCreateShader,
+    FailedToCompile(String),
+}
+
+impl Display for ShaderCreationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ShaderCreationError::FailedToCreateShader => write!(f, "Failed to create shader"),
+            ShaderCreationError::FailedToCompile(s) => write!(f, "Failed to compile shader: {}", s),
+        }
+    }
+}
+
+impl Error for ShaderCreationError {}
+
+#[derive(Debug)]
+pub struct Shader {
+    context: Rc<WebGl2RenderingContext>,
+    shader: WebGlShader,
+    shader_type: ShaderType,
+}
+
+impl Shader {
+    pub fn compile(
+        context: Rc<WebGl2RenderingContext>,
+        shader_type: ShaderType,
+        source: &str,
+    ) -> Result<Shader, ShaderCreationError> {
+        let shader = context
+            .create_shader(to_gl_shader_type(shader_type))
+            .ok_or(ShaderCreationError::FailedToCreateShader)?;
+        context.shader_source(&shader, source);
+        context.compile_shader(&shader);
+
+        if context
+            .get_shader_parameter(&shader, WebGl2RenderingContext::COMPILE_STATUS)
+            .as_bool()
+            .unwrap_or(false)
+        {
+            Ok(Shader {
+                context,
+                shader,
+                shader_type,
+            })
+        } else {
+            Err(context
+                .get_shader_info_log(&shader)
+                .map(ShaderCreationError::FailedToCompile)
+                .unwrap_or_else(|| {
+                    ShaderCreationError::FailedToCompile(String::from("Unknown compilation error"))
+                }))
+        }
+    }
+}
+
+impl Drop for Shader {
+    fn drop(&mut self) {
+        // Programs that the shader is attached to keep it alive until they're deleted.
+        self.context.delete_shader(Some(&self.shader));
+    }
+}
+
+impl shader::Shader for Shader {
+    type RenderingContext = WebGl2Context;
+
+    fn shader_type(&self) -> ShaderType {
+        self.shader_type
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ShaderLinkError {
+    FailedToCreateShaderProgram,
+    FailedToCreateVertexArray,
+    FailedToCreateUniformBuffer,
+    FailedToLink(String),
+}
+
+impl Display for ShaderLinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ShaderLinkError::FailedToCreateShaderProgram => {
+                write!(f, "Failed to create shader program")
+            }
+            ShaderLinkError::FailedToCreateVertexArray => {
+                write!(f, "Failed to create vertex array object")
+            }
+            ShaderLinkError::FailedToCreateUniformBuffer => {
+                write!(f, "Failed to create uniform buffer")
+            }
+            ShaderLinkError::FailedToLink(s) => write!(f, "Failed to link shader: {}", s),
+        }
+    }
+}
+
+impl Error for ShaderLinkError {}
+
+/// Where a uniform's value is stored
+#[derive(Debug)]
+enum UniformStorage {
+    /// A uniform in the default block, which is set directly
+    Location(WebGlUniformLocation),
+    /// A member of a uniform block, which is written into the block's buffer
+    Block { block: usize, offset: usize },
+    /// A member of the frame block, which `FrameUniforms` sets once per frame
+    Frame,
+}
+
+#[derive(Debug)]
+struct ShaderUniformInformation {
+    name: Box<str>,
+    storage: UniformStorage,
+}
+
+/// A uniform block and the buffer that backs it
+///
+/// Values are staged in `data` and only uploaded when they're needed.
+#[derive(Debug)]
+pub struct UniformBlock {
+    context: Rc<WebGl2RenderingContext>,
+    buffer: WebGlBuffer,
+    data: RefCell<Vec<u8>>,
+    dirty: Cell<bool>,
+}
+
+impl UniformBlock {
+    /// Creates a block whose buffer holds `size` bytes
+    pub fn new(context: Rc<WebGl2RenderingContext>, size: usize) -> Option<UniformBlock> {
+        let buffer = context.create_buffer()?;
+        context.bind_buffer(WebGl2RenderingContext::UNIFORM_BUFFER, Some(&buffer));
+        context.buffer_data_with_i32(
+            WebGl2RenderingContext::UNIFORM_BUFFER,
+            size as i32,
+            WebGl2RenderingContext::DYNAMIC_DRAW,
+        );
+        Some(UniformBlock {
+            context,
+            buffer,
+            data: RefCell::new(vec![0; size]),
+            dirty: Cell::new(false),
+        })
+    }
+
+    /// Stages values to be uploaded, starting `offset` bytes into the block
+    pub fn write(&self, offset: usize, values: &[f32]) {
+        let mut data = self.data.borrow_mut();
+        let end = offset + std::mem::size_of_val(values);
+        if end > data.len() {
+            log::error!("Uniform doesn't fit in its block");
+            return;
+        }
+        for (bytes, value) in data[offset..end].chunks_exact_mut(4).zip(values.iter()) {
+            bytes.copy_from_slice(&value.to_ne_bytes());
+        }
+        self.dirty.set(true);
+    }
+
+    /// Uploads the staged values, if they've changed since they were last uploaded
+    pub fn upload(&self) {
+        if self.dirty.replace(false) {
+            self.context
+                .bind_buffer(WebGl2RenderingContext::UNIFORM_BUFFER, Some(&self.buffer));
+            self.context.buffer_sub_data_with_i32_and_u8_array(
+                WebGl2RenderingContext::UNIFORM_BUFFER,
+                0,
+                &self.data.borrow(),
+            );
+        }
+    }
+
+    /// Makes the programs whose blocks use a binding point read from this block's buffer
+    pub fn bind(&self, binding: u32) {
+        self.context.bind_buffer_base(
+            WebGl2RenderingContext::UNIFORM_BUFFER,
+            binding,
+            Some(&self.buffer),
+        );
+    }
+}
+
+impl Drop for UniformBlock {
+    fn drop(&mut self) {
+        self.context.delete_buffer(Some(&self.buffer));
+    }
+}
+
+/// A linked program, along with the vertex array object that holds its attribute bindings
+///
+/// Giving each program its own vertex array object keeps attributes that one program enables
+/// (or gives a divisor) from affecting draws with another program.
+#[derive(Debug)]
+pub struct ShaderProgram {
+    program: WebGlProgram,
+    context: Rc<WebGl2RenderingContext>,
+    vertex_array: WebGlVertexArrayObject,
+    attributes: HashMap<Box<str>, ShaderParamInfo>,
+    uniforms: Vec<ShaderUniformInformation>,
+    /// The program's uniform blocks, or `None` for the frame block, which shares one buffer
+    blocks: Vec<Option<UniformBlock>>,
+}
+
+impl ShaderProgram {
+    pub fn link<'a, T: Iterator<Item = &'a Shader>>(
+        context: Rc<WebGl2RenderingContext>,
+        shaders: T,
+    ) -> Result<ShaderProgram, ShaderLinkError> {
+        let program = context
+            .create_program()
+            .ok_or(ShaderLinkError::FailedToCreateShaderProgram)?;
+        for shader in shaders {
+            context.attach_shader(&program, &shader.shader)
+        }
+        context.link_program(&program);
+
+        if context
+            .get_program_parameter(&program, WebGl2RenderingContext::LINK_STATUS)
+            .as_bool()
+            .unwrap_or(false)
+        {
+            let vertex_array = context
+                .create_vertex_array()
+                .ok_or(ShaderLinkError::FailedToCreateVertexArray)?;
+            let attributes = ShaderProgram::get_attribute_info(&context, &program);
+            let blocks = ShaderProgram::create_uniform_blocks(&context, &program)?;
+            let uniforms = ShaderProgram::get_uniform_info(&context, &program, &blocks);
+            Ok(ShaderProgram {
+                program,
+                context,
+                vertex_array,
+                attributes,
+                uniforms,
+                blocks,
+            })
+        } else {
+            Err(context
+                .get_program_info_log(&program)
+                .map(ShaderLinkError::FailedToLink)
+                .unwrap_or_else(|| {
+                    ShaderLinkError::FailedToLink(String::from("Unknown linking error"))
+                }))
+        }
+    }
+
+    pub fn is_same_context(&self, context: &Rc<WebGl2RenderingContext>) -> bool {
+        Rc::ptr_eq(&self.context, context)
+    }
+
+    pub fn bind(&self) {
+        self.context.use_program(Some(&self.program));
+        self.context.bind_vertex_array(Some(&self.vertex_array));
+        // Every program numbers its own blocks' binding points from the same place, so they
+        // have to be claimed again whenever the program is bound.
+        for (index, block) in self.blocks.iter().enumerate() {
+            if let Some(block) = block {
+                block.bind(block_binding(index as u32));
+            }
+        }
+    }
+
+    /// Uploads the uniform blocks that have changed since they were last uploaded
+    fn upload_uniform_blocks(&self) {
+        for block in self.blocks.iter().flatten() {
+            block.upload();
+        }
+    }
+
+    fn write_to_block(&self, block: usize, offset: usize, values: &[f32]) {
+        if let Some(Some(block)) = self.blocks.get(block) {
+            block.write(offset, values);
+        }
+    }
+
+    fn get_attribute_info(
+        context: &WebGl2RenderingContext,
+        program: &WebGlProgram,
+    ) -> HashMap<Box<str>, ShaderParamInfo> {
+        let num_attributes = context
+            .get_program_parameter(program, WebGl2RenderingContext::ACTIVE_ATTRIBUTES)
+            .as_f64()
+            .unwrap() as u32;
+        let mut attributes = HashMap::<Box<str>, ShaderParamInfo>::new();
+        for i in 0..num_attributes {
+            if let Some(info) = context.get_active_attrib(program, i) {
+                let location = context.get_attrib_location(program, &info.name());
+                if location >= 0 {
+                    attributes.insert(
+                        info.name().into_boxed_str(),
+                        ShaderParamInfo {
+                            index: location as usize,
+                        },
+                    );
+                }
+            }
+        }
+        attributes
+    }
+
+    fn create_uniform_blocks(
+        context: &Rc<WebGl2RenderingContext>,
+        program: &WebGlProgram,
+    ) -> Result<Vec<Option<UniformBlock>>, ShaderLinkError> {
+        let num_blocks = context
+            .get_program_parameter(program, WebGl2RenderingContext::ACTIVE_UNIFORM_BLOCKS)
+            .as_f64()
+            .unwrap_or(0.0) as u32;
+        let mut blocks = Vec::with_capacity(num_blocks as usize);
+        for i in 0..num_blocks {
+            if context.get_active_uniform_block_name(program, i).as_deref()
+                == Some(frame::FRAME_BLOCK)
+            {
+                context.uniform_block_binding(program, i, frame::FRAME_BLOCK_BINDING);
+                blocks.push(None);
+                continue;
+            }
+
+            let size = context
+                .get_active_uniform_block_parameter(
+                    program,
+                    i,
+                    WebGl2RenderingContext::UNIFORM_BLOCK_DATA_SIZE,
+                )
+                .ok()
+                .and_then(|size| size.as_f64())
+                .unwrap_or(0.0) as usize;
+            context.uniform_block_binding(program, i, block_binding(i));
+            let block = UniformBlock::new(Rc::clone(context), size)
+                .ok_or(ShaderLinkError::FailedToCreateUniformBuffer)?;
+            blocks.push(Some(block));
+        }
+        Ok(blocks)
+    }
+
+    fn get_uniform_info(
+        context: &WebGl2RenderingContext,
+        program: &WebGlProgram,
+        blocks: &[Option<UniformBlock>],
+    ) -> Vec<ShaderUniformInformation> {
+        let num_uniforms = context
+            .get_program_parameter(program, WebGl2RenderingContext::ACTIVE_UNIFORMS)
+            .as_f64()
+            .unwrap() as u32;
+        let indices = (0..num_uniforms)
+            .map(wasm_bindgen::JsValue::from)
+            .collect::<js_sys::Array>();
+        let query = |pname| {
+            js_sys::Array::from(&context.get_active_uniforms(program, &indices, pname))
+                .iter()
+                .map(|value| value.as_f64().unwrap_or(-1.0) as i64)
+                .collect::<Vec<_>>()
+        };
+        let block_indices = query(WebGl2RenderingContext::UNIFORM_BLOCK_INDEX);
+        let offsets = query(WebGl2RenderingContext::UNIFORM_OFFSET);
+
+        let mut uniforms = Vec::<ShaderUniformInformation>::with_capacity(num_uniforms as usize);
+        for i in 0..num_uniforms {
+            // TODO: log errors?
+            if let Some(info) = context.get_active_uniform(program, i) {
+                let name: Box<str> = info.name().into();
+                let block = block_indices.get(i as usize).copied().unwrap_or(-1);
+                let offset = offsets.get(i as usize).copied().unwrap_or(-1);
+                let storage = if block >= 0 && offset >= 0 {
+                    if let Some(Some(_)) = blocks.get(block as usize) {
+                        UniformStorage::Block {
+                            block: block as usize,
+                            offset: offset as usize,
+                        }
+                    } else {
+                        if frame::member_offset(&name) != Some(offset as usize) {
+                            log::error!("Frame block member {} is at offset {}", name, offset);
+                        }
+                        UniformStorage::Frame
+                    }
+                } else if let Some(location) = context.get_uniform_location(program, &name) {
+                    UniformStorage::Location(location)
+                } else {
+                    continue;
+                };
+                uniforms.push(ShaderUniformInformation { name, storage });
+            }
+        }
+        uniforms
+    }
+}
+
+impl Drop for ShaderProgram {
+    fn drop(&mut self) {
+        self.context.delete_vertex_array(Some(&self.vertex_array));
+        self.context.delete_program(Some(&self.program));
+    }
+}
+
+impl shader::ShaderProgram for ShaderProgram {
+    type RenderingContext = WebGl2Context;
+
+    fn attribute_names(&self) -> Vec<String> {
+        self.attributes
+            .keys()
+            .map(|e| String::from(e.as_ref()))
+            .collect::<Vec<_>>()
+    }
+
+    fn uniform_names(&self) -> Vec<String> {
+        self.uniforms
+            .iter()
+            .map(|u| String::from(u.name.as_ref()))
+            .collect::<Vec<_>>()
+    }
+
+    fn attribute(&self, name: &str) -> Option<ShaderParamInfo> {
+        self.attributes.get(name).cloned()
+    }
+
+    fn uniform(&self, name: &str) -> Option<ShaderParamInfo> {
+        self.uniforms
+            .iter()
+            .enumerate()
+            .find(|(_, inf)| inf.name.as_ref() == name)
+            .map(|(i, _)| ShaderParamInfo { index: i })
+    }
+}
+
+pub struct WebGl2BoundShader {
+    context: Rc<WebGl2RenderingContext>,
+    shader: Rc<ShaderProgram>,
+    texture_units: TextureUnits,
+    double_sided: Cell<bool>,
+}
+
+impl WebGl2BoundShader {
+    pub fn new(
+        context: Rc<WebGl2RenderingContext>,
+        shader: Rc<ShaderProgram>,
+        max_texture_units: usize,
+    ) -> Self {
+        WebGl2BoundShader {
+            context,
+            shader,
+            texture_units: TextureUnits::new(max_texture_units),
+            double_sided: Cell::new(false),
+        }
+    }
+
+    /// Sets a uniform, either through its location or by writing it into its block
+    fn set_uniform<F>(&self, index: usize, values: &[f32], set_location: F)
+    where
+        F: FnOnce(&WebGl2RenderingContext, &WebGlUniformLocation),
+    {
+        match self.shader.uniforms[index].storage {
+            UniformStorage::Location(ref location) => set_location(&self.context, location),
+            UniformStorage::Block { block, offset } => {
+                self.shader.write_to_block(block, offset, values)
+            }
+            // Every draw in a frame shares the same camera and light.
+            UniformStorage::Frame => {}
+        }
+    }
+
+    fn draw_arrays(&self, mode: u32, count: usize) {
+        self.shader.upload_uniform_blocks();
+        self.context.draw_arrays(mode, 0, count as i32);
+    }
+}
+
+impl Drop for WebGl2BoundShader {
+    fn drop(&mut self) {
+        self.set_double_sided(false);
+    }
+}
+
+impl BoundShader<WebGl2Context> for WebGl2BoundShader {
+    fn program(&self) -> &ShaderProgram {
+        &self.shader
+    }
+
+    fn draw_triangles(&self, count: usize) {
+        self.draw_arrays(WebGl2RenderingContext::TRIANGLES, count);
+    }
+
+    fn draw_indexed_triangles(&self, indices: &ElementIndices<WebGl2Context>) {
+        self.shader.upload_uniform_blocks();
+        indices.buffer.bind();
+        self.context.draw_elements_with_i32(
+            WebGl2RenderingContext::TRIANGLES,
+            indices.binding.count as i32,
+            to_gl_element_type(indices.binding.index_type),
+            indices.binding.offset as i32,
+        );
+    }
+
+    fn draw_triangles_instanced(&self, count: usize, instance_count: usize) {
+        self.shader.upload_uniform_blocks();
+        self.context.draw_arrays_instanced(
+            WebGl2RenderingContext::TRIANGLES,
+            0,
+            count as i32,
+            instance_count as i32,
+        );
+    }
+
+    fn draw_indexed_triangles_instanced(
+        &self,
+        indices: &ElementIndices<WebGl2Context>,
+        instance_count: usize,
+    ) {
+        self.shader.upload_uniform_blocks();
+        indices.buffer.bind();
+        self.context.draw_elements_instanced_with_i32(
+            WebGl2RenderingContext::TRIANGLES,
+            indices.binding.count as i32,
+            to_gl_element_type(indices.binding.index_type),
+            indices.binding.offset as i32,
+            instance_count as i32,
+        );
+    }
+
+    fn draw_polyline(&self, num_vertices: usize) {
+        self.draw_arrays(WebGl2RenderingContext::LINE_STRIP, num_vertices);
+    }
+
+    fn draw_triangle_strip(&self, num_vertices: usize) {
+        self.draw_arrays(WebGl2RenderingContext::TRIANGLE_STRIP, num_vertices);
+    }
+
+    fn draw_points(&self, num_vertices: usize) {
+        self.draw_arrays(WebGl2RenderingContext::POINTS, num_vertices);
+    }
+
+    fn set_double_sided(&self, double_sided: bool) {
+        if double_sided != self.double_sided.replace(double_sided) {
+            if double_sided {
+                self.context.disable(WebGl2RenderingContext::CULL_FACE);
+            } else {
+                self.context.enable(WebGl2RenderingContext::CULL_FACE);
+            }
+        }
+    }
+
+    fn disable_attribute(&self, index: usize) {
+        self.context.disable_vertex_attrib_array(index as u32);
+    }
+
+    fn set_uniform_bool(&self, index: usize, value: bool) {
+        // Blocks store booleans as 32-bit integers, which have the same size as a float.
+        let bits = f32::from_bits(value as u32);
+        self.set_uniform(index, &[bits], |context, location| {
+            context.uniform1i(Some(location), value as i32)
+        });
+    }
+
+    fn set_uniform_f32(&self, index: usize, value: f32) {
+        self.set_uniform(index, &[value], |context, location| {
+            context.uniform1f(Some(location), value)
+        });
+    }
+
+    fn set_uniform_mat4(&self, index: usize, value: Matrix4<f32>) {
+        self.set_uniform(index, value.as_slice(), |context, location| {
+            context.uniform_matrix4fv_with_f32_array(Some(location), false, value.as_slice())
+        });
+    }
+
+    fn set_uniform_vec2(&self, index: usize, value: Vector2<f32>) {
+        self.set_uniform(index, value.as_slice(), |context, location| {
+            context.uniform2fv_with_f32_array(Some(location), value.as_slice())
+        });
+    }
+
+    fn set_uniform_vec3(&self, index: usize, value: Vector3<f32>) {
+        self.set_uniform(index, value.as_slice(), |context, location| {
+            context.uniform3fv_with_f32_array(Some(location), value.as_slice())
+        });
+    }
+
+    fn set_uniform_vec4(&self, index: usize, value: Vector4<f32>) {
+        self.set_uniform(index, value.as_slice(), |context, location| {
+            context.uniform4fv_with_f32_array(Some(location), value.as_slice())
+        });
+    }
+
+    fn set_uniform_texture(&self, index: usize, texture: &Texture) {
+        // Samplers are opaque, so they never live in a uniform block.
+        if let UniformStorage::Location(ref location) = self.shader.uniforms[index].storage {
+            if let Some(unit) = self.texture_units.unit_for(index) {
+                self.context
+                    .active_texture(WebGl2RenderingContext::TEXTURE0 + unit as u32);
+                texture.bind();
+                self.context.uniform1i(Some(location), unit as i32);
+            }
+        }
+    }
+}
+
+/// Returns the binding point of a program's own uniform block
+///
+/// These come after the frame block's binding point, so that binding a program doesn't replace
+/// the frame's buffer.
+fn block_binding(index: u32) -> u32 {
+    frame::FRAME_BLOCK_BINDING + 1 + index
+}
+
+fn to_gl_shader_type(shader_type: ShaderType) -> u32 {
+    match shader_type {
+        ShaderType::Vertex => WebGl2RenderingContext::VERTEX_SHADER,
+        ShaderType::Fragment => WebGl2RenderingContext::FRAGMENT_SHADER,
+    }
+}
+
+fn to_gl_element_type(attr_type: IndexType) -> u32 {
+    match attr_type {
+        IndexType::UnsignedByte => WebGl2RenderingContext::UNSIGNED_BYTE,
+        IndexType::UnsignedShort => WebGl2RenderingContext::UNSIGNED_SHORT,
+        IndexType::UnsignedInt => WebGl2RenderingContext::UNSIGNED_INT,
+    }
+}
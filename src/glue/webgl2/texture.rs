@@ -0,0 +1,193 @@
+use std::cell::Cell;
+use std::fmt::{Debug, Formatter};
+use std::rc::Rc;
+
+use web_sys::{WebGl2RenderingContext, WebGlTexture};
+
+use crate::glue::webgl2::WebGl2Context;
+use crate::rendering::texture;
+use crate::rendering::texture::{
+    MagFilter, MinFilter, Sampler, SetTextureDataError, TextureData, TextureFormat, TextureShape,
+    WrapMode,
+};
+
+pub struct Texture {
+    context: Rc<WebGl2RenderingContext>,
+    texture: WebGlTexture,
+    shape: Cell<Option<TextureShape>>,
+    sampler: Cell<Sampler>,
+    has_mipmaps: Cell<bool>,
+}
+
+impl Texture {
+    pub fn new(context: Rc<WebGl2RenderingContext>) -> Option<Texture> {
+        let texture = context.create_texture()?;
+        Some(Texture {
+            context,
+            texture,
+            shape: Cell::new(None),
+            sampler: Cell::new(Sampler::default()),
+            has_mipmaps: Cell::new(false),
+        })
+    }
+
+    pub fn bind(&self) {
+        self.context
+            .bind_texture(WebGl2RenderingContext::TEXTURE_2D, Some(&self.texture));
+    }
+
+    /// Sets the texture parameters from the sampler, only filtering between mipmaps that exist
+    fn apply_sampler(&self) {
+        let mut sampler = self.sampler.get();
+        if !self.has_mipmaps.get() {
+            sampler.min_filter = sampler.min_filter.without_mipmaps();
+        }
+
+        self.bind();
+        let set = |name, value| {
+            self.context
+                .tex_parameteri(WebGl2RenderingContext::TEXTURE_2D, name, value as i32)
+        };
+        set(
+            WebGl2RenderingContext::TEXTURE_WRAP_S,
+            to_gl_wrap(sampler.wrap_s),
+        );
+        set(
+            WebGl2RenderingContext::TEXTURE_WRAP_T,
+            to_gl_wrap(sampler.wrap_t),
+        );
+        set(
+            WebGl2RenderingContext::TEXTURE_MIN_FILTER,
+            to_gl_min_filter(sampler.min_filter),
+        );
+        set(
+            WebGl2RenderingContext::TEXTURE_MAG_FILTER,
+            to_gl_mag_filter(sampler.mag_filter),
+        );
+    }
+}
+
+impl Debug for Texture {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "Texture")
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        self.context.delete_texture(Some(&self.texture));
+    }
+}
+
+impl texture::Texture for Texture {
+    type RenderingContext = WebGl2Context;
+
+    fn set_data(&self, data: TextureData) -> Result<(), SetTextureDataError> {
+        self.bind();
+        // Rows are tightly packed, rather than padded to the default alignment of 4 bytes.
+        self.context
+            .pixel_storei(WebGl2RenderingContext::UNPACK_ALIGNMENT, 1);
+        self.context
+            .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                WebGl2RenderingContext::TEXTURE_2D,
+                0,
+                to_gl_internal_format(data.format()) as i32,
+                data.width() as i32,
+                data.height() as i32,
+                0,
+                to_gl_format(data.format()),
+                WebGl2RenderingContext::UNSIGNED_BYTE,
+                Some(data.pixels()),
+            )
+            .map_err(|_| SetTextureDataError::UploadFailed)?;
+        self.shape.set(Some(data.shape()));
+        self.has_mipmaps.set(false);
+        self.apply_sampler();
+        Ok(())
+    }
+
+    fn set_sub_data(&self, x: u32, y: u32, data: TextureData) -> Result<(), SetTextureDataError> {
+        self.shape
+            .get()
+            .ok_or(SetTextureDataError::IncompatibleSubImage)?
+            .check_sub_image(x, y, &data)?;
+        self.bind();
+        self.context
+            .pixel_storei(WebGl2RenderingContext::UNPACK_ALIGNMENT, 1);
+        self.context
+            .tex_sub_image_2d_with_i32_and_i32_and_u32_and_type_and_opt_u8_array(
+                WebGl2RenderingContext::TEXTURE_2D,
+                0,
+                x as i32,
+                y as i32,
+                data.width() as i32,
+                data.height() as i32,
+                to_gl_format(data.format()),
+                WebGl2RenderingContext::UNSIGNED_BYTE,
+                Some(data.pixels()),
+            )
+            .map_err(|_| SetTextureDataError::UploadFailed)
+    }
+
+    fn generate_mipmaps(&self) {
+        if self.shape.get().is_none() {
+            log::warn!("Can't generate mipmaps for an empty texture");
+            return;
+        }
+        self.bind();
+        self.context
+            .generate_mipmap(WebGl2RenderingContext::TEXTURE_2D);
+        self.has_mipmaps.set(true);
+        self.apply_sampler();
+    }
+
+    fn set_sampler(&self, sampler: &Sampler) {
+        self.sampler.set(*sampler);
+        self.apply_sampler();
+    }
+}
+
+fn to_gl_format(format: TextureFormat) -> u32 {
+    match format {
+        TextureFormat::Gray => WebGl2RenderingContext::LUMINANCE,
+        TextureFormat::GrayAlpha => WebGl2RenderingContext::LUMINANCE_ALPHA,
+        TextureFormat::Rgb => WebGl2RenderingContext::RGB,
+        TextureFormat::Rgba => WebGl2RenderingContext::RGBA,
+    }
+}
+
+fn to_gl_internal_format(format: TextureFormat) -> u32 {
+    match format {
+        // The luminance formats have no sized versions.
+        TextureFormat::Gray => WebGl2RenderingContext::LUMINANCE,
+        TextureFormat::GrayAlpha => WebGl2RenderingContext::LUMINANCE_ALPHA,
+        TextureFormat::Rgb => WebGl2RenderingContext::RGB8,
+        TextureFormat::Rgba => WebGl2RenderingContext::RGBA8,
+    }
+}
+
+fn to_gl_wrap(wrap: WrapMode) -> u32 {
+    match wrap {
+        WrapMode::Repeat => WebGl2RenderingContext::REPEAT,
+        WrapMode::MirroredRepeat => WebGl2RenderingContext::MIRRORED_REPEAT,
+        WrapMode::ClampToEdge => WebGl2RenderingContext::CLAMP_TO_EDGE,
+    }
+}
+
+fn to_gl_min_filter(filter: MinFilter) -> u32 {
+    match filter {
+        MinFilter::Nearest => WebGl2RenderingContext::NEAREST,
+        MinFilter::Linear => WebGl2RenderingContext::LINEAR,
+        MinFilter::NearestMipmapNearest => WebGl2RenderingContext::NEAREST_MIPMAP_NEAREST,
+        MinFilter::LinearMipmapNearest => WebGl2RenderingContext::LINEAR_MIPMAP_NEAREST,
+        MinFilter::NearestMipmapLinear => WebGl2RenderingContext::NEAREST_MIPMAP_LINEAR,
+        MinFilter::LinearMipmapLinear => WebGl2RenderingContext::LINEAR_MIPMAP_LINEAR,
+    }
+}
+
+fn to_gl_mag_filter(filter: MagFilter) -> u32 {
+    match filter {
+        MagFilter::Nearest => WebGl2RenderingContext::NEAREST,
+        MagFilter::Linear => WebGl2RenderingContext::LINEAR,
+    }
+}
@@ -1,4 +1,4 @@
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 use std::mem;
 use std::rc::Rc;
 
@@ -7,7 +7,8 @@ use crate::rendering::context::RenderingContext;
 use crate::rendering::material::Material;
 use crate::rendering::mesh::{ElementIndices, Mesh, Primitive, PrimitiveGeometry, VertexAttribute};
 
-use nalgebra::Vector3;
+use nalgebra::{Point2, Vector3};
+use rand::Rng;
 
 pub struct GridMesh {
     positions: Vec<Vector3<f32>>,
@@ -77,31 +78,46 @@ impl GridMesh {
         context: &Context,
     ) -> Result<PrimitiveGeometry<Context>, ()> {
         let index_data = self.face_indices::<u16>(); // TODO: use smaller index types where possible.
-        let indices = ElementIndices::from_data(&index_data, context)?;
-
-        let attribute_buf = Rc::new(context.make_attribute_buffer()?);
-        let mut attribute_data = Vec::<Vector3<f32>>::new();
-        attribute_data.reserve(self.positions.len() + self.normals.len());
-        for i in 0..self.positions.len() {
-            attribute_data.push(self.positions[i]);
-            attribute_data.push(self.normals[i]);
-        }
-        attribute_buf.set_data(attribute_data.as_bytes());
+        make_geometry(&self.positions, &self.normals, &index_data, context)
+    }
+}
+
+/// Builds a primitive with interleaved position and normal data
+pub fn make_geometry<Context: RenderingContext>(
+    positions: &[Vector3<f32>],
+    normals: &[Vector3<f32>],
+    index_data: &[u16],
+    context: &Context,
+) -> Result<PrimitiveGeometry<Context>, ()> {
+    let indices = ElementIndices::from_data(&index_data, context)?;
 
-        let vec_size = mem::size_of::<Vector3<f32>>();
-        let stride = vec_size * 2;
+    let attribute_buf = Rc::new(context.make_attribute_buffer()?);
+    let mut attribute_data = Vec::<Vector3<f32>>::with_capacity(positions.len() + normals.len());
+    for (position, normal) in positions.iter().zip(normals.iter()) {
+        attribute_data.push(*position);
+        attribute_data.push(*normal);
+    }
+    attribute_buf.set_data(attribute_data.as_bytes());
 
-        let mut pos_binding = VertexAttributeBinding::typed::<Vector3<f32>>(self.positions.len());
-        pos_binding.set_stride(stride);
-        let positions = VertexAttribute::new(Rc::clone(&attribute_buf), pos_binding);
+    let vec_size = mem::size_of::<Vector3<f32>>();
+    let stride = vec_size * 2;
 
-        let mut normal_binding = VertexAttributeBinding::typed::<Vector3<f32>>(self.normals.len());
-        normal_binding.set_offset(vec_size);
-        normal_binding.set_stride(stride);
-        let normals = VertexAttribute::new(Rc::clone(&attribute_buf), normal_binding);
+    let mut pos_binding = VertexAttributeBinding::typed::<Vector3<f32>>(positions.len());
+    pos_binding.set_stride(stride);
+    let positions = VertexAttribute::new(Rc::clone(&attribute_buf), pos_binding);
 
-        Ok(PrimitiveGeometry::new(Some(indices), positions, normals))
-    }
+    let mut normal_binding = VertexAttributeBinding::typed::<Vector3<f32>>(normals.len());
+    normal_binding.set_offset(vec_size);
+    normal_binding.set_stride(stride);
+    let normals = VertexAttribute::new(Rc::clone(&attribute_buf), normal_binding);
+
+    Ok(PrimitiveGeometry::new(
+        Some(indices),
+        positions,
+        normals,
+        None,
+        None,
+    ))
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -197,6 +213,9 @@ pub fn gen_part_sphere(radius: f32, segments: usize, face: CubeFace) -> GridMesh
     mesh
 }
 
+/// Generates a sphere as a single primitive, so that copies of it can be drawn in one draw call
+///
+/// The sphere is built from six grids, one for each face of a cube.
 pub fn gen_sphere<Context>(
     radius: f32,
     segments: usize,
@@ -206,17 +225,165 @@ pub fn gen_sphere<Context>(
 where
     Context: RenderingContext,
 {
-    let primitives = CubeFace::ALL
-        .iter()
-        .map(|f| gen_part_sphere(radius, segments, *f))
-        .map(|m| m.make_primitive(context))
-        .map(move |g| {
-            Ok(Primitive {
-                material: material.clone(),
-                geometry: Rc::new(g?),
-            })
+    // Every vertex has to be addressable with 16-bit indices.
+    let verts_per_face = (segments + 1) * (segments + 1);
+    if u16::try_from(verts_per_face * CubeFace::ALL.len()).is_err() {
+        return Err(());
+    }
+
+    let mut positions = Vec::with_capacity(verts_per_face * CubeFace::ALL.len());
+    let mut normals = Vec::with_capacity(verts_per_face * CubeFace::ALL.len());
+    let mut index_data = Vec::<u16>::new();
+    for face in CubeFace::ALL.iter() {
+        let grid = gen_part_sphere(radius, segments, *face);
+        let offset = positions.len() as u16;
+        index_data.extend(grid.face_indices::<u16>().iter().map(|i| i + offset));
+        positions.extend_from_slice(&grid.positions);
+        normals.extend_from_slice(&grid.normals);
+    }
+    let geometry = make_geometry(&positions, &normals, &index_data, context)?;
+
+    Ok(Mesh::new(vec![Primitive {
+        material,
+        geometry: Rc::new(geometry),
+    }]))
+}
+
+/// Generates a random, possibly concave outline for an asteroid
+///
+/// The outline is star-shaped around the origin, and its points are in counterclockwise order.
+/// The roughness (from 0 to 1) controls how far each point's radius strays from the mean radius.
+pub fn gen_asteroid_outline<R: Rng>(
+    rng: &mut R,
+    mean_radius: f32,
+    num_points: usize,
+    roughness: f32,
+) -> Vec<Point2<f32>> {
+    let step = 2.0 * std::f32::consts::PI / num_points as f32;
+    (0..num_points)
+        .map(|i| {
+            // Jitter the angles a little, but not enough to change the points' order.
+            let angle = (i as f32 + rng.gen_range(-0.3..0.3)) * step;
+            let radius = mean_radius * (1.0 + rng.gen_range(-roughness..=roughness));
+            Point2::new(angle.cos() * radius, angle.sin() * radius)
         })
-        .collect::<Result<Vec<_>, _>>()?;
+        .collect()
+}
+
+/// Returns the area of a simple polygon whose points are in counterclockwise order
+pub fn polygon_area(points: &[Point2<f32>]) -> f32 {
+    let doubled: f32 = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| a.x * b.y - b.x * a.y)
+        .sum();
+    doubled * 0.5
+}
+
+/// Checks whether a polygon whose points are in counterclockwise order is convex
+pub fn is_convex(points: &[Point2<f32>]) -> bool {
+    let n = points.len();
+    (0..n).all(|i| {
+        let a = points[i];
+        let b = points[(i + 1) % n];
+        let c = points[(i + 2) % n];
+        (b - a).perp(&(c - b)) >= 0.0
+    })
+}
+
+/// Splits a simple polygon whose points are in counterclockwise order into triangles
+///
+/// Returns the indices of the points at the corners of each triangle, also in counterclockwise
+/// order. Self-intersecting polygons may be left partly uncovered.
+pub fn triangulate(points: &[Point2<f32>]) -> Vec<[usize; 3]> {
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len().saturating_sub(2));
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let corners = |i: usize| {
+            [
+                remaining[(i + n - 1) % n],
+                remaining[i],
+                remaining[(i + 1) % n],
+            ]
+        };
+        // Clip a convex corner whose triangle doesn't contain any of the other points.
+        let ear = (0..n).find(|&i| {
+            let [a, b, c] = corners(i);
+            let (a, b, c) = (points[a], points[b], points[c]);
+            (b - a).perp(&(c - b)) > 0.0
+                && remaining.iter().all(|&j| {
+                    let p = points[j];
+                    p == a
+                        || p == b
+                        || p == c
+                        || (b - a).perp(&(p - a)) < 0.0
+                        || (c - b).perp(&(p - b)) < 0.0
+                        || (a - c).perp(&(p - c)) < 0.0
+                })
+        });
+        match ear {
+            Some(i) => {
+                triangles.push(corners(i));
+                remaining.remove(i);
+            }
+            None => return triangles,
+        }
+    }
+    if remaining.len() == 3 {
+        triangles.push([remaining[0], remaining[1], remaining[2]]);
+    }
+    triangles
+}
+
+/// Generates a faceted asteroid mesh from a star-shaped outline
+///
+/// The outline forms the asteroid's rim, and the faces slope up (and down) to peaks of the given
+/// height above (and below) the origin.
+pub fn gen_asteroid<Context>(
+    outline: &[Point2<f32>],
+    height: f32,
+    context: &Context,
+    material: Material<Context>,
+) -> Result<Mesh<Context>, ()>
+where
+    Context: RenderingContext,
+{
+    let num_faces = outline.len() * 2;
+    let mut positions = Vec::with_capacity(num_faces * 3);
+    let mut normals = Vec::with_capacity(num_faces * 3);
+    for (a, b) in outline.iter().zip(outline.iter().cycle().skip(1)) {
+        let a = Vector3::new(a.x, a.y, 0.0);
+        let b = Vector3::new(b.x, b.y, 0.0);
+        for peak in [
+            Vector3::new(0.0, 0.0, height),
+            Vector3::new(0.0, 0.0, -height),
+        ]
+        .iter()
+        {
+            // Keep the winding counterclockwise when viewed from outside.
+            let triangle = if peak.z > 0.0 {
+                [*peak, a, b]
+            } else {
+                [*peak, b, a]
+            };
+            let normal = (triangle[1] - triangle[0])
+                .cross(&(triangle[2] - triangle[0]))
+                .normalize();
+            for corner in triangle.iter() {
+                positions.push(*corner);
+                normals.push(normal);
+            }
+        }
+    }
+    let indices: Vec<u16> = (0..positions.len())
+        .map(|i| i.try_into())
+        .collect::<Result<_, _>>()
+        .map_err(|_| ())?;
 
-    Ok(Mesh::new(primitives))
+    let geometry = make_geometry(&positions, &normals, &indices, context)?;
+    Ok(Mesh::new(vec![Primitive {
+        material,
+        geometry: Rc::new(geometry),
+    }]))
 }
@@ -3,11 +3,11 @@ use std::fmt::Debug;
 use std::mem;
 use std::slice;
 
-use nalgebra::Vector3;
+use nalgebra::{Vector3, Vector4};
 
 use crate::rendering::context;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum AttributeType {
     Byte,
     UnsignedByte,
@@ -18,7 +18,7 @@ pub enum AttributeType {
     Float,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum IndexType {
     UnsignedByte,
     UnsignedShort,
@@ -75,14 +75,14 @@ where
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct ElementBinding {
     pub index_type: IndexType,
     pub count: usize,
     pub offset: usize,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct VertexAttributeBinding {
     pub attr_type: AttributeType,
     pub num_components: usize,
@@ -90,6 +90,8 @@ pub struct VertexAttributeBinding {
     pub stride: usize,
     pub offset: usize,
     pub count: usize,
+    /// Number of instances that use each value, or 0 to read a value for each vertex
+    pub divisor: usize,
 }
 
 impl VertexAttributeBinding {
@@ -101,6 +103,7 @@ impl VertexAttributeBinding {
             stride: 0,
             offset: 0,
             count,
+            divisor: 0,
         }
     }
 
@@ -118,6 +121,11 @@ impl VertexAttributeBinding {
         self.offset = offset;
         self
     }
+
+    pub fn set_divisor(&mut self, divisor: usize) -> &mut VertexAttributeBinding {
+        self.divisor = divisor;
+        self
+    }
 }
 
 /// Marker trait for types that can be used as attribute data for rendering buffers
@@ -183,3 +191,11 @@ where
     const ATTRIB_TYPE: AttributeType = <T as VertexAttributeData>::ATTRIB_TYPE;
     const ATTRIB_COUNT: usize = 3;
 }
+
+unsafe impl<T> VertexAttributeData for Vector4<T>
+where
+    T: VertexAttributeData + nalgebra::Scalar,
+{
+    const ATTRIB_TYPE: AttributeType = <T as VertexAttributeData>::ATTRIB_TYPE;
+    const ATTRIB_COUNT: usize = 4;
+}
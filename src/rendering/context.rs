@@ -30,6 +30,13 @@ pub trait RenderingContext: Debug {
         shaders: T,
     ) -> Result<Self::ShaderProgram, Self::ShaderLinkError>;
 
+    /// Returns the width and height of the drawing area in device pixels
+    fn viewport_size(&self) -> (u32, u32);
+    /// Returns the number of device pixels per CSS pixel
+    fn pixel_ratio(&self) -> f32;
+    /// Returns whether attribute divisors and instanced draw calls are available
+    fn supports_instancing(&self) -> bool;
+
     fn bind_shader(
         &self,
         shader: Rc<Self::ShaderProgram>,
@@ -0,0 +1,174 @@
+//! Reads the attribute and uniform declarations from the game's GLSL shaders
+//!
+//! Contexts that don't hand shaders to a GPU use these declarations to find out which program
+//! they're running and which uniforms it has.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Display;
+
+use nalgebra::{Matrix4, Vector2, Vector3, Vector4};
+
+#[derive(Clone, Debug)]
+pub enum ShaderCreationError {
+    UnsupportedType(String),
+    UnbalancedBraces,
+}
+
+impl Display for ShaderCreationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ShaderCreationError::UnsupportedType(t) => {
+                write!(f, "Unsupported type for shader parameter: {}", t)
+            }
+            ShaderCreationError::UnbalancedBraces => write!(f, "Unbalanced braces in shader"),
+        }
+    }
+}
+
+impl Error for ShaderCreationError {}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UniformType {
+    Bool,
+    Float,
+    Vec2,
+    Vec3,
+    Vec4,
+    Mat4,
+    Sampler2D,
+}
+
+impl UniformType {
+    fn parse(name: &str) -> Option<UniformType> {
+        match name {
+            "bool" => Some(UniformType::Bool),
+            "float" => Some(UniformType::Float),
+            "vec2" => Some(UniformType::Vec2),
+            "vec3" => Some(UniformType::Vec3),
+            "vec4" => Some(UniformType::Vec4),
+            "mat4" => Some(UniformType::Mat4),
+            "sampler2D" => Some(UniformType::Sampler2D),
+            _ => None,
+        }
+    }
+}
+
+/// The attributes and uniforms declared by a shader's source
+#[derive(Clone, Debug, Default)]
+pub struct Declarations {
+    pub attributes: Vec<String>,
+    pub uniforms: Vec<(String, UniformType)>,
+}
+
+impl Declarations {
+    /// Finds the top-level attribute and uniform declarations in GLSL source
+    ///
+    /// Uniform structs are flattened into one uniform per field (such as `material.roughness`),
+    /// like the names that WebGL reports.
+    pub fn parse(source: &str) -> Result<Declarations, ShaderCreationError> {
+        let mut code = String::with_capacity(source.len());
+        for line in source.lines() {
+            let line = line.split("//").next().unwrap_or("");
+            for c in line.chars() {
+                if "{};,()".contains(c) {
+                    code.push(' ');
+                    code.push(c);
+                    code.push(' ');
+                } else {
+                    code.push(c);
+                }
+            }
+            code.push('\n');
+        }
+        let tokens: Vec<&str> = code.split_whitespace().collect();
+
+        let mut declarations = Declarations::default();
+        let mut structs: HashMap<&str, Vec<(&str, &str)>> = HashMap::new();
+        let mut depth = 0usize;
+        let mut i = 0;
+        while i < tokens.len() {
+            match (depth, tokens[i]) {
+                (_, "{") => depth += 1,
+                (_, "}") => {
+                    depth = depth
+                        .checked_sub(1)
+                        .ok_or(ShaderCreationError::UnbalancedBraces)?
+                }
+                (0, "struct") if i + 2 < tokens.len() && tokens[i + 2] == "{" => {
+                    let name = tokens[i + 1];
+                    let mut fields = Vec::new();
+                    i += 3;
+                    while i + 1 < tokens.len() && tokens[i] != "}" {
+                        fields.push((tokens[i], tokens[i + 1]));
+                        // Skip the semicolon.
+                        i += 3;
+                    }
+                    structs.insert(name, fields);
+                }
+                (0, "attribute") if i + 2 < tokens.len() => {
+                    let (type_name, name) = (tokens[i + 1], tokens[i + 2]);
+                    UniformType::parse(type_name)
+                        .ok_or_else(|| ShaderCreationError::UnsupportedType(type_name.into()))?;
+                    declarations.attributes.push(String::from(name));
+                    i += 2;
+                }
+                (0, "uniform") if i + 2 < tokens.len() => {
+                    let (type_name, name) = (tokens[i + 1], tokens[i + 2]);
+                    match structs.get(type_name) {
+                        Some(fields) => {
+                            for (field_type, field) in fields.iter() {
+                                let uniform_type =
+                                    UniformType::parse(field_type).ok_or_else(|| {
+                                        ShaderCreationError::UnsupportedType((*field_type).into())
+                                    })?;
+                                declarations
+                                    .uniforms
+                                    .push((format!("{}.{}", name, field), uniform_type));
+                            }
+                        }
+                        None => {
+                            let uniform_type = UniformType::parse(type_name).ok_or_else(|| {
+                                ShaderCreationError::UnsupportedType(type_name.into())
+                            })?;
+                            declarations
+                                .uniforms
+                                .push((String::from(name), uniform_type));
+                        }
+                    }
+                    i += 2;
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        if depth != 0 {
+            return Err(ShaderCreationError::UnbalancedBraces);
+        }
+        Ok(declarations)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UniformValue {
+    /// A boolean, or the texture unit of a sampler
+    Int(i32),
+    Float(f32),
+    Vec2(Vector2<f32>),
+    Vec3(Vector3<f32>),
+    Vec4(Vector4<f32>),
+    Mat4(Matrix4<f32>),
+}
+
+impl UniformValue {
+    pub fn zero(uniform_type: UniformType) -> UniformValue {
+        match uniform_type {
+            UniformType::Bool | UniformType::Sampler2D => UniformValue::Int(0),
+            UniformType::Float => UniformValue::Float(0.0),
+            UniformType::Vec2 => UniformValue::Vec2(Vector2::zeros()),
+            UniformType::Vec3 => UniformValue::Vec3(Vector3::zeros()),
+            UniformType::Vec4 => UniformValue::Vec4(Vector4::zeros()),
+            UniformType::Mat4 => UniformValue::Mat4(Matrix4::zeros()),
+        }
+    }
+}
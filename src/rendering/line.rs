@@ -1,40 +1,66 @@
+use std::mem;
 use std::ops::Deref;
 use std::rc::Rc;
 
-use nalgebra::{Matrix4, Vector3};
+use nalgebra::{Matrix4, Vector2, Vector3};
 
 use crate::rendering;
-use crate::rendering::buffer::{AttributeBuffer, Buffer, BufferData, VertexAttributeBinding};
+use crate::rendering::buffer::{
+    AttributeBuffer, Buffer, BufferData, VertexAttributeBinding, VertexAttributeData,
+};
 use crate::rendering::context::RenderingContext;
 use crate::rendering::shader::{BoundShader, ShaderBindError};
 use crate::rendering::shader::{ShaderInfoError, ShaderParamInfo};
 use crate::rendering::Rgb;
 
+/// A connected series of line segments, with a transparency value at each vertex
 #[derive(Clone, Debug)]
 pub struct PolyLine<Context: RenderingContext> {
     buffer: Context::AttributeBuffer,
     binding: VertexAttributeBinding,
+    alpha_buffer: Context::AttributeBuffer,
+    alpha_binding: VertexAttributeBinding,
     pub color: Rgb,
+    /// Multiplies the alpha of every vertex
+    pub opacity: f32,
 }
 
 impl<Context: RenderingContext> PolyLine<Context> {
-    pub fn new(buffer: Context::AttributeBuffer, color: Rgb) -> PolyLine<Context> {
-        PolyLine {
-            buffer,
+    pub fn new(context: &Context, color: Rgb) -> Result<PolyLine<Context>, ()> {
+        Ok(PolyLine {
+            buffer: context.make_attribute_buffer()?,
             binding: VertexAttributeBinding::typed::<Vector3<f32>>(0),
+            alpha_buffer: context.make_attribute_buffer()?,
+            alpha_binding: VertexAttributeBinding::typed::<f32>(0),
             color,
-        }
+            opacity: 1.0,
+        })
     }
 
+    /// Sets the line's vertices, making them all opaque
     pub fn set_positions(&mut self, locations: &[Vector3<f32>]) {
+        self.set_vertices(locations, &vec![1.0; locations.len()]);
+    }
+
+    /// Sets the line's vertices and the alpha at each one
+    ///
+    /// Extra positions or alpha values are ignored.
+    pub fn set_vertices(&mut self, locations: &[Vector3<f32>], alphas: &[f32]) {
+        let count = locations.len().min(alphas.len());
+        let (locations, alphas) = (&locations[..count], &alphas[..count]);
         self.buffer.set_data(locations.as_bytes());
-        self.binding = VertexAttributeBinding::typed::<Vector3<f32>>(locations.len());
+        self.binding = VertexAttributeBinding::typed::<Vector3<f32>>(count);
+        self.alpha_buffer.set_data(alphas.as_bytes());
+        self.alpha_binding = VertexAttributeBinding::typed::<f32>(count);
     }
 
     pub fn draw(&self, context: &BoundLineShader<Context>) {
         context.info.bind_color(&self.color, context.deref());
+        context.set_uniform_f32(context.info.opacity.index, self.opacity);
         self.buffer
             .bind_to_attribute(context.info.position.index, &self.binding);
+        self.alpha_buffer
+            .bind_to_attribute(context.info.alpha.index, &self.alpha_binding);
         context.draw_polyline(self.binding.count);
     }
 }
@@ -42,7 +68,9 @@ impl<Context: RenderingContext> PolyLine<Context> {
 #[derive(Clone, Debug)]
 pub struct LineShaderInfo {
     pub position: ShaderParamInfo,
+    pub alpha: ShaderParamInfo,
     pub color: ShaderParamInfo,
+    pub opacity: ShaderParamInfo,
     pub projection: ShaderParamInfo,
     pub view_transform: ShaderParamInfo,
 }
@@ -55,12 +83,16 @@ impl LineShaderInfo {
         Context: RenderingContext,
     {
         let position = ShaderParamInfo::attribute(program, "position")?;
+        let alpha = ShaderParamInfo::attribute(program, "alpha")?;
         let color = ShaderParamInfo::uniform(program, "color")?;
+        let opacity = ShaderParamInfo::uniform(program, "opacity")?;
         let projection = ShaderParamInfo::uniform(program, "projection")?;
         let view_transform = ShaderParamInfo::uniform(program, "view")?;
         Ok(LineShaderInfo {
             position,
+            alpha,
             color,
+            opacity,
             projection,
             view_transform,
         })
@@ -133,3 +165,248 @@ where
         &self.bound_shader
     }
 }
+
+/// How a thick line is drawn
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LineStyle {
+    /// Width in CSS pixels (which is scaled by the device pixel ratio)
+    pub width: f32,
+    /// Lengths of each dash and the gap after it (in CSS pixels), or `None` for a solid line
+    pub dashes: Option<(f32, f32)>,
+}
+
+impl LineStyle {
+    pub const fn solid(width: f32) -> LineStyle {
+        LineStyle {
+            width,
+            dashes: None,
+        }
+    }
+
+    pub const fn dashed(width: f32, dash_length: f32, gap: f32) -> LineStyle {
+        LineStyle {
+            width,
+            dashes: Some((dash_length, gap)),
+        }
+    }
+}
+
+// Number of floats in each thick line vertex: position, previous and next positions, side, alpha
+// and distance along the line
+const THICK_LINE_VERTEX_FLOATS: usize = 12;
+
+/// A line with a constant width on screen, drawn as a strip of triangles
+///
+/// Unlike `PolyLine`, this doesn't depend on the line widths that the platform supports. Points
+/// are joined with (limited) miter joins, and the ends have square caps. Clones share the same
+/// vertex buffer, but each one has its own model transform, so one line can be drawn in several
+/// places.
+#[derive(Debug)]
+pub struct ThickLine<Context: RenderingContext> {
+    buffer: Rc<Context::AttributeBuffer>,
+    num_vertices: usize,
+    /// Transforms the line's points into world coordinates
+    pub model_transform: Matrix4<f32>,
+    pub color: Rgb,
+    /// Multiplies the alpha of every vertex
+    pub opacity: f32,
+    pub style: LineStyle,
+}
+
+impl<Context: RenderingContext> ThickLine<Context> {
+    pub fn new(context: &Context, color: Rgb, style: LineStyle) -> Result<ThickLine<Context>, ()> {
+        Ok(ThickLine {
+            buffer: Rc::new(context.make_attribute_buffer()?),
+            num_vertices: 0,
+            model_transform: Matrix4::identity(),
+            color,
+            opacity: 1.0,
+            style,
+        })
+    }
+
+    /// Sets the line's points, making them all opaque
+    pub fn set_positions(&mut self, locations: &[Vector3<f32>]) {
+        self.set_vertices(locations, &vec![1.0; locations.len()]);
+    }
+
+    /// Sets the line's points and the alpha at each one
+    ///
+    /// Extra positions or alpha values are ignored.
+    pub fn set_vertices(&mut self, locations: &[Vector3<f32>], alphas: &[f32]) {
+        let count = locations.len().min(alphas.len());
+        let mut data = Vec::with_capacity(count * 2 * THICK_LINE_VERTEX_FLOATS);
+        let mut distance = 0.0;
+        for i in 0..count {
+            let position = locations[i];
+            let previous = locations[i.saturating_sub(1)];
+            let next = locations[(i + 1).min(count - 1)];
+            distance += (position - previous).magnitude();
+            // The left side comes first so the strip's triangles face forward.
+            for &side in [1.0, -1.0].iter() {
+                data.extend_from_slice(position.as_slice());
+                data.extend_from_slice(previous.as_slice());
+                data.extend_from_slice(next.as_slice());
+                data.extend_from_slice(&[side, alphas[i], distance]);
+            }
+        }
+        self.buffer.set_data(data.as_bytes());
+        self.num_vertices = count * 2;
+    }
+
+    fn binding<T: VertexAttributeData>(&self, offset: usize) -> VertexAttributeBinding {
+        let mut binding = VertexAttributeBinding::typed::<T>(self.num_vertices);
+        binding
+            .set_stride(THICK_LINE_VERTEX_FLOATS * mem::size_of::<f32>())
+            .set_offset(offset * mem::size_of::<f32>());
+        binding
+    }
+
+    pub fn draw(&self, context: &BoundThickLineShader<Context>) {
+        let info = &context.info;
+        info.bind_color(&self.color, context.deref());
+        context.set_uniform_f32(info.opacity.index, self.opacity);
+        context.set_uniform_mat4(info.model_transform.index, self.model_transform);
+        context.set_uniform_f32(info.width.index, self.style.width * context.pixel_ratio);
+        let (dash_length, dash_gap) = self.style.dashes.unwrap_or((0.0, 0.0));
+        context.set_uniform_f32(info.dash_length.index, dash_length * context.pixel_ratio);
+        context.set_uniform_f32(info.dash_gap.index, dash_gap * context.pixel_ratio);
+
+        let attributes = [
+            (&info.position, self.binding::<Vector3<f32>>(0)),
+            (&info.previous, self.binding::<Vector3<f32>>(3)),
+            (&info.next, self.binding::<Vector3<f32>>(6)),
+            (&info.side, self.binding::<f32>(9)),
+            (&info.alpha, self.binding::<f32>(10)),
+            (&info.distance, self.binding::<f32>(11)),
+        ];
+        for (param, binding) in attributes.iter() {
+            self.buffer.bind_to_attribute(param.index, binding);
+        }
+        context.draw_triangle_strip(self.num_vertices);
+    }
+}
+
+impl<Context: RenderingContext> Clone for ThickLine<Context> {
+    fn clone(&self) -> Self {
+        ThickLine {
+            buffer: Rc::clone(&self.buffer),
+            num_vertices: self.num_vertices,
+            model_transform: self.model_transform,
+            color: self.color,
+            opacity: self.opacity,
+            style: self.style,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ThickLineShaderInfo {
+    pub position: ShaderParamInfo,
+    pub previous: ShaderParamInfo,
+    pub next: ShaderParamInfo,
+    pub side: ShaderParamInfo,
+    pub alpha: ShaderParamInfo,
+    pub distance: ShaderParamInfo,
+    pub color: ShaderParamInfo,
+    pub opacity: ShaderParamInfo,
+    pub width: ShaderParamInfo,
+    pub dash_length: ShaderParamInfo,
+    pub dash_gap: ShaderParamInfo,
+    pub viewport: ShaderParamInfo,
+    pub projection: ShaderParamInfo,
+    pub view_transform: ShaderParamInfo,
+    pub model_transform: ShaderParamInfo,
+}
+
+impl ThickLineShaderInfo {
+    pub fn from_program<Context>(
+        program: &Context::ShaderProgram,
+    ) -> Result<ThickLineShaderInfo, ShaderInfoError>
+    where
+        Context: RenderingContext,
+    {
+        Ok(ThickLineShaderInfo {
+            position: ShaderParamInfo::attribute(program, "position")?,
+            previous: ShaderParamInfo::attribute(program, "previous")?,
+            next: ShaderParamInfo::attribute(program, "next")?,
+            side: ShaderParamInfo::attribute(program, "side")?,
+            alpha: ShaderParamInfo::attribute(program, "alpha")?,
+            distance: ShaderParamInfo::attribute(program, "arc_length")?,
+            color: ShaderParamInfo::uniform(program, "color")?,
+            opacity: ShaderParamInfo::uniform(program, "opacity")?,
+            width: ShaderParamInfo::uniform(program, "width")?,
+            dash_length: ShaderParamInfo::uniform(program, "dash_length")?,
+            dash_gap: ShaderParamInfo::uniform(program, "dash_gap")?,
+            viewport: ShaderParamInfo::uniform(program, "viewport")?,
+            projection: ShaderParamInfo::uniform(program, "projection")?,
+            view_transform: ShaderParamInfo::uniform(program, "view")?,
+            model_transform: ShaderParamInfo::uniform(program, "model")?,
+        })
+    }
+
+    pub fn bind_color<Context>(&self, color: &Rgb, context: &dyn BoundShader<Context>)
+    where
+        Context: RenderingContext,
+    {
+        context.set_uniform_vec3(self.color.index, rendering::rgb_as_vec3(color));
+    }
+}
+
+#[derive(Debug)]
+pub struct ThickLineShader<Context: RenderingContext> {
+    pub program: Rc<Context::ShaderProgram>,
+    pub info: ThickLineShaderInfo,
+}
+
+impl<Context: RenderingContext> ThickLineShader<Context> {
+    pub fn new(program: Context::ShaderProgram) -> Result<Self, ShaderInfoError> {
+        let info = ThickLineShaderInfo::from_program::<Context>(&program)?;
+        Ok(ThickLineShader {
+            program: Rc::new(program),
+            info,
+        })
+    }
+}
+
+pub struct BoundThickLineShader<Context: RenderingContext> {
+    bound_shader: Context::BoundShader,
+    info: ThickLineShaderInfo,
+    pixel_ratio: f32,
+}
+
+impl<Context: RenderingContext> BoundThickLineShader<Context> {
+    pub fn new(
+        context: &Context,
+        shader: &ThickLineShader<Context>,
+        world: &dyn LineWorldContext,
+    ) -> Result<Self, ShaderBindError> {
+        let bound_shader = context.bind_shader(Rc::clone(&shader.program))?;
+        bound_shader.set_uniform_mat4(shader.info.projection.index, world.projection());
+        bound_shader.set_uniform_mat4(shader.info.view_transform.index, world.view());
+        let (width, height) = context.viewport_size();
+        bound_shader.set_uniform_vec2(
+            shader.info.viewport.index,
+            Vector2::new(width as f32, height as f32),
+        );
+        Ok(BoundThickLineShader {
+            bound_shader,
+            info: shader.info.clone(),
+            pixel_ratio: context.pixel_ratio(),
+        })
+    }
+
+    pub fn info(&self) -> &ThickLineShaderInfo {
+        &self.info
+    }
+}
+
+impl<Context> Deref for BoundThickLineShader<Context>
+where
+    Context: RenderingContext,
+{
+    type Target = dyn BoundShader<Context>;
+    fn deref(&self) -> &Self::Target {
+        &self.bound_shader
+    }
+}
@@ -10,15 +10,136 @@ use crate::rendering::shader::{BoundShader, ShaderBindError};
 use crate::rendering::shader::{ShaderInfoError, ShaderParamInfo, ShaderProgram};
 use crate::rendering::{Rgb, Rgba};
 
+/// How a material's alpha is used
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlphaMode {
+    /// Alpha is ignored, and the surface is fully opaque
+    Opaque,
+    /// Fragments with an alpha below the cutoff are discarded, and the rest are opaque
+    Mask(f32),
+    /// The surface is blended over what is behind it
+    Blend,
+}
+
 #[derive(Debug)]
 pub struct Material<Context: RenderingContext> {
     pub base_color: Rgba,
     pub base_color_texture: Option<Rc<Context::Texture>>,
     pub metal_factor: f32,
     pub roughness: f32,
+    /// Multiplies the roughness by its green channel, and the metal factor by its blue channel
+    pub metallic_roughness_texture: Option<Rc<Context::Texture>>,
+    /// A tangent space normal map, which needs geometry with texture coordinates and tangents
+    pub normal_texture: Option<Rc<Context::Texture>>,
+    /// Scales the X and Y components of the normal map's normals
+    pub normal_scale: f32,
+    /// Darkens ambient light with its red channel
+    pub occlusion_texture: Option<Rc<Context::Texture>>,
+    /// How much of the occlusion texture is applied, from 0 (none) to 1
+    pub occlusion_strength: f32,
+    pub emissive: Rgb,
+    pub emissive_texture: Option<Rc<Context::Texture>>,
+    pub alpha_mode: AlphaMode,
+    /// Whether back faces are drawn (with flipped normals) instead of culled
+    pub double_sided: bool,
     pub extras: Option<serde_json::Value>,
 }
 
+/// Identifies the shader uniforms and textures that a material sets, except for its base color
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SurfaceKey {
+    textures: [usize; 5],
+    factors: [u32; 10],
+}
+
+impl<Context: RenderingContext> Material<Context> {
+    /// Creates an untextured material, which is blended if the color is translucent
+    pub fn from_color(base_color: Rgba, metal_factor: f32, roughness: f32) -> Self {
+        let alpha_mode = if base_color.a < 1.0 {
+            AlphaMode::Blend
+        } else {
+            AlphaMode::Opaque
+        };
+        Material {
+            base_color,
+            base_color_texture: None,
+            metal_factor,
+            roughness,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+            occlusion_texture: None,
+            occlusion_strength: 1.0,
+            emissive: Rgb::new(0.0, 0.0, 0.0),
+            emissive_texture: None,
+            alpha_mode,
+            double_sided: false,
+            extras: None,
+        }
+    }
+
+    /// Returns whether the material needs to be drawn after opaque surfaces, back to front
+    pub fn is_blended(&self) -> bool {
+        self.alpha_mode == AlphaMode::Blend
+    }
+
+    /// Returns a key that is equal for materials that set the same shader uniforms
+    ///
+    /// Materials that only differ in their base color have keys with the same start.
+    pub fn sort_key(&self) -> (SurfaceKey, [u32; 4]) {
+        let color = &self.base_color;
+        (
+            self.surface_key(),
+            [
+                color.r.to_bits(),
+                color.g.to_bits(),
+                color.b.to_bits(),
+                color.a.to_bits(),
+            ],
+        )
+    }
+
+    /// Returns a key that is equal for materials that can be drawn in the same instanced draw
+    pub fn instance_key(&self) -> SurfaceKey {
+        self.surface_key()
+    }
+
+    fn surface_key(&self) -> SurfaceKey {
+        // Textures are identified by their address, or 0 if they aren't set.
+        let texture_key = |texture: &Option<Rc<Context::Texture>>| {
+            texture
+                .as_ref()
+                .map_or(0, |texture| Rc::as_ptr(texture) as usize)
+        };
+        let (alpha_mode, alpha_cutoff) = match self.alpha_mode {
+            AlphaMode::Opaque => (0, 0.0),
+            AlphaMode::Mask(cutoff) => (1, cutoff),
+            AlphaMode::Blend => (2, 0.0),
+        };
+        SurfaceKey {
+            textures: [
+                texture_key(&self.base_color_texture),
+                texture_key(&self.metallic_roughness_texture),
+                texture_key(&self.normal_texture),
+                texture_key(&self.occlusion_texture),
+                texture_key(&self.emissive_texture),
+            ],
+            factors: [
+                self.metal_factor.to_bits(),
+                self.roughness.to_bits(),
+                self.normal_scale.to_bits(),
+                self.occlusion_strength.to_bits(),
+                self.emissive.r.to_bits(),
+                self.emissive.g.to_bits(),
+                self.emissive.b.to_bits(),
+                alpha_mode,
+                alpha_cutoff.to_bits(),
+                self.double_sided as u32,
+            ],
+        }
+    }
+}
+
 // TODO: why doesn't #[derive(Clone)] work properly?
 impl<Context: RenderingContext> Clone for Material<Context> {
     fn clone(&self) -> Self {
@@ -27,22 +148,104 @@ impl<Context: RenderingContext> Clone for Material<Context> {
             base_color_texture: self.base_color_texture.clone(),
             metal_factor: self.metal_factor,
             roughness: self.roughness,
+            metallic_roughness_texture: self.metallic_roughness_texture.clone(),
+            normal_texture: self.normal_texture.clone(),
+            normal_scale: self.normal_scale,
+            occlusion_texture: self.occlusion_texture.clone(),
+            occlusion_strength: self.occlusion_strength,
+            emissive: self.emissive,
+            emissive_texture: self.emissive_texture.clone(),
+            alpha_mode: self.alpha_mode,
+            double_sided: self.double_sided,
             extras: self.extras.clone(),
         }
     }
 }
 
+/// The per-instance attributes of an instanced material shader
+#[derive(Clone, Debug)]
+pub struct InstanceShaderInfo {
+    /// The columns of the model transform
+    pub model_transform: [ShaderParamInfo; 4],
+    /// Multiplies the material's base color
+    pub color: ShaderParamInfo,
+}
+
+impl InstanceShaderInfo {
+    pub fn from_program<Context: RenderingContext>(
+        program: &dyn ShaderProgram<RenderingContext = Context>,
+    ) -> Result<InstanceShaderInfo, ShaderInfoError> {
+        Ok(InstanceShaderInfo {
+            model_transform: [
+                ShaderParamInfo::attribute(program, "model0")?,
+                ShaderParamInfo::attribute(program, "model1")?,
+                ShaderParamInfo::attribute(program, "model2")?,
+                ShaderParamInfo::attribute(program, "model3")?,
+            ],
+            color: ShaderParamInfo::attribute(program, "color")?,
+        })
+    }
+}
+
+/// A material texture's sampler uniform, and the flag that tells the shader whether it is set
+#[derive(Clone, Debug)]
+pub struct TextureShaderInfo {
+    pub sampler: ShaderParamInfo,
+    pub is_set: ShaderParamInfo,
+}
+
+impl TextureShaderInfo {
+    pub fn from_program<Context: RenderingContext>(
+        program: &dyn ShaderProgram<RenderingContext = Context>,
+        sampler: &str,
+        is_set: &str,
+    ) -> Result<TextureShaderInfo, ShaderInfoError> {
+        Ok(TextureShaderInfo {
+            sampler: ShaderParamInfo::uniform(program, sampler)?,
+            is_set: ShaderParamInfo::uniform(program, is_set)?,
+        })
+    }
+
+    pub fn bind<Context: RenderingContext>(
+        &self,
+        texture: &Option<Rc<Context::Texture>>,
+        context: &dyn BoundShader<Context>,
+    ) {
+        if let Some(ref texture) = texture {
+            context.set_uniform_texture(self.sampler.index, texture);
+        }
+        context.set_uniform_bool(self.is_set.index, texture.is_some());
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct MaterialShaderInfo {
     pub position: ShaderParamInfo,
     pub normal: ShaderParamInfo,
+    pub tex_coord: Option<ShaderParamInfo>,
+    pub tangent: Option<ShaderParamInfo>,
 
     pub projection: ShaderParamInfo,
-    pub model_transform: ShaderParamInfo,
+    /// The model transform uniform, which instanced shaders don't have
+    pub model_transform: Option<ShaderParamInfo>,
+    /// The per-instance attributes, for instanced shaders
+    pub instance: Option<InstanceShaderInfo>,
     pub view_transform: ShaderParamInfo,
     pub base_color: Option<ShaderParamInfo>,
     pub metal_factor: Option<ShaderParamInfo>,
     pub roughness: Option<ShaderParamInfo>,
+    pub base_color_texture: Option<TextureShaderInfo>,
+    pub metallic_roughness_texture: Option<TextureShaderInfo>,
+    pub normal_texture: Option<TextureShaderInfo>,
+    pub normal_scale: Option<ShaderParamInfo>,
+    pub occlusion_texture: Option<TextureShaderInfo>,
+    pub occlusion_strength: Option<ShaderParamInfo>,
+    pub emissive: Option<ShaderParamInfo>,
+    pub emissive_texture: Option<TextureShaderInfo>,
+    pub alpha_mask: Option<ShaderParamInfo>,
+    pub alpha_cutoff: Option<ShaderParamInfo>,
+    pub alpha_blend: Option<ShaderParamInfo>,
+    pub double_sided: Option<ShaderParamInfo>,
 
     pub lights: LightShaderInfo,
 }
@@ -51,15 +254,41 @@ impl MaterialShaderInfo {
     pub fn from_program<Context: RenderingContext>(
         program: &dyn ShaderProgram<RenderingContext = Context>,
     ) -> Result<MaterialShaderInfo, ShaderInfoError> {
+        let instance = InstanceShaderInfo::from_program(program).ok();
+        let model_transform = match instance {
+            Some(_) => None,
+            None => Some(ShaderParamInfo::uniform(program, "model")?),
+        };
+        let uniform = |name| ShaderParamInfo::uniform(program, name).ok();
+        let texture =
+            |sampler, is_set| TextureShaderInfo::from_program(program, sampler, is_set).ok();
         Ok(MaterialShaderInfo {
             position: ShaderParamInfo::attribute(program, "position")?,
             normal: ShaderParamInfo::attribute(program, "normal")?,
+            tex_coord: ShaderParamInfo::attribute(program, "texCoord").ok(),
+            tangent: ShaderParamInfo::attribute(program, "tangent").ok(),
             projection: ShaderParamInfo::uniform(program, "projection")?,
-            model_transform: ShaderParamInfo::uniform(program, "model")?,
+            model_transform,
+            instance,
             view_transform: ShaderParamInfo::uniform(program, "view")?,
-            base_color: ShaderParamInfo::uniform(program, "material.baseColor").ok(),
-            metal_factor: ShaderParamInfo::uniform(program, "material.metal").ok(),
-            roughness: ShaderParamInfo::uniform(program, "material.roughness").ok(),
+            base_color: uniform("material.baseColor"),
+            metal_factor: uniform("material.metalFactor"),
+            roughness: uniform("material.roughness"),
+            base_color_texture: texture("baseColorTexture", "material.hasBaseColorTexture"),
+            metallic_roughness_texture: texture(
+                "metallicRoughnessTexture",
+                "material.hasMetallicRoughnessTexture",
+            ),
+            normal_texture: texture("normalTexture", "material.hasNormalTexture"),
+            normal_scale: uniform("material.normalScale"),
+            occlusion_texture: texture("occlusionTexture", "material.hasOcclusionTexture"),
+            occlusion_strength: uniform("material.occlusionStrength"),
+            emissive: uniform("material.emissive"),
+            emissive_texture: texture("emissiveTexture", "material.hasEmissiveTexture"),
+            alpha_mask: uniform("material.alphaMask"),
+            alpha_cutoff: uniform("material.alphaCutoff"),
+            alpha_blend: uniform("material.alphaBlend"),
+            double_sided: uniform("material.doubleSided"),
             lights: LightShaderInfo::from_program(program),
         })
     }
@@ -68,20 +297,74 @@ impl MaterialShaderInfo {
         &self,
         material: &Material<Context>,
         context: &dyn BoundShader<Context>,
+    ) {
+        self.bind_base_color(&material.base_color, context);
+        self.bind_surface(material, context);
+    }
+
+    pub fn bind_base_color<Context: RenderingContext>(
+        &self,
+        color: &Rgba,
+        context: &dyn BoundShader<Context>,
     ) {
         if let Some(ref base_color) = self.base_color {
-            context.set_uniform_vec4(
-                base_color.index,
-                rendering::rgba_as_vec4(&material.base_color),
-            );
+            context.set_uniform_vec4(base_color.index, rendering::rgba_as_vec4(color));
+        }
+    }
+
+    /// Binds everything about a material except for its base color
+    pub fn bind_surface<Context: RenderingContext>(
+        &self,
+        material: &Material<Context>,
+        context: &dyn BoundShader<Context>,
+    ) {
+        let textures = [
+            (&self.base_color_texture, &material.base_color_texture),
+            (
+                &self.metallic_roughness_texture,
+                &material.metallic_roughness_texture,
+            ),
+            (&self.normal_texture, &material.normal_texture),
+            (&self.occlusion_texture, &material.occlusion_texture),
+            (&self.emissive_texture, &material.emissive_texture),
+        ];
+        for (info, texture) in textures.iter() {
+            if let Some(info) = info {
+                info.bind(texture, context);
+            }
+        }
+        let floats = [
+            (&self.metal_factor, material.metal_factor),
+            (&self.roughness, material.roughness),
+            (&self.normal_scale, material.normal_scale),
+            (&self.occlusion_strength, material.occlusion_strength),
+        ];
+        for (info, value) in floats.iter() {
+            if let Some(info) = info {
+                context.set_uniform_f32(info.index, *value);
+            }
+        }
+        if let Some(ref emissive) = self.emissive {
+            context.set_uniform_vec3(emissive.index, rendering::rgb_as_vec3(&material.emissive));
+        }
+
+        let (mask, cutoff) = match material.alpha_mode {
+            AlphaMode::Mask(cutoff) => (true, cutoff),
+            _ => (false, 0.0),
+        };
+        if let Some(ref alpha_mask) = self.alpha_mask {
+            context.set_uniform_bool(alpha_mask.index, mask);
+        }
+        if let Some(ref alpha_cutoff) = self.alpha_cutoff {
+            context.set_uniform_f32(alpha_cutoff.index, cutoff);
         }
-        // TODO: handle textures.
-        if let Some(ref metal_factor) = self.metal_factor {
-            context.set_uniform_f32(metal_factor.index, material.metal_factor);
+        if let Some(ref alpha_blend) = self.alpha_blend {
+            context.set_uniform_bool(alpha_blend.index, material.is_blended());
         }
-        if let Some(ref roughness) = self.roughness {
-            context.set_uniform_f32(roughness.index, material.roughness);
+        if let Some(ref double_sided) = self.double_sided {
+            context.set_uniform_bool(double_sided.index, material.double_sided);
         }
+        context.set_double_sided(material.double_sided);
     }
 }
 
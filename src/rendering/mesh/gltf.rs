@@ -1,28 +1,112 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::convert::TryInto;
 use std::rc::Rc;
 
 use gltf;
 use gltf::accessor::DataType;
 use gltf::buffer::Source;
-use gltf::{Accessor, Gltf, Semantic};
-use image::{ImageError, ImageFormat, RgbImage};
+use gltf::{Accessor, Document, Gltf, Semantic};
+use image::{DynamicImage, ImageError, ImageFormat};
+use nalgebra::{Matrix4, Vector2, Vector3, Vector4};
 
-use crate::rendering::buffer::{AttributeType, Buffer, ElementBinding, VertexAttributeBinding};
+use crate::rendering::buffer::{
+    AttributeType, Buffer, BufferData, ElementBinding, VertexAttributeBinding,
+};
 use crate::rendering::context::RenderingContext;
-use crate::rendering::material::Material;
+use crate::rendering::material::{AlphaMode, Material};
+use crate::rendering::mesh::tangent;
 use crate::rendering::mesh::{ElementIndices, Mesh, Primitive, PrimitiveGeometry, VertexAttribute};
-use crate::rendering::texture::{SetTextureDataError, Texture};
-use crate::rendering::Rgba;
+use crate::rendering::scene::{Scene, SceneNode};
+use crate::rendering::texture::{
+    MagFilter, MinFilter, Sampler, SetTextureDataError, Texture, TextureData, WrapMode,
+};
+use crate::rendering::{Rgb, Rgba};
 
 pub struct GltfLoader<'a, Context: RenderingContext> {
     context: Rc<Context>,
     gltf: &'a Gltf, // TODO: make sure all input parameters come from this Gltf instance?
+    resolver: Option<&'a dyn UriResolver>,
+    /// The contents of the buffers that have been read, by index
+    buffers: HashMap<usize, Rc<[u8]>>,
+    /// Meshes that have been loaded for scenes, by index
+    meshes: HashMap<usize, Rc<Mesh<Context>>>,
+    cache: Rc<GltfCache<Context>>,
+    /// The name that the file's resources are stored under in the cache
+    name: Box<str>,
+}
+
+/// The buffers and textures that have been uploaded from glTF files to a rendering context
+///
+/// Loaders that share a cache upload each buffer, index buffer view and image of a file only
+/// once, however many times the file is loaded. Files are told apart by the names that they are
+/// loaded under, which are usually their URIs.
+pub struct GltfCache<Context: RenderingContext> {
+    context: Rc<Context>,
+    files: RefCell<HashMap<Box<str>, FileResources<Context>>>,
+}
+
+/// The resources that have been uploaded from one file
+struct FileResources<Context: RenderingContext> {
+    /// Uploaded copies of whole buffers, by index, which attributes refer to parts of
+    attribute_buffers: HashMap<usize, Rc<Context::AttributeBuffer>>,
+    /// Uploaded copies of buffer views that hold indices, by view index
+    ///
+    /// WebGL doesn't allow a buffer to hold both indices and attributes, so only the views that
+    /// indices are stored in are uploaded as index buffers.
+    index_buffers: HashMap<usize, Rc<Context::IndexBuffer>>,
+    /// Textures that have been created, by image index and sampler index
+    textures: HashMap<(usize, Option<usize>), Rc<Context::Texture>>,
+}
+
+impl<Context: RenderingContext> GltfCache<Context> {
+    pub fn new(context: Rc<Context>) -> GltfCache<Context> {
+        GltfCache {
+            context,
+            files: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn context(&self) -> &Rc<Context> {
+        &self.context
+    }
+}
+
+impl<Context: RenderingContext> FileResources<Context> {
+    fn new() -> FileResources<Context> {
+        FileResources {
+            attribute_buffers: HashMap::new(),
+            index_buffers: HashMap::new(),
+            textures: HashMap::new(),
+        }
+    }
+}
+
+/// Provides the files that a glTF file refers to by URI, such as `.bin` buffers and images
+///
+/// `data:` URIs are decoded by the loader, so they never reach the resolver.
+pub trait UriResolver {
+    /// Returns the contents of a file, given its URI (which is usually relative to the glTF file)
+    fn resolve(&self, uri: &str) -> Option<Vec<u8>>;
+}
+
+impl<F> UriResolver for F
+where
+    F: Fn(&str) -> Option<Vec<u8>>,
+{
+    fn resolve(&self, uri: &str) -> Option<Vec<u8>> {
+        self(uri)
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
 pub enum BufferLoadError {
     InvalidSource,
-    UnsupportedStride,
+    /// The loader has no resolver, or the resolver couldn't find the file
+    UnresolvedUri,
+    InvalidDataUri,
+    /// The data is shorter than the glTF file says that it is
+    OutOfBounds,
 }
 
 #[derive(Debug)]
@@ -48,39 +132,87 @@ pub enum PrimitiveLoadError {
     InvalidExtrasError(serde_json::Error),
 }
 
+#[derive(Debug)]
+pub enum SceneLoadError {
+    PrimitiveLoadError(PrimitiveLoadError),
+    /// A node is its own ancestor
+    NodeCycle,
+    InvalidExtrasError(serde_json::Error),
+}
+
 impl<'a, Context> GltfLoader<'a, Context>
 where
     Context: RenderingContext,
 {
+    /// Creates a loader that can only read buffers and images that are embedded in the file
     pub fn new(context: Rc<Context>, gltf: &'a Gltf) -> GltfLoader<'a, Context> {
-        GltfLoader { context, gltf }
+        GltfLoader::with_cache(Rc::new(GltfCache::new(context)), "", gltf, None)
     }
 
-    pub fn gltf(&self) -> &'a Gltf {
-        self.gltf
+    /// Creates a loader that reads external buffers and images through a resolver
+    pub fn with_resolver(
+        context: Rc<Context>,
+        gltf: &'a Gltf,
+        resolver: &'a dyn UriResolver,
+    ) -> GltfLoader<'a, Context> {
+        GltfLoader::with_cache(Rc::new(GltfCache::new(context)), "", gltf, Some(resolver))
     }
 
-    pub fn load_attribute(&mut self, accessor: &Accessor) -> Result<VertexAttribute<Context>, ()> {
-        if accessor.sparse().is_some() {
-            return Err(()); // TODO: support sparse accessors.
+    /// Creates a loader that reuses the resources in a cache that were uploaded from the same file
+    ///
+    /// `name` must be different for every file that is loaded with the cache.
+    pub fn with_cache(
+        cache: Rc<GltfCache<Context>>,
+        name: &str,
+        gltf: &'a Gltf,
+        resolver: Option<&'a dyn UriResolver>,
+    ) -> GltfLoader<'a, Context> {
+        GltfLoader {
+            context: Rc::clone(&cache.context),
+            gltf,
+            resolver,
+            buffers: HashMap::new(),
+            meshes: HashMap::new(),
+            cache,
+            name: name.into(),
         }
+    }
 
-        let buffer = self.context.make_attribute_buffer()?; // TODO: share these between attributes.
-        let view = accessor.view().ok_or(())?; // TODO: support sparse accessors (should create our own all-zero buffer).
-        let src_buf = view.buffer();
+    pub fn gltf(&self) -> &'a Gltf {
+        self.gltf
+    }
 
-        self.load_buffer(&buffer, &src_buf);
+    pub fn load_attribute(&mut self, accessor: &Accessor) -> Result<VertexAttribute<Context>, ()> {
+        let (buffer, offset, stride) = match accessor.view().filter(|_| accessor.sparse().is_none())
+        {
+            Some(view) => {
+                check_accessor_bounds(accessor, &view).map_err(|_| ())?;
+                (
+                    self.load_attribute_buffer(&view.buffer())?,
+                    view.offset() + accessor.offset(),
+                    view.stride().unwrap_or(0),
+                )
+            }
+            // Materialized accessors aren't shared, so they get a buffer of their own.
+            None => {
+                let data = self.load_dense_accessor(accessor).map_err(|_| ())?;
+                let buffer = self.context.make_attribute_buffer()?;
+                buffer.set_data(&data);
+                (Rc::new(buffer), 0, 0)
+            }
+        };
 
         let binding = VertexAttributeBinding {
-            attr_type: AttributeType::Float, // TODO: get from accessor.
+            attr_type: accessor.data_type().into(),
             num_components: accessor.dimensions().multiplicity(),
             normalized: accessor.normalized(),
-            stride: view.stride().unwrap_or(0),
-            offset: view.offset() + accessor.offset(),
+            stride,
+            offset,
             count: accessor.count(),
+            divisor: 0,
         };
 
-        Ok(VertexAttribute::new(Rc::new(buffer), binding))
+        Ok(VertexAttribute::new(buffer, binding))
     }
 
     pub fn load_material(
@@ -89,26 +221,33 @@ where
     ) -> Result<Material<Context>, MaterialLoadError> {
         let pbr = material.pbr_metallic_roughness();
 
-        let texture_info = pbr.base_color_texture().map(|t| t.texture());
-        let image = match texture_info {
-            Some(t) => Some(
-                self.load_image(&t.source().source())
-                    .map_err(MaterialLoadError::ImageLoadError)?,
-            ),
+        let texture = match pbr.base_color_texture() {
+            Some(info) => Some(self.load_texture(&info.texture())?),
             None => None,
         };
-        let texture = match &image {
-            Some(image) => {
-                let tex = self
-                    .context
-                    .make_texture()
-                    .map_err(|_| MaterialLoadError::TextureCreationError)?;
-                tex.set_image_data(image)
-                    .map_err(MaterialLoadError::SetTextureDataError)?;
-                Some(Rc::new(tex)) // TODO: share images between loads.
-            }
+        let metallic_roughness_texture = match pbr.metallic_roughness_texture() {
+            Some(info) => Some(self.load_texture(&info.texture())?),
+            None => None,
+        };
+        let (normal_texture, normal_scale) = match material.normal_texture() {
+            Some(info) => (Some(self.load_texture(&info.texture())?), info.scale()),
+            None => (None, 1.0),
+        };
+        let (occlusion_texture, occlusion_strength) = match material.occlusion_texture() {
+            Some(info) => (Some(self.load_texture(&info.texture())?), info.strength()),
+            None => (None, 1.0),
+        };
+        let emissive_texture = match material.emissive_texture() {
+            Some(info) => Some(self.load_texture(&info.texture())?),
             None => None,
         };
+        let alpha_mode = match material.alpha_mode() {
+            gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+            gltf::material::AlphaMode::Mask => {
+                AlphaMode::Mask(material.alpha_cutoff().unwrap_or(0.5))
+            }
+            gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+        };
 
         let extras = material
             .extras()
@@ -122,10 +261,124 @@ where
             base_color_texture: texture,
             metal_factor: pbr.metallic_factor(),
             roughness: pbr.roughness_factor(),
+            metallic_roughness_texture,
+            normal_texture,
+            normal_scale,
+            occlusion_texture,
+            occlusion_strength,
+            emissive: from3(material.emissive_factor()),
+            emissive_texture,
+            alpha_mode,
+            double_sided: material.double_sided(),
             extras,
         })
     }
 
+    /// Creates a texture, or returns the one that was already created for the same image
+    ///
+    /// Samplers are set on textures, so textures that share an image but not a sampler still
+    /// get textures of their own.
+    pub fn load_texture(
+        &mut self,
+        texture: &gltf::Texture,
+    ) -> Result<Rc<Context::Texture>, MaterialLoadError> {
+        let key = (texture.source().index(), texture.sampler().index());
+        if let Some(tex) = self.with_resources(|resources| resources.textures.get(&key).cloned()) {
+            return Ok(tex);
+        }
+        let image = self
+            .load_image(&texture.source().source())
+            .map_err(MaterialLoadError::ImageLoadError)?;
+        let data = TextureData::from_image(&image).ok_or(MaterialLoadError::ImageLoadError(
+            ImageLoadError::UnsupportedColorFormat,
+        ))?;
+        let tex = self
+            .context
+            .make_texture()
+            .map_err(|_| MaterialLoadError::TextureCreationError)?;
+        tex.set_data(data)
+            .map_err(MaterialLoadError::SetTextureDataError)?;
+
+        let gltf_sampler = texture.sampler();
+        let sampler = Sampler {
+            wrap_s: gltf_sampler.wrap_s().into(),
+            wrap_t: gltf_sampler.wrap_t().into(),
+            // glTF leaves the filters up to the renderer when they aren't set.
+            min_filter: gltf_sampler
+                .min_filter()
+                .map_or(MinFilter::LinearMipmapLinear, MinFilter::from),
+            mag_filter: gltf_sampler
+                .mag_filter()
+                .map_or(MagFilter::Linear, MagFilter::from),
+        };
+        tex.set_sampler(&sampler);
+        if sampler.min_filter.uses_mipmaps() {
+            tex.generate_mipmaps();
+        }
+        let tex = Rc::new(tex);
+        self.with_resources(|resources| resources.textures.insert(key, Rc::clone(&tex)));
+        Ok(tex)
+    }
+
+    /// Loads the scene that the file chooses to show, or its first scene if it doesn't choose one
+    pub fn load_default_scene(&mut self) -> Option<Result<Scene<Context>, SceneLoadError>> {
+        let gltf = self.gltf;
+        let scene = gltf.default_scene().or_else(|| gltf.scenes().next())?;
+        Some(self.load_scene(&scene))
+    }
+
+    pub fn load_scene(&mut self, scene: &gltf::Scene) -> Result<Scene<Context>, SceneLoadError> {
+        let nodes: Result<Vec<SceneNode<Context>>, SceneLoadError> = scene
+            .nodes()
+            .map(|ref node| self.load_node(node, &mut Vec::new()))
+            .collect();
+        Ok(Scene::new(nodes?))
+    }
+
+    /// Loads a node and its descendants, given the indices of the nodes above it
+    fn load_node(
+        &mut self,
+        node: &gltf::Node,
+        ancestors: &mut Vec<usize>,
+    ) -> Result<SceneNode<Context>, SceneLoadError> {
+        if ancestors.contains(&node.index()) {
+            return Err(SceneLoadError::NodeCycle);
+        }
+        let mesh = match node.mesh() {
+            Some(ref mesh) => Some(match self.meshes.get(&mesh.index()) {
+                Some(loaded) => Rc::clone(loaded),
+                None => {
+                    let loaded = Rc::new(
+                        self.load_mesh(mesh)
+                            .map_err(SceneLoadError::PrimitiveLoadError)?,
+                    );
+                    self.meshes.insert(mesh.index(), Rc::clone(&loaded));
+                    loaded
+                }
+            }),
+            None => None,
+        };
+        let extras = node
+            .extras()
+            .as_ref()
+            .map(|e| serde_json::from_str(e.get()))
+            .transpose()
+            .map_err(SceneLoadError::InvalidExtrasError)?;
+
+        ancestors.push(node.index());
+        let children: Result<Vec<SceneNode<Context>>, SceneLoadError> = node
+            .children()
+            .map(|ref child| self.load_node(child, ancestors))
+            .collect();
+        ancestors.pop();
+
+        let mut new_node = SceneNode::new(Matrix4::from(node.transform().matrix()), mesh);
+        new_node.name = node.name().map(String::from);
+        new_node.children = children?;
+        new_node.extras = extras;
+        Ok(new_node)
+    }
+
     pub fn load_mesh(&mut self, mesh: &gltf::Mesh) -> Result<Mesh<Context>, PrimitiveLoadError> {
         let primitives: Result<Vec<Primitive<Context>>, PrimitiveLoadError> = mesh
             .primitives()
@@ -170,63 +423,283 @@ where
         let normals = self
             .load_attribute(&normal_accessor)
             .map_err(|_| PrimitiveLoadError::MissingAttribute)?;
-        let geometry = Rc::new(PrimitiveGeometry::new(indices, positions, normals));
+        let tex_coords = match primitive
+            .attributes()
+            .find(|(semantic, _)| *semantic == Semantic::TexCoords(0))
+        {
+            Some((_, ref accessor)) => Some(
+                self.load_attribute(accessor)
+                    .map_err(|_| PrimitiveLoadError::MissingAttribute)?,
+            ),
+            None => None,
+        };
+        let tangents = match primitive
+            .attributes()
+            .find(|(semantic, _)| *semantic == Semantic::Tangents)
+        {
+            Some((_, ref accessor)) => Some(
+                self.load_attribute(accessor)
+                    .map_err(|_| PrimitiveLoadError::MissingAttribute)?,
+            ),
+            // Tangents are only needed to normal map, and glTF leaves generating them to us.
+            None if material.normal_texture.is_some() && tex_coords.is_some() => {
+                match self.generate_tangents(primitive) {
+                    Ok(tangents) => Some(tangents),
+                    Err(_) => {
+                        log::warn!("Couldn't generate tangents, so the normal map is ignored");
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+        let geometry = Rc::new(PrimitiveGeometry::new(
+            indices, positions, normals, tex_coords, tangents,
+        ));
         Ok(Primitive { material, geometry })
     }
 
-    fn load_slice_from_view(&self, view: &gltf::buffer::View) -> Result<&[u8], BufferLoadError> {
-        if view.stride().unwrap_or(0) != 0 {
-            return Err(BufferLoadError::UnsupportedStride);
+    /// Generates tangents from a triangle list's positions, normals and texture coordinates
+    fn generate_tangents(
+        &mut self,
+        primitive: &gltf::Primitive,
+    ) -> Result<VertexAttribute<Context>, ()> {
+        if primitive.mode() != gltf::mesh::Mode::Triangles {
+            return Err(());
         }
-        let blob = self.gltf.blob.as_ref();
-        let data = match view.buffer().source() {
-            Source::Bin => blob.map(|vec| &vec[..]).unwrap_or(&[]),
-            Source::Uri(_) => Err(BufferLoadError::InvalidSource)?,
+        let positions: Vec<Vector3<f32>> = self
+            .load_attribute_floats(primitive, Semantic::Positions)?
+            .chunks_exact(3)
+            .map(Vector3::from_column_slice)
+            .collect();
+        let normals: Vec<Vector3<f32>> = self
+            .load_attribute_floats(primitive, Semantic::Normals)?
+            .chunks_exact(3)
+            .map(Vector3::from_column_slice)
+            .collect();
+        let tex_coords: Vec<Vector2<f32>> = self
+            .load_attribute_floats(primitive, Semantic::TexCoords(0))?
+            .chunks_exact(2)
+            .map(Vector2::from_column_slice)
+            .collect();
+        let indices: Vec<u32> = match primitive.indices() {
+            Some(ref accessor) => self
+                .load_dense_accessor(accessor)
+                .map_err(|_| ())?
+                .chunks_exact(accessor.size())
+                .map(read_uint)
+                .collect(),
+            None => (0..positions.len() as u32).collect(),
         };
-        Ok(&data[view.offset()..view.offset() + view.length()])
+
+        let tangents = tangent::generate_tangents(&positions, &normals, &tex_coords, &indices);
+        let buffer = self.context.make_attribute_buffer()?;
+        buffer.set_data(tangents.as_bytes());
+        Ok(VertexAttribute::new(
+            Rc::new(buffer),
+            VertexAttributeBinding::typed::<Vector4<f32>>(tangents.len()),
+        ))
     }
 
-    fn load_buffer(&self, gl_buf: &dyn Buffer<RenderingContext = Context>, src_buf: &gltf::Buffer) {
-        let blob = self.gltf.blob.as_ref();
-        let data = match src_buf.source() {
-            Source::Bin => blob.map(|vec| &vec[..]).unwrap_or(&[]),
-            Source::Uri(_) => &[], // TODO: implement.
+    /// Returns the contents of a buffer, reading it the first time that it's needed
+    fn buffer_data(&mut self, buffer: &gltf::Buffer) -> Result<Rc<[u8]>, BufferLoadError> {
+        if let Some(data) = self.buffers.get(&buffer.index()) {
+            return Ok(Rc::clone(data));
+        }
+        let data: Rc<[u8]> = match buffer.source() {
+            Source::Bin => Rc::from(
+                self.gltf
+                    .blob
+                    .as_deref()
+                    .ok_or(BufferLoadError::InvalidSource)?,
+            ),
+            Source::Uri(uri) => Rc::from(self.read_uri(uri)?),
         };
-        gl_buf.set_data(&data[0..src_buf.length()]);
+        if data.len() < buffer.length() {
+            return Err(BufferLoadError::OutOfBounds);
+        }
+        self.buffers.insert(buffer.index(), Rc::clone(&data));
+        Ok(data)
     }
 
-    fn load_indices(&mut self, accessor: &Accessor) -> Result<ElementIndices<Context>, ()> {
+    /// Reads a file that the glTF file refers to, decoding it if it's a `data:` URI
+    fn read_uri(&self, uri: &str) -> Result<Vec<u8>, BufferLoadError> {
+        match uri.strip_prefix("data:") {
+            Some(data_uri) => decode_data_uri(data_uri).ok_or(BufferLoadError::InvalidDataUri),
+            None => self
+                .resolver
+                .and_then(|resolver| resolver.resolve(uri))
+                .ok_or(BufferLoadError::UnresolvedUri),
+        }
+    }
+
+    fn load_slice_from_view(
+        &mut self,
+        view: &gltf::buffer::View,
+    ) -> Result<Rc<[u8]>, BufferLoadError> {
+        let data = self.buffer_data(&view.buffer())?;
+        let slice = slice_at(&data, view.offset(), view.length())?;
+        Ok(Rc::from(slice))
+    }
+
+    /// Calls a function with the cached resources of this loader's file
+    fn with_resources<T>(&self, f: impl FnOnce(&mut FileResources<Context>) -> T) -> T {
+        let mut files = self.cache.files.borrow_mut();
+        let resources = files
+            .entry(self.name.clone())
+            .or_insert_with(FileResources::new);
+        f(resources)
+    }
+
+    /// Returns a buffer, uploading it as an attribute buffer the first time that it's needed
+    fn load_attribute_buffer(
+        &mut self,
+        src_buf: &gltf::Buffer,
+    ) -> Result<Rc<Context::AttributeBuffer>, ()> {
+        let index = src_buf.index();
+        if let Some(buffer) =
+            self.with_resources(|resources| resources.attribute_buffers.get(&index).cloned())
+        {
+            return Ok(buffer);
+        }
+        let data = self.buffer_data(src_buf).map_err(|_| ())?;
+        let buffer = self.context.make_attribute_buffer()?;
+        buffer.set_data(&data[0..src_buf.length()]);
+        let buffer = Rc::new(buffer);
+        self.with_resources(|resources| {
+            resources
+                .attribute_buffers
+                .insert(index, Rc::clone(&buffer))
+        });
+        Ok(buffer)
+    }
+
+    /// Returns a buffer view, uploading it as an index buffer the first time that it's needed
+    fn load_index_buffer(
+        &mut self,
+        view: &gltf::buffer::View,
+    ) -> Result<Rc<Context::IndexBuffer>, ()> {
+        let index = view.index();
+        if let Some(buffer) =
+            self.with_resources(|resources| resources.index_buffers.get(&index).cloned())
+        {
+            return Ok(buffer);
+        }
+        let data = self.load_slice_from_view(view).map_err(|_| ())?;
         let buffer = self.context.make_index_buffer()?;
-        let view = accessor.view().ok_or(())?; // TODO: support sparse accessors (should create our own all-zero buffer).
-        self.load_buffer(&buffer, &view.buffer());
+        buffer.set_data(&data);
+        let buffer = Rc::new(buffer);
+        self.with_resources(|resources| resources.index_buffers.insert(index, Rc::clone(&buffer)));
+        Ok(buffer)
+    }
+
+    /// Reads an accessor's elements into a tightly packed array, with its sparse values applied
+    ///
+    /// Elements start out as zeros if the accessor has no buffer view.
+    fn load_dense_accessor(&mut self, accessor: &Accessor) -> Result<Vec<u8>, BufferLoadError> {
+        let size = accessor.size();
+        let mut data = vec![0; size * accessor.count()];
+        if let Some(view) = accessor.view() {
+            let view_data = self.load_slice_from_view(&view)?;
+            let stride = element_stride(accessor, &view);
+            for (i, element) in data.chunks_exact_mut(size).enumerate() {
+                element.copy_from_slice(slice_at(
+                    &view_data,
+                    accessor.offset() + i * stride,
+                    size,
+                )?);
+            }
+        }
+        if let Some(sparse) = accessor.sparse() {
+            let (indices, values) = (sparse.indices(), sparse.values());
+            let index_size = indices.index_type().size();
+            let index_data = self.load_slice_from_view(&indices.view())?;
+            let value_data = self.load_slice_from_view(&values.view())?;
+            for i in 0..sparse.count() {
+                let index = slice_at(&index_data, indices.offset() + i * index_size, index_size)?;
+                let value = slice_at(&value_data, values.offset() + i * size, size)?;
+                let start = read_uint(index) as usize * size;
+                data.get_mut(start..start + size)
+                    .ok_or(BufferLoadError::OutOfBounds)?
+                    .copy_from_slice(value);
+            }
+        }
+        Ok(data)
+    }
+
+    /// Reads an attribute's components as floats, which quantized attributes are converted to
+    fn load_attribute_floats(
+        &mut self,
+        primitive: &gltf::Primitive,
+        semantic: Semantic,
+    ) -> Result<Vec<f32>, ()> {
+        let accessor = primitive.get(&semantic).ok_or(())?;
+        let data_type = accessor.data_type();
+        Ok(self
+            .load_dense_accessor(&accessor)
+            .map_err(|_| ())?
+            .chunks_exact(data_type.size())
+            .map(|bytes| read_float(bytes, data_type, accessor.normalized()))
+            .collect())
+    }
+
+    fn load_indices(&mut self, accessor: &Accessor) -> Result<ElementIndices<Context>, ()> {
+        let (buffer, offset) = match accessor.view().filter(|_| accessor.sparse().is_none()) {
+            Some(view) => {
+                check_accessor_bounds(accessor, &view).map_err(|_| ())?;
+                (self.load_index_buffer(&view)?, accessor.offset())
+            }
+            None => {
+                let data = self.load_dense_accessor(accessor).map_err(|_| ())?;
+                let buffer = self.context.make_index_buffer()?;
+                buffer.set_data(&data);
+                (Rc::new(buffer), 0)
+            }
+        };
         let attr_type: AttributeType = accessor.data_type().into();
         Ok(ElementIndices::new(
-            Rc::new(buffer),
+            buffer,
             ElementBinding {
                 count: accessor.count(),
                 index_type: attr_type.try_into()?,
-                offset: view.offset() + accessor.offset(),
+                offset,
             },
         ))
     }
 
-    fn load_image(&mut self, source: &gltf::image::Source) -> Result<RgbImage, ImageLoadError> {
+    /// Decodes an image, converting it to 8 bits per channel if it has more
+    fn load_image(&mut self, source: &gltf::image::Source) -> Result<DynamicImage, ImageLoadError> {
         use gltf::image::Source;
-        match source {
-            Source::View { view, mime_type } => {
-                let buf = self
-                    .load_slice_from_view(view)
-                    .map_err(ImageLoadError::BufferLoadError)?;
-                let image_format = image_format_from_mime(mime_type)
-                    .ok_or(ImageLoadError::UnsupportedImageFormat)?;
-                let image = image::load_from_memory_with_format(buf, image_format)
-                    .map_err(ImageLoadError::ImageError)?;
-                Ok(image.to_rgb8())
-            }
-            Source::Uri { .. } => Err(ImageLoadError::BufferLoadError(
-                BufferLoadError::InvalidSource,
-            )),
+        let (data, mime_type): (Rc<[u8]>, _) = match source {
+            Source::View { view, mime_type } => (
+                self.load_slice_from_view(view)
+                    .map_err(ImageLoadError::BufferLoadError)?,
+                Some(*mime_type),
+            ),
+            Source::Uri { uri, mime_type } => (
+                Rc::from(
+                    self.read_uri(uri)
+                        .map_err(ImageLoadError::BufferLoadError)?,
+                ),
+                *mime_type,
+            ),
+        };
+        // Files that are referred to by URI don't need a MIME type, so fall back to sniffing.
+        let image_format = match mime_type {
+            Some(mime_type) => image_format_from_mime(mime_type),
+            None => image::guess_format(&data).ok(),
         }
+        .ok_or(ImageLoadError::UnsupportedImageFormat)?;
+        let image = image::load_from_memory_with_format(&data, image_format)
+            .map_err(ImageLoadError::ImageError)?;
+        Ok(match image {
+            DynamicImage::ImageLuma8(_)
+            | DynamicImage::ImageLumaA8(_)
+            | DynamicImage::ImageRgb8(_)
+            | DynamicImage::ImageRgba8(_) => image,
+            _ if image.color().has_alpha() => DynamicImage::ImageRgba8(image.to_rgba8()),
+            _ => DynamicImage::ImageRgb8(image.to_rgb8()),
+        })
     }
 }
 
@@ -243,10 +716,174 @@ impl From<DataType> for AttributeType {
     }
 }
 
+impl From<gltf::texture::WrappingMode> for WrapMode {
+    fn from(mode: gltf::texture::WrappingMode) -> Self {
+        use gltf::texture::WrappingMode;
+        match mode {
+            WrappingMode::ClampToEdge => WrapMode::ClampToEdge,
+            WrappingMode::MirroredRepeat => WrapMode::MirroredRepeat,
+            WrappingMode::Repeat => WrapMode::Repeat,
+        }
+    }
+}
+
+impl From<gltf::texture::MinFilter> for MinFilter {
+    fn from(filter: gltf::texture::MinFilter) -> Self {
+        use gltf::texture::MinFilter as GltfMinFilter;
+        match filter {
+            GltfMinFilter::Nearest => MinFilter::Nearest,
+            GltfMinFilter::Linear => MinFilter::Linear,
+            GltfMinFilter::NearestMipmapNearest => MinFilter::NearestMipmapNearest,
+            GltfMinFilter::LinearMipmapNearest => MinFilter::LinearMipmapNearest,
+            GltfMinFilter::NearestMipmapLinear => MinFilter::NearestMipmapLinear,
+            GltfMinFilter::LinearMipmapLinear => MinFilter::LinearMipmapLinear,
+        }
+    }
+}
+
+impl From<gltf::texture::MagFilter> for MagFilter {
+    fn from(filter: gltf::texture::MagFilter) -> Self {
+        match filter {
+            gltf::texture::MagFilter::Nearest => MagFilter::Nearest,
+            gltf::texture::MagFilter::Linear => MagFilter::Linear,
+        }
+    }
+}
+
+/// Returns the distance between the starts of consecutive elements of an accessor
+fn element_stride(accessor: &Accessor, view: &gltf::buffer::View) -> usize {
+    match view.stride() {
+        Some(stride) if stride > 0 => stride,
+        _ => accessor.size(),
+    }
+}
+
+/// Checks that an accessor's elements fit in its buffer view, and the view in its buffer
+fn check_accessor_bounds(
+    accessor: &Accessor,
+    view: &gltf::buffer::View,
+) -> Result<(), BufferLoadError> {
+    let length = match accessor.count() {
+        0 => 0,
+        count => element_stride(accessor, view) * (count - 1) + accessor.size(),
+    };
+    if accessor.offset() + length > view.length()
+        || view.offset() + view.length() > view.buffer().length()
+    {
+        return Err(BufferLoadError::OutOfBounds);
+    }
+    Ok(())
+}
+
+fn slice_at(data: &[u8], start: usize, length: usize) -> Result<&[u8], BufferLoadError> {
+    data.get(start..start + length)
+        .ok_or(BufferLoadError::OutOfBounds)
+}
+
+/// Reads a little-endian unsigned integer of one, two or four bytes
+fn read_uint(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .rev()
+        .fold(0, |value, &byte| value << 8 | u32::from(byte))
+}
+
+/// Reads a component as a float, mapping normalized integers to [-1, 1] or [0, 1] like GL does
+fn read_float(bytes: &[u8], data_type: DataType, normalized: bool) -> f32 {
+    let (value, max) = match data_type {
+        DataType::I8 => (f32::from(bytes[0] as i8), f32::from(i8::MAX)),
+        DataType::U8 => (f32::from(bytes[0]), f32::from(u8::MAX)),
+        DataType::I16 => (
+            f32::from(i16::from_le_bytes([bytes[0], bytes[1]])),
+            f32::from(i16::MAX),
+        ),
+        DataType::U16 => (f32::from(read_uint(bytes) as u16), f32::from(u16::MAX)),
+        DataType::U32 => (read_uint(bytes) as f32, u32::MAX as f32),
+        DataType::F32 => return f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+    };
+    if normalized {
+        (value / max).max(-1.0)
+    } else {
+        value
+    }
+}
+
+fn from3(c: [f32; 3]) -> Rgb {
+    Rgb::new(c[0], c[1], c[2])
+}
+
 fn from4(c: [f32; 4]) -> Rgba {
     Rgba::new(c[0], c[1], c[2], c[3])
 }
 
+/// Extensions that the loader handles itself, whether or not the gltf crate knows about them
+const SUPPORTED_EXTENSIONS: &[&str] = &["KHR_mesh_quantization"];
+
+/// Parses a glTF or GLB file, accepting files that require extensions that the loader supports
+///
+/// The gltf crate's validation rejects files that require any extension that it doesn't know.
+pub fn parse_gltf(data: &[u8]) -> Result<Gltf, gltf::Error> {
+    let Gltf { document, blob } = Gltf::from_slice_without_validation(data)?;
+    let mut json = document.into_json();
+    json.extensions_required
+        .retain(|extension| !SUPPORTED_EXTENSIONS.contains(&extension.as_str()));
+    let document = Document::from_json(json)?;
+    Ok(Gltf { document, blob })
+}
+
+/// Returns the URIs of the buffers and images that a glTF file keeps in other files
+///
+/// These are the URIs that a `UriResolver` will be asked for. Each one is only listed once.
+pub fn external_uris(gltf: &Gltf) -> Vec<&str> {
+    let buffer_uris = gltf.buffers().filter_map(|buffer| match buffer.source() {
+        Source::Uri(uri) => Some(uri),
+        Source::Bin => None,
+    });
+    let image_uris = gltf.images().filter_map(|image| match image.source() {
+        gltf::image::Source::Uri { uri, .. } => Some(uri),
+        gltf::image::Source::View { .. } => None,
+    });
+    let mut uris: Vec<&str> = Vec::new();
+    for uri in buffer_uris.chain(image_uris) {
+        if !uri.starts_with("data:") && !uris.contains(&uri) {
+            uris.push(uri);
+        }
+    }
+    uris
+}
+
+/// Decodes the part of a `data:` URI after the scheme (`[<media type>][;base64],<data>`)
+fn decode_data_uri(data_uri: &str) -> Option<Vec<u8>> {
+    let comma = data_uri.find(',')?;
+    let (header, data) = (&data_uri[..comma], &data_uri[comma + 1..]);
+    if header.ends_with(";base64") {
+        base64::decode(data).ok()
+    } else {
+        percent_decode(data)
+    }
+}
+
+/// Replaces `%XX` escapes with the bytes that they stand for
+fn percent_decode(text: &str) -> Option<Vec<u8>> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = text.get(i + 1..i + 3)?;
+            if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return None;
+            }
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    Some(decoded)
+}
+
 fn image_format_from_mime(mime_type: &str) -> Option<ImageFormat> {
     // TODO: support proper MIME type handling?
     match mime_type {
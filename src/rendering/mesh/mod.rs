@@ -6,6 +6,7 @@ use crate::rendering::context::RenderingContext;
 use crate::rendering::material::{BoundMaterialShader, Material};
 
 pub mod gltf;
+pub mod tangent;
 
 #[derive(Debug)]
 pub struct Mesh<Context: RenderingContext> {
@@ -62,6 +63,9 @@ pub struct PrimitiveGeometry<Context: RenderingContext> {
     indices: Option<ElementIndices<Context>>,
     positions: VertexAttribute<Context>,
     normals: VertexAttribute<Context>,
+    tex_coords: Option<VertexAttribute<Context>>,
+    /// Tangents, with the handedness of the bitangent in W
+    tangents: Option<VertexAttribute<Context>>,
 }
 
 impl<Context: RenderingContext> PrimitiveGeometry<Context> {
@@ -69,25 +73,57 @@ impl<Context: RenderingContext> PrimitiveGeometry<Context> {
         indices: Option<ElementIndices<Context>>,
         positions: VertexAttribute<Context>,
         normals: VertexAttribute<Context>,
+        tex_coords: Option<VertexAttribute<Context>>,
+        tangents: Option<VertexAttribute<Context>>,
     ) -> Self {
         PrimitiveGeometry {
             indices,
             positions,
             normals,
+            tex_coords,
+            tangents,
         }
     }
 
     /// Binds each primitive's buffers and makes the appropriate WebGL draw calls.
     /// The projection and modelview matrix uniforms must already be bound.
-    // TODO: break out a separate bind() method?
     pub fn draw(&self, shader: &BoundMaterialShader<Context>) {
-        self.positions.bind(shader.info().position.index);
-        self.normals.bind(shader.info().normal.index);
+        self.bind(shader);
         match self.indices {
             Some(ref indices) => shader.draw_indexed_triangles(indices),
             None => shader.draw_triangles(self.positions.binding.count),
         }
     }
+
+    /// Draws the geometry once for each instance
+    ///
+    /// The shader must be instanced, and its per-instance attributes must already be bound.
+    pub fn draw_instanced(&self, shader: &BoundMaterialShader<Context>, instance_count: usize) {
+        self.bind(shader);
+        match self.indices {
+            Some(ref indices) => shader.draw_indexed_triangles_instanced(indices, instance_count),
+            None => shader.draw_triangles_instanced(self.positions.binding.count, instance_count),
+        }
+    }
+
+    fn bind(&self, shader: &BoundMaterialShader<Context>) {
+        let info = shader.info();
+        self.positions.bind(info.position.index);
+        self.normals.bind(info.normal.index);
+        let optional = [
+            (&info.tex_coord, &self.tex_coords),
+            (&info.tangent, &self.tangents),
+        ];
+        for (param, attribute) in optional.iter() {
+            if let Some(param) = param {
+                match attribute {
+                    Some(attribute) => attribute.bind(param.index),
+                    // Otherwise, the attribute could still be reading another primitive's buffer.
+                    None => shader.disable_attribute(param.index),
+                }
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
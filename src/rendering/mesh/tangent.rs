@@ -0,0 +1,73 @@
+use nalgebra::{Vector2, Vector3, Vector4};
+
+/// Generates a tangent for each vertex of a triangle list, for normal mapping
+///
+/// Each triangle's tangent points along increasing U, and the tangents of the triangles around a
+/// vertex are averaged and made perpendicular to its normal. W holds the handedness that glTF
+/// expects: the bitangent is `cross(normal, tangent) * w`, and points along decreasing V because
+/// glTF texture coordinates start at the top of the image. Triangles with indices out of range
+/// are skipped.
+pub fn generate_tangents(
+    positions: &[Vector3<f32>],
+    normals: &[Vector3<f32>],
+    tex_coords: &[Vector2<f32>],
+    indices: &[u32],
+) -> Vec<Vector4<f32>> {
+    let count = positions.len().min(normals.len()).min(tex_coords.len());
+    let mut tangents = vec![Vector3::zeros(); count];
+    let mut bitangents = vec![Vector3::zeros(); count];
+
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [
+            triangle[0] as usize,
+            triangle[1] as usize,
+            triangle[2] as usize,
+        ];
+        if a >= count || b >= count || c >= count {
+            continue;
+        }
+        let edge1 = positions[b] - positions[a];
+        let edge2 = positions[c] - positions[a];
+        let uv1 = tex_coords[b] - tex_coords[a];
+        let uv2 = tex_coords[c] - tex_coords[a];
+        let det = uv1.x * uv2.y - uv2.x * uv1.y;
+        if det.abs() < f32::EPSILON {
+            continue;
+        }
+        let tangent = (edge1 * uv2.y - edge2 * uv1.y) / det;
+        let bitangent = (edge2 * uv1.x - edge1 * uv2.x) / det;
+        for &vertex in &[a, b, c] {
+            tangents[vertex] += tangent;
+            bitangents[vertex] += bitangent;
+        }
+    }
+
+    (0..count)
+        .map(|vertex| {
+            let normal = normals[vertex];
+            let tangent = tangents[vertex] - normal * normal.dot(&tangents[vertex]);
+            let tangent = tangent
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(|| any_perpendicular(&normal));
+            // The bitangent above points along increasing V.
+            let handedness = if normal.cross(&tangent).dot(&bitangents[vertex]) > 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            Vector4::new(tangent.x, tangent.y, tangent.z, handedness)
+        })
+        .collect()
+}
+
+fn any_perpendicular(normal: &Vector3<f32>) -> Vector3<f32> {
+    let axis = if normal.x.abs() < 0.9 {
+        Vector3::x()
+    } else {
+        Vector3::y()
+    };
+    normal
+        .cross(&axis)
+        .try_normalize(f32::EPSILON)
+        .unwrap_or_else(Vector3::x)
+}
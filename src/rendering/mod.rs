@@ -1,9 +1,12 @@
 pub mod buffer;
 pub mod context;
+pub mod glsl;
 pub mod light;
 pub mod line;
 pub mod material;
 pub mod mesh;
+pub mod particle;
+pub mod queue;
 pub mod scene;
 pub mod shader;
 pub mod texture;
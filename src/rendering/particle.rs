@@ -0,0 +1,334 @@
+use std::f32::consts::PI;
+use std::mem;
+use std::ops::Deref;
+use std::rc::Rc;
+
+use nalgebra::{Vector2, Vector3};
+use rand::Rng;
+
+use crate::rendering::buffer::{
+    AttributeBuffer, Buffer, BufferData, VertexAttributeBinding, VertexAttributeData,
+};
+use crate::rendering::context::RenderingContext;
+use crate::rendering::line::LineWorldContext;
+use crate::rendering::shader::{BoundShader, ShaderBindError};
+use crate::rendering::shader::{ShaderInfoError, ShaderParamInfo};
+use crate::rendering::Rgb;
+
+#[derive(Clone, Debug)]
+pub struct Particle {
+    pub position: Vector3<f32>,
+    /// Position at the previous tick, which is used to interpolate between ticks
+    pub previous_position: Vector3<f32>,
+    /// Velocity in units per second
+    pub velocity: Vector3<f32>,
+    /// Time since the particle was emitted (in seconds)
+    pub age: f32,
+    /// Total time that the particle lives (in seconds)
+    pub lifetime: f32,
+    /// Diameter in world units
+    pub size: f32,
+    pub start_color: Rgb,
+    pub end_color: Rgb,
+    /// Fraction of the particle's velocity that it loses each second
+    pub drag: f32,
+}
+
+impl Particle {
+    /// Returns how far the particle is through its life, from 0 to 1
+    pub fn progress(&self) -> f32 {
+        (self.age / self.lifetime).clamp(0.0, 1.0)
+    }
+
+    pub fn color(&self) -> Rgb {
+        let t = self.progress();
+        self.start_color * (1.0 - t) + self.end_color * t
+    }
+
+    pub fn alpha(&self) -> f32 {
+        1.0 - self.progress()
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.age < self.lifetime
+    }
+}
+
+/// Describes a group of particles that are emitted together
+///
+/// Ranges are given as (minimum, maximum) pairs, and each particle gets a random value from
+/// each range.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParticleEmitter {
+    pub count: usize,
+    /// Speed in units per second
+    pub speed: (f32, f32),
+    /// Largest angle (in radians) between a particle's direction and the emitter's direction
+    ///
+    /// A spread of pi sends particles in every direction.
+    pub spread: f32,
+    /// Lifetime in seconds
+    pub lifetime: (f32, f32),
+    /// Diameter in world units
+    pub size: (f32, f32),
+    pub start_color: Rgb,
+    pub end_color: Rgb,
+    /// Fraction of each particle's velocity that it loses each second
+    pub drag: f32,
+}
+
+// Number of floats in each particle vertex: position, size, color and alpha
+const PARTICLE_VERTEX_FLOATS: usize = 8;
+
+/// A set of short-lived particles that move in straight lines and fade out
+///
+/// Particles are simulated at a fixed interval (normally the game tick) and drawn at positions
+/// interpolated between the last two updates.
+#[derive(Clone, Debug)]
+pub struct ParticleSystem {
+    particles: Vec<Particle>,
+    max_particles: usize,
+    ticks: usize,
+    /// Fraction of an update interval that has passed since the last update
+    pub interpolation: f32,
+}
+
+impl ParticleSystem {
+    pub fn new(max_particles: usize) -> ParticleSystem {
+        ParticleSystem {
+            particles: Vec::new(),
+            max_particles,
+            ticks: 0,
+            interpolation: 0.0,
+        }
+    }
+
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    /// Returns the number of times that the particles have been updated
+    pub fn ticks(&self) -> usize {
+        self.ticks
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    /// Emits particles at `position`, centered on the given direction (in radians)
+    ///
+    /// Particles beyond the system's limit are dropped.
+    pub fn emit(&mut self, emitter: &ParticleEmitter, position: Vector3<f32>, direction: f32) {
+        let mut rng = rand::thread_rng();
+        let count = emitter
+            .count
+            .min(self.max_particles.saturating_sub(self.particles.len()));
+        for _ in 0..count {
+            let angle = direction + rng.gen_range(-1.0..=1.0) * emitter.spread.min(PI);
+            let speed = random_in(&mut rng, emitter.speed);
+            let velocity = Vector2::new(angle.cos(), angle.sin()) * speed;
+            self.particles.push(Particle {
+                position,
+                previous_position: position,
+                velocity: Vector3::new(velocity.x, velocity.y, 0.0),
+                age: 0.0,
+                lifetime: random_in(&mut rng, emitter.lifetime).max(f32::EPSILON),
+                size: random_in(&mut rng, emitter.size),
+                start_color: emitter.start_color,
+                end_color: emitter.end_color,
+                drag: emitter.drag,
+            });
+        }
+    }
+
+    /// Moves and ages every particle by `interval` seconds, removing the ones that have expired
+    pub fn update(&mut self, interval: f32) {
+        self.ticks += 1;
+        self.interpolation = 0.0;
+        for particle in self.particles.iter_mut() {
+            particle.previous_position = particle.position;
+            particle.position += particle.velocity * interval;
+            particle.velocity *= (1.0 - particle.drag * interval).max(0.0);
+            particle.age += interval;
+        }
+        self.particles.retain(Particle::is_alive);
+    }
+
+    /// Appends the vertex data used by `ParticleBuffer` for every particle
+    pub fn write_vertices(&self, data: &mut Vec<f32>) {
+        let t = self.interpolation.clamp(0.0, 1.0);
+        data.reserve(self.particles.len() * PARTICLE_VERTEX_FLOATS);
+        for particle in self.particles.iter() {
+            let position = particle.previous_position.lerp(&particle.position, t);
+            let color = particle.color();
+            data.extend_from_slice(position.as_slice());
+            data.extend_from_slice(&[particle.size, color.r, color.g, color.b, particle.alpha()]);
+        }
+    }
+}
+
+fn random_in<R: Rng>(rng: &mut R, (min, max): (f32, f32)) -> f32 {
+    if max > min {
+        rng.gen_range(min..max)
+    } else {
+        min
+    }
+}
+
+/// Holds particle vertices on the GPU, where each particle is drawn as a point sprite
+///
+/// Clones share the same vertex buffer.
+#[derive(Debug)]
+pub struct ParticleBuffer<Context: RenderingContext> {
+    buffer: Rc<Context::AttributeBuffer>,
+    num_particles: usize,
+    data: Vec<f32>,
+}
+
+impl<Context: RenderingContext> ParticleBuffer<Context> {
+    pub fn new(context: &Context) -> Result<ParticleBuffer<Context>, ()> {
+        Ok(ParticleBuffer {
+            buffer: Rc::new(context.make_attribute_buffer()?),
+            num_particles: 0,
+            data: Vec::new(),
+        })
+    }
+
+    pub fn set_particles(&mut self, particles: &ParticleSystem) {
+        self.data.clear();
+        particles.write_vertices(&mut self.data);
+        self.buffer.set_data(self.data.as_bytes());
+        self.num_particles = particles.len();
+    }
+
+    fn binding<T: VertexAttributeData>(&self, offset: usize) -> VertexAttributeBinding {
+        let mut binding = VertexAttributeBinding::typed::<T>(self.num_particles);
+        binding
+            .set_stride(PARTICLE_VERTEX_FLOATS * mem::size_of::<f32>())
+            .set_offset(offset * mem::size_of::<f32>());
+        binding
+    }
+
+    pub fn draw(&self, context: &BoundParticleShader<Context>) {
+        if self.num_particles == 0 {
+            return;
+        }
+        let info = &context.info;
+        let attributes = [
+            (&info.position, self.binding::<Vector3<f32>>(0)),
+            (&info.size, self.binding::<f32>(3)),
+            (&info.color, self.binding::<Vector3<f32>>(4)),
+            (&info.alpha, self.binding::<f32>(7)),
+        ];
+        for (param, binding) in attributes.iter() {
+            self.buffer.bind_to_attribute(param.index, binding);
+        }
+        context.draw_points(self.num_particles);
+    }
+}
+
+impl<Context: RenderingContext> Clone for ParticleBuffer<Context> {
+    fn clone(&self) -> Self {
+        ParticleBuffer {
+            buffer: Rc::clone(&self.buffer),
+            num_particles: self.num_particles,
+            // The vertex data is only kept to reuse its allocation.
+            data: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ParticleShaderInfo {
+    pub position: ShaderParamInfo,
+    pub size: ShaderParamInfo,
+    pub color: ShaderParamInfo,
+    pub alpha: ShaderParamInfo,
+    pub viewport: ShaderParamInfo,
+    pub projection: ShaderParamInfo,
+    pub view_transform: ShaderParamInfo,
+}
+
+impl ParticleShaderInfo {
+    pub fn from_program<Context>(
+        program: &Context::ShaderProgram,
+    ) -> Result<ParticleShaderInfo, ShaderInfoError>
+    where
+        Context: RenderingContext,
+    {
+        Ok(ParticleShaderInfo {
+            position: ShaderParamInfo::attribute(program, "position")?,
+            size: ShaderParamInfo::attribute(program, "size")?,
+            color: ShaderParamInfo::attribute(program, "color")?,
+            alpha: ShaderParamInfo::attribute(program, "alpha")?,
+            viewport: ShaderParamInfo::uniform(program, "viewport")?,
+            projection: ShaderParamInfo::uniform(program, "projection")?,
+            view_transform: ShaderParamInfo::uniform(program, "view")?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ParticleShader<Context: RenderingContext> {
+    pub program: Rc<Context::ShaderProgram>,
+    pub info: ParticleShaderInfo,
+}
+
+impl<Context: RenderingContext> ParticleShader<Context> {
+    pub fn new(program: Context::ShaderProgram) -> Result<Self, ShaderInfoError> {
+        let info = ParticleShaderInfo::from_program::<Context>(&program)?;
+        Ok(ParticleShader {
+            program: Rc::new(program),
+            info,
+        })
+    }
+}
+
+pub struct BoundParticleShader<Context: RenderingContext> {
+    bound_shader: Context::BoundShader,
+    info: ParticleShaderInfo,
+}
+
+impl<Context: RenderingContext> BoundParticleShader<Context> {
+    pub fn new(
+        context: &Context,
+        shader: &ParticleShader<Context>,
+        world: &dyn LineWorldContext,
+    ) -> Result<Self, ShaderBindError> {
+        let bound_shader = context.bind_shader(Rc::clone(&shader.program))?;
+        bound_shader.set_uniform_mat4(shader.info.projection.index, world.projection());
+        bound_shader.set_uniform_mat4(shader.info.view_transform.index, world.view());
+        let (width, height) = context.viewport_size();
+        bound_shader.set_uniform_vec2(
+            shader.info.viewport.index,
+            Vector2::new(width as f32, height as f32),
+        );
+        Ok(BoundParticleShader {
+            bound_shader,
+            info: shader.info.clone(),
+        })
+    }
+
+    pub fn info(&self) -> &ParticleShaderInfo {
+        &self.info
+    }
+}
+
+impl<Context> Deref for BoundParticleShader<Context>
+where
+    Context: RenderingContext,
+{
+    type Target = dyn BoundShader<Context>;
+    fn deref(&self) -> &Self::Target {
+        &self.bound_shader
+    }
+}
@@ -0,0 +1,368 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::mem;
+use std::ops::Deref;
+use std::rc::Rc;
+
+use nalgebra::Matrix4;
+
+use crate::rendering::buffer::{AttributeBuffer, Buffer, BufferData, VertexAttributeBinding};
+use crate::rendering::context::RenderingContext;
+use crate::rendering::line::{BoundThickLineShader, LineWorldContext, ThickLine, ThickLineShader};
+use crate::rendering::material::{
+    BoundMaterialShader, MaterialShader, MaterialWorldContext, SurfaceKey,
+};
+use crate::rendering::mesh::{Mesh, Primitive};
+use crate::rendering::particle::{BoundParticleShader, ParticleBuffer, ParticleShader};
+use crate::rendering::shader::ShaderBindError;
+use crate::rendering::Rgba;
+
+/// A group of draw items that is rendered after the groups before it
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum RenderPass {
+    /// Solid geometry, drawn front to back
+    Opaque,
+    /// Blended geometry, drawn back to front so that it covers what's behind it
+    Transparent,
+    /// Effects and annotations on top of the scene, drawn in the order they were submitted
+    Overlay,
+}
+
+/// The shader programs that draw commands can use
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum ShaderKind {
+    Material,
+    InstancedMaterial,
+    ThickLine,
+    Particle,
+}
+
+#[derive(Debug)]
+pub enum DrawCommand<Context: RenderingContext> {
+    /// One of a mesh's primitives
+    Primitive {
+        mesh: Rc<Mesh<Context>>,
+        index: usize,
+        model_transform: Matrix4<f32>,
+    },
+    ThickLine(ThickLine<Context>),
+    Particles(ParticleBuffer<Context>),
+}
+
+impl<Context: RenderingContext> DrawCommand<Context> {
+    pub fn shader(&self) -> ShaderKind {
+        match self {
+            DrawCommand::Primitive { .. } => ShaderKind::Material,
+            DrawCommand::ThickLine(_) => ShaderKind::ThickLine,
+            DrawCommand::Particles(_) => ShaderKind::Particle,
+        }
+    }
+
+    fn primitive(&self) -> Option<&Primitive<Context>> {
+        match self {
+            DrawCommand::Primitive { mesh, index, .. } => Some(&mesh.primitives[*index]),
+            _ => None,
+        }
+    }
+
+    /// Returns a key that is equal for draws that use the same geometry and material uniforms
+    fn state_key(&self) -> Option<(usize, (SurfaceKey, [u32; 4]))> {
+        self.primitive().map(|primitive| {
+            let geometry = Rc::as_ptr(&primitive.geometry) as usize;
+            (geometry, primitive.material.sort_key())
+        })
+    }
+
+    /// Returns a key that is equal for draws that can be combined into one instanced draw
+    fn instance_key(&self) -> Option<(usize, SurfaceKey)> {
+        self.primitive().map(|primitive| {
+            let geometry = Rc::as_ptr(&primitive.geometry) as usize;
+            (geometry, primitive.material.instance_key())
+        })
+    }
+}
+
+/// Something to draw, along with where it belongs in the frame
+#[derive(Debug)]
+pub struct DrawItem<Context: RenderingContext> {
+    pub pass: RenderPass,
+    /// Distance from the camera, which orders items within a pass
+    pub depth: f32,
+    pub command: DrawCommand<Context>,
+}
+
+impl<Context: RenderingContext> DrawItem<Context> {
+    pub fn new(pass: RenderPass, depth: f32, command: DrawCommand<Context>) -> Self {
+        DrawItem {
+            pass,
+            depth,
+            command,
+        }
+    }
+
+    fn cmp_draw_order(&self, other: &Self) -> Ordering {
+        let by_state = |a: &Self, b: &Self| {
+            a.command
+                .shader()
+                .cmp(&b.command.shader())
+                .then_with(|| a.command.state_key().cmp(&b.command.state_key()))
+        };
+        let by_depth =
+            |a: &Self, b: &Self| a.depth.partial_cmp(&b.depth).unwrap_or(Ordering::Equal);
+        self.pass.cmp(&other.pass).then_with(|| match self.pass {
+            // Grouping by shader, geometry and material minimizes state changes (and lets
+            // copies of the same geometry be instanced), and drawing the nearest items first
+            // lets the depth test skip hidden fragments.
+            RenderPass::Opaque => by_state(self, other).then_with(|| by_depth(self, other)),
+            // Blending only works if farther items are drawn first.
+            RenderPass::Transparent => by_depth(other, self).then_with(|| by_state(self, other)),
+            RenderPass::Overlay => Ordering::Equal,
+        })
+    }
+}
+
+/// The shaders used to execute a render queue
+#[derive(Debug)]
+pub struct QueueShaders<'a, Context: RenderingContext> {
+    pub material: &'a MaterialShader<Context>,
+    /// Draws many copies of a primitive at once, if the context supports instancing
+    pub instanced_material: Option<&'a MaterialShader<Context>>,
+    pub thick_line: &'a ThickLineShader<Context>,
+    pub particle: &'a ParticleShader<Context>,
+}
+
+enum BoundQueueShader<Context: RenderingContext> {
+    Material(BoundMaterialShader<Context>),
+    InstancedMaterial(BoundMaterialShader<Context>),
+    ThickLine(BoundThickLineShader<Context>),
+    Particle(BoundParticleShader<Context>),
+}
+
+impl<Context: RenderingContext> BoundQueueShader<Context> {
+    fn kind(&self) -> ShaderKind {
+        match self {
+            BoundQueueShader::Material(_) => ShaderKind::Material,
+            BoundQueueShader::InstancedMaterial(_) => ShaderKind::InstancedMaterial,
+            BoundQueueShader::ThickLine(_) => ShaderKind::ThickLine,
+            BoundQueueShader::Particle(_) => ShaderKind::Particle,
+        }
+    }
+}
+
+// Number of floats for each instance: the model transform followed by the color
+const INSTANCE_FLOATS: usize = 20;
+
+/// Draw items collected over a frame, which are sorted before any of them are drawn
+///
+/// Renderers submit items instead of drawing immediately, so that a frame's draws can be
+/// reordered to bind each shader and material as few times as possible and to blend transparent
+/// geometry correctly. Neighboring items that draw the same geometry are combined into a single
+/// instanced draw when the context supports it.
+#[derive(Debug)]
+pub struct RenderQueue<Context: RenderingContext> {
+    items: RefCell<Vec<DrawItem<Context>>>,
+    instance_buffer: RefCell<Option<Context::AttributeBuffer>>,
+    instance_data: RefCell<Vec<f32>>,
+}
+
+impl<Context: RenderingContext> RenderQueue<Context> {
+    pub fn new() -> Self {
+        RenderQueue {
+            items: RefCell::new(Vec::new()),
+            instance_buffer: RefCell::new(None),
+            instance_data: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn submit(&self, item: DrawItem<Context>) {
+        self.items.borrow_mut().push(item);
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.borrow().is_empty()
+    }
+
+    pub fn clear(&self) {
+        self.items.borrow_mut().clear();
+    }
+
+    /// Sorts the items into the order that they will be drawn in
+    ///
+    /// Items that are equal in the order keep the order they were submitted in.
+    pub fn sort(&self) {
+        self.items.borrow_mut().sort_by(|a, b| a.cmp_draw_order(b));
+    }
+
+    /// Returns the number of items starting at `items[0]` that can be drawn together
+    fn batch_len(&self, context: &Context, items: &[DrawItem<Context>]) -> usize {
+        let first = &items[0];
+        let key = match first.command.instance_key() {
+            Some(key) => key,
+            None => return 1,
+        };
+        let len = items
+            .iter()
+            .take_while(|item| item.pass == first.pass && item.command.instance_key() == Some(key))
+            .count();
+        if len > 1 && self.instance_buffer.borrow().is_none() {
+            match context.make_attribute_buffer() {
+                Ok(buffer) => *self.instance_buffer.borrow_mut() = Some(buffer),
+                Err(_) => return 1,
+            }
+        }
+        len
+    }
+
+    /// Draws a primitive once for each item, reading the transforms and colors from the items
+    fn draw_instanced(&self, shader: &BoundMaterialShader<Context>, items: &[DrawItem<Context>]) {
+        let instance_info = match shader.info().instance {
+            Some(ref info) => info,
+            None => return,
+        };
+        let buffer = self.instance_buffer.borrow();
+        let buffer = match *buffer {
+            Some(ref buffer) => buffer,
+            None => return,
+        };
+        let mut data = self.instance_data.borrow_mut();
+        data.clear();
+        for item in items.iter() {
+            if let DrawCommand::Primitive {
+                mesh,
+                index,
+                model_transform,
+            } = &item.command
+            {
+                let color = &mesh.primitives[*index].material.base_color;
+                data.extend_from_slice(model_transform.as_slice());
+                data.extend_from_slice(&[color.r, color.g, color.b, color.a]);
+            }
+        }
+        buffer.set_data(data.as_bytes());
+
+        let float_size = mem::size_of::<f32>();
+        let bind = |index: usize, offset: usize| {
+            let mut binding = VertexAttributeBinding::typed::<f32>(items.len());
+            binding.num_components = 4;
+            binding
+                .set_stride(INSTANCE_FLOATS * float_size)
+                .set_offset(offset * float_size)
+                .set_divisor(1);
+            buffer.bind_to_attribute(index, &binding);
+        };
+        for (column, param) in instance_info.model_transform.iter().enumerate() {
+            bind(param.index, column * 4);
+        }
+        bind(instance_info.color.index, 16);
+
+        let primitive = match items[0].command.primitive() {
+            Some(primitive) => primitive,
+            None => return,
+        };
+        // Each instance's color multiplies the material's, so the material's is left out.
+        let info = shader.info();
+        info.bind_base_color(&Rgba::new(1.0, 1.0, 1.0, 1.0), shader.deref());
+        info.bind_surface(&primitive.material, shader.deref());
+        primitive.geometry.draw_instanced(shader, items.len());
+    }
+
+    /// Draws and removes every item in the queue in its current order
+    ///
+    /// A shader is only bound when an item needs a different one than the item before it, and
+    /// likewise for materials. If a shader can't be bound, the remaining items are discarded.
+    pub fn execute<W>(
+        &self,
+        context: &Context,
+        shaders: &QueueShaders<Context>,
+        world: &W,
+    ) -> Result<(), ShaderBindError>
+    where
+        W: MaterialWorldContext + LineWorldContext,
+    {
+        let items = self.items.replace(Vec::new());
+        let mut bound: Option<BoundQueueShader<Context>> = None;
+        let mut material = None;
+        let mut start = 0;
+        while start < items.len() {
+            let item = &items[start];
+            let batch_len = match shaders.instanced_material {
+                Some(_) => self.batch_len(context, &items[start..]),
+                None => 1,
+            };
+            let shader = if batch_len > 1 {
+                ShaderKind::InstancedMaterial
+            } else {
+                item.command.shader()
+            };
+            if bound.as_ref().map(|b| b.kind()) != Some(shader) {
+                // Contexts may only allow one shader to be bound at a time.
+                drop(bound.take());
+                bound =
+                    Some(match shader {
+                        ShaderKind::Material => BoundQueueShader::Material(
+                            BoundMaterialShader::new(context, shaders.material, world)?,
+                        ),
+                        ShaderKind::InstancedMaterial => {
+                            // The instanced shader is always present when batches are made.
+                            let instanced = shaders.instanced_material.unwrap_or(shaders.material);
+                            BoundQueueShader::InstancedMaterial(BoundMaterialShader::new(
+                                context, instanced, world,
+                            )?)
+                        }
+                        ShaderKind::ThickLine => BoundQueueShader::ThickLine(
+                            BoundThickLineShader::new(context, shaders.thick_line, world)?,
+                        ),
+                        ShaderKind::Particle => BoundQueueShader::Particle(
+                            BoundParticleShader::new(context, shaders.particle, world)?,
+                        ),
+                    });
+                material = None;
+            }
+
+            match (&item.command, bound.as_ref()) {
+                (_, Some(BoundQueueShader::InstancedMaterial(shader))) => {
+                    self.draw_instanced(shader, &items[start..start + batch_len]);
+                }
+                (
+                    DrawCommand::Primitive {
+                        mesh,
+                        index,
+                        model_transform,
+                    },
+                    Some(BoundQueueShader::Material(shader)),
+                ) => {
+                    let primitive = &mesh.primitives[*index];
+                    let info = shader.info();
+                    if let Some(ref model) = info.model_transform {
+                        shader.set_uniform_mat4(model.index, *model_transform);
+                    }
+                    let key = item.command.state_key().map(|(_, key)| key);
+                    if material != key {
+                        info.bind_material(&primitive.material, shader.deref());
+                        material = key;
+                    }
+                    primitive.geometry.draw(shader);
+                }
+                (DrawCommand::ThickLine(line), Some(BoundQueueShader::ThickLine(shader))) => {
+                    line.draw(shader);
+                }
+                (DrawCommand::Particles(buffer), Some(BoundQueueShader::Particle(shader))) => {
+                    buffer.draw(shader);
+                }
+                _ => unreachable!("the bound shader always matches the command"),
+            }
+            start += batch_len;
+        }
+        Ok(())
+    }
+}
+
+impl<Context: RenderingContext> Default for RenderQueue<Context> {
+    fn default() -> Self {
+        RenderQueue::new()
+    }
+}
@@ -1,4 +1,12 @@
-use nalgebra::{Isometry, Orthographic3, Translation, UnitQuaternion, Vector3};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use nalgebra::{
+    Isometry, Matrix4, Orthographic3, Point3, Translation, UnitQuaternion, Vector2, Vector3,
+};
+
+use crate::rendering::context::RenderingContext;
+use crate::rendering::mesh::Mesh;
 
 #[derive(Clone, Debug)]
 pub struct Camera {
@@ -34,6 +42,22 @@ impl Camera {
     pub fn view(&self) -> Isometry<f32, UnitQuaternion<f32>, 3> {
         Isometry::from_parts(Translation::from(self.position), self.rotation).inverse()
     }
+
+    /// Returns the corners of the area of the world's XY plane that the camera can see
+    pub fn visible_bounds(&self) -> (Vector2<f32>, Vector2<f32>) {
+        let a = self.unproject(&Vector2::new(-1.0, -1.0));
+        let b = self.unproject(&Vector2::new(1.0, 1.0));
+        (a.inf(&b), a.sup(&b))
+    }
+
+    /// Converts normalized device coordinates to a position on the world's XY plane
+    pub fn unproject(&self, ndc: &Vector2<f32>) -> Vector2<f32> {
+        let view_pos = self
+            .projection()
+            .unproject_point(&Point3::new(ndc.x, ndc.y, 0.0));
+        let world_pos = self.view().inverse_transform_point(&view_pos);
+        world_pos.coords.xy()
+    }
 }
 
 impl Default for Camera {
@@ -41,3 +65,111 @@ impl Default for Camera {
         Camera::new()
     }
 }
+
+/// A hierarchy of nodes that place meshes relative to each other, such as the parts of a model
+#[derive(Debug)]
+pub struct Scene<Context: RenderingContext> {
+    pub nodes: Vec<SceneNode<Context>>,
+}
+
+/// A node in a scene, positioned relative to its parent (or to the scene, for root nodes)
+#[derive(Debug)]
+pub struct SceneNode<Context: RenderingContext> {
+    pub name: Option<String>,
+    pub transform: Matrix4<f32>,
+    pub mesh: Option<Rc<Mesh<Context>>>,
+    pub children: Vec<SceneNode<Context>>,
+    pub extras: Option<serde_json::Value>,
+}
+
+impl<Context: RenderingContext> Scene<Context> {
+    pub fn new(nodes: Vec<SceneNode<Context>>) -> Self {
+        Scene { nodes }
+    }
+
+    /// Creates a scene with a single mesh at its origin
+    pub fn from_mesh(mesh: Rc<Mesh<Context>>) -> Self {
+        Scene::new(vec![SceneNode::new(Matrix4::identity(), Some(mesh))])
+    }
+
+    /// Returns each mesh in the scene, along with its transform relative to the scene
+    ///
+    /// Meshes are listed depth first, in the order that their nodes appear.
+    pub fn meshes(&self) -> Vec<(Matrix4<f32>, Rc<Mesh<Context>>)> {
+        let mut meshes = Vec::new();
+        for node in self.nodes.iter() {
+            node.collect_meshes(&Matrix4::identity(), &mut meshes);
+        }
+        meshes
+    }
+
+    /// Returns a copy of the scene with every mesh replaced
+    ///
+    /// A mesh that is used by several nodes is only converted once, and the copies share the
+    /// result.
+    pub fn map_meshes<F>(&self, mut convert: F) -> Self
+    where
+        F: FnMut(&Mesh<Context>) -> Mesh<Context>,
+    {
+        let mut converted = HashMap::new();
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| node.map_meshes(&mut convert, &mut converted))
+            .collect();
+        Scene::new(nodes)
+    }
+}
+
+impl<Context: RenderingContext> SceneNode<Context> {
+    pub fn new(transform: Matrix4<f32>, mesh: Option<Rc<Mesh<Context>>>) -> Self {
+        SceneNode {
+            name: None,
+            transform,
+            mesh,
+            children: Vec::new(),
+            extras: None,
+        }
+    }
+
+    fn collect_meshes(
+        &self,
+        parent_transform: &Matrix4<f32>,
+        meshes: &mut Vec<(Matrix4<f32>, Rc<Mesh<Context>>)>,
+    ) {
+        let transform = parent_transform * self.transform;
+        if let Some(ref mesh) = self.mesh {
+            meshes.push((transform, Rc::clone(mesh)));
+        }
+        for child in self.children.iter() {
+            child.collect_meshes(&transform, meshes);
+        }
+    }
+
+    fn map_meshes<F>(
+        &self,
+        convert: &mut F,
+        converted: &mut HashMap<*const Mesh<Context>, Rc<Mesh<Context>>>,
+    ) -> Self
+    where
+        F: FnMut(&Mesh<Context>) -> Mesh<Context>,
+    {
+        let mesh = self.mesh.as_ref().map(|mesh| {
+            let new_mesh = converted
+                .entry(Rc::as_ptr(mesh))
+                .or_insert_with(|| Rc::new(convert(mesh)));
+            Rc::clone(new_mesh)
+        });
+        SceneNode {
+            name: self.name.clone(),
+            transform: self.transform,
+            mesh,
+            children: self
+                .children
+                .iter()
+                .map(|child| child.map_meshes(convert, converted))
+                .collect(),
+            extras: self.extras.clone(),
+        }
+    }
+}
@@ -1,6 +1,7 @@
-use std::fmt::Debug;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
 
-use nalgebra::{Matrix4, Vector3, Vector4};
+use nalgebra::{Matrix4, Vector2, Vector3, Vector4};
 
 use crate::rendering::context::RenderingContext;
 use crate::rendering::mesh::ElementIndices;
@@ -36,7 +37,7 @@ impl ShaderParamInfo {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum ShaderType {
     Vertex,
     Fragment,
@@ -57,17 +58,51 @@ pub trait ShaderProgram: Debug {
     fn uniform(&self, name: &str) -> Option<ShaderParamInfo>;
 }
 
+/// How a draw call assembles its vertices into shapes
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PrimitiveType {
+    Triangles,
+    TriangleStrip,
+    LineStrip,
+    Points,
+}
+
 pub trait BoundShader<Context: RenderingContext + ?Sized> {
     fn program(&self) -> &Context::ShaderProgram;
 
     fn draw_triangles(&self, count: usize);
     fn draw_indexed_triangles(&self, indices: &ElementIndices<Context>);
+    /// Draws triangles once for each instance, if the context supports instancing
+    fn draw_triangles_instanced(&self, count: usize, instance_count: usize);
+    /// Draws indexed triangles once for each instance, if the context supports instancing
+    fn draw_indexed_triangles_instanced(
+        &self,
+        indices: &ElementIndices<Context>,
+        instance_count: usize,
+    );
     fn draw_polyline(&self, num_vertices: usize);
+    fn draw_triangle_strip(&self, num_vertices: usize);
+    fn draw_points(&self, num_vertices: usize);
+
+    /// Sets whether triangles that face away from the camera are drawn, rather than culled
+    ///
+    /// The setting only lasts as long as this bound shader: back faces are culled again once it
+    /// is dropped.
+    fn set_double_sided(&self, double_sided: bool);
+
+    /// Stops reading an attribute from a buffer, so that it reads as (0, 0, 0, 1)
+    fn disable_attribute(&self, index: usize);
 
+    fn set_uniform_bool(&self, index: usize, value: bool);
     fn set_uniform_f32(&self, index: usize, value: f32);
     fn set_uniform_mat4(&self, index: usize, value: Matrix4<f32>);
+    fn set_uniform_vec2(&self, index: usize, value: Vector2<f32>);
     fn set_uniform_vec3(&self, index: usize, value: Vector3<f32>);
     fn set_uniform_vec4(&self, index: usize, value: Vector4<f32>);
+    /// Binds a texture to a texture unit and points a sampler uniform at that unit
+    ///
+    /// Each sampler keeps its unit for as long as the shader is bound.
+    fn set_uniform_texture(&self, index: usize, texture: &Context::Texture);
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -75,3 +110,16 @@ pub enum ShaderBindError {
     InvalidContextForShader,
     CannotBindMoreShaders,
 }
+
+impl Display for ShaderBindError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            ShaderBindError::InvalidContextForShader => {
+                write!(f, "Shader was created by a different context")
+            }
+            ShaderBindError::CannotBindMoreShaders => write!(f, "Unable to bind another shader"),
+        }
+    }
+}
+
+impl Error for ShaderBindError {}
@@ -1,23 +1,321 @@
+use std::cell::RefCell;
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
 
-use image::RgbImage;
+use image::{DynamicImage, GrayAlphaImage, GrayImage, RgbImage, RgbaImage};
 
 use crate::rendering::context::RenderingContext;
 
 #[derive(Clone, Copy, Debug)]
-pub struct SetTextureDataError;
+pub enum SetTextureDataError {
+    /// The number of bytes doesn't match the size and format of the image
+    WrongDataLength,
+    /// A sub-image has a different format from the texture, or doesn't fit inside it
+    IncompatibleSubImage,
+    /// The rendering context rejected the data
+    UploadFailed,
+}
 
 impl Display for SetTextureDataError {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
-        write!(f, "Unable to set texture data")
+        match self {
+            SetTextureDataError::WrongDataLength => {
+                write!(f, "Texture data doesn't match the image's size")
+            }
+            SetTextureDataError::IncompatibleSubImage => {
+                write!(f, "Sub-image doesn't match the texture")
+            }
+            SetTextureDataError::UploadFailed => write!(f, "Unable to set texture data"),
+        }
     }
 }
 
 impl Error for SetTextureDataError {}
 
+/// The channels of a texture's pixels, which have one byte each
+///
+/// Gray textures are sampled as (l, l, l, 1), and gray textures with alpha as (l, l, l, a).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TextureFormat {
+    Gray,
+    GrayAlpha,
+    Rgb,
+    Rgba,
+}
+
+impl TextureFormat {
+    pub fn channels(self) -> usize {
+        match self {
+            TextureFormat::Gray => 1,
+            TextureFormat::GrayAlpha => 2,
+            TextureFormat::Rgb => 3,
+            TextureFormat::Rgba => 4,
+        }
+    }
+}
+
+/// The format and size of a texture's image
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TextureShape {
+    pub format: TextureFormat,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl TextureShape {
+    pub fn is_power_of_two(&self) -> bool {
+        self.width.is_power_of_two() && self.height.is_power_of_two()
+    }
+
+    /// Checks that a sub-image placed at (x, y) has this format and lies inside the image
+    pub fn check_sub_image(
+        &self,
+        x: u32,
+        y: u32,
+        data: &TextureData,
+    ) -> Result<(), SetTextureDataError> {
+        let fits = |offset: u32, size: u32, limit: u32| matches!(offset.checked_add(size), Some(end) if end <= limit);
+        if data.format != self.format
+            || !fits(x, data.width, self.width)
+            || !fits(y, data.height, self.height)
+        {
+            return Err(SetTextureDataError::IncompatibleSubImage);
+        }
+        Ok(())
+    }
+}
+
+/// Tightly packed pixels to upload to a texture, in rows from the top
+#[derive(Clone, Copy, Debug)]
+pub struct TextureData<'a> {
+    format: TextureFormat,
+    width: u32,
+    height: u32,
+    pixels: &'a [u8],
+}
+
+impl<'a> TextureData<'a> {
+    pub fn new(
+        format: TextureFormat,
+        width: u32,
+        height: u32,
+        pixels: &'a [u8],
+    ) -> Result<TextureData<'a>, SetTextureDataError> {
+        let expected = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|count| count.checked_mul(format.channels()));
+        if expected != Some(pixels.len()) {
+            return Err(SetTextureDataError::WrongDataLength);
+        }
+        Ok(TextureData::from_packed(format, width, height, pixels))
+    }
+
+    /// Borrows the pixels of an image with 8 bits per channel
+    ///
+    /// Returns `None` for other images, which can be converted with `to_rgba8()` first.
+    pub fn from_image(image: &'a DynamicImage) -> Option<TextureData<'a>> {
+        match image {
+            DynamicImage::ImageLuma8(image) => Some(image.into()),
+            DynamicImage::ImageLumaA8(image) => Some(image.into()),
+            DynamicImage::ImageRgb8(image) => Some(image.into()),
+            DynamicImage::ImageRgba8(image) => Some(image.into()),
+            _ => None,
+        }
+    }
+
+    /// Wraps pixels whose length is known to match the size and format
+    fn from_packed(
+        format: TextureFormat,
+        width: u32,
+        height: u32,
+        pixels: &'a [u8],
+    ) -> TextureData<'a> {
+        TextureData {
+            format,
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &'a [u8] {
+        self.pixels
+    }
+
+    pub fn shape(&self) -> TextureShape {
+        TextureShape {
+            format: self.format,
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
+
+impl<'a> From<&'a GrayImage> for TextureData<'a> {
+    fn from(image: &'a GrayImage) -> TextureData<'a> {
+        TextureData::from_packed(TextureFormat::Gray, image.width(), image.height(), image)
+    }
+}
+
+impl<'a> From<&'a GrayAlphaImage> for TextureData<'a> {
+    fn from(image: &'a GrayAlphaImage) -> TextureData<'a> {
+        TextureData::from_packed(
+            TextureFormat::GrayAlpha,
+            image.width(),
+            image.height(),
+            image,
+        )
+    }
+}
+
+impl<'a> From<&'a RgbImage> for TextureData<'a> {
+    fn from(image: &'a RgbImage) -> TextureData<'a> {
+        TextureData::from_packed(TextureFormat::Rgb, image.width(), image.height(), image)
+    }
+}
+
+impl<'a> From<&'a RgbaImage> for TextureData<'a> {
+    fn from(image: &'a RgbaImage) -> TextureData<'a> {
+        TextureData::from_packed(TextureFormat::Rgba, image.width(), image.height(), image)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WrapMode {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MagFilter {
+    Nearest,
+    Linear,
+}
+
+/// How a texture is sampled when it's drawn smaller than its size
+///
+/// The mipmap filters pick the nearest mipmap level, or blend the two nearest levels, and then
+/// filter within the level as the first part of their name says.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MinFilter {
+    Nearest,
+    Linear,
+    NearestMipmapNearest,
+    LinearMipmapNearest,
+    NearestMipmapLinear,
+    LinearMipmapLinear,
+}
+
+impl MinFilter {
+    pub fn uses_mipmaps(self) -> bool {
+        !matches!(self, MinFilter::Nearest | MinFilter::Linear)
+    }
+
+    /// Returns the filter that samples the base image the same way, without mipmaps
+    pub fn without_mipmaps(self) -> MinFilter {
+        match self {
+            MinFilter::Nearest
+            | MinFilter::NearestMipmapNearest
+            | MinFilter::NearestMipmapLinear => MinFilter::Nearest,
+            MinFilter::Linear | MinFilter::LinearMipmapNearest | MinFilter::LinearMipmapLinear => {
+                MinFilter::Linear
+            }
+        }
+    }
+}
+
+/// How a texture is read by the shaders that sample it
+///
+/// The default clamps and filters linearly without mipmaps, which works for images of any size.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Sampler {
+    pub wrap_s: WrapMode,
+    pub wrap_t: WrapMode,
+    pub min_filter: MinFilter,
+    pub mag_filter: MagFilter,
+}
+
+impl Sampler {
+    /// Returns a sampler that reads texels without blending them, for crisp pixel art and text
+    pub fn nearest() -> Sampler {
+        Sampler {
+            min_filter: MinFilter::Nearest,
+            mag_filter: MagFilter::Nearest,
+            ..Sampler::default()
+        }
+    }
+}
+
+impl Default for Sampler {
+    fn default() -> Sampler {
+        Sampler {
+            wrap_s: WrapMode::ClampToEdge,
+            wrap_t: WrapMode::ClampToEdge,
+            min_filter: MinFilter::Linear,
+            mag_filter: MagFilter::Linear,
+        }
+    }
+}
+
 pub trait Texture: Debug {
     type RenderingContext: RenderingContext + ?Sized;
 
-    fn set_image_data(&self, image: &RgbImage) -> Result<(), SetTextureDataError>;
+    /// Replaces the texture's image, discarding its mipmaps
+    fn set_data(&self, data: TextureData) -> Result<(), SetTextureDataError>;
+
+    /// Overwrites part of the texture's image, starting `x` pixels from the left and `y` from
+    /// the top
+    ///
+    /// The sub-image must have the texture's format. Mipmaps aren't updated until they are
+    /// generated again.
+    fn set_sub_data(&self, x: u32, y: u32, data: TextureData) -> Result<(), SetTextureDataError>;
+
+    /// Generates mipmaps from the texture's current image
+    fn generate_mipmaps(&self);
+
+    fn set_sampler(&self, sampler: &Sampler);
+}
+
+/// Assigns texture units to the sampler uniforms of a bound shader
+#[derive(Debug, Default)]
+pub struct TextureUnits {
+    /// The uniform index of the sampler that uses each unit
+    samplers: RefCell<Vec<usize>>,
+    max_units: usize,
+}
+
+impl TextureUnits {
+    pub fn new(max_units: usize) -> TextureUnits {
+        TextureUnits {
+            samplers: RefCell::new(Vec::new()),
+            max_units,
+        }
+    }
+
+    /// Returns the unit for a sampler uniform, or `None` if every unit is taken
+    pub fn unit_for(&self, uniform_index: usize) -> Option<usize> {
+        let mut samplers = self.samplers.borrow_mut();
+        if let Some(unit) = samplers.iter().position(|&s| s == uniform_index) {
+            return Some(unit);
+        }
+        if samplers.len() >= self.max_units {
+            log::error!("Out of texture units");
+            return None;
+        }
+        samplers.push(uniform_index);
+        Some(samplers.len() - 1)
+    }
 }
@@ -10,5 +10,17 @@ pub const MISSILE_MAX_VELOCITY: f32 = 10.0;
 /// Scaling factor from missile velocity units to actual game units per second
 pub const MISSILE_VELOCITY_SCALE: f32 = 10.0;
 
+/// Maximum number of players in a game
+pub const MAX_PLAYERS: usize = 8;
+
+/// Number of ticks of a missile's predicted path shown by the trajectory preview
+pub const TRAJECTORY_PREVIEW_TICKS: usize = 60;
+
+/// Maximum number of particles (for explosions and exhaust) that can exist at once
+pub const MAX_PARTICLES: usize = 4096;
+
 /// Gravitational constant
 pub const GRAVITATIONAL_CONSTANT: f32 = 5e-10;
+
+/// Distance beyond a wormhole exit's collision shape at which teleported missiles reappear
+pub const WORMHOLE_EXIT_CLEARANCE: f32 = 0.1;
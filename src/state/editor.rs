@@ -0,0 +1,543 @@
+use std::error::Error;
+use std::fmt::Display;
+use std::rc::Rc;
+
+use nalgebra::{Isometry, Translation, UnitComplex, Vector2, Vector3};
+use ncollide2d::shape::{Ball, Shape};
+
+use crate::rendering::context::RenderingContext;
+use crate::rendering::scene::Scene;
+use crate::rendering::Rgb;
+use crate::state::constants::MAX_PLAYERS;
+use crate::state::mapfile::{
+    MapFile, MapLighting, MapLoadError, PlanetMaterial, PlanetSpec, SpawnPoint,
+};
+use crate::state::mapgen;
+use crate::state::mapgen::SphereRendererFactory;
+use crate::state::{Entity, EntityRenderer, GamePhase, GameState, PlayerRendererFactory};
+use crate::state_renderer::{CompositeRenderer, GameRenderer, RingRenderer};
+
+// Color of the ring drawn around the selected entity
+const SELECTION_COLOR: Rgb = Rgb::new(1.0, 1.0, 1.0);
+// Gap between the selected entity's collision shape and the selection ring
+const SELECTION_RING_GAP: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug)]
+pub enum EditorEvent {
+    /// Selects the entity at the given position, or clears the selection if there isn't one
+    Select(Vector2<f32>),
+    /// Adds a planet and selects it
+    AddPlanet {
+        position: Vector2<f32>,
+        radius: f32,
+        density: f32,
+    },
+    MoveSelected(Vector2<f32>),
+    /// Changes the radius of the selected planet
+    ResizeSelected(f32),
+    /// Changes the density of the selected planet
+    SetSelectedDensity(f32),
+    DeleteSelected,
+    /// Places the given player's ship, moving it if it already exists
+    PlaceSpawn {
+        player: usize,
+        position: Vector2<f32>,
+    },
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum EditorError {
+    NotEditing,
+    NothingSelected,
+    NotAPlanet,
+    OutOfBounds,
+    Collision,
+    InvalidRadius,
+    InvalidDensity,
+    InvalidPlayer,
+    CouldNotCreateEntity,
+    /// The edited map wouldn't load
+    InvalidMap(MapLoadError),
+}
+
+impl Display for EditorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EditorError::NotEditing => write!(f, "The map editor isn't active"),
+            EditorError::NothingSelected => write!(f, "Nothing is selected"),
+            EditorError::NotAPlanet => write!(f, "The selection isn't a planet"),
+            EditorError::OutOfBounds => write!(f, "Position is outside the map"),
+            EditorError::Collision => write!(f, "Change would overlap another entity"),
+            EditorError::InvalidRadius => write!(f, "Invalid planet radius"),
+            EditorError::InvalidDensity => write!(f, "Invalid planet density"),
+            EditorError::InvalidPlayer => write!(f, "Invalid player ID"),
+            EditorError::CouldNotCreateEntity => write!(f, "Unable to create entity"),
+            EditorError::InvalidMap(err) => write!(f, "Invalid map: {}", err),
+        }
+    }
+}
+
+impl Error for EditorError {}
+
+/// Creates the entities and renderers used by the editor
+pub trait EditorFactory {
+    fn make_planet_renderer(&self, material: &PlanetMaterial) -> Option<Rc<dyn EntityRenderer>>;
+    /// Creates a ship (with a renderer) for the given player
+    fn make_ship(&self, player_id: usize) -> Option<Entity>;
+    /// Creates a renderer that highlights a selected entity with the given radius
+    fn make_selection_renderer(&self, radius: f32) -> Option<Rc<dyn EntityRenderer>>;
+}
+
+/// Editor factory that uses the same renderers as generated maps
+pub struct MeshEditorFactory<Context>
+where
+    Context: RenderingContext + 'static,
+{
+    pub game_renderer: Rc<dyn GameRenderer<Context = Context>>,
+    /// Creates the planets' renderers, which share one sphere so they can be drawn together
+    pub planet_renderers: SphereRendererFactory<Context>,
+    pub ship_scene: Rc<Scene<Context>>,
+    pub make_ship_renderer: PlayerRendererFactory,
+}
+
+impl<Context> EditorFactory for MeshEditorFactory<Context>
+where
+    Context: RenderingContext + 'static,
+{
+    fn make_planet_renderer(&self, material: &PlanetMaterial) -> Option<Rc<dyn EntityRenderer>> {
+        Some(
+            self.planet_renderers
+                .make_renderer_with(material.to_material()),
+        )
+    }
+
+    fn make_ship(&self, player_id: usize) -> Option<Entity> {
+        let mut ship = mapgen::make_ship(&self.ship_scene, player_id, Vector2::zeros());
+        let player = mapgen::make_player(player_id);
+        ship.renderer = Some((self.make_ship_renderer)(&player).ok()?);
+        Some(ship)
+    }
+
+    fn make_selection_renderer(&self, radius: f32) -> Option<Rc<dyn EntityRenderer>> {
+        let renderer =
+            RingRenderer::new(Rc::clone(&self.game_renderer), radius, SELECTION_COLOR).ok()?;
+        Some(Rc::new(renderer))
+    }
+}
+
+/// What an entity in the editor represents in the map file
+#[derive(Clone, Debug)]
+enum EditorItem {
+    Planet(PlanetSpec),
+    Spawn(usize),
+}
+
+/// State of the map editor
+///
+/// Each entity in the game state corresponds to one item in the editor.
+pub struct MapEditor {
+    name: String,
+    width: f32,
+    height: f32,
+    lighting: Option<MapLighting>,
+    items: Vec<EditorItem>,
+    selected: Option<usize>,
+    // The selected entity's renderer without the selection highlight
+    selected_renderer: Option<Rc<dyn EntityRenderer>>,
+    factory: Box<dyn EditorFactory>,
+}
+
+impl MapEditor {
+    /// Returns the index of the selected entity
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    /// Returns the selected planet's properties
+    pub fn selected_planet(&self) -> Option<&PlanetSpec> {
+        match self.items.get(self.selected?)? {
+            EditorItem::Planet(ref spec) => Some(spec),
+            EditorItem::Spawn(_) => None,
+        }
+    }
+
+    /// Returns the player whose spawn point is selected
+    pub fn selected_spawn(&self) -> Option<usize> {
+        match self.items.get(self.selected?)? {
+            EditorItem::Spawn(player) => Some(*player),
+            EditorItem::Planet(_) => None,
+        }
+    }
+
+    fn contains(&self, pos: &Vector2<f32>) -> bool {
+        pos.x.abs() <= self.width * 0.5 && pos.y.abs() <= self.height * 0.5
+    }
+}
+
+impl GameState {
+    /// Switches to the map editor, replacing all entities with the contents of the given map
+    pub fn start_editor(
+        &mut self,
+        map: &MapFile,
+        factory: Box<dyn EditorFactory>,
+    ) -> Result<(), EditorError> {
+        self.clear_entities();
+        let mut editor = MapEditor {
+            name: map.name.clone(),
+            width: map.width,
+            height: map.height,
+            lighting: map.lighting,
+            items: Vec::new(),
+            selected: None,
+            selected_renderer: None,
+            factory,
+        };
+
+        for planet in map.planets.iter() {
+            let renderer = editor.factory.make_planet_renderer(&planet.material);
+            self.add_entity(planet.make_entity(renderer));
+            editor.items.push(EditorItem::Planet(planet.clone()));
+        }
+        for spawn in map.spawns.iter() {
+            let ship = make_spawn_ship(editor.factory.as_ref(), spawn.player, spawn.position)?;
+            self.add_entity(ship);
+            editor.items.push(EditorItem::Spawn(spawn.player));
+        }
+
+        self.set_players(mapgen::make_players(map.num_players()));
+        self.editor = Some(editor);
+        self.phase = GamePhase::Editing;
+        Ok(())
+    }
+
+    pub fn editor(&self) -> Option<&MapEditor> {
+        self.editor.as_ref()
+    }
+
+    /// Returns the map being edited, if it passes the same checks as when loading a map
+    pub fn export_map(&self) -> Result<MapFile, EditorError> {
+        let editor = self.editor.as_ref().ok_or(EditorError::NotEditing)?;
+        let mut planets = Vec::new();
+        let mut spawns = Vec::new();
+        for (index, (item, entity)) in editor.items.iter().zip(self.entities.iter()).enumerate() {
+            match item {
+                EditorItem::Planet(spec) => planets.push(spec.clone()),
+                EditorItem::Spawn(player) => {
+                    let position = entity.position().xy();
+                    if self.check_entity_at(index, &position).is_err() {
+                        let err = MapLoadError::SpawnPointBlocked(*player);
+                        return Err(EditorError::InvalidMap(err));
+                    }
+                    spawns.push(SpawnPoint {
+                        player: *player,
+                        position,
+                    });
+                }
+            }
+        }
+        spawns.sort_by_key(|s| s.player);
+        let map = MapFile {
+            name: editor.name.clone(),
+            width: editor.width,
+            height: editor.height,
+            planets,
+            spawns,
+            lighting: editor.lighting,
+        };
+        map.validate().map_err(EditorError::InvalidMap)?;
+        Ok(map)
+    }
+
+    pub(super) fn handle_edit(&mut self, event: &EditorEvent) -> Result<(), EditorError> {
+        let mut editor = self.editor.take().ok_or(EditorError::NotEditing)?;
+        let result = self.apply_edit(&mut editor, event);
+        self.editor = Some(editor);
+        self.rebuild_spatial_index();
+        result
+    }
+
+    fn apply_edit(
+        &mut self,
+        editor: &mut MapEditor,
+        event: &EditorEvent,
+    ) -> Result<(), EditorError> {
+        match *event {
+            EditorEvent::Select(pos) => {
+                let index = self
+                    .entities
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, e)| (e.position().xy() - pos).magnitude() <= e.collision_radius())
+                    .min_by(|(_, a), (_, b)| {
+                        let distance_a = (a.position().xy() - pos).magnitude();
+                        let distance_b = (b.position().xy() - pos).magnitude();
+                        distance_a.total_cmp(&distance_b)
+                    })
+                    .map(|(i, _)| i);
+                self.select(editor, index);
+                Ok(())
+            }
+            EditorEvent::AddPlanet {
+                position,
+                radius,
+                density,
+            } => {
+                let spec = PlanetSpec {
+                    position,
+                    radius,
+                    density,
+                    material: PlanetMaterial::default(),
+                };
+                check_planet(&spec)?;
+                if !editor.contains(&position) {
+                    return Err(EditorError::OutOfBounds);
+                }
+                if !self.is_clear(None, &Ball::new(radius), &position) {
+                    return Err(EditorError::Collision);
+                }
+                let renderer = editor.factory.make_planet_renderer(&spec.material);
+                let index = self.add_entity(spec.make_entity(renderer));
+                editor.items.push(EditorItem::Planet(spec));
+                self.select(editor, Some(index));
+                Ok(())
+            }
+            EditorEvent::MoveSelected(position) => {
+                let index = editor.selected.ok_or(EditorError::NothingSelected)?;
+                if !editor.contains(&position) {
+                    return Err(EditorError::OutOfBounds);
+                }
+                self.check_entity_at(index, &position)?;
+                self.entities[index].transform.position = Vector3::new(position.x, position.y, 0.0);
+                if let EditorItem::Planet(ref mut spec) = editor.items[index] {
+                    spec.position = position;
+                }
+                Ok(())
+            }
+            EditorEvent::ResizeSelected(radius) => {
+                let index = editor.selected.ok_or(EditorError::NothingSelected)?;
+                let mut spec = editor
+                    .selected_planet()
+                    .ok_or(EditorError::NotAPlanet)?
+                    .clone();
+                spec.radius = radius;
+                check_planet(&spec)?;
+                if !self.is_clear(Some(index), &Ball::new(radius), &spec.position) {
+                    return Err(EditorError::Collision);
+                }
+                let entity = &mut self.entities[index];
+                entity.collision_shape = Some(Box::new(Ball::new(radius)));
+                entity.transform.scale = radius;
+                entity.mass = spec.mass();
+                editor.items[index] = EditorItem::Planet(spec);
+                // Resize the selection highlight to match.
+                self.select(editor, Some(index));
+                Ok(())
+            }
+            EditorEvent::SetSelectedDensity(density) => {
+                let index = editor.selected.ok_or(EditorError::NothingSelected)?;
+                let mut spec = editor
+                    .selected_planet()
+                    .ok_or(EditorError::NotAPlanet)?
+                    .clone();
+                spec.density = density;
+                check_planet(&spec)?;
+                self.entities[index].mass = spec.mass();
+                editor.items[index] = EditorItem::Planet(spec);
+                Ok(())
+            }
+            EditorEvent::DeleteSelected => {
+                let index = editor.selected.ok_or(EditorError::NothingSelected)?;
+                self.select(editor, None);
+                self.remove_entity(index);
+                editor.items.remove(index);
+                Ok(())
+            }
+            EditorEvent::PlaceSpawn { player, position } => {
+                if player >= MAX_PLAYERS {
+                    return Err(EditorError::InvalidPlayer);
+                }
+                if !editor.contains(&position) {
+                    return Err(EditorError::OutOfBounds);
+                }
+                let existing = editor
+                    .items
+                    .iter()
+                    .position(|item| matches!(item, EditorItem::Spawn(p) if *p == player));
+                match existing {
+                    Some(index) => {
+                        self.check_entity_at(index, &position)?;
+                        self.entities[index].transform.position =
+                            Vector3::new(position.x, position.y, 0.0);
+                        self.select(editor, Some(index));
+                    }
+                    None => {
+                        let ship = make_spawn_ship(editor.factory.as_ref(), player, position)?;
+                        let clear = match ship.collision_shape {
+                            Some(ref shape) => self.is_clear(None, shape.as_ref(), &position),
+                            None => true,
+                        };
+                        if !clear {
+                            return Err(EditorError::Collision);
+                        }
+                        let index = self.add_entity(ship);
+                        editor.items.push(EditorItem::Spawn(player));
+                        if player >= self.players.len() {
+                            self.set_players(mapgen::make_players(player + 1));
+                        }
+                        self.select(editor, Some(index));
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Changes the selection, moving the selection highlight to the new entity
+    fn select(&mut self, editor: &mut MapEditor, index: Option<usize>) {
+        if let Some(old) = editor.selected.take() {
+            self.entities[old].renderer = editor.selected_renderer.take();
+        }
+        let index = match index {
+            Some(index) => index,
+            None => return,
+        };
+        let entity = &mut self.entities[index];
+        let radius = entity.collision_radius() + SELECTION_RING_GAP;
+        let highlight = editor.factory.make_selection_renderer(radius);
+        editor.selected_renderer = entity.renderer.clone();
+        entity.renderer = match (entity.renderer.clone(), highlight) {
+            (Some(renderer), Some(highlight)) => {
+                Some(Rc::new(CompositeRenderer::new(vec![renderer, highlight])))
+            }
+            (renderer, highlight) => renderer.or(highlight),
+        };
+        editor.selected = Some(index);
+    }
+
+    /// Checks whether an existing entity could be moved to a new position
+    fn check_entity_at(&self, index: usize, position: &Vector2<f32>) -> Result<(), EditorError> {
+        match self.entities[index].collision_shape {
+            Some(ref shape) if !self.is_clear(Some(index), shape.as_ref(), position) => {
+                Err(EditorError::Collision)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks whether a shape at the given position would overlap any entity except `ignore`
+    fn is_clear(
+        &self,
+        ignore: Option<usize>,
+        shape: &dyn Shape<f32>,
+        position: &Vector2<f32>,
+    ) -> bool {
+        let transform = Isometry::from_parts(Translation::from(*position), UnitComplex::identity());
+        self.entities_near(&shape.aabb(&transform))
+            .filter(|(i, _)| Some(*i) != ignore)
+            .all(|(_, e)| !e.collides_with_shape(shape, &transform))
+    }
+}
+
+fn check_planet(spec: &PlanetSpec) -> Result<(), EditorError> {
+    if !(spec.radius.is_finite() && spec.radius > 0.0) {
+        return Err(EditorError::InvalidRadius);
+    }
+    if !(spec.density.is_finite() && spec.density >= 0.0) {
+        return Err(EditorError::InvalidDensity);
+    }
+    Ok(())
+}
+
+fn make_spawn_ship(
+    factory: &dyn EditorFactory,
+    player: usize,
+    position: Vector2<f32>,
+) -> Result<Entity, EditorError> {
+    let mut ship = factory
+        .make_ship(player)
+        .ok_or(EditorError::CouldNotCreateEntity)?;
+    ship.transform.position = Vector3::new(position.x, position.y, 0.0);
+    Ok(ship)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestFactory;
+
+    impl EditorFactory for TestFactory {
+        fn make_planet_renderer(&self, _: &PlanetMaterial) -> Option<Rc<dyn EntityRenderer>> {
+            None
+        }
+
+        fn make_ship(&self, _: usize) -> Option<Entity> {
+            let mut ship = Entity::new(Vector3::zeros());
+            ship.collision_shape = Some(Box::new(Ball::new(1.0)));
+            Some(ship)
+        }
+
+        fn make_selection_renderer(&self, _: f32) -> Option<Rc<dyn EntityRenderer>> {
+            None
+        }
+    }
+
+    fn start_editor() -> GameState {
+        let map = MapFile::from_json(
+            r#"{
+                "width": 100, "height": 60,
+                "planets": [{"position": [0, 0], "radius": 10, "density": 1}],
+                "spawns": [
+                    {"player": 0, "position": [-40, 0]},
+                    {"player": 1, "position": [40, 0]}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let mut state = GameState::new(Box::new(|_| None));
+        state.start_editor(&map, Box::new(TestFactory)).unwrap();
+        state
+    }
+
+    #[test]
+    fn exports_edited_map() {
+        let mut state = start_editor();
+        state
+            .handle_edit(&EditorEvent::PlaceSpawn {
+                player: 1,
+                position: Vector2::new(40.0, 20.0),
+            })
+            .unwrap();
+        let map = state.export_map().unwrap();
+        assert_eq!(map.planets.len(), 1);
+        assert_eq!(map.spawns[1].position, Vector2::new(40.0, 20.0));
+    }
+
+    #[test]
+    fn rejects_invalid_exports() {
+        let mut state = start_editor();
+        state
+            .handle_edit(&EditorEvent::AddPlanet {
+                position: Vector2::new(48.0, 0.0),
+                radius: 5.0,
+                density: 1.0,
+            })
+            .unwrap();
+        assert!(matches!(
+            state.export_map(),
+            Err(EditorError::InvalidMap(MapLoadError::PlanetOutsideMap(1)))
+        ));
+
+        state.handle_edit(&EditorEvent::DeleteSelected).unwrap();
+        state
+            .handle_edit(&EditorEvent::PlaceSpawn {
+                player: 3,
+                position: Vector2::new(0.0, 20.0),
+            })
+            .unwrap();
+        assert!(matches!(
+            state.export_map(),
+            Err(EditorError::InvalidMap(MapLoadError::MissingSpawnPoint(2)))
+        ));
+    }
+}
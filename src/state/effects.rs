@@ -0,0 +1,107 @@
+use std::f32::consts::PI;
+
+use crate::rendering::particle::ParticleEmitter;
+use crate::rendering::Rgb;
+use crate::state::constants::TICK_INTERVAL;
+use crate::state::entity::missile::MissileEvent;
+use crate::state::GameState;
+
+// Emitted behind each missile on every tick of its flight
+const EXHAUST: ParticleEmitter = ParticleEmitter {
+    count: 2,
+    speed: (2.0, 6.0),
+    spread: 0.35,
+    lifetime: (0.2, 0.5),
+    size: (0.3, 0.6),
+    start_color: Rgb::new(1.0, 0.9, 0.6),
+    end_color: Rgb::new(0.6, 0.2, 0.1),
+    drag: 2.0,
+};
+
+// Emitted where a missile hits something
+const EXPLOSION: ParticleEmitter = ParticleEmitter {
+    count: 60,
+    speed: (4.0, 20.0),
+    spread: PI,
+    lifetime: (0.4, 1.2),
+    size: (0.5, 1.5),
+    start_color: Rgb::new(1.0, 0.95, 0.7),
+    end_color: Rgb::new(0.8, 0.2, 0.05),
+    drag: 1.5,
+};
+
+// Emitted when a missile runs out of time without hitting anything
+const FIZZLE: ParticleEmitter = ParticleEmitter {
+    count: 12,
+    speed: (1.0, 4.0),
+    spread: PI,
+    lifetime: (0.3, 0.8),
+    size: (0.3, 0.6),
+    start_color: Rgb::new(0.8, 0.8, 0.8),
+    end_color: Rgb::new(0.3, 0.3, 0.3),
+    drag: 1.0,
+};
+
+// Emitted when a ship is destroyed, in addition to the explosion
+// The colors are replaced with the ship's player color.
+const DEBRIS: ParticleEmitter = ParticleEmitter {
+    count: 40,
+    speed: (2.0, 10.0),
+    spread: PI,
+    lifetime: (1.5, 3.0),
+    size: (0.4, 0.9),
+    start_color: Rgb::new(1.0, 1.0, 1.0),
+    end_color: Rgb::new(1.0, 1.0, 1.0),
+    drag: 0.3,
+};
+// Brightness of the debris's player color at the end of its life
+const DEBRIS_END_BRIGHTNESS: f32 = 0.3;
+
+impl GameState {
+    /// Moves the particles forward by one tick
+    pub fn update_particles(&mut self) {
+        self.particles.update(TICK_INTERVAL);
+    }
+
+    /// Emits exhaust behind a missile in flight
+    pub(super) fn emit_exhaust(&mut self, missile: usize) {
+        let trail = match self.entities[missile].missile_trail {
+            Some(ref trail) if trail.time_to_live > 0.0 => trail,
+            _ => return,
+        };
+        let velocity = trail.velocity.xy();
+        if velocity.magnitude_squared() == 0.0 {
+            return;
+        }
+        let direction = (-velocity.y).atan2(-velocity.x);
+        let position = *self.entities[missile].position();
+        self.particles.emit(&EXHAUST, position, direction);
+    }
+
+    /// Emits the particles that show what happened to a missile
+    pub(super) fn emit_missile_event_effects(&mut self, missile: usize, event: &MissileEvent) {
+        let position = *self.entities[missile].position();
+        match event {
+            MissileEvent::Expired => self.particles.emit(&FIZZLE, position, 0.0),
+            MissileEvent::HitEntity(id) => {
+                self.particles.emit(&EXPLOSION, position, 0.0);
+                let target = &self.entities[*id];
+                let color = target
+                    .ship
+                    .as_ref()
+                    .and_then(|ship| self.players.get(ship.player_id))
+                    .map(|player| player.color);
+                if let Some(color) = color {
+                    let debris = ParticleEmitter {
+                        start_color: color,
+                        end_color: color * DEBRIS_END_BRIGHTNESS,
+                        ..DEBRIS
+                    };
+                    self.particles.emit(&debris, *target.position(), 0.0);
+                }
+            }
+            // Swallowed missiles and missiles passing through wormholes just disappear.
+            MissileEvent::Swallowed(_) | MissileEvent::EnteredWormhole { .. } => {}
+        }
+    }
+}
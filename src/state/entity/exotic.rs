@@ -0,0 +1,10 @@
+/// Special behavior for bodies that aren't ordinary planets
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ExoticBody {
+    /// Swallows any missile that crosses its event horizon (its collision shape)
+    BlackHole,
+    /// Has negative mass, so it pushes missiles away
+    Repulsor,
+    /// Teleports missiles that enter it to the entity with the given index, preserving their velocity
+    Wormhole { exit: usize },
+}
@@ -1,15 +1,20 @@
-use nalgebra::Vector3;
+use nalgebra::{Point2, Vector3};
+use ncollide2d::bounding_volume::AABB;
 use ncollide2d::query::Ray;
 
 use crate::state::constants;
+use crate::state::entity::exotic::ExoticBody;
 use crate::state::entity::Entity;
 
 #[derive(Clone, Debug)]
 pub struct MissileTrail {
     pub player_id: usize,
+    /// Number of shots fired before this one
+    pub shot: usize,
     pub time_to_live: f32,
     pub velocity: Vector3<f32>,
     positions: Vec<Vector3<f32>>,
+    segment_starts: Vec<usize>,
     data_version: usize,
 }
 
@@ -17,9 +22,11 @@ impl MissileTrail {
     pub fn new(player_id: usize, position: Vector3<f32>, velocity: Vector3<f32>) -> MissileTrail {
         MissileTrail {
             player_id,
+            shot: 0,
             time_to_live: constants::MISSILE_TIME_TO_LIVE,
             velocity,
             positions: vec![position],
+            segment_starts: vec![0],
             data_version: 0,
         }
     }
@@ -37,6 +44,28 @@ impl MissileTrail {
         self.positions.push(position);
     }
 
+    /// Returns the continuous sections of the trail
+    ///
+    /// The trail is broken up whenever the missile teleports.
+    pub fn segments(&self) -> impl Iterator<Item = &[Vector3<f32>]> + '_ {
+        let ends = self
+            .segment_starts
+            .iter()
+            .skip(1)
+            .cloned()
+            .chain(std::iter::once(self.positions.len()));
+        self.segment_starts
+            .iter()
+            .zip(ends)
+            .map(move |(&start, end)| &self.positions[start..end])
+    }
+
+    /// Moves the missile to a new position without connecting it to the previous one
+    pub fn teleport(&mut self, position: Vector3<f32>) {
+        self.segment_starts.push(self.positions.len());
+        self.add_position(position);
+    }
+
     pub fn time_to_collision(&self, entity: &Entity, solid: bool) -> Option<f32> {
         let pos = self.positions.last()?;
         let velocity = self.velocity.xy();
@@ -48,9 +77,27 @@ impl MissileTrail {
         }
     }
 
+    /// Returns the bounding box of the path the missile will take during the next tick
+    pub fn step_bounds(&self) -> AABB<f32> {
+        let pos = self
+            .positions
+            .last()
+            .cloned()
+            .unwrap_or_else(Vector3::zeros)
+            .xy();
+        let next = pos + self.velocity.xy() * constants::TICK_INTERVAL;
+        AABB::new(Point2::from(pos.inf(&next)), Point2::from(pos.sup(&next)))
+    }
+
+    /// Moves the missile forward by one tick
+    ///
+    /// The missile is checked for collisions against the given entities, which should include
+    /// everything that the missile might reach during this tick (see `step_bounds`). The
+    /// gravitational acceleration at the missile's current position is applied afterward.
     pub fn update<'a>(
         &mut self,
         other_entities: &mut dyn Iterator<Item = (usize, &'a Entity)>,
+        gravity: Vector3<f32>,
     ) -> Option<MissileEvent> {
         if self.time_to_live <= 0.0 {
             return None;
@@ -60,12 +107,18 @@ impl MissileTrail {
             self.time_to_live -= constants::TICK_INTERVAL;
             for (i, other) in other_entities {
                 if let Some(toi) = self.time_to_collision(other, true) {
-                    self.time_to_live = 0.0;
                     self.add_position(last_pos + self.velocity * toi);
-                    return Some(MissileEvent::HitEntity(i));
+                    if let Some(ExoticBody::Wormhole { exit }) = other.exotic {
+                        return Some(MissileEvent::EnteredWormhole { entrance: i, exit });
+                    }
+                    self.time_to_live = 0.0;
+                    return Some(match other.exotic {
+                        Some(ExoticBody::BlackHole) => MissileEvent::Swallowed(i),
+                        _ => MissileEvent::HitEntity(i),
+                    });
                 }
-                self.velocity += other.gravity_at(&last_pos);
             }
+            self.velocity += gravity;
             self.add_position(last_pos + self.velocity * constants::TICK_INTERVAL);
         }
 
@@ -77,7 +130,21 @@ impl MissileTrail {
     }
 }
 
+#[derive(Clone, Copy, Debug)]
 pub enum MissileEvent {
     Expired,
     HitEntity(usize),
+    /// The missile crossed a black hole's event horizon
+    Swallowed(usize),
+    EnteredWormhole {
+        entrance: usize,
+        exit: usize,
+    },
+}
+
+impl MissileEvent {
+    /// Returns true if the missile stops flying after this event
+    pub fn ends_flight(&self) -> bool {
+        !matches!(self, MissileEvent::EnteredWormhole { .. })
+    }
 }
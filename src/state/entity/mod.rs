@@ -2,13 +2,18 @@ use std::fmt::Debug;
 use std::rc::Rc;
 
 use nalgebra::{Isometry, Similarity, Translation, UnitComplex, UnitQuaternion, Vector3};
+use ncollide2d::bounding_volume::AABB;
 use ncollide2d::query::{Proximity, Ray, RayCast};
 use ncollide2d::shape::Shape;
 use num_complex::Complex;
 
+use crate::state::entity::exotic::ExoticBody;
 use crate::state::entity::missile::MissileTrail;
+use crate::state::entity::orbit::Orbit;
 use crate::state::{constants, GameState};
+pub mod exotic;
 pub mod missile;
+pub mod orbit;
 
 pub struct Entity {
     pub transform: EntityTransform,
@@ -17,6 +22,8 @@ pub struct Entity {
     pub renderer: Option<Rc<dyn EntityRenderer>>,
     pub missile_trail: Option<MissileTrail>,
     pub ship: Option<Ship>,
+    pub orbit: Option<Orbit>,
+    pub exotic: Option<ExoticBody>,
 }
 
 impl Entity {
@@ -28,6 +35,8 @@ impl Entity {
             renderer: None,
             missile_trail: None,
             ship: None,
+            orbit: None,
+            exotic: None,
         }
     }
 
@@ -65,6 +74,23 @@ impl Entity {
         false
     }
 
+    /// Returns the axis-aligned bounding box of the entity's collision shape
+    pub fn bounds(&self) -> Option<AABB<f32>> {
+        let shape = self.collision_shape.as_ref()?;
+        Some(shape.aabb(&self.collision_transform()))
+    }
+
+    /// Returns the radius of the smallest circle around the entity's position that contains its collision shape
+    pub fn collision_radius(&self) -> f32 {
+        self.collision_shape
+            .as_ref()
+            .map(|s| {
+                let bounds = s.local_bounding_sphere();
+                bounds.center().coords.magnitude() + bounds.radius()
+            })
+            .unwrap_or(0.0)
+    }
+
     pub fn ray_time_to_collision(&self, ray: &Ray<f32>, max_time: f32, solid: bool) -> Option<f32> {
         let shape = self.collision_shape.as_ref()?;
         let transform = self.collision_transform();
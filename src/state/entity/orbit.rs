@@ -0,0 +1,75 @@
+use std::f32::consts::PI;
+
+use nalgebra::{Rotation2, Vector2};
+
+// Number of Newton iterations used to solve Kepler's equation
+const KEPLER_ITERATIONS: usize = 8;
+
+/// A Keplerian orbit around another entity (or around a fixed point)
+#[derive(Clone, Debug)]
+pub struct Orbit {
+    /// Index of the entity being orbited
+    ///
+    /// If this is `None`, the orbit's focus is `center`.
+    pub parent: Option<usize>,
+    pub center: Vector2<f32>,
+    pub semi_major_axis: f32,
+    pub eccentricity: f32,
+    /// Angle (in radians) of the periapsis from the X axis
+    pub periapsis_angle: f32,
+    /// Time (in seconds) to complete one orbit; negative periods orbit clockwise
+    pub period: f32,
+    /// Mean anomaly (in radians) at time zero
+    pub phase: f32,
+}
+
+impl Orbit {
+    pub fn circular(parent: Option<usize>, radius: f32, period: f32, phase: f32) -> Orbit {
+        Orbit {
+            parent,
+            center: Vector2::zeros(),
+            semi_major_axis: radius,
+            eccentricity: 0.0,
+            periapsis_angle: 0.0,
+            period,
+            phase,
+        }
+    }
+
+    /// Returns the closest distance between the orbiting body and the focus
+    pub fn periapsis(&self) -> f32 {
+        self.semi_major_axis * (1.0 - self.eccentricity)
+    }
+
+    /// Returns the farthest distance between the orbiting body and the focus
+    pub fn apoapsis(&self) -> f32 {
+        self.semi_major_axis * (1.0 + self.eccentricity)
+    }
+
+    /// Returns the body's offset from the focus at the given time (in seconds)
+    pub fn offset_at(&self, time: f32) -> Vector2<f32> {
+        let mean_anomaly = if self.period != 0.0 {
+            self.phase + 2.0 * PI * time / self.period
+        } else {
+            self.phase
+        };
+        let mean_anomaly = mean_anomaly.rem_euclid(2.0 * PI);
+        let e = self.eccentricity;
+
+        // Solve Kepler's equation (M = E - e * sin(E)) for the eccentric anomaly.
+        let mut eccentric_anomaly = if e < 0.8 { mean_anomaly } else { PI };
+        for _ in 0..KEPLER_ITERATIONS {
+            let f = eccentric_anomaly - e * eccentric_anomaly.sin() - mean_anomaly;
+            let f_prime = 1.0 - e * eccentric_anomaly.cos();
+            eccentric_anomaly -= f / f_prime;
+        }
+
+        let a = self.semi_major_axis;
+        let b = a * (1.0 - e * e).sqrt();
+        let offset = Vector2::new(
+            a * (eccentric_anomaly.cos() - e),
+            b * eccentric_anomaly.sin(),
+        );
+        Rotation2::new(self.periapsis_angle) * offset
+    }
+}
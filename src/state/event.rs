@@ -3,32 +3,33 @@ use std::fmt::Display;
 
 use nalgebra::Vector2;
 
+use crate::state::editor::{EditorError, EditorEvent};
+
 #[derive(Clone, Copy, Debug)]
 pub enum InputEventError {
     NoShipToFireMissile,
     InvalidMissileAngle,
     InvalidMissileSpeed,
     CannotFireNow,
+    InvalidEdit(EditorError),
 }
 
 impl Display for InputEventError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(
-            f,
-            "Invalid game input event: {}",
-            match self {
-                InputEventError::NoShipToFireMissile => "No ship that can fire a missile",
-                InputEventError::InvalidMissileAngle => "Invalid angle for missile",
-                InputEventError::InvalidMissileSpeed => "Invalid speed for missile",
-                InputEventError::CannotFireNow => "Cannot fire a missile at this time",
-            }
-        )
+        write!(f, "Invalid game input event: ")?;
+        match self {
+            InputEventError::NoShipToFireMissile => write!(f, "No ship that can fire a missile"),
+            InputEventError::InvalidMissileAngle => write!(f, "Invalid angle for missile"),
+            InputEventError::InvalidMissileSpeed => write!(f, "Invalid speed for missile"),
+            InputEventError::CannotFireNow => write!(f, "Cannot fire a missile at this time"),
+            InputEventError::InvalidEdit(error) => write!(f, "{}", error),
+        }
     }
 }
 
 impl Error for InputEventError {}
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct MissileParams {
     pub angle: f32,
     pub speed: f32,
@@ -39,4 +40,7 @@ pub enum InputEvent {
     PanCamera(Vector2<f32>),
     ZoomCamera(f32),
     FireMissile(MissileParams),
+    /// Updates the tentative aim used for the trajectory preview without firing
+    Aim(MissileParams),
+    Edit(EditorEvent),
 }
@@ -0,0 +1,56 @@
+use std::f32::consts::PI;
+
+use crate::state::constants::MISSILE_MAX_VELOCITY;
+use crate::state::entity::missile::MissileEvent;
+use crate::state::event::MissileParams;
+use crate::state::GameState;
+
+// Number of firing angles to try from each ship
+const FAIRNESS_ANGLES: usize = 32;
+// Firing speeds to try, as fractions of the maximum speed
+const FAIRNESS_SPEEDS: &[f32] = &[0.4, 0.65, 0.9];
+// Longest flight to simulate for each shot, in ticks (a third of a missile's lifetime)
+const FAIRNESS_FLIGHT_TICKS: usize = 300;
+
+/// Checks that every ship has at least one shot that hits each opponent's ship
+///
+/// Shots are simulated over a fixed set of angles and speeds. This runs while a map is being
+/// generated, so to keep it quick, bodies are frozen in place and flights are cut short: this
+/// checks that opponents can reach each other directly, not every trick shot. Returns the first
+/// (shooter, target) pair of player IDs for which none of the shots hit, or `None` if the map is
+/// fair.
+pub fn find_unreachable_opponent(state: &mut GameState) -> Option<(usize, usize)> {
+    let ships: Vec<(usize, usize)> = state
+        .entities
+        .iter()
+        .enumerate()
+        .filter_map(|(i, e)| e.ship.as_ref().map(|s| (i, s.player_id)))
+        .collect();
+
+    for &(shooter, player) in ships.iter() {
+        let mut unreached: Vec<(usize, usize)> = ships
+            .iter()
+            .filter(|(_, owner)| *owner != player)
+            .cloned()
+            .collect();
+        'shots: for &speed in FAIRNESS_SPEEDS {
+            for i in 0..FAIRNESS_ANGLES {
+                if unreached.is_empty() {
+                    break 'shots;
+                }
+                let params = MissileParams {
+                    angle: 2.0 * PI * i as f32 / FAIRNESS_ANGLES as f32,
+                    speed: speed * MISSILE_MAX_VELOCITY,
+                };
+                let event = state.simulate_shot_frozen(shooter, params, FAIRNESS_FLIGHT_TICKS);
+                if let Some(MissileEvent::HitEntity(target)) = event {
+                    unreached.retain(|&(ship, _)| ship != target);
+                }
+            }
+        }
+        if let Some(&(_, opponent)) = unreached.first() {
+            return Some((player, opponent));
+        }
+    }
+    None
+}
@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+
+use nalgebra::{Vector2, Vector3};
+
+use crate::state::gravity::point_potential;
+use crate::state::GameState;
+
+// Maximum number of samples in a gravity field (which limits its resolution)
+const MAX_FIELD_SAMPLES: usize = 1 << 16;
+
+/// The combined gravitational potential and acceleration sampled on a regular grid
+#[derive(Clone, Debug, PartialEq)]
+pub struct GravityField {
+    origin: Vector2<f32>,
+    spacing: f32,
+    columns: usize,
+    rows: usize,
+    potential: Vec<f32>,
+    acceleration: Vec<Vector2<f32>>,
+}
+
+impl GravityField {
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Returns the distance between adjacent samples
+    pub fn spacing(&self) -> f32 {
+        self.spacing
+    }
+
+    /// Returns the position of a sample
+    pub fn position(&self, column: usize, row: usize) -> Vector2<f32> {
+        self.origin + Vector2::new(column as f32, row as f32) * self.spacing
+    }
+
+    /// Returns the potential energy per unit mass at a sample
+    pub fn potential(&self, column: usize, row: usize) -> f32 {
+        self.potential[row * self.columns + column]
+    }
+
+    pub fn acceleration(&self, column: usize, row: usize) -> Vector2<f32> {
+        self.acceleration[row * self.columns + column]
+    }
+
+    /// Returns the lowest and highest sampled potential, or `None` if the field is empty
+    pub fn potential_range(&self) -> Option<(f32, f32)> {
+        let first = *self.potential.first()?;
+        Some(
+            self.potential
+                .iter()
+                .fold((first, first), |(min, max), &p| (min.min(p), max.max(p))),
+        )
+    }
+
+    /// Returns the magnitude of the strongest sampled acceleration
+    pub fn max_acceleration(&self) -> f32 {
+        self.acceleration
+            .iter()
+            .map(|a| a.magnitude())
+            .fold(0.0, f32::max)
+    }
+
+    /// Traces the curves where the potential equals `level` using marching squares
+    ///
+    /// Each curve is returned as a list of points. Closed curves end with their first point.
+    pub fn contour(&self, level: f32) -> Vec<Vec<Vector2<f32>>> {
+        if self.columns < 2 || self.rows < 2 {
+            return Vec::new();
+        }
+
+        // Each segment joins two grid edges that the contour crosses.
+        let mut segments: Vec<(usize, usize)> = Vec::new();
+        for row in 0..self.rows - 1 {
+            for column in 0..self.columns - 1 {
+                self.add_cell_segments(column, row, level, &mut segments);
+            }
+        }
+
+        let mut segments_at_edge: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (i, &(a, b)) in segments.iter().enumerate() {
+            segments_at_edge.entry(a).or_default().push(i);
+            segments_at_edge.entry(b).or_default().push(i);
+        }
+
+        // Join segments that share an edge into curves, extending each curve in both directions.
+        let mut used = vec![false; segments.len()];
+        let mut curves = Vec::new();
+        for start in 0..segments.len() {
+            if used[start] {
+                continue;
+            }
+            used[start] = true;
+            let (first, last) = segments[start];
+            let mut forward = vec![first, last];
+            self.extend_curve(&mut forward, &segments, &segments_at_edge, &mut used);
+            let mut backward = vec![first];
+            self.extend_curve(&mut backward, &segments, &segments_at_edge, &mut used);
+            backward.reverse();
+            backward.extend_from_slice(&forward[1..]);
+            curves.push(
+                backward
+                    .into_iter()
+                    .map(|edge| self.edge_crossing(edge, level))
+                    .collect(),
+            );
+        }
+        curves
+    }
+
+    fn extend_curve(
+        &self,
+        curve: &mut Vec<usize>,
+        segments: &[(usize, usize)],
+        segments_at_edge: &HashMap<usize, Vec<usize>>,
+        used: &mut [bool],
+    ) {
+        while let Some(&end) = curve.last() {
+            let next = segments_at_edge
+                .get(&end)
+                .and_then(|s| s.iter().find(|&&i| !used[i]).cloned());
+            let next = match next {
+                Some(next) => next,
+                None => return,
+            };
+            used[next] = true;
+            let (a, b) = segments[next];
+            curve.push(if a == end { b } else { a });
+        }
+    }
+
+    /// Returns the ID of the horizontal grid edge from a sample to the next column
+    fn horizontal_edge(&self, column: usize, row: usize) -> usize {
+        (row * self.columns + column) * 2
+    }
+
+    /// Returns the ID of the vertical grid edge from a sample to the next row
+    fn vertical_edge(&self, column: usize, row: usize) -> usize {
+        (row * self.columns + column) * 2 + 1
+    }
+
+    fn add_cell_segments(
+        &self,
+        column: usize,
+        row: usize,
+        level: f32,
+        segments: &mut Vec<(usize, usize)>,
+    ) {
+        let corners = [
+            self.potential(column, row),
+            self.potential(column + 1, row),
+            self.potential(column + 1, row + 1),
+            self.potential(column, row + 1),
+        ];
+        let case = corners
+            .iter()
+            .enumerate()
+            .filter(|(_, &p)| p >= level)
+            .fold(0, |case, (i, _)| case | (1 << i));
+        let bottom = self.horizontal_edge(column, row);
+        let right = self.vertical_edge(column + 1, row);
+        let top = self.horizontal_edge(column, row + 1);
+        let left = self.vertical_edge(column, row);
+        // Saddle cells are resolved using the average of the corners.
+        let center_above = corners.iter().sum::<f32>() * 0.25 >= level;
+
+        match case {
+            1 | 14 => segments.push((left, bottom)),
+            2 | 13 => segments.push((bottom, right)),
+            3 | 12 => segments.push((left, right)),
+            4 | 11 => segments.push((right, top)),
+            6 | 9 => segments.push((bottom, top)),
+            7 | 8 => segments.push((left, top)),
+            5 | 10 => {
+                // Keep the corners on the same side of the level as the center connected.
+                if center_above == (case == 5) {
+                    segments.push((bottom, right));
+                    segments.push((top, left));
+                } else {
+                    segments.push((left, bottom));
+                    segments.push((right, top));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns where the contour at `level` crosses a grid edge
+    fn edge_crossing(&self, edge: usize, level: f32) -> Vector2<f32> {
+        let sample = edge / 2;
+        let (column, row) = (sample % self.columns, sample / self.columns);
+        let (end_column, end_row) = if edge & 1 == 0 {
+            (column + 1, row)
+        } else {
+            (column, row + 1)
+        };
+        let (a, b) = (
+            self.potential(column, row),
+            self.potential(end_column, end_row),
+        );
+        let t = if a == b { 0.5 } else { (level - a) / (b - a) };
+        let start = self.position(column, row);
+        start + (self.position(end_column, end_row) - start) * t.clamp(0.0, 1.0)
+    }
+}
+
+impl GameState {
+    /// Returns the combined gravitational potential energy per unit mass at `pos`
+    ///
+    /// Gravity pulls toward lower potential. Unlike `gravity_at`, this is always calculated
+    /// exactly.
+    pub fn potential_at(&self, pos: &Vector3<f32>) -> f32 {
+        let pos = pos.xy();
+        self.entities
+            .iter()
+            .filter(|e| e.mass != 0.0)
+            .map(|e| point_potential(&e.position().xy(), e.mass, &pos))
+            .sum()
+    }
+
+    /// Samples gravity on a grid covering the given rectangle
+    ///
+    /// The spacing is increased if the grid would otherwise be too large.
+    pub fn sample_gravity(
+        &self,
+        mins: Vector2<f32>,
+        maxs: Vector2<f32>,
+        spacing: f32,
+    ) -> GravityField {
+        let size = (maxs - mins).sup(&Vector2::zeros());
+        let min_spacing = (size.x * size.y / MAX_FIELD_SAMPLES as f32).sqrt();
+        let spacing = spacing.max(min_spacing).max(f32::EPSILON);
+        let columns = (size.x / spacing).floor() as usize + 1;
+        let rows = (size.y / spacing).floor() as usize + 1;
+        // Center the grid in the rectangle.
+        let used = Vector2::new((columns - 1) as f32, (rows - 1) as f32) * spacing;
+        let origin = mins + (size - used) * 0.5;
+
+        let mut potential = Vec::with_capacity(columns * rows);
+        let mut acceleration = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            for column in 0..columns {
+                let pos = origin + Vector2::new(column as f32, row as f32) * spacing;
+                let pos = Vector3::new(pos.x, pos.y, 0.0);
+                potential.push(self.potential_at(&pos));
+                acceleration.push(self.gravity_at(&pos).xy());
+            }
+        }
+
+        GravityField {
+            origin,
+            spacing,
+            columns,
+            rows,
+            potential,
+            acceleration,
+        }
+    }
+}
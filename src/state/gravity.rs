@@ -0,0 +1,176 @@
+use nalgebra::{Vector2, Vector3};
+
+use crate::state::constants::GRAVITATIONAL_CONSTANT;
+
+/// Number of bodies above which gravity is approximated with a Barnes-Hut tree
+pub const BARNES_HUT_THRESHOLD: usize = 64;
+/// Maximum ratio of a tree node's size to its distance for the node to be treated as one body
+pub const BARNES_HUT_THETA: f32 = 0.5;
+// Maximum depth of the tree (which limits subdivision when bodies share a position)
+const MAX_TREE_DEPTH: usize = 16;
+
+/// Returns the gravitational acceleration produced by a point mass on a mass at `pos`
+///
+/// This matches `Entity::gravity_at`.
+pub fn point_gravity(mass_pos: &Vector2<f32>, mass: f32, pos: &Vector2<f32>) -> Vector2<f32> {
+    let difference = mass_pos - pos;
+    let magnitude_sq = difference.magnitude_squared();
+    if magnitude_sq == 0.0 {
+        return Vector2::zeros();
+    }
+    let strength = magnitude_sq * mass * GRAVITATIONAL_CONSTANT;
+    difference.normalize() * strength
+}
+
+/// Returns the gravitational potential energy per unit mass at `pos` due to a point mass
+///
+/// The acceleration from `point_gravity` is the negative gradient of this potential.
+pub fn point_potential(mass_pos: &Vector2<f32>, mass: f32, pos: &Vector2<f32>) -> f32 {
+    let distance = (mass_pos - pos).magnitude();
+    distance.powi(3) * mass * GRAVITATIONAL_CONSTANT / 3.0
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Body {
+    position: Vector2<f32>,
+    mass: f32,
+}
+
+#[derive(Clone, Debug)]
+enum NodeContents {
+    Leaf(Vec<Body>),
+    Branch(Box<[Node; 4]>),
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    half_size: f32,
+    mass: f32,
+    center_of_mass: Vector2<f32>,
+    contents: NodeContents,
+}
+
+impl Node {
+    fn build(center: Vector2<f32>, half_size: f32, bodies: Vec<Body>, depth: usize) -> Node {
+        let mass: f32 = bodies.iter().map(|b| b.mass).sum();
+        let center_of_mass = if mass > 0.0 {
+            bodies
+                .iter()
+                .map(|b| b.position * b.mass)
+                .sum::<Vector2<f32>>()
+                / mass
+        } else {
+            center
+        };
+
+        let contents = if bodies.len() <= 1 || depth >= MAX_TREE_DEPTH {
+            NodeContents::Leaf(bodies)
+        } else {
+            let mut quadrants: [Vec<Body>; 4] = Default::default();
+            for body in bodies {
+                let offset = body.position - center;
+                let quadrant = (offset.x >= 0.0) as usize + 2 * (offset.y >= 0.0) as usize;
+                quadrants[quadrant].push(body);
+            }
+            let quarter = half_size * 0.5;
+            let child_center = |quadrant: usize| {
+                let x = if quadrant & 1 != 0 { quarter } else { -quarter };
+                let y = if quadrant & 2 != 0 { quarter } else { -quarter };
+                center + Vector2::new(x, y)
+            };
+            let [q0, q1, q2, q3] = quadrants;
+            NodeContents::Branch(Box::new([
+                Node::build(child_center(0), quarter, q0, depth + 1),
+                Node::build(child_center(1), quarter, q1, depth + 1),
+                Node::build(child_center(2), quarter, q2, depth + 1),
+                Node::build(child_center(3), quarter, q3, depth + 1),
+            ]))
+        };
+
+        Node {
+            half_size,
+            mass,
+            center_of_mass,
+            contents,
+        }
+    }
+
+    fn acceleration_at(&self, pos: &Vector2<f32>, theta: f32) -> Vector2<f32> {
+        if self.mass <= 0.0 {
+            return Vector2::zeros();
+        }
+        match self.contents {
+            NodeContents::Leaf(ref bodies) => bodies
+                .iter()
+                .map(|b| point_gravity(&b.position, b.mass, pos))
+                .sum(),
+            NodeContents::Branch(ref children) => {
+                let distance = (self.center_of_mass - pos).magnitude();
+                if distance > 0.0 && (self.half_size * 2.0) / distance < theta {
+                    point_gravity(&self.center_of_mass, self.mass, pos)
+                } else {
+                    children.iter().map(|c| c.acceleration_at(pos, theta)).sum()
+                }
+            }
+        }
+    }
+}
+
+/// Barnes-Hut quadtree for approximating the combined gravity of many bodies
+///
+/// Clusters of distant bodies are treated as a single body at their center of mass. Bodies with
+/// negative mass would make the centers of mass meaningless, so they're evaluated exactly.
+#[derive(Clone, Debug)]
+pub struct GravityTree {
+    root: Option<Node>,
+    negative_bodies: Vec<Body>,
+}
+
+impl GravityTree {
+    pub fn build(bodies: impl Iterator<Item = (Vector3<f32>, f32)>) -> GravityTree {
+        let mut positive = Vec::new();
+        let mut negative_bodies = Vec::new();
+        for (position, mass) in bodies {
+            let body = Body {
+                position: position.xy(),
+                mass,
+            };
+            if mass > 0.0 {
+                positive.push(body);
+            } else if mass < 0.0 {
+                negative_bodies.push(body);
+            }
+        }
+
+        let root = if positive.is_empty() {
+            None
+        } else {
+            let (mut min, mut max) = (positive[0].position, positive[0].position);
+            for body in positive.iter() {
+                min = min.inf(&body.position);
+                max = max.sup(&body.position);
+            }
+            let center = (min + max) * 0.5;
+            let half_size = ((max - min).max() * 0.5).max(f32::EPSILON);
+            Some(Node::build(center, half_size, positive, 0))
+        };
+
+        GravityTree {
+            root,
+            negative_bodies,
+        }
+    }
+
+    pub fn acceleration_at(&self, pos: &Vector3<f32>) -> Vector3<f32> {
+        let pos = pos.xy();
+        let mut acceleration = self
+            .root
+            .as_ref()
+            .map(|r| r.acceleration_at(&pos, BARNES_HUT_THETA))
+            .unwrap_or_else(Vector2::zeros);
+        for body in self.negative_bodies.iter() {
+            acceleration += point_gravity(&body.position, body.mass, &pos);
+        }
+        Vector3::new(acceleration.x, acceleration.y, 0.0)
+    }
+}
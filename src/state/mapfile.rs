@@ -0,0 +1,719 @@
+use std::error::Error;
+use std::fmt::Display;
+use std::rc::Rc;
+
+use nalgebra::{Unit, Vector2, Vector3};
+use ncollide2d::shape::Ball;
+use serde_json::{json, Map, Value};
+
+use crate::rendering::context::RenderingContext;
+use crate::rendering::light::SunLight;
+use crate::rendering::material::Material;
+use crate::rendering::scene::Scene;
+use crate::rendering::{Rgb, Rgba};
+use crate::state::constants::MAX_PLAYERS;
+use crate::state::mapgen;
+use crate::state::mapgen::SphereRendererFactory;
+use crate::state::{Entity, EntityRenderer, GameState, PlayerRendererFactory, WorldLight};
+use crate::state_renderer::GameRenderer;
+
+// Largest number of planets that a map file can have
+const MAX_PLANETS: usize = 256;
+
+#[derive(Debug)]
+pub enum MapFileError {
+    InvalidJson(serde_json::Error),
+    MissingField(String),
+    InvalidField(String),
+}
+
+impl Display for MapFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MapFileError::InvalidJson(e) => write!(f, "Map file is not valid JSON: {}", e),
+            MapFileError::MissingField(name) => write!(f, "Map file is missing field {}", name),
+            MapFileError::InvalidField(name) => write!(f, "Map file has invalid field {}", name),
+        }
+    }
+}
+
+impl Error for MapFileError {}
+
+#[derive(Clone, Copy, Debug)]
+pub enum MapLoadError {
+    NoSpawnPoints,
+    /// A player (given by ID) has no spawn point
+    MissingSpawnPoint(usize),
+    /// A player (given by ID) has more than one spawn point
+    DuplicateSpawnPoint(usize),
+    /// A player's spawn point is outside the map or overlaps a planet
+    SpawnPointBlocked(usize),
+    /// A planet (given by index) extends outside the map
+    PlanetOutsideMap(usize),
+    /// Two planets (given by index) overlap
+    PlanetsOverlap(usize, usize),
+    CouldNotCreatePlanetRenderer,
+    CouldNotCreateShipRenderers,
+}
+
+impl Display for MapLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MapLoadError::NoSpawnPoints => write!(f, "Map has no spawn points"),
+            MapLoadError::MissingSpawnPoint(id) => {
+                write!(f, "Map has no spawn point for player {}", id)
+            }
+            MapLoadError::DuplicateSpawnPoint(id) => {
+                write!(f, "Map has more than one spawn point for player {}", id)
+            }
+            MapLoadError::SpawnPointBlocked(id) => {
+                write!(f, "Spawn point for player {} is blocked", id)
+            }
+            MapLoadError::PlanetOutsideMap(index) => {
+                write!(f, "Planet {} extends outside the map", index)
+            }
+            MapLoadError::PlanetsOverlap(a, b) => write!(f, "Planets {} and {} overlap", a, b),
+            MapLoadError::CouldNotCreatePlanetRenderer => {
+                write!(f, "Unable to create planet renderer")
+            }
+            MapLoadError::CouldNotCreateShipRenderers => {
+                write!(f, "Unable to create ship renderers")
+            }
+        }
+    }
+}
+
+impl Error for MapLoadError {}
+
+/// Surface properties of a planet
+#[derive(Clone, Copy, Debug)]
+pub struct PlanetMaterial {
+    pub color: Rgba,
+    pub metal_factor: f32,
+    pub roughness: f32,
+}
+
+impl PlanetMaterial {
+    pub fn to_material<Context: RenderingContext>(&self) -> Material<Context> {
+        Material::from_color(self.color, self.metal_factor, self.roughness)
+    }
+}
+
+impl Default for PlanetMaterial {
+    fn default() -> Self {
+        PlanetMaterial {
+            color: Rgba::new(0.0, 0.0, 1.0, 1.0),
+            metal_factor: 0.0,
+            roughness: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PlanetSpec {
+    pub position: Vector2<f32>,
+    pub radius: f32,
+    pub density: f32,
+    pub material: PlanetMaterial,
+}
+
+impl PlanetSpec {
+    pub fn mass(&self) -> f32 {
+        mapgen::sphere_volume(self.radius) * self.density
+    }
+
+    /// Creates the planet's entity with the given renderer
+    pub fn make_entity(&self, renderer: Option<Rc<dyn EntityRenderer>>) -> Entity {
+        let mut entity = Entity::new(Vector3::new(self.position.x, self.position.y, 0.0));
+        entity.collision_shape = Some(Box::new(Ball::new(self.radius)));
+        entity.mass = self.mass();
+        entity.transform.scale = self.radius;
+        entity.renderer = renderer;
+        entity
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SpawnPoint {
+    pub player: usize,
+    pub position: Vector2<f32>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MapLighting {
+    pub sun_color: Rgb,
+    pub sun_direction: Unit<Vector3<f32>>,
+    pub ambient: Rgb,
+}
+
+/// A hand-made map layout
+///
+/// Maps are stored as JSON objects with these fields:
+///
+/// - `name`: the map's display name
+/// - `width`, `height`: the size of the playable area, which is centered on the origin
+/// - `planets`: an array of objects with a `position` (`[x, y]`), `radius`, `density` and an
+///   optional `material` (`{"color": [r, g, b, a], "metal_factor": m, "roughness": r}`); planets
+///   must be inside the map and can't overlap
+/// - `spawns`: an array of objects with a `player` ID (less than `MAX_PLAYERS`) and a `position`;
+///   each player needs exactly one spawn point
+/// - `lighting` (optional): an object with `sun_color` (`[r, g, b]`), `sun_direction`
+///   (`[x, y, z]`) and `ambient` (`[r, g, b]`)
+#[derive(Clone, Debug)]
+pub struct MapFile {
+    pub name: String,
+    pub width: f32,
+    pub height: f32,
+    pub planets: Vec<PlanetSpec>,
+    pub spawns: Vec<SpawnPoint>,
+    pub lighting: Option<MapLighting>,
+}
+
+impl MapFile {
+    pub fn from_json(text: &str) -> Result<MapFile, MapFileError> {
+        let root: Value = serde_json::from_str(text).map_err(MapFileError::InvalidJson)?;
+        let root = root
+            .as_object()
+            .ok_or_else(|| MapFileError::InvalidField(String::from("(root)")))?;
+
+        let name = match root.get("name") {
+            Some(name) => name
+                .as_str()
+                .ok_or_else(|| MapFileError::InvalidField(String::from("name")))?
+                .to_owned(),
+            None => String::new(),
+        };
+        let width = parse_positive(root, "width", "")?;
+        let height = parse_positive(root, "height", "")?;
+
+        let planets = parse_array(root, "planets")?;
+        if planets.len() > MAX_PLANETS {
+            return Err(MapFileError::InvalidField(String::from("planets")));
+        }
+        let planets = planets
+            .iter()
+            .enumerate()
+            .map(|(i, planet)| parse_planet(planet, &format!("planets[{}]", i)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let spawns = parse_array(root, "spawns")?
+            .iter()
+            .enumerate()
+            .map(|(i, spawn)| parse_spawn(spawn, &format!("spawns[{}]", i)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let lighting = match root.get("lighting") {
+            Some(lighting) => Some(parse_lighting(lighting)?),
+            None => None,
+        };
+
+        Ok(MapFile {
+            name,
+            width,
+            height,
+            planets,
+            spawns,
+            lighting,
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        let planets: Vec<Value> = self
+            .planets
+            .iter()
+            .map(|p| {
+                let color = p.material.color;
+                json!({
+                    "position": [p.position.x, p.position.y],
+                    "radius": p.radius,
+                    "density": p.density,
+                    "material": {
+                        "color": [color.r, color.g, color.b, color.a],
+                        "metal_factor": p.material.metal_factor,
+                        "roughness": p.material.roughness,
+                    },
+                })
+            })
+            .collect();
+        let spawns: Vec<Value> = self
+            .spawns
+            .iter()
+            .map(|s| json!({ "player": s.player, "position": [s.position.x, s.position.y] }))
+            .collect();
+        let mut root = json!({
+            "name": self.name,
+            "width": self.width,
+            "height": self.height,
+            "planets": planets,
+            "spawns": spawns,
+        });
+        if let Some(ref lighting) = self.lighting {
+            let (sun, dir, ambient) =
+                (lighting.sun_color, lighting.sun_direction, lighting.ambient);
+            root["lighting"] = json!({
+                "sun_color": [sun.r, sun.g, sun.b],
+                "sun_direction": [dir.x, dir.y, dir.z],
+                "ambient": [ambient.r, ambient.g, ambient.b],
+            });
+        }
+        serde_json::to_string_pretty(&root).unwrap_or_default()
+    }
+
+    /// Returns the number of players that the map supports
+    pub fn num_players(&self) -> usize {
+        self.spawns.iter().map(|s| s.player + 1).max().unwrap_or(0)
+    }
+
+    /// Checks the parts of the layout that don't depend on the size of the ships
+    ///
+    /// Every player needs exactly one spawn point inside the map, and every planet has to be
+    /// inside the map without overlapping any others. Spawn points that overlap planets are only
+    /// found when the map is loaded.
+    pub fn validate(&self) -> Result<(), MapLoadError> {
+        self.check_spawns()?;
+        self.check_planets()
+    }
+
+    fn check_spawns(&self) -> Result<(), MapLoadError> {
+        let num_players = self.num_players();
+        if num_players == 0 {
+            return Err(MapLoadError::NoSpawnPoints);
+        }
+        let mut seen = vec![false; num_players];
+        for spawn in self.spawns.iter() {
+            if seen[spawn.player] {
+                return Err(MapLoadError::DuplicateSpawnPoint(spawn.player));
+            }
+            seen[spawn.player] = true;
+            let pos = spawn.position;
+            if pos.x.abs() > self.width * 0.5 || pos.y.abs() > self.height * 0.5 {
+                return Err(MapLoadError::SpawnPointBlocked(spawn.player));
+            }
+        }
+        match seen.iter().position(|s| !s) {
+            Some(player) => Err(MapLoadError::MissingSpawnPoint(player)),
+            None => Ok(()),
+        }
+    }
+
+    fn check_planets(&self) -> Result<(), MapLoadError> {
+        for (i, planet) in self.planets.iter().enumerate() {
+            let pos = planet.position;
+            if pos.x.abs() + planet.radius > self.width * 0.5
+                || pos.y.abs() + planet.radius > self.height * 0.5
+            {
+                return Err(MapLoadError::PlanetOutsideMap(i));
+            }
+            for (j, other) in self.planets[..i].iter().enumerate() {
+                if (pos - other.position).magnitude() < planet.radius + other.radius {
+                    return Err(MapLoadError::PlanetsOverlap(j, i));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Builds a game from a map file
+///
+/// This is the counterpart to `MapgenParams` for hand-made maps.
+pub struct MapLoader<'a, Context>
+where
+    Context: RenderingContext + 'static,
+{
+    pub game_state: &'a mut GameState,
+    pub game_renderer: Rc<dyn GameRenderer<Context = Context>>,
+    pub ship_scene: &'a Scene<Context>,
+    pub make_ship_renderer: PlayerRendererFactory,
+}
+
+impl<'a, Context> MapLoader<'a, Context>
+where
+    Context: RenderingContext + 'static,
+{
+    pub fn load_map(&mut self, map: &MapFile) -> Result<(), MapLoadError> {
+        map.validate()?;
+        self.game_state.clear_entities();
+        self.game_state
+            .set_players(mapgen::make_players(map.num_players()));
+        if let Some(ref lighting) = map.lighting {
+            self.game_state.light = WorldLight {
+                sun: SunLight {
+                    color: lighting.sun_color,
+                    direction: lighting.sun_direction,
+                },
+                ambient: lighting.ambient,
+            };
+        }
+
+        let spheres = SphereRendererFactory::new(&self.game_renderer)
+            .map_err(|_| MapLoadError::CouldNotCreatePlanetRenderer)?;
+        for planet in map.planets.iter() {
+            let renderer = spheres.make_renderer_with(planet.material.to_material());
+            self.game_state
+                .add_entity(planet.make_entity(Some(renderer)));
+        }
+
+        for spawn in map.spawns.iter() {
+            let player = &self.game_state.players()[spawn.player];
+            let renderer = (self.make_ship_renderer)(player)
+                .map_err(|_| MapLoadError::CouldNotCreateShipRenderers)?;
+            let mut ship = mapgen::make_ship(self.ship_scene, spawn.player, spawn.position);
+            let blocked = {
+                let shape = ship.collision_shape.as_ref().unwrap();
+                let transform = ship.collision_transform();
+                self.game_state
+                    .entities_near(&shape.aabb(&transform))
+                    .any(|(_, e)| e.collides_with_shape(shape.as_ref(), &transform))
+            };
+            if blocked {
+                return Err(MapLoadError::SpawnPointBlocked(spawn.player));
+            }
+            ship.renderer = Some(renderer);
+            self.game_state.add_entity(ship);
+        }
+        Ok(())
+    }
+}
+
+fn field_name(context: &str, name: &str) -> String {
+    if context.is_empty() {
+        String::from(name)
+    } else {
+        format!("{}.{}", context, name)
+    }
+}
+
+fn parse_array<'a>(
+    object: &'a Map<String, Value>,
+    name: &str,
+) -> Result<&'a Vec<Value>, MapFileError> {
+    object
+        .get(name)
+        .ok_or_else(|| MapFileError::MissingField(String::from(name)))?
+        .as_array()
+        .ok_or_else(|| MapFileError::InvalidField(String::from(name)))
+}
+
+fn parse_number(
+    object: &Map<String, Value>,
+    name: &str,
+    context: &str,
+) -> Result<f32, MapFileError> {
+    let value = object
+        .get(name)
+        .ok_or_else(|| MapFileError::MissingField(field_name(context, name)))?;
+    value
+        .as_f64()
+        .map(|v| v as f32)
+        .filter(|v| v.is_finite())
+        .ok_or_else(|| MapFileError::InvalidField(field_name(context, name)))
+}
+
+fn parse_positive(
+    object: &Map<String, Value>,
+    name: &str,
+    context: &str,
+) -> Result<f32, MapFileError> {
+    let value = parse_number(object, name, context)?;
+    if value > 0.0 {
+        Ok(value)
+    } else {
+        Err(MapFileError::InvalidField(field_name(context, name)))
+    }
+}
+
+fn parse_non_negative(
+    object: &Map<String, Value>,
+    name: &str,
+    context: &str,
+) -> Result<f32, MapFileError> {
+    let value = parse_number(object, name, context)?;
+    if value >= 0.0 {
+        Ok(value)
+    } else {
+        Err(MapFileError::InvalidField(field_name(context, name)))
+    }
+}
+
+/// Parses an array of exactly `N` finite numbers
+fn parse_numbers<const N: usize>(value: &Value, name: &str) -> Result<[f32; N], MapFileError> {
+    let invalid = || MapFileError::InvalidField(String::from(name));
+    let array = value.as_array().ok_or_else(invalid)?;
+    if array.len() != N {
+        return Err(invalid());
+    }
+    let mut numbers = [0.0; N];
+    for (number, value) in numbers.iter_mut().zip(array.iter()) {
+        *number = value
+            .as_f64()
+            .map(|v| v as f32)
+            .filter(|v| v.is_finite())
+            .ok_or_else(invalid)?;
+    }
+    Ok(numbers)
+}
+
+fn parse_vector(
+    object: &Map<String, Value>,
+    name: &str,
+    context: &str,
+) -> Result<Vector2<f32>, MapFileError> {
+    let full_name = field_name(context, name);
+    let value = object
+        .get(name)
+        .ok_or_else(|| MapFileError::MissingField(full_name.clone()))?;
+    let [x, y] = parse_numbers::<2>(value, &full_name)?;
+    Ok(Vector2::new(x, y))
+}
+
+fn parse_planet(value: &Value, context: &str) -> Result<PlanetSpec, MapFileError> {
+    let object = value
+        .as_object()
+        .ok_or_else(|| MapFileError::InvalidField(String::from(context)))?;
+    let position = parse_vector(object, "position", context)?;
+    let radius = parse_positive(object, "radius", context)?;
+    let density = parse_non_negative(object, "density", context)?;
+    let material = match object.get("material") {
+        Some(material) => parse_material(material, &field_name(context, "material"))?,
+        None => PlanetMaterial::default(),
+    };
+    Ok(PlanetSpec {
+        position,
+        radius,
+        density,
+        material,
+    })
+}
+
+fn parse_material(value: &Value, context: &str) -> Result<PlanetMaterial, MapFileError> {
+    let object = value
+        .as_object()
+        .ok_or_else(|| MapFileError::InvalidField(String::from(context)))?;
+    let defaults = PlanetMaterial::default();
+    let color = match object.get("color") {
+        Some(color) => {
+            let [r, g, b, a] = parse_numbers::<4>(color, &field_name(context, "color"))?;
+            Rgba::new(r, g, b, a)
+        }
+        None => defaults.color,
+    };
+    let metal_factor = match object.get("metal_factor") {
+        Some(_) => parse_number(object, "metal_factor", context)?,
+        None => defaults.metal_factor,
+    };
+    let roughness = match object.get("roughness") {
+        Some(_) => parse_number(object, "roughness", context)?,
+        None => defaults.roughness,
+    };
+    Ok(PlanetMaterial {
+        color,
+        metal_factor,
+        roughness,
+    })
+}
+
+fn parse_spawn(value: &Value, context: &str) -> Result<SpawnPoint, MapFileError> {
+    let object = value
+        .as_object()
+        .ok_or_else(|| MapFileError::InvalidField(String::from(context)))?;
+    let player_name = field_name(context, "player");
+    let player = object
+        .get("player")
+        .ok_or_else(|| MapFileError::MissingField(player_name.clone()))?
+        .as_u64()
+        .map(|player| player as usize)
+        .filter(|&player| player < MAX_PLAYERS)
+        .ok_or(MapFileError::InvalidField(player_name))?;
+    let position = parse_vector(object, "position", context)?;
+    Ok(SpawnPoint { player, position })
+}
+
+fn parse_lighting(value: &Value) -> Result<MapLighting, MapFileError> {
+    let context = "lighting";
+    let object = value
+        .as_object()
+        .ok_or_else(|| MapFileError::InvalidField(String::from(context)))?;
+    let get = |name: &str| {
+        object
+            .get(name)
+            .ok_or_else(|| MapFileError::MissingField(field_name(context, name)))
+    };
+    let [r, g, b] = parse_numbers::<3>(get("sun_color")?, "lighting.sun_color")?;
+    let sun_color = Rgb::new(r, g, b);
+    let [x, y, z] = parse_numbers::<3>(get("sun_direction")?, "lighting.sun_direction")?;
+    let sun_direction = Unit::try_new(Vector3::new(x, y, z), f32::EPSILON)
+        .ok_or_else(|| MapFileError::InvalidField(String::from("lighting.sun_direction")))?;
+    let [r, g, b] = parse_numbers::<3>(get("ambient")?, "lighting.ambient")?;
+    let ambient = Rgb::new(r, g, b);
+    Ok(MapLighting {
+        sun_color,
+        sun_direction,
+        ambient,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map_json(planets: &str, spawns: &str) -> String {
+        format!(
+            r#"{{"name": "Test", "width": 100, "height": 60, "planets": [{}], "spawns": [{}]}}"#,
+            planets, spawns
+        )
+    }
+
+    const SPAWNS: &str =
+        r#"{"player": 0, "position": [-40, 0]}, {"player": 1, "position": [40, 0]}"#;
+
+    fn planet(x: f32, y: f32, radius: f32, density: f32) -> String {
+        format!(
+            r#"{{"position": [{}, {}], "radius": {}, "density": {}}}"#,
+            x, y, radius, density
+        )
+    }
+
+    #[test]
+    fn parses_valid_map() {
+        let map = MapFile::from_json(&map_json(&planet(0.0, 0.0, 10.0, 1.0), SPAWNS)).unwrap();
+        assert_eq!(map.name, "Test");
+        assert_eq!(map.planets.len(), 1);
+        assert_eq!(map.num_players(), 2);
+        assert!(map.validate().is_ok());
+    }
+
+    #[test]
+    fn classic_maps_are_valid() {
+        for text in [
+            include_str!("../../maps/duel.json"),
+            include_str!("../../maps/binary.json"),
+            include_str!("../../maps/gauntlet.json"),
+            include_str!("../../maps/triangle.json"),
+        ] {
+            let map = MapFile::from_json(text).unwrap();
+            assert!(map.validate().is_ok(), "{}", map.name);
+        }
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let map = MapFile::from_json(&map_json(&planet(5.0, -5.0, 10.0, 2.0), SPAWNS)).unwrap();
+        let reparsed = MapFile::from_json(&map.to_json()).unwrap();
+        assert_eq!(reparsed.planets[0].position, map.planets[0].position);
+        assert_eq!(reparsed.planets[0].density, map.planets[0].density);
+        assert_eq!(reparsed.spawns.len(), map.spawns.len());
+    }
+
+    #[test]
+    fn rejects_invalid_json() {
+        assert!(matches!(
+            MapFile::from_json("{"),
+            Err(MapFileError::InvalidJson(_))
+        ));
+        assert!(matches!(
+            MapFile::from_json("[]"),
+            Err(MapFileError::InvalidField(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_missing_fields() {
+        let result = MapFile::from_json(r#"{"width": 100, "height": 60, "spawns": []}"#);
+        assert!(matches!(result, Err(MapFileError::MissingField(name)) if name == "planets"));
+    }
+
+    #[test]
+    fn rejects_large_player_ids() {
+        for player in [MAX_PLAYERS as u64, u64::MAX] {
+            let spawn = format!(r#"{{"player": {}, "position": [0, 0]}}"#, player);
+            let result = MapFile::from_json(&map_json("", &spawn));
+            assert!(
+                matches!(result, Err(MapFileError::InvalidField(ref name)) if name == "spawns[0].player")
+            );
+        }
+        let spawn = r#"{"player": -1, "position": [0, 0]}"#;
+        assert!(MapFile::from_json(&map_json("", spawn)).is_err());
+    }
+
+    #[test]
+    fn rejects_negative_density() {
+        let result = MapFile::from_json(&map_json(&planet(0.0, 0.0, 10.0, -1.0), SPAWNS));
+        assert!(
+            matches!(result, Err(MapFileError::InvalidField(name)) if name == "planets[0].density")
+        );
+        let map = MapFile::from_json(&map_json(&planet(0.0, 0.0, 10.0, 0.0), SPAWNS)).unwrap();
+        assert_eq!(map.planets[0].density, 0.0);
+    }
+
+    #[test]
+    fn rejects_non_positive_sizes() {
+        let result = MapFile::from_json(&map_json(&planet(0.0, 0.0, 0.0, 1.0), SPAWNS));
+        assert!(
+            matches!(result, Err(MapFileError::InvalidField(name)) if name == "planets[0].radius")
+        );
+        let text = r#"{"width": -1, "height": 60, "planets": [], "spawns": []}"#;
+        assert!(matches!(
+            MapFile::from_json(text),
+            Err(MapFileError::InvalidField(name)) if name == "width"
+        ));
+    }
+
+    #[test]
+    fn rejects_too_many_planets() {
+        let planets = vec![planet(0.0, 0.0, 1.0, 1.0); MAX_PLANETS + 1].join(",");
+        let result = MapFile::from_json(&map_json(&planets, SPAWNS));
+        assert!(matches!(result, Err(MapFileError::InvalidField(name)) if name == "planets"));
+    }
+
+    #[test]
+    fn validates_spawn_points() {
+        let validate = |spawns: &str| {
+            MapFile::from_json(&map_json("", spawns))
+                .unwrap()
+                .validate()
+        };
+        assert!(matches!(validate(""), Err(MapLoadError::NoSpawnPoints)));
+        assert!(matches!(
+            validate(r#"{"player": 1, "position": [0, 0]}"#),
+            Err(MapLoadError::MissingSpawnPoint(0))
+        ));
+        assert!(matches!(
+            validate(r#"{"player": 0, "position": [0, 0]}, {"player": 0, "position": [1, 0]}"#),
+            Err(MapLoadError::DuplicateSpawnPoint(0))
+        ));
+        assert!(matches!(
+            validate(r#"{"player": 0, "position": [0, 31]}"#),
+            Err(MapLoadError::SpawnPointBlocked(0))
+        ));
+    }
+
+    #[test]
+    fn rejects_planets_outside_map() {
+        for (x, y) in [(45.0, 0.0), (0.0, -25.0)] {
+            let planets = [planet(0.0, 0.0, 1.0, 1.0), planet(x, y, 10.0, 1.0)].join(",");
+            let map = MapFile::from_json(&map_json(&planets, SPAWNS)).unwrap();
+            assert!(matches!(
+                map.validate(),
+                Err(MapLoadError::PlanetOutsideMap(1))
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_overlapping_planets() {
+        let planets = [
+            planet(-20.0, 0.0, 5.0, 1.0),
+            planet(20.0, 0.0, 5.0, 1.0),
+            planet(-12.0, 0.0, 5.0, 1.0),
+        ]
+        .join(",");
+        let map = MapFile::from_json(&map_json(&planets, SPAWNS)).unwrap();
+        assert!(matches!(
+            map.validate(),
+            Err(MapLoadError::PlanetsOverlap(0, 2))
+        ));
+
+        let planets = [planet(-10.0, 0.0, 5.0, 1.0), planet(0.0, 0.0, 5.0, 1.0)].join(",");
+        let map = MapFile::from_json(&map_json(&planets, SPAWNS)).unwrap();
+        assert!(map.validate().is_ok());
+    }
+}
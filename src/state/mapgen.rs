@@ -2,23 +2,41 @@ use std::f32::consts::PI;
 use std::rc::Rc;
 
 use log::warn;
-use nalgebra::{Isometry, Point2, Translation, UnitComplex, UnitQuaternion, Vector2, Vector3};
-use ncollide2d::shape::{Ball, Polyline, Shape};
+use nalgebra::{
+    Isometry, Isometry2, Point2, Translation, UnitComplex, UnitQuaternion, Vector2, Vector3,
+};
+use ncollide2d::query::{self, Proximity};
+use ncollide2d::shape::{Ball, Compound, ConvexPolygon, Polyline, Shape, ShapeHandle};
+use rand::seq::SliceRandom;
 use rand::{self, Rng};
 use rand_distr::{Distribution, Normal};
 
 use crate::meshgen;
 use crate::rendering::context::RenderingContext;
 use crate::rendering::material::Material;
-use crate::rendering::mesh::Mesh;
+use crate::rendering::mesh::{Mesh, Primitive};
+use crate::rendering::scene::Scene;
 use crate::rendering::{Rgb, Rgba};
-use crate::state::{Entity, EntityRenderer, GameState, Player, Ship};
-use crate::state_renderer::{GameRenderer, MeshRenderer};
+use crate::state::exotic::ExoticBody;
+use crate::state::fairness;
+use crate::state::orbit::Orbit;
+use crate::state::placement::{PlacementConstraints, PlacementError, PoissonDiskSampler};
+use crate::state::symmetry::{MapSymmetry, SymmetryTransform};
+use crate::state::{Entity, EntityRenderer, GameState, Player, PlayerRendererFactory, Ship};
+use crate::state_renderer::{
+    CompositeRenderer, GameRenderer, MeshRenderer, RingRenderer, SceneRenderer,
+};
 
 /// Default player colors
 pub const PLAYER_COLORS: &[(f32, f32, f32)] = &[(1.0, 0.0, 0.0), (0.0, 0.0, 1.0), (1.0, 1.0, 0.0)];
-// Maximum number of tries to place an entity
-const MAX_PLACE_ENTITY_TRIES: usize = 256;
+// Maximum number of times to regenerate a map that can't be placed or isn't fair
+const MAX_MAP_ATTEMPTS: usize = 8;
+// Ratio of the spacing between candidate positions to the radius of the entity being placed
+const PLACEMENT_SPACING_RATIO: f32 = 0.5;
+// Minimum spacing between candidate positions for an entity
+const PLACEMENT_MIN_SPACING: f32 = 1.0;
+// Spacing between candidate ship positions
+const SHIP_CANDIDATE_SPACING: f32 = 4.0;
 
 // Mean planet radius
 const PLANET_RAD_MEAN: f64 = 12.0;
@@ -39,11 +57,120 @@ const PLANET_DENS_MEAN: f64 = 3.0;
 // Standard deviation of planet density
 const PLANET_DENS_STD_DEV: f64 = 1.0;
 
+// Mean star radius
+const STAR_RAD_MEAN: f64 = 10.0;
+// Standard deviation of star radius
+const STAR_RAD_STD_DEV: f64 = 2.0;
+// Star density
+const STAR_DENSITY: f32 = 6.0;
+// Maximum number of planets orbiting each star
+const MAX_ORBITING_PLANETS: usize = 2;
+// Maximum number of moons orbiting each planet
+const MAX_MOONS: usize = 1;
+// Mean radius of orbiting planets
+const ORBITING_PLANET_RAD_MEAN: f64 = 5.0;
+// Standard deviation of orbiting planet radius
+const ORBITING_PLANET_RAD_STD_DEV: f64 = 1.5;
+// Minimum radius of orbiting planets and moons
+const ORBITING_BODY_RAD_MIN: f64 = 1.0;
+// Ratio of moon radius to planet radius
+const MOON_RAD_RATIO: f32 = 0.35;
+// Maximum orbital eccentricity
+const MAX_ECCENTRICITY: f32 = 0.15;
+// Minimum clearance between the regions swept out by neighboring orbits
+const ORBIT_GAP: f32 = 2.0;
+// Mean number of black holes per square unit of map space
+const BLACK_HOLE_FREQ_MEAN: f64 = 2e-5;
+// Mean number of repulsors per square unit of map space
+const REPULSOR_FREQ_MEAN: f64 = 3e-5;
+// Mean number of wormhole pairs per square unit of map space
+const WORMHOLE_PAIR_FREQ_MEAN: f64 = 2e-5;
+// Radius of a black hole's event horizon
+const BLACK_HOLE_HORIZON_RAD: f32 = 3.0;
+// Ratio of the visible radius of a black hole to its event horizon
+const BLACK_HOLE_CORE_RATIO: f32 = 0.5;
+// Mass of a black hole
+const BLACK_HOLE_MASS: f32 = 40000.0;
+// Radius of a repulsor
+const REPULSOR_RAD: f32 = 4.0;
+// Mass of a repulsor (which is negative so it pushes missiles away)
+const REPULSOR_MASS: f32 = -15000.0;
+// Radius of a wormhole
+const WORMHOLE_RAD: f32 = 3.0;
+
+// Minimum radius of the center line of an asteroid belt
+const BELT_RAD_MIN: f32 = 20.0;
+// Maximum radius of the center line of an asteroid belt
+const BELT_RAD_MAX: f32 = 40.0;
+// Width of an asteroid belt
+const BELT_WIDTH: f32 = 8.0;
+// Number of asteroids to attempt to place in each belt
+const ASTEROIDS_PER_BELT: usize = 40;
+// Mean asteroid radius
+const ASTEROID_RAD_MEAN: f64 = 1.5;
+// Standard deviation of asteroid radius
+const ASTEROID_RAD_STD_DEV: f64 = 0.5;
+// Minimum asteroid radius
+const ASTEROID_RAD_MIN: f64 = 0.5;
+// Minimum number of points in an asteroid's outline
+const ASTEROID_POINTS_MIN: usize = 6;
+// Maximum number of points in an asteroid's outline
+const ASTEROID_POINTS_MAX: usize = 12;
+// Amount by which asteroid outlines deviate from a circle (from 0 to 1)
+const ASTEROID_ROUGHNESS: f32 = 0.35;
+// Ratio of an asteroid's peak height to its mean radius
+const ASTEROID_HEIGHT_RATIO: f32 = 0.6;
+// Asteroid density
+const ASTEROID_DENSITY: f32 = 2.0;
+
+// Orbital period (in seconds) of a body with a semi-major axis of ORBIT_REFERENCE_RADIUS
+const ORBIT_REFERENCE_PERIOD: f32 = 40.0;
+// Semi-major axis used as a reference for orbital periods
+const ORBIT_REFERENCE_RADIUS: f32 = 20.0;
+
+/// Mean number of each kind of exotic body per square unit of map space
+#[derive(Clone, Copy, Debug)]
+pub struct ExoticFrequencies {
+    pub black_holes: f64,
+    pub repulsors: f64,
+    pub wormhole_pairs: f64,
+}
+
+impl ExoticFrequencies {
+    /// Frequencies for a map with only ordinary planets
+    pub fn none() -> ExoticFrequencies {
+        ExoticFrequencies {
+            black_holes: 0.0,
+            repulsors: 0.0,
+            wormhole_pairs: 0.0,
+        }
+    }
+}
+
+impl Default for ExoticFrequencies {
+    fn default() -> Self {
+        ExoticFrequencies {
+            black_holes: BLACK_HOLE_FREQ_MEAN,
+            repulsors: REPULSOR_FREQ_MEAN,
+            wormhole_pairs: WORMHOLE_PAIR_FREQ_MEAN,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum MapgenError {
-    CouldNotPlaceEntity,
+    CouldNotPlaceEntity(PlacementError),
     CouldNotCreatePlanetRenderer,
+    CouldNotCreateExoticRenderers,
+    CouldNotCreateAsteroidRenderer,
     CouldNotCreateShipRenderers,
+    /// The map's symmetry doesn't support the number of players
+    UnsupportedSymmetry,
+    /// Some player has no simulated shot that hits the given opponent
+    UnfairMap {
+        player: usize,
+        opponent: usize,
+    },
 }
 
 pub struct MapgenParams<'a, Context>
@@ -54,9 +181,20 @@ where
     pub width: f32,
     pub height: f32,
     pub num_players: usize,
+    /// Number of star systems (stars with orbiting planets and moons) to generate
+    pub num_systems: usize,
+    pub exotic_frequencies: ExoticFrequencies,
+    pub num_asteroid_belts: usize,
+    pub placement: PlacementConstraints,
+    /// How the layout is repeated for each player
+    ///
+    /// Entity counts are rounded up to a multiple of the number of copies.
+    pub symmetry: MapSymmetry,
+    /// Whether to regenerate maps where some ship can't hit an opponent
+    pub validate_fairness: bool,
     pub game_renderer: Rc<dyn GameRenderer<Context = Context>>,
-    pub ship_mesh: &'a Mesh<Context>,
-    pub make_ship_renderer: Box<dyn Fn(&Player) -> Result<Rc<dyn EntityRenderer>, ()>>,
+    pub ship_scene: &'a Scene<Context>,
+    pub make_ship_renderer: PlayerRendererFactory,
 }
 
 impl<'a, Context> MapgenParams<'a, Context>
@@ -64,43 +202,294 @@ where
     Context: RenderingContext + 'static,
 {
     pub fn generate_map(&mut self) -> Result<(), MapgenError> {
+        if self.symmetry.transforms(self.num_players).is_none() {
+            return Err(MapgenError::UnsupportedSymmetry);
+        }
         self.add_players();
-        self.add_planets()?;
+
+        let mut result = Ok(());
+        for attempt in 1..=MAX_MAP_ATTEMPTS {
+            self.game_state.clear_entities();
+            result = self.generate_layout().and_then(|_| self.check_fairness());
+            match result {
+                Err(MapgenError::CouldNotPlaceEntity(_)) | Err(MapgenError::UnfairMap { .. }) => {
+                    warn!("Map attempt {} failed: {:?}", attempt, result);
+                }
+                _ => return result,
+            }
+        }
+        result
+    }
+
+    fn generate_layout(&mut self) -> Result<(), MapgenError> {
+        let spheres = SphereRendererFactory::new(&self.game_renderer)
+            .map_err(|_| MapgenError::CouldNotCreatePlanetRenderer)?;
+        self.add_systems(&spheres)?;
+        self.add_exotic_bodies(&spheres)?;
+        self.add_asteroid_belts()?;
+        self.add_planets(&spheres)?;
         self.add_ships()?;
         Ok(())
     }
 
+    fn check_fairness(&mut self) -> Result<(), MapgenError> {
+        if !self.validate_fairness {
+            return Ok(());
+        }
+        match fairness::find_unreachable_opponent(self.game_state) {
+            Some((player, opponent)) => Err(MapgenError::UnfairMap { player, opponent }),
+            None => Ok(()),
+        }
+    }
+
+    fn symmetry_transforms(&self) -> Vec<SymmetryTransform> {
+        self.symmetry
+            .transforms(self.num_players)
+            .unwrap_or_else(|| vec![SymmetryTransform::identity()])
+    }
+
+    /// Divides a number of entities between the symmetric copies of the map, rounding up
+    fn symmetric_count(&self, count: usize) -> usize {
+        let copies = self.symmetry_transforms().len();
+        count.div_ceil(copies)
+    }
+
+    /// Adds an entity along with its copies under the map's symmetry, returning their indices
+    ///
+    /// The copies are stored right after the original, and orbits are assumed to have parents that
+    /// were also added with this method.
+    fn add_symmetric(&mut self, entity: Entity) -> Vec<usize> {
+        let images: Vec<Entity> = self
+            .symmetry_transforms()
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(i, transform)| make_image(&entity, transform, i))
+            .collect();
+        let mut indices = vec![self.game_state.add_entity(entity)];
+        for image in images {
+            indices.push(self.game_state.add_entity(image));
+        }
+        indices
+    }
+
     fn add_players(&mut self) {
-        let mut players = Vec::with_capacity(self.num_players);
-        for i in 0..self.num_players {
-            let player = Player {
-                color: PLAYER_COLORS[i % PLAYER_COLORS.len()].into(),
+        self.game_state.set_players(make_players(self.num_players));
+    }
+
+    fn make_ring_renderer(&self, radius: f32, color: Rgb) -> Result<Rc<dyn EntityRenderer>, ()> {
+        let renderer = RingRenderer::new(Rc::clone(&self.game_renderer), radius, color)?;
+        Ok(Rc::new(renderer))
+    }
+
+    fn add_systems(&mut self, spheres: &SphereRendererFactory<Context>) -> Result<(), MapgenError> {
+        if self.num_systems == 0 {
+            return Ok(());
+        }
+        let star_renderer = spheres.make_renderer(Rgba::new(1.0, 0.8, 0.2, 1.0));
+        let planet_renderer = spheres.make_renderer(Rgba::new(0.0, 0.0, 1.0, 1.0));
+
+        for _ in 0..self.symmetric_count(self.num_systems) {
+            let system = SystemLayout::generate(&mut rand::thread_rng());
+            let clearance = Box::new(Ball::new(system.extent()));
+            let center = match self.place_entity(clearance) {
+                Ok(entity) => *entity.position(),
+                Err(_) => {
+                    warn!(
+                        "Unable to place star system with radius {}",
+                        system.extent()
+                    );
+                    continue;
+                }
             };
-            players.push(player);
+
+            let mut star = Entity::new(center);
+            star.collision_shape = Some(Box::new(Ball::new(system.star_radius)));
+            star.mass = sphere_volume(system.star_radius) * STAR_DENSITY;
+            star.transform.scale = system.star_radius;
+            star.renderer = Some(Rc::clone(&star_renderer));
+            let star_index = self.add_symmetric(star)[0];
+
+            for planet in system.planets.iter() {
+                let entity = planet.body.make_entity(Some(star_index), &planet_renderer);
+                let planet_index = self.add_symmetric(entity)[0];
+                for moon in planet.moons.iter() {
+                    let entity = moon.make_entity(Some(planet_index), &planet_renderer);
+                    self.add_symmetric(entity);
+                }
+            }
         }
-        self.game_state.set_players(players.into());
+        self.game_state.update_body_positions();
+
+        Ok(())
     }
 
-    fn add_planets(&mut self) -> Result<(), MapgenError> {
+    /// Picks a random number of bodies to place based on their frequency
+    ///
+    /// With a symmetric map, this is the number to place in each copy.
+    fn random_count(&self, frequency: f64) -> usize {
+        let expected = frequency * (self.width * self.height) as f64;
+        let whole = expected.floor();
+        let extra = rand::thread_rng().gen_bool((expected - whole).clamp(0.0, 1.0));
+        self.symmetric_count(whole as usize + extra as usize)
+    }
+
+    fn add_exotic_bodies(
+        &mut self,
+        spheres: &SphereRendererFactory<Context>,
+    ) -> Result<(), MapgenError> {
+        let freqs = self.exotic_frequencies;
+        let black_hole_renderer: Rc<dyn EntityRenderer> = Rc::new(CompositeRenderer::new(vec![
+            spheres.make_renderer(Rgba::new(0.02, 0.02, 0.02, 1.0)),
+            self.make_ring_renderer(BLACK_HOLE_HORIZON_RAD, Rgb::new(1.0, 0.5, 0.1))
+                .map_err(|_| MapgenError::CouldNotCreateExoticRenderers)?,
+        ]));
+        let repulsor_renderer = spheres.make_renderer(Rgba::new(1.0, 0.2, 0.6, 1.0));
+        let wormhole_renderer: Rc<dyn EntityRenderer> = Rc::new(CompositeRenderer::new(vec![
+            self.make_ring_renderer(WORMHOLE_RAD, Rgb::new(0.2, 1.0, 1.0))
+                .map_err(|_| MapgenError::CouldNotCreateExoticRenderers)?,
+            self.make_ring_renderer(WORMHOLE_RAD * 0.6, Rgb::new(0.1, 0.5, 1.0))
+                .map_err(|_| MapgenError::CouldNotCreateExoticRenderers)?,
+        ]));
+
+        for _ in 0..self.random_count(freqs.black_holes) {
+            let shape = Box::new(Ball::new(BLACK_HOLE_HORIZON_RAD));
+            match self.place_entity(shape) {
+                Ok(mut black_hole) => {
+                    black_hole.mass = BLACK_HOLE_MASS;
+                    black_hole.transform.scale = BLACK_HOLE_HORIZON_RAD * BLACK_HOLE_CORE_RATIO;
+                    black_hole.exotic = Some(ExoticBody::BlackHole);
+                    black_hole.renderer = Some(Rc::clone(&black_hole_renderer));
+                    self.add_symmetric(black_hole);
+                }
+                Err(_) => warn!("Unable to place black hole"),
+            }
+        }
+
+        for _ in 0..self.random_count(freqs.repulsors) {
+            let shape = Box::new(Ball::new(REPULSOR_RAD));
+            match self.place_entity(shape) {
+                Ok(mut repulsor) => {
+                    repulsor.mass = REPULSOR_MASS;
+                    repulsor.transform.scale = REPULSOR_RAD;
+                    repulsor.exotic = Some(ExoticBody::Repulsor);
+                    repulsor.renderer = Some(Rc::clone(&repulsor_renderer));
+                    self.add_symmetric(repulsor);
+                }
+                Err(_) => warn!("Unable to place repulsor"),
+            }
+        }
+
+        for _ in 0..self.random_count(freqs.wormhole_pairs) {
+            let mut pair = Vec::with_capacity(2);
+            for _ in 0..2 {
+                let shape = Box::new(Ball::new(WORMHOLE_RAD));
+                match self.place_entity(shape) {
+                    Ok(mut wormhole) => {
+                        wormhole.renderer = Some(Rc::clone(&wormhole_renderer));
+                        // Reserve the spot so the other end of the pair doesn't overlap it.
+                        pair.push(self.add_symmetric(wormhole));
+                    }
+                    Err(_) => break,
+                }
+            }
+            match pair[..] {
+                [ref entrances, ref exits] => {
+                    for (&entrance, &exit) in entrances.iter().zip(exits.iter()) {
+                        self.game_state.entities[entrance].exotic =
+                            Some(ExoticBody::Wormhole { exit });
+                        self.game_state.entities[exit].exotic =
+                            Some(ExoticBody::Wormhole { exit: entrance });
+                    }
+                }
+                _ => {
+                    // Remove the unpaired end (and its copies).
+                    let num_added: usize = pair.iter().map(|indices| indices.len()).sum();
+                    let num_entities = self.game_state.entities.len();
+                    self.game_state.entities.truncate(num_entities - num_added);
+                    self.game_state.rebuild_spatial_index();
+                    warn!("Unable to place wormhole pair");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_asteroid_belts(&mut self) -> Result<(), MapgenError> {
+        let mut rng = rand::thread_rng();
+        let radius_distribution = Normal::new(ASTEROID_RAD_MEAN, ASTEROID_RAD_STD_DEV).unwrap();
+        let half_width = self.width * 0.5;
+        let half_height = self.height * 0.5;
+
+        for _ in 0..self.symmetric_count(self.num_asteroid_belts) {
+            let center = Vector2::new(
+                rng.gen_range(-half_width..=half_width),
+                rng.gen_range(-half_height..=half_height),
+            );
+            let belt_radius = rng.gen_range(BELT_RAD_MIN..=BELT_RAD_MAX);
+            let mut num_placed = 0;
+            for _ in 0..ASTEROIDS_PER_BELT {
+                let angle = rng.gen_range(0.0..2.0 * PI);
+                let distance = belt_radius + rng.gen_range(-0.5..=0.5) * BELT_WIDTH;
+                let pos = center + Vector2::new(angle.cos(), angle.sin()) * distance;
+
+                let radius = radius_distribution.sample(&mut rng).max(ASTEROID_RAD_MIN) as f32;
+                let num_points = rng.gen_range(ASTEROID_POINTS_MIN..=ASTEROID_POINTS_MAX);
+                let outline =
+                    meshgen::gen_asteroid_outline(&mut rng, radius, num_points, ASTEROID_ROUGHNESS);
+                let shape = match make_polygon_shape(&outline) {
+                    Some(shape) => shape,
+                    None => continue,
+                };
+                if let Ok(mut asteroid) = self.try_place_at(shape, pos) {
+                    let height = radius * ASTEROID_HEIGHT_RATIO;
+                    // The mesh is a pair of pyramids on either side of the outline.
+                    let volume = 2.0 * meshgen::polygon_area(&outline) * height / 3.0;
+                    asteroid.mass = volume * ASTEROID_DENSITY;
+                    // Each copy needs its own mesh since the outline is rotated or mirrored.
+                    let renderers = self
+                        .symmetry_transforms()
+                        .iter()
+                        .map(|t| self.make_asteroid_renderer(&t.apply_outline(&outline), height))
+                        .collect::<Result<Vec<_>, ()>>()
+                        .map_err(|_| MapgenError::CouldNotCreateAsteroidRenderer)?;
+                    let indices = self.add_symmetric(asteroid);
+                    for (index, renderer) in indices.into_iter().zip(renderers) {
+                        self.game_state.entities[index].renderer = Some(renderer);
+                    }
+                    num_placed += 1;
+                }
+            }
+            if num_placed < ASTEROIDS_PER_BELT {
+                warn!(
+                    "Placed {} of {} asteroids in belt",
+                    num_placed, ASTEROIDS_PER_BELT
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn make_asteroid_renderer(
+        &self,
+        outline: &[Point2<f32>],
+        height: f32,
+    ) -> Result<Rc<dyn EntityRenderer>, ()> {
         let renderer = Rc::clone(&self.game_renderer);
-        // TODO: probably don't build the planet renderer here.
-        // TODO: break down primitives so we can more easily share buffers between planets that have different materials.
-        let planet_material = Material {
-            base_color: Rgba::new(0.0, 0.0, 1.0, 1.0),
-            base_color_texture: None,
-            metal_factor: 0.0,
-            roughness: 1.0,
-            extras: None,
-        };
-        let planet_mesh = meshgen::gen_sphere(1.0, 10, renderer.context(), planet_material)
-            .map_err(|_| MapgenError::CouldNotCreatePlanetRenderer)?;
-        let planet_renderer = Rc::new(MeshRenderer::new(renderer, planet_mesh));
+        let material = Material::from_color(Rgba::new(0.45, 0.4, 0.35, 1.0), 0.0, 1.0);
+        let mesh = meshgen::gen_asteroid(outline, height, renderer.context(), material)?;
+        Ok(Rc::new(MeshRenderer::new(renderer, mesh)))
+    }
+
+    fn add_planets(&mut self, spheres: &SphereRendererFactory<Context>) -> Result<(), MapgenError> {
+        let planet_renderer = spheres.make_renderer(Rgba::new(0.0, 0.0, 1.0, 1.0));
 
         let num_planets = {
             let distribution = Normal::new(PLANET_FREQ_MEAN, PLANET_FREQ_STD_DEV).unwrap();
             let density = distribution.sample(&mut rand::thread_rng()) as f32;
             let count = (density * self.width * self.height).round() as usize;
-            count.max(1)
+            self.symmetric_count(count.max(1))
         };
 
         let radius_distribution = Normal::new(PLANET_RAD_MEAN, PLANET_RAD_STD_DEV).unwrap();
@@ -115,11 +504,10 @@ where
                 let density = density_distribution
                     .sample(&mut rand::thread_rng())
                     .max(0.0) as f32;
-                let volume = (4.0 / 3.0) * std::f32::consts::PI * radius.powi(3);
-                planet.mass = volume * density;
+                planet.mass = sphere_volume(radius) * density;
                 planet.transform.scale = radius;
-                planet.renderer = Some(Rc::clone(&planet_renderer) as Rc<dyn EntityRenderer>);
-                self.game_state.entities.push(planet);
+                planet.renderer = Some(Rc::clone(&planet_renderer));
+                self.add_symmetric(planet);
             } else {
                 warn!("Unable to place planet with radius {}", radius);
             }
@@ -132,14 +520,20 @@ where
         let renderers = self
             .make_player_ship_renderers()
             .map_err(|_| MapgenError::CouldNotCreateShipRenderers)?;
-        for (id, _player) in self.game_state.players.iter().enumerate() {
-            let mesh_shape = make_collision_shape(self.ship_mesh);
-            let shape = mesh_shape.unwrap_or_else(|| Box::new(Ball::new(0.5)));
-            let mut ship = self.place_entity(shape)?;
-            ship.ship = Some(Ship::new(id));
-            ship.transform.rotation = UnitQuaternion::from_axis_angle(&Vector3::x_axis(), PI * 0.5);
-            ship.renderer = Some(Rc::clone(&renderers[id]));
-            self.game_state.entities.push(ship)
+        let owners: Vec<usize> = (0..renderers.len()).collect();
+        let positions = self
+            .choose_ship_positions(&owners)
+            .map_err(MapgenError::CouldNotPlaceEntity)?;
+
+        let transforms = self.symmetry_transforms();
+        for ((id, renderer), pos) in renderers.iter().enumerate().zip(positions) {
+            let mut ship = make_ship(self.ship_scene, id, pos);
+            // Symmetric ships face in symmetric directions.
+            if let Some(transform) = transforms.get(id).filter(|_| transforms.len() > 1) {
+                ship.transform.rotation = transform.apply_rotation(&ship.transform.rotation);
+            }
+            ship.renderer = Some(Rc::clone(renderer));
+            self.game_state.add_entity(ship);
         }
         Ok(())
     }
@@ -154,49 +548,460 @@ where
         Ok(renderers)
     }
 
+    /// Picks positions for ships that satisfy the placement constraints
+    ///
+    /// Each ship must also be clear of every other entity by the required clearance.
+    fn choose_ship_positions(&self, owners: &[usize]) -> Result<Vec<Vector2<f32>>, PlacementError> {
+        let mut constraints = self.placement;
+        let half_size = Vector2::new(self.width * 0.5, self.height * 0.5);
+        let margin = Vector2::repeat(constraints.edge_margin);
+        let (mins, maxs) = (-half_size + margin, half_size - margin);
+        if mins.x > maxs.x || mins.y > maxs.y {
+            return Err(PlacementError::NoRoomInsideMargin);
+        }
+
+        // All ships share the same mesh, so one clearance shape covers them all.
+        let ship_radius = {
+            let bounds = make_ship_collision_shape(self.ship_scene).local_bounding_sphere();
+            bounds.center().coords.magnitude() + bounds.radius()
+        };
+        let clearance = Ball::new(ship_radius + constraints.ship_clearance);
+        // Ships must also be clear of each other.
+        constraints.min_ship_distance = constraints
+            .min_ship_distance
+            .max(2.0 * ship_radius + constraints.ship_clearance);
+
+        let mut rng = rand::thread_rng();
+        let candidates: Vec<Vector2<f32>> =
+            PoissonDiskSampler::new(&mut rng, mins, maxs, SHIP_CANDIDATE_SPACING)
+                .filter(|pos| self.can_place_at(&clearance, *pos))
+                .collect();
+
+        let transforms = self.symmetry_transforms();
+        if transforms.len() > 1 {
+            // Each candidate determines every ship's position.
+            if candidates.is_empty() {
+                return Err(PlacementError::NoFreeSpace);
+            }
+            let in_bounds =
+                |p: &Vector2<f32>| p.x >= mins.x && p.x <= maxs.x && p.y >= mins.y && p.y <= maxs.y;
+            let layouts: Vec<Vec<Vector2<f32>>> = candidates
+                .iter()
+                .map(|pos| transforms.iter().map(|t| t.apply(pos)).collect::<Vec<_>>())
+                .filter(|layout| {
+                    layout.iter().all(in_bounds) && constraints.is_valid_layout(layout, owners)
+                })
+                .collect();
+            return layouts
+                .choose(&mut rng)
+                .cloned()
+                .ok_or(PlacementError::NoSymmetricLayout);
+        }
+        constraints.choose_ship_positions(&mut rng, &candidates, owners)
+    }
+
+    /// Creates an entity with the given shape at a random position where it doesn't overlap anything
+    ///
+    /// Candidate positions are spread evenly over the whole map, so this only fails if there's
+    /// no room for the shape.
     fn place_entity(&self, shape: Box<dyn Shape<f32>>) -> Result<Entity, MapgenError> {
-        let half_width = self.width * 0.5;
-        let half_height = self.height * 0.5;
+        let half_size = Vector2::new(self.width * 0.5, self.height * 0.5);
+        let radius = shape.local_bounding_sphere().radius();
+        let spacing = (radius * PLACEMENT_SPACING_RATIO).max(PLACEMENT_MIN_SPACING);
         let mut rng = rand::thread_rng();
 
-        for _ in 0..MAX_PLACE_ENTITY_TRIES {
-            let x = rng.gen_range(-half_width..=half_width);
-            let y = rng.gen_range(-half_height..=half_height);
-            let pos = Vector2::new(x, y);
-            let transform = Isometry::from_parts(Translation::from(pos), UnitComplex::identity());
-            if self
-                .game_state
-                .iter_entities()
-                .all(|e| !e.collides_with_shape(shape.as_ref(), &transform))
-            {
-                let mut entity = Entity::new(Vector3::new(pos.x, pos.y, 0.0));
-                entity.collision_shape = Some(shape);
-                return Ok(entity);
+        let pos = PoissonDiskSampler::new(&mut rng, -half_size, half_size, spacing)
+            .find(|pos| self.can_place_at(shape.as_ref(), *pos))
+            .ok_or(MapgenError::CouldNotPlaceEntity(
+                PlacementError::NoFreeSpace,
+            ))?;
+        let mut entity = Entity::new(Vector3::new(pos.x, pos.y, 0.0));
+        entity.collision_shape = Some(shape);
+        Ok(entity)
+    }
+
+    /// Creates an entity with the given shape at a specific position if it doesn't overlap anything
+    fn try_place_at(
+        &self,
+        shape: Box<dyn Shape<f32>>,
+        pos: Vector2<f32>,
+    ) -> Result<Entity, MapgenError> {
+        if self.can_place_at(shape.as_ref(), pos) {
+            let mut entity = Entity::new(Vector3::new(pos.x, pos.y, 0.0));
+            entity.collision_shape = Some(shape);
+            Ok(entity)
+        } else {
+            Err(MapgenError::CouldNotPlaceEntity(
+                PlacementError::NoFreeSpace,
+            ))
+        }
+    }
+
+    /// Checks whether a shape can be placed at a position without overlapping anything
+    ///
+    /// With a symmetric map, the shape's copies must also be on the map and clear of everything
+    /// (including each other).
+    fn can_place_at(&self, shape: &dyn Shape<f32>, pos: Vector2<f32>) -> bool {
+        let half_size = Vector2::new(self.width * 0.5, self.height * 0.5);
+        let image_shapes: Vec<(Box<dyn Shape<f32>>, Vector2<f32>)> = self
+            .symmetry_transforms()
+            .iter()
+            .skip(1)
+            .map(|t| (t.apply_shape(shape), t.apply(&pos)))
+            .collect();
+        if image_shapes
+            .iter()
+            .any(|(_, p)| p.x.abs() > half_size.x || p.y.abs() > half_size.y)
+        {
+            return false;
+        }
+
+        let mut placed: Vec<(&dyn Shape<f32>, Isometry2<f32>)> = Vec::new();
+        let copies =
+            std::iter::once((shape, pos)).chain(image_shapes.iter().map(|(s, p)| (s.as_ref(), *p)));
+        for (copy, copy_pos) in copies {
+            let transform =
+                Isometry::from_parts(Translation::from(copy_pos), UnitComplex::identity());
+            let overlaps_copy = placed.iter().any(|(other, other_transform)| {
+                query::proximity(&transform, copy, other_transform, *other, 0.0)
+                    != Proximity::Disjoint
+            });
+            if overlaps_copy || !self.is_clear(copy, &transform) {
+                return false;
+            }
+            placed.push((copy, transform));
+        }
+        true
+    }
+
+    fn is_clear(
+        &self,
+        shape: &dyn Shape<f32>,
+        transform: &Isometry<f32, UnitComplex<f32>, 2>,
+    ) -> bool {
+        let bounds = shape.aabb(transform);
+        self.game_state
+            .entities_near(&bounds)
+            .all(|(i, e)| match self.game_state.orbit_sweep(i) {
+                Some(sweep) => !intersects_sweep(sweep, e, shape, transform),
+                None => !e.collides_with_shape(shape, transform),
+            })
+    }
+}
+
+/// Makes a copy of an entity under a symmetry transform
+///
+/// `copy_index` is the position of the transform in the map's list of symmetry transforms. It's
+/// used to find the matching copy of an orbit's parent.
+fn make_image(entity: &Entity, transform: &SymmetryTransform, copy_index: usize) -> Entity {
+    let pos = transform.apply(&entity.position().xy());
+    let mut image = Entity::new(Vector3::new(pos.x, pos.y, entity.position().z));
+    image.transform.rotation = entity.transform.rotation;
+    image.transform.scale = entity.transform.scale;
+    image.mass = entity.mass;
+    image.collision_shape = entity
+        .collision_shape
+        .as_ref()
+        .map(|shape| transform.apply_shape(shape.as_ref()));
+    image.renderer = entity.renderer.clone();
+    image.orbit = entity.orbit.as_ref().map(|orbit| Orbit {
+        parent: orbit.parent.map(|parent| parent + copy_index),
+        ..transform.apply_orbit(orbit)
+    });
+    image.exotic = entity.exotic;
+    image
+}
+
+/// Checks whether a shape overlaps the region swept out by an orbiting body
+fn intersects_sweep(
+    (center, inner, outer): (Vector2<f32>, f32, f32),
+    body: &Entity,
+    shape: &dyn Shape<f32>,
+    transform: &Isometry<f32, UnitComplex<f32>, 2>,
+) -> bool {
+    let body_radius = body.collision_radius();
+    let bounds = shape.bounding_sphere(transform);
+    let distance = (bounds.center().coords - center).magnitude();
+    distance + bounds.radius() > inner - body_radius
+        && distance - bounds.radius() < outer + body_radius
+}
+
+/// Size and orbit of a planet or moon in a generated star system
+struct OrbitingBody {
+    radius: f32,
+    density: f32,
+    orbit: Orbit,
+}
+
+impl OrbitingBody {
+    fn generate<R: Rng>(rng: &mut R, radius: f32, inner_clearance: f32, reach: f32) -> Self {
+        let density_distribution = Normal::new(PLANET_DENS_MEAN, PLANET_DENS_STD_DEV).unwrap();
+        let eccentricity = rng.gen_range(0.0..MAX_ECCENTRICITY);
+        // Make sure that everything attached to this body stays clear of the previous orbit.
+        let semi_major_axis = (inner_clearance + reach + ORBIT_GAP) / (1.0 - eccentricity);
+        let period = ORBIT_REFERENCE_PERIOD * (semi_major_axis / ORBIT_REFERENCE_RADIUS).powf(1.5);
+        OrbitingBody {
+            radius,
+            density: density_distribution.sample(rng).max(0.0) as f32,
+            orbit: Orbit {
+                parent: None,
+                center: Vector2::zeros(),
+                semi_major_axis,
+                eccentricity,
+                periapsis_angle: rng.gen_range(0.0..2.0 * PI),
+                period,
+                phase: rng.gen_range(0.0..2.0 * PI),
+            },
+        }
+    }
+
+    fn make_entity(&self, parent: Option<usize>, renderer: &Rc<dyn EntityRenderer>) -> Entity {
+        let mut entity = Entity::new(Vector3::zeros());
+        entity.collision_shape = Some(Box::new(Ball::new(self.radius)));
+        entity.mass = sphere_volume(self.radius) * self.density;
+        entity.transform.scale = self.radius;
+        entity.renderer = Some(Rc::clone(renderer));
+        let mut orbit = self.orbit.clone();
+        orbit.parent = parent;
+        entity.orbit = Some(orbit);
+        entity
+    }
+}
+
+struct OrbitingPlanet {
+    body: OrbitingBody,
+    moons: Vec<OrbitingBody>,
+}
+
+impl OrbitingPlanet {
+    /// Returns the maximum distance from the planet's center that its moons can reach
+    fn reach(&self) -> f32 {
+        self.moons
+            .iter()
+            .map(|m| m.orbit.apoapsis() + m.radius)
+            .fold(self.body.radius, f32::max)
+    }
+}
+
+/// Layout of a star system with non-overlapping orbits
+///
+/// Since no two orbits' swept regions overlap, the bodies in the system can never collide.
+struct SystemLayout {
+    star_radius: f32,
+    planets: Vec<OrbitingPlanet>,
+}
+
+impl SystemLayout {
+    fn generate<R: Rng>(rng: &mut R) -> Self {
+        let star_rad_distribution = Normal::new(STAR_RAD_MEAN, STAR_RAD_STD_DEV).unwrap();
+        let planet_rad_distribution =
+            Normal::new(ORBITING_PLANET_RAD_MEAN, ORBITING_PLANET_RAD_STD_DEV).unwrap();
+        let star_radius = star_rad_distribution.sample(rng).max(ORBITING_BODY_RAD_MIN) as f32;
+        let clockwise = rng.gen_bool(0.5);
+
+        let num_planets = rng.gen_range(1..=MAX_ORBITING_PLANETS);
+        let mut planets: Vec<OrbitingPlanet> = Vec::with_capacity(num_planets);
+        for _ in 0..num_planets {
+            let radius = planet_rad_distribution
+                .sample(rng)
+                .max(ORBITING_BODY_RAD_MIN) as f32;
+
+            let num_moons = rng.gen_range(0..=MAX_MOONS);
+            let mut moons: Vec<OrbitingBody> = Vec::with_capacity(num_moons);
+            for _ in 0..num_moons {
+                let moon_radius = (radius * MOON_RAD_RATIO).max(ORBITING_BODY_RAD_MIN as f32);
+                let inner_clearance = moons
+                    .last()
+                    .map(|m| m.orbit.apoapsis() + m.radius)
+                    .unwrap_or(radius);
+                moons.push(OrbitingBody::generate(
+                    rng,
+                    moon_radius,
+                    inner_clearance,
+                    moon_radius,
+                ));
+            }
+
+            let reach = moons
+                .iter()
+                .map(|m| m.orbit.apoapsis() + m.radius)
+                .fold(radius, f32::max);
+            let inner_clearance = planets
+                .last()
+                .map(|p| p.body.orbit.apoapsis() + p.reach())
+                .unwrap_or(star_radius);
+            let planet = OrbitingPlanet {
+                body: OrbitingBody::generate(rng, radius, inner_clearance, reach),
+                moons,
+            };
+            planets.push(planet);
+        }
+
+        if clockwise {
+            for planet in planets.iter_mut() {
+                planet.body.orbit.period = -planet.body.orbit.period;
+                for moon in planet.moons.iter_mut() {
+                    moon.orbit.period = -moon.orbit.period;
+                }
             }
         }
-        Err(MapgenError::CouldNotPlaceEntity)
+
+        SystemLayout {
+            star_radius,
+            planets,
+        }
+    }
+
+    /// Returns the maximum distance from the star's center that any body in the system can reach
+    fn extent(&self) -> f32 {
+        self.planets
+            .iter()
+            .map(|p| p.body.orbit.apoapsis() + p.reach())
+            .fold(self.star_radius, f32::max)
+    }
+}
+
+/// Makes a solid collision shape from a polygon outline
+///
+/// Concave outlines are split into triangles, since ncollide's convex polygons can't represent
+/// them. Outlines that can't be split (because they intersect themselves) are filled in to their
+/// convex hulls.
+fn make_polygon_shape(outline: &[Point2<f32>]) -> Option<Box<dyn Shape<f32>>> {
+    let mut outline = outline.to_vec();
+    // Clockwise outlines have negative areas.
+    if meshgen::polygon_area(&outline) < 0.0 {
+        outline.reverse();
+    }
+    if meshgen::is_convex(&outline) {
+        if let Some(polygon) = ConvexPolygon::try_new(outline.clone()) {
+            return Some(Box::new(polygon));
+        }
+    }
+    let triangles = meshgen::triangulate(&outline);
+    if triangles.len() + 2 == outline.len() {
+        let parts: Vec<(Isometry2<f32>, ShapeHandle<f32>)> = triangles
+            .iter()
+            .filter_map(|&[a, b, c]| {
+                ConvexPolygon::try_new(vec![outline[a], outline[b], outline[c]])
+            })
+            .map(|triangle| (Isometry2::identity(), ShapeHandle::new(triangle)))
+            .collect();
+        if !parts.is_empty() {
+            return Some(Box::new(Compound::new(parts)));
+        }
     }
+    let hull = ConvexPolygon::try_from_points(&outline)?;
+    Some(Box::new(hull))
 }
 
-pub fn make_ship_mesh_renderer<Context>(
+pub fn sphere_volume(radius: f32) -> f32 {
+    (4.0 / 3.0) * std::f32::consts::PI * radius.powi(3)
+}
+
+/// Creates the players for a game, giving each one a default color
+pub fn make_players(num_players: usize) -> Box<[Player]> {
+    (0..num_players).map(make_player).collect()
+}
+
+/// Creates a player with the default color for the given ID
+pub fn make_player(player_id: usize) -> Player {
+    Player {
+        color: PLAYER_COLORS[player_id % PLAYER_COLORS.len()].into(),
+    }
+}
+
+/// Creates renderers for spheres that share the geometry of one unit sphere
+///
+/// Entities are scaled to match their radius. Because the geometry is shared, spheres whose
+/// materials only differ in color are drawn together, with each color passed as instance data.
+#[derive(Debug)]
+pub struct SphereRendererFactory<Context: RenderingContext> {
+    sphere: Mesh<Context>,
     renderer: Rc<dyn GameRenderer<Context = Context>>,
-    mesh: &Mesh<Context>,
+}
+
+impl<Context> SphereRendererFactory<Context>
+where
+    Context: RenderingContext + 'static,
+{
+    pub fn new(renderer: &Rc<dyn GameRenderer<Context = Context>>) -> Result<Self, ()> {
+        let material = Material::from_color(Rgba::new(1.0, 1.0, 1.0, 1.0), 0.0, 1.0);
+        Ok(SphereRendererFactory {
+            sphere: meshgen::gen_sphere(1.0, 10, renderer.context(), material)?,
+            renderer: Rc::clone(renderer),
+        })
+    }
+
+    /// Creates a renderer for a matte sphere with the given color
+    pub fn make_renderer(&self, color: Rgba) -> Rc<dyn EntityRenderer> {
+        self.make_renderer_with(Material::from_color(color, 0.0, 1.0))
+    }
+
+    pub fn make_renderer_with(&self, material: Material<Context>) -> Rc<dyn EntityRenderer> {
+        let primitives = self
+            .sphere
+            .primitives
+            .iter()
+            .map(|primitive| Primitive {
+                material: material.clone(),
+                geometry: Rc::clone(&primitive.geometry),
+            })
+            .collect();
+        Rc::new(MeshRenderer::new(
+            Rc::clone(&self.renderer),
+            Mesh::new(primitives),
+        ))
+    }
+}
+
+/// Creates an upright ship (without a renderer) for the given player
+pub fn make_ship<Context: RenderingContext>(
+    ship_scene: &Scene<Context>,
+    player_id: usize,
+    position: Vector2<f32>,
+) -> Entity {
+    let mut ship = Entity::new(Vector3::new(position.x, position.y, 0.0));
+    ship.collision_shape = Some(make_ship_collision_shape(ship_scene));
+    ship.ship = Some(Ship::new(player_id));
+    ship.transform.rotation = UnitQuaternion::from_axis_angle(&Vector3::x_axis(), PI * 0.5);
+    ship
+}
+
+/// Returns the collision shape stored in the first ship mesh that has one, or a small ball if
+/// none of them do
+pub fn make_ship_collision_shape<Context: RenderingContext>(
+    ship_scene: &Scene<Context>,
+) -> Box<dyn Shape<f32>> {
+    ship_scene
+        .meshes()
+        .iter()
+        .find_map(|(_, mesh)| make_collision_shape(mesh))
+        .unwrap_or_else(|| Box::new(Ball::new(0.5)))
+}
+
+/// Creates a renderer for a ship's scene, with its team colored parts in the player's color
+pub fn make_ship_renderer<Context>(
+    renderer: Rc<dyn GameRenderer<Context = Context>>,
+    scene: &Scene<Context>,
     color: &Rgb,
 ) -> Rc<dyn EntityRenderer>
 where
     Context: RenderingContext + 'static,
 {
-    let mut new_mesh: Mesh<Context> = mesh.clone();
-    for mut primitive in new_mesh.primitives.iter_mut() {
-        if let Some(ref extra) = primitive.material.extras {
-            if let Some(team_color) = extra.get("team_color") {
-                if let Some(1) = team_color.as_u64() {
-                    primitive.material.base_color = color.alpha(1.0);
+    let colored_scene = scene.map_meshes(|mesh| {
+        let mut new_mesh: Mesh<Context> = mesh.clone();
+        for mut primitive in new_mesh.primitives.iter_mut() {
+            if let Some(ref extra) = primitive.material.extras {
+                if let Some(team_color) = extra.get("team_color") {
+                    if let Some(1) = team_color.as_u64() {
+                        primitive.material.base_color = color.alpha(1.0);
+                    }
                 }
             }
         }
-    }
-    Rc::new(MeshRenderer::new(renderer, new_mesh))
+        new_mesh
+    });
+    Rc::new(SceneRenderer::new(renderer, &colored_scene))
 }
 
 fn make_collision_shape<Context: RenderingContext>(
@@ -214,3 +1019,61 @@ fn make_collision_shape<Context: RenderingContext>(
     }
     Some(Box::new(Polyline::new(points, None)))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::glue::recording::game_renderer::RecordingRenderer;
+    use crate::glue::recording::RecordingContext;
+
+    fn choose_ship_positions(
+        symmetry: MapSymmetry,
+        placement: PlacementConstraints,
+    ) -> Result<Vec<Vector2<f32>>, PlacementError> {
+        let context = Rc::new(RecordingContext::new(400, 300));
+        let renderer = Rc::new(RecordingRenderer::new(context).unwrap());
+        let mut state = GameState::new(Box::new(|_| None));
+        let ship_scene = Scene::new(Vec::new());
+        let params = MapgenParams {
+            game_state: &mut state,
+            width: 100.0,
+            height: 60.0,
+            num_players: 2,
+            num_systems: 0,
+            exotic_frequencies: ExoticFrequencies::none(),
+            num_asteroid_belts: 0,
+            placement,
+            symmetry,
+            validate_fairness: false,
+            game_renderer: renderer,
+            ship_scene: &ship_scene,
+            make_ship_renderer: Box::new(|_| Err(())),
+        };
+        params.choose_ship_positions(&[0, 1])
+    }
+
+    #[test]
+    fn reports_when_ships_have_no_symmetric_layout() {
+        let placement = PlacementConstraints {
+            min_opponent_distance: 1000.0,
+            ..PlacementConstraints::none()
+        };
+        assert!(matches!(
+            choose_ship_positions(MapSymmetry::Mirror, placement),
+            Err(PlacementError::NoSymmetricLayout)
+        ));
+        assert!(matches!(
+            choose_ship_positions(MapSymmetry::None, placement),
+            Err(PlacementError::NotEnoughSeparatedPositions {
+                placed: 1,
+                required: 2
+            })
+        ));
+        assert_eq!(
+            choose_ship_positions(MapSymmetry::Mirror, PlacementConstraints::none())
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+}
@@ -1,23 +1,41 @@
+use std::fmt::Debug;
 use std::rc::Rc;
 
-use nalgebra::{Unit, Vector3};
+use nalgebra::{Point2, Unit, Vector2, Vector3};
+use ncollide2d::bounding_volume::AABB;
 use ncollide2d::query::Ray;
 
 use crate::rendering::light::SunLight;
+use crate::rendering::particle::ParticleSystem;
 use crate::rendering::scene::Camera;
 use crate::rendering::Rgb;
 use crate::state::constants::*;
+use crate::state::editor::MapEditor;
+use crate::state::entity::exotic::ExoticBody;
 use crate::state::entity::missile::MissileEvent;
 use crate::state::event::{InputEvent, InputEventError, MissileParams};
+use crate::state::gravity::{GravityTree, BARNES_HUT_THRESHOLD};
+use crate::state::rules::GameRules;
+use crate::state::spatial::SpatialGrid;
 
 pub use crate::state::entity::missile::MissileTrail;
 pub use crate::state::entity::*;
 pub use crate::state::turn::{GamePhase, Turn, TurnState};
 
 pub mod constants;
+pub mod editor;
+pub mod effects;
 pub mod entity;
 pub mod event;
+pub mod fairness;
+pub mod field;
+pub mod gravity;
+pub mod mapfile;
 pub mod mapgen;
+pub mod placement;
+pub mod rules;
+pub mod spatial;
+pub mod symmetry;
 pub mod turn;
 
 pub struct Player {
@@ -29,14 +47,41 @@ pub struct WorldLight {
     pub ambient: Rgb,
 }
 
-pub type RendererFactory = Box<dyn FnMut() -> Option<Rc<dyn EntityRenderer>>>;
+/// Creates a renderer for an entity belonging to the given player
+pub type RendererFactory = Box<dyn FnMut(&Player) -> Option<Rc<dyn EntityRenderer>>>;
+pub type PlayerRendererFactory = Box<dyn Fn(&Player) -> Result<Rc<dyn EntityRenderer>, ()>>;
+/// The aim, current player and gravity version that a trajectory preview was predicted from
+type PreviewKey = (MissileParams, usize, u64);
+
+/// Draws something that isn't tied to a single entity, such as a visualization of the whole map
+pub trait OverlayRenderer: Debug {
+    fn render(&self, world: &GameState);
+}
 
 pub struct GameState {
     pub entities: Vec<Entity>,
     players: Box<[Player]>,
     phase: GamePhase,
+    time: f32,
+    shots_fired: usize,
+    spatial_index: SpatialGrid,
+    gravity_tree: Option<GravityTree>,
+    // Changed whenever gravitating bodies might have moved or changed mass
+    gravity_version: u64,
+    editor: Option<MapEditor>,
+    aim: Option<MissileParams>,
+    // Along with the aim, player and gravity version that it was predicted from
+    trajectory_preview: Option<(PreviewKey, MissileTrail)>,
+    pub rules: GameRules,
     pub camera: Camera,
     pub light: WorldLight,
+    /// Explosions, debris and missile exhaust
+    pub particles: ParticleSystem,
+    pub particle_renderer: Option<Rc<dyn OverlayRenderer>>,
+    /// Draws the trajectory preview
+    pub preview_renderer: Option<Rc<dyn OverlayRenderer>>,
+    /// Drawn after all of the entities
+    pub overlay: Option<Rc<dyn OverlayRenderer>>,
     pub make_missile_renderer: RendererFactory,
 }
 
@@ -56,8 +101,21 @@ impl GameState {
             entities: Vec::new(),
             players: Box::from([]),
             phase: GamePhase::NotStarted,
+            time: 0.0,
+            shots_fired: 0,
+            spatial_index: SpatialGrid::new(),
+            gravity_tree: None,
+            gravity_version: 0,
+            editor: None,
+            aim: None,
+            trajectory_preview: None,
+            rules: GameRules::default(),
             camera,
             light,
+            particles: ParticleSystem::new(MAX_PARTICLES),
+            particle_renderer: None,
+            preview_renderer: None,
+            overlay: None,
             make_missile_renderer,
         }
     }
@@ -66,6 +124,116 @@ impl GameState {
         self.entities.iter()
     }
 
+    /// Adds an entity to the game, the spatial index and the gravity approximation, returning the
+    /// entity's index
+    pub fn add_entity(&mut self, entity: Entity) -> usize {
+        let index = self.entities.len();
+        let has_mass = entity.mass != 0.0;
+        self.entities.push(entity);
+        self.index_entity(index);
+        if has_mass {
+            self.refresh_gravity_tree();
+        }
+        index
+    }
+
+    /// Removes every entity from the game
+    pub fn clear_entities(&mut self) {
+        self.entities.clear();
+        self.rebuild_spatial_index();
+    }
+
+    /// Removes an entity, shifting the indices of later entities down by one
+    ///
+    /// Orbits around the removed entity are dropped, as are wormholes that lead to it.
+    pub fn remove_entity(&mut self, index: usize) -> Entity {
+        let removed = self.entities.remove(index);
+        let remap = |i: usize| match i {
+            i if i < index => Some(i),
+            i if i > index => Some(i - 1),
+            _ => None,
+        };
+        for entity in self.entities.iter_mut() {
+            if let Some(ref mut orbit) = entity.orbit {
+                if let Some(parent) = orbit.parent {
+                    match remap(parent) {
+                        Some(parent) => orbit.parent = Some(parent),
+                        None => entity.orbit = None,
+                    }
+                }
+            }
+            if let Some(ExoticBody::Wormhole { exit }) = entity.exotic {
+                entity.exotic = remap(exit).map(|exit| ExoticBody::Wormhole { exit });
+            }
+        }
+        self.rebuild_spatial_index();
+        removed
+    }
+
+    /// Rebuilds the spatial index and gravity approximation from scratch
+    ///
+    /// This must be called after modifying `entities` directly (rather than through `add_entity`).
+    pub fn rebuild_spatial_index(&mut self) {
+        self.spatial_index.clear();
+        for i in 0..self.entities.len() {
+            self.index_entity(i);
+        }
+        self.refresh_gravity_tree();
+    }
+
+    fn index_entity(&mut self, index: usize) {
+        let entity = &self.entities[index];
+        // Orbiting bodies occupy their whole orbit so the index doesn't need to track their motion.
+        let bounds = match self.orbit_sweep(index) {
+            Some((center, _, outer)) => {
+                let radius = outer + entity.collision_radius();
+                let extents = Vector2::new(radius, radius);
+                Some(AABB::new(
+                    Point2::from(center - extents),
+                    Point2::from(center + extents),
+                ))
+            }
+            None => entity.bounds(),
+        };
+        if let Some(bounds) = bounds {
+            self.spatial_index.insert(index, &bounds);
+        }
+    }
+
+    fn refresh_gravity_tree(&mut self) {
+        self.gravity_version += 1;
+        let bodies = self.entities.iter().filter(|e| e.mass != 0.0);
+        self.gravity_tree = if bodies.clone().count() > BARNES_HUT_THRESHOLD {
+            Some(GravityTree::build(bodies.map(|e| (*e.position(), e.mass))))
+        } else {
+            None
+        };
+    }
+
+    /// Returns the entities whose collision shapes might overlap the given region
+    pub fn entities_near<'a>(
+        &'a self,
+        bounds: &AABB<f32>,
+    ) -> impl Iterator<Item = (usize, &'a Entity)> + 'a {
+        self.spatial_index
+            .query(bounds)
+            .into_iter()
+            .map(move |i| (i, &self.entities[i]))
+    }
+
+    /// Returns the combined gravitational acceleration of all entities on a mass at `pos`
+    pub fn gravity_at(&self, pos: &Vector3<f32>) -> Vector3<f32> {
+        match self.gravity_tree {
+            Some(ref tree) => tree.acceleration_at(pos),
+            None => self
+                .entities
+                .iter()
+                .filter(|e| e.mass != 0.0 && e.position() != pos)
+                .map(|e| e.gravity_at(pos))
+                .sum(),
+        }
+    }
+
     pub fn get_ship(&self) -> Option<&Entity> {
         let id = self.phase.turn()?.current_player;
         self.entities.iter().find(|ref e| match e.ship {
@@ -98,7 +266,100 @@ impl GameState {
         self.phase.turn()
     }
 
+    /// Returns the number of missiles that have been fired this game
+    pub fn shots_fired(&self) -> usize {
+        self.shots_fired
+    }
+
+    /// Returns the game time (in seconds) used to position orbiting bodies
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Advances the game clock by one tick and moves orbiting bodies to match
+    pub fn update_bodies(&mut self) {
+        self.time += TICK_INTERVAL;
+        self.update_body_positions();
+    }
+
+    /// Moves all orbiting bodies to their positions at the current game time
+    pub fn update_body_positions(&mut self) {
+        let mut moved = false;
+        for i in 0..self.entities.len() {
+            if self.entities[i].orbit.is_some() {
+                let position = self.body_position_at(i, self.time);
+                self.entities[i].transform.position = position;
+                moved = true;
+            }
+        }
+        if moved {
+            self.gravity_version += 1;
+            if self.gravity_tree.is_some() {
+                self.refresh_gravity_tree();
+            }
+        }
+    }
+
+    /// Returns a number that changes whenever the positions or masses of gravitating bodies might
+    /// have changed, so that anything derived from the gravitational field can tell when it's stale
+    pub fn gravity_version(&self) -> u64 {
+        self.gravity_version
+    }
+
+    /// Returns the position of the given entity at the given game time
+    ///
+    /// Entities that aren't orbiting anything are assumed to be stationary.
+    pub fn body_position_at(&self, index: usize, time: f32) -> Vector3<f32> {
+        let mut position = Vector2::zeros();
+        let mut current = index;
+        // Walk up the orbit hierarchy; the depth limit guards against cycles.
+        for _ in 0..self.entities.len() {
+            let entity = &self.entities[current];
+            match entity.orbit {
+                Some(ref orbit) => {
+                    position += orbit.offset_at(time);
+                    match orbit.parent {
+                        Some(parent) => current = parent,
+                        None => {
+                            position += orbit.center;
+                            break;
+                        }
+                    }
+                }
+                None => {
+                    position += entity.position().xy();
+                    break;
+                }
+            }
+        }
+        let z = self.entities[index].position().z;
+        Vector3::new(position.x, position.y, z)
+    }
+
+    /// Returns the region swept out by an orbiting entity's center
+    ///
+    /// The region is an annulus, returned as its center and its inner and outer radii.
+    pub fn orbit_sweep(&self, index: usize) -> Option<(Vector2<f32>, f32, f32)> {
+        let orbit = self.entities[index].orbit.as_ref()?;
+        match orbit.parent {
+            Some(parent) => match self.orbit_sweep(parent) {
+                Some((center, inner, outer)) => Some((
+                    center,
+                    (inner - orbit.apoapsis()).max(0.0),
+                    outer + orbit.apoapsis(),
+                )),
+                None => Some((
+                    self.entities[parent].position().xy(),
+                    orbit.periapsis(),
+                    orbit.apoapsis(),
+                )),
+            },
+            None => Some((orbit.center, orbit.periapsis(), orbit.apoapsis())),
+        }
+    }
+
     pub fn start_game(&mut self) {
+        self.rebuild_spatial_index();
         let turn = Turn::new(0);
         let next_phase =
             turn.skip_eliminated_players(self.players.len(), &mut self.active_players());
@@ -117,49 +378,38 @@ impl GameState {
                 Ok(())
             }
             InputEvent::FireMissile(params) => self.fire_missile(*params),
+            InputEvent::Aim(params) => {
+                check_missile_params(params)?;
+                self.aim = Some(*params);
+                self.update_trajectory_preview();
+                Ok(())
+            }
+            InputEvent::Edit(edit) => self.handle_edit(edit).map_err(InputEventError::InvalidEdit),
         }
     }
 
     fn fire_missile(&mut self, params: MissileParams) -> Result<(), InputEventError> {
-        let turn = self.turn().ok_or(InputEventError::CannotFireNow)?;
+        let turn = *self.turn().ok_or(InputEventError::CannotFireNow)?;
         if turn.state != TurnState::Aiming {
             return Err(InputEventError::CannotFireNow);
         }
-        if !params.angle.is_finite() {
-            return Err(InputEventError::InvalidMissileAngle);
-        }
-        if params.speed < 0.0 || params.speed > MISSILE_MAX_VELOCITY {
-            return Err(InputEventError::InvalidMissileSpeed);
-        }
+        check_missile_params(&params)?;
 
         let missile = {
             let ship = self
                 .get_ship()
                 .ok_or(InputEventError::NoShipToFireMissile)?;
-            let speed = params.speed * MISSILE_VELOCITY_SCALE;
-            let mut position = *ship.position();
-            let direction = Vector3::new(params.angle.cos(), params.angle.sin(), 0.0);
-            let velocity = speed * direction;
-            if let Some(ref shape) = ship.collision_shape {
-                let radius = shape.bounding_sphere(&ship.collision_transform()).radius();
-                // Make sure we've gotten past the ship's own collision shape. Convex shapes may have multiple intersections before achieving clearance.
-                while (position - ship.position()).magnitude_squared() < radius * radius {
-                    let ray = Ray::new(position.xy().into(), direction.xy());
-                    if let Some(collision) = ship.ray_time_to_collision(&ray, radius, false) {
-                        position += direction * (collision + 0.01);
-                    } else {
-                        break;
-                    }
-                }
-            }
-            let trail = MissileTrail::new(turn.current_player, position, velocity);
+            let mut trail = launch_missile(ship, turn.current_player, params);
+            trail.shot = self.shots_fired;
 
             let mut entity = Entity::new(*ship.position());
             entity.missile_trail = Some(trail);
-            entity.renderer = (self.make_missile_renderer)();
+            entity.renderer = (self.make_missile_renderer)(&self.players[turn.current_player]);
             entity
         };
-        self.entities.push(missile);
+        self.add_entity(missile);
+        self.shots_fired += 1;
+        self.trajectory_preview = None;
         match &mut self.phase {
             GamePhase::Playing(turn) => turn.state = TurnState::Firing,
             _ => unreachable!(),
@@ -174,33 +424,166 @@ impl GameState {
         };
 
         let mut events = Vec::new();
-        let entities = &mut self.entities[..];
-        for i in 0..entities.len() {
-            // Break off mutable slices to all of the other entities.
-            let (before, after) = entities.split_at_mut(i);
-            let (entity, after) = after.split_first_mut().unwrap();
-            if let Some(ref mut missile) = entity.missile_trail {
-                let before_with_index = before.iter().enumerate();
-                let after_with_index = after.iter().enumerate().map(|(n, e)| (i + n, e));
-                let event = missile.update(&mut before_with_index.chain(after_with_index));
-                if let Some(new_pos) = missile.positions().last() {
-                    entity.transform.position = *new_pos;
-                }
-                if let Some(event) = event {
-                    events.push(event);
-                }
+        for i in 0..self.entities.len() {
+            // Take the missile out of its entity so we can look at the other entities while updating it.
+            let mut missile = match self.entities[i].missile_trail.take() {
+                Some(missile) => missile,
+                None => continue,
+            };
+            let event = self.step_missile(&mut missile);
+            let entity = &mut self.entities[i];
+            if let Some(new_pos) = missile.positions().last() {
+                entity.transform.position = *new_pos;
+            }
+            entity.missile_trail = Some(missile);
+            match event {
+                Some(event) => events.push((i, event)),
+                None => self.emit_exhaust(i),
             }
         }
-        for event in events.iter() {
-            self.handle_missile_event(event);
+        for (missile, event) in events.iter() {
+            self.emit_missile_event_effects(*missile, event);
+            self.handle_missile_event(*missile, event);
         }
-        if !events.is_empty() {
+        if events.iter().any(|(_, e)| e.ends_flight()) {
             let next_phase = turn.next_player(self.players.len(), &mut self.active_players());
             self.phase = next_phase;
         }
     }
 
-    fn handle_missile_event(&mut self, event: &MissileEvent) {
+    /// Moves a missile forward by one tick, checking it for collisions with other entities
+    fn step_missile(&self, missile: &mut MissileTrail) -> Option<MissileEvent> {
+        let pos = missile.positions().last()?;
+        let gravity = self.gravity_at(pos);
+        let bounds = missile.step_bounds();
+        missile.update(&mut self.entities_near(&bounds), gravity)
+    }
+
+    /// Simulates a missile fired from the given ship without changing the game
+    ///
+    /// Orbiting bodies move during the simulated flight just as they would during a real one, and
+    /// are returned to their current positions afterward. Returns the event that ended the flight.
+    pub fn simulate_shot(&mut self, ship: usize, params: MissileParams) -> Option<MissileEvent> {
+        let entity = &self.entities[ship];
+        let player_id = entity.ship.as_ref()?.player_id;
+        let mut missile = launch_missile(entity, player_id, params);
+        self.simulate_flight(&mut missile, usize::MAX, true)
+    }
+
+    /// Simulates a missile like `simulate_shot`, but with every body held where it is
+    ///
+    /// This is much cheaper, since the gravity approximation doesn't need to be rebuilt on every
+    /// tick. The flight lasts for at most `ticks` ticks.
+    pub fn simulate_shot_frozen(
+        &mut self,
+        ship: usize,
+        params: MissileParams,
+        ticks: usize,
+    ) -> Option<MissileEvent> {
+        let entity = &self.entities[ship];
+        let player_id = entity.ship.as_ref()?.player_id;
+        let mut missile = launch_missile(entity, player_id, params);
+        self.simulate_flight(&mut missile, ticks, false)
+    }
+
+    /// Returns the path that a missile fired with the given parameters would take
+    ///
+    /// The missile is simulated for at most `ticks` ticks, exactly as in `simulate_shot`.
+    pub fn predict_trajectory(
+        &mut self,
+        ship: usize,
+        params: MissileParams,
+        ticks: usize,
+    ) -> Option<MissileTrail> {
+        let entity = &self.entities[ship];
+        let player_id = entity.ship.as_ref()?.player_id;
+        let mut missile = launch_missile(entity, player_id, params);
+        self.simulate_flight(&mut missile, ticks, true);
+        Some(missile)
+    }
+
+    /// Moves a missile forward for up to `ticks` ticks, then restores the game time
+    ///
+    /// Orbiting bodies only move along with the missile if `move_bodies` is set. Returns the event
+    /// that ended the flight, if it ended.
+    fn simulate_flight(
+        &mut self,
+        missile: &mut MissileTrail,
+        ticks: usize,
+        move_bodies: bool,
+    ) -> Option<MissileEvent> {
+        let start_time = self.time;
+        let mut result = None;
+        let mut tick = 0;
+        while missile.time_to_live > 0.0 && tick < ticks {
+            tick += 1;
+            if move_bodies {
+                self.update_bodies();
+            }
+            match self.step_missile(missile) {
+                Some(MissileEvent::EnteredWormhole { exit, .. }) => {
+                    self.teleport_trail(missile, exit);
+                }
+                Some(event) => {
+                    result = Some(event);
+                    break;
+                }
+                None => {}
+            }
+        }
+        if move_bodies {
+            self.time = start_time;
+            self.update_body_positions();
+        }
+        result
+    }
+
+    /// Returns the predicted path of the current player's aim, if it's being shown
+    pub fn trajectory_preview(&self) -> Option<&MissileTrail> {
+        self.trajectory_preview.as_ref().map(|(_, trail)| trail)
+    }
+
+    /// Recalculates the trajectory preview, if the aim, the current player or the positions of
+    /// gravitating bodies have changed since it was last calculated
+    pub fn update_trajectory_preview(&mut self) {
+        let current_player = match self.turn() {
+            Some(Turn {
+                state: TurnState::Aiming,
+                current_player,
+            }) => *current_player,
+            _ => {
+                self.trajectory_preview = None;
+                return;
+            }
+        };
+        let aim = match self.aim {
+            Some(aim) if self.rules.trajectory_preview => aim,
+            _ => {
+                self.trajectory_preview = None;
+                return;
+            }
+        };
+        if let Some((key, _)) = self.trajectory_preview {
+            if key == (aim, current_player, self.gravity_version) {
+                return;
+            }
+        }
+
+        self.trajectory_preview = None;
+        let ship = self.entities.iter().position(|e| match e.ship {
+            Some(ref ship) => ship.player_id == current_player,
+            None => false,
+        });
+        if let Some(ship) = ship {
+            let preview = self.predict_trajectory(ship, aim, TRAJECTORY_PREVIEW_TICKS);
+            // Bodies are put back where they were after the prediction moves them, but that
+            // still changes the gravity version.
+            let key = (aim, current_player, self.gravity_version);
+            self.trajectory_preview = preview.map(|trail| (key, trail));
+        }
+    }
+
+    fn handle_missile_event(&mut self, missile: usize, event: &MissileEvent) {
         match event {
             MissileEvent::Expired => {}
             MissileEvent::HitEntity(id) => {
@@ -208,6 +591,146 @@ impl GameState {
                     ship.state = ShipState::Disabled;
                 }
             }
+            MissileEvent::Swallowed(_) => {}
+            MissileEvent::EnteredWormhole { exit, .. } => self.teleport_missile(missile, *exit),
+        }
+    }
+
+    /// Moves a missile just outside the given wormhole exit, keeping its velocity
+    fn teleport_missile(&mut self, missile: usize, exit: usize) {
+        let mut trail = match self.entities[missile].missile_trail.take() {
+            Some(trail) => trail,
+            None => return,
+        };
+        let position = self.teleport_trail(&mut trail, exit);
+        let entity = &mut self.entities[missile];
+        if let Some(position) = position {
+            entity.transform.position = position;
+        }
+        entity.missile_trail = Some(trail);
+    }
+
+    /// Moves a missile trail just outside the given wormhole exit, returning its new position
+    fn teleport_trail(&self, trail: &mut MissileTrail, exit: usize) -> Option<Vector3<f32>> {
+        let exit_entity = self.entities.get(exit)?;
+        let direction = trail.velocity.try_normalize(f32::EPSILON)?;
+        let position = exit_entity.position()
+            + direction * (exit_entity.collision_radius() + WORMHOLE_EXIT_CLEARANCE);
+        trail.teleport(position);
+        Some(position)
+    }
+}
+
+fn check_missile_params(params: &MissileParams) -> Result<(), InputEventError> {
+    if !params.angle.is_finite() {
+        return Err(InputEventError::InvalidMissileAngle);
+    }
+    if params.speed < 0.0 || params.speed > MISSILE_MAX_VELOCITY {
+        return Err(InputEventError::InvalidMissileSpeed);
+    }
+    Ok(())
+}
+
+/// Creates a missile fired from a ship, starting just outside the ship's collision shape
+fn launch_missile(ship: &Entity, player_id: usize, params: MissileParams) -> MissileTrail {
+    let speed = params.speed * MISSILE_VELOCITY_SCALE;
+    let mut position = *ship.position();
+    let direction = Vector3::new(params.angle.cos(), params.angle.sin(), 0.0);
+    let velocity = speed * direction;
+    if let Some(ref shape) = ship.collision_shape {
+        let radius = shape.bounding_sphere(&ship.collision_transform()).radius();
+        // Make sure we've gotten past the ship's own collision shape. Convex shapes may have multiple intersections before achieving clearance.
+        while (position - ship.position()).magnitude_squared() < radius * radius {
+            let ray = Ray::new(position.xy().into(), direction.xy());
+            if let Some(collision) = ship.ray_time_to_collision(&ray, radius, false) {
+                position += direction * (collision + 0.01);
+            } else {
+                break;
+            }
+        }
+    }
+    MissileTrail::new(player_id, position, velocity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::entity::orbit::Orbit;
+    use crate::state::mapgen::make_players;
+
+    const AIM: MissileParams = MissileParams {
+        angle: 0.5,
+        speed: 10.0,
+    };
+
+    /// Returns a game between two ships, with a planet orbiting between them
+    fn aiming_game(rules: GameRules) -> GameState {
+        let mut state = GameState::new(Box::new(|_| None));
+        state.rules = rules;
+        state.set_players(make_players(2));
+        for player in 0..2 {
+            let mut ship = Entity::new(Vector3::new(player as f32 * 40.0 - 20.0, 0.0, 0.0));
+            ship.ship = Some(Ship::new(player));
+            state.add_entity(ship);
         }
+        let mut planet = Entity::new(Vector3::zeros());
+        planet.mass = 100.0;
+        planet.orbit = Some(Orbit::circular(None, 5.0, 30.0, 0.0));
+        state.add_entity(planet);
+        state.start_game();
+        state
+    }
+
+    #[test]
+    fn added_bodies_pull_once_the_gravity_tree_is_used() {
+        let mut state = GameState::new(Box::new(|_| None));
+        for i in 0..=BARNES_HUT_THRESHOLD {
+            let mut body = Entity::new(Vector3::new(-100.0, i as f32, 0.0));
+            body.mass = 1.0;
+            state.add_entity(body);
+        }
+        state.rebuild_spatial_index();
+        let before = state.gravity_at(&Vector3::zeros());
+
+        let mut planet = Entity::new(Vector3::new(10.0, 0.0, 0.0));
+        planet.mass = 1000.0;
+        let expected = planet.gravity_at(&Vector3::zeros());
+        state.add_entity(planet);
+
+        let pull = state.gravity_at(&Vector3::zeros()) - before;
+        assert!(expected.x > 0.0);
+        assert!((pull - expected).norm() < expected.norm() * 0.01);
+    }
+
+    #[test]
+    fn trajectory_preview_is_only_predicted_when_something_changes() {
+        let mut state = aiming_game(GameRules::practice());
+        state.handle_input(&InputEvent::Aim(AIM)).unwrap();
+        assert!(state.trajectory_preview().is_some());
+
+        // Predicting moves the orbiting planet, which changes the gravity version.
+        let version = state.gravity_version();
+        state.update_trajectory_preview();
+        assert_eq!(state.gravity_version(), version);
+
+        state.update_bodies();
+        let moved = state.gravity_version();
+        state.update_trajectory_preview();
+        assert_ne!(state.gravity_version(), moved);
+
+        let version = state.gravity_version();
+        let aim = MissileParams { angle: 1.0, ..AIM };
+        state.handle_input(&InputEvent::Aim(aim)).unwrap();
+        assert_ne!(state.gravity_version(), version);
+    }
+
+    #[test]
+    fn trajectory_preview_is_skipped_when_the_rules_disable_it() {
+        let mut state = aiming_game(GameRules::competitive());
+        let version = state.gravity_version();
+        state.handle_input(&InputEvent::Aim(AIM)).unwrap();
+        state.update_trajectory_preview();
+        assert!(state.trajectory_preview().is_none());
+        assert_eq!(state.gravity_version(), version);
     }
 }
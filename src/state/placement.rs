@@ -0,0 +1,264 @@
+use std::error::Error;
+use std::f32::consts::PI;
+use std::fmt::Display;
+
+use nalgebra::Vector2;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+// Number of candidates generated around each active sample before it's retired
+const POISSON_CANDIDATES_PER_SAMPLE: usize = 30;
+// Maximum number of grid cells used by a Poisson-disk sampler (which limits its spacing)
+const MAX_POISSON_CELLS: usize = 1 << 16;
+// Number of random orderings to try when choosing ship positions
+const MAX_SHIP_LAYOUT_TRIES: usize = 32;
+
+#[derive(Clone, Copy, Debug)]
+pub enum PlacementError {
+    /// The edge margin doesn't leave any room on the map
+    NoRoomInsideMargin,
+    /// Every candidate position overlaps an existing entity
+    NoFreeSpace,
+    /// Too few positions satisfy the distance constraints
+    NotEnoughSeparatedPositions { placed: usize, required: usize },
+    /// No position for the first ship has symmetric copies that are all inside the edge margin
+    /// and far enough apart
+    NoSymmetricLayout,
+}
+
+impl Display for PlacementError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PlacementError::NoRoomInsideMargin => {
+                write!(f, "The map is too small for its edge margin")
+            }
+            PlacementError::NoFreeSpace => write!(f, "No free space to place an entity"),
+            PlacementError::NotEnoughSeparatedPositions { placed, required } => write!(
+                f,
+                "Only {} of {} ships could be placed far enough apart",
+                placed, required
+            ),
+            PlacementError::NoSymmetricLayout => write!(
+                f,
+                "No ship position has symmetric copies that fit on the map far enough apart"
+            ),
+        }
+    }
+}
+
+impl Error for PlacementError {}
+
+/// Rules for where ships are allowed to be placed
+#[derive(Clone, Copy, Debug)]
+pub struct PlacementConstraints {
+    /// Minimum distance between any two ships
+    pub min_ship_distance: f32,
+    /// Minimum distance between a ship's collision shape and any other body
+    pub ship_clearance: f32,
+    /// Minimum distance between a ship and the edges of the map
+    pub edge_margin: f32,
+    /// Minimum distance between ships belonging to different players
+    pub min_opponent_distance: f32,
+}
+
+impl PlacementConstraints {
+    /// Constraints that only prevent ships from overlapping other entities
+    pub fn none() -> PlacementConstraints {
+        PlacementConstraints {
+            min_ship_distance: 0.0,
+            ship_clearance: 0.0,
+            edge_margin: 0.0,
+            min_opponent_distance: 0.0,
+        }
+    }
+
+    /// Returns the minimum distance between ships owned by the given players
+    pub fn required_separation(&self, player_a: usize, player_b: usize) -> f32 {
+        if player_a == player_b {
+            self.min_ship_distance
+        } else {
+            self.min_ship_distance.max(self.min_opponent_distance)
+        }
+    }
+
+    /// Checks whether every pair of ships is far enough apart
+    ///
+    /// `owners` contains the player ID of the ship at each position.
+    pub fn is_valid_layout(&self, positions: &[Vector2<f32>], owners: &[usize]) -> bool {
+        positions.iter().enumerate().all(|(i, pos)| {
+            positions[..i].iter().enumerate().all(|(j, other)| {
+                (pos - other).magnitude() >= self.required_separation(owners[i], owners[j])
+            })
+        })
+    }
+
+    /// Chooses a position for each ship from a list of candidates
+    ///
+    /// `owners` contains the player ID of each ship. The returned positions are in the same order.
+    pub fn choose_ship_positions<R: Rng>(
+        &self,
+        rng: &mut R,
+        candidates: &[Vector2<f32>],
+        owners: &[usize],
+    ) -> Result<Vec<Vector2<f32>>, PlacementError> {
+        if candidates.is_empty() && !owners.is_empty() {
+            return Err(PlacementError::NoFreeSpace);
+        }
+
+        let mut order: Vec<usize> = (0..candidates.len()).collect();
+        let mut most_placed = 0;
+        for _ in 0..MAX_SHIP_LAYOUT_TRIES {
+            order.shuffle(rng);
+            let mut chosen: Vec<Vector2<f32>> = Vec::with_capacity(owners.len());
+            for (ship, &owner) in owners.iter().enumerate() {
+                let found = order.iter().map(|&i| candidates[i]).find(|pos| {
+                    chosen.iter().enumerate().all(|(other, other_pos)| {
+                        let separation = self.required_separation(owner, owners[other]);
+                        (pos - other_pos).magnitude() >= separation
+                    })
+                });
+                match found {
+                    Some(pos) => chosen.push(pos),
+                    None => {
+                        most_placed = most_placed.max(ship);
+                        break;
+                    }
+                }
+            }
+            if chosen.len() == owners.len() {
+                return Ok(chosen);
+            }
+        }
+
+        Err(PlacementError::NotEnoughSeparatedPositions {
+            placed: most_placed,
+            required: owners.len(),
+        })
+    }
+}
+
+impl Default for PlacementConstraints {
+    fn default() -> Self {
+        PlacementConstraints {
+            min_ship_distance: 10.0,
+            ship_clearance: 2.0,
+            edge_margin: 5.0,
+            min_opponent_distance: 40.0,
+        }
+    }
+}
+
+/// Generates evenly spread random points in a rectangle using Bridson's algorithm
+///
+/// No two points are closer than the sampler's spacing, and every part of the rectangle is within
+/// twice the spacing of some point. Points are generated lazily, spreading outward from a random
+/// starting point.
+pub struct PoissonDiskSampler<'a, R: Rng> {
+    rng: &'a mut R,
+    mins: Vector2<f32>,
+    maxs: Vector2<f32>,
+    spacing: f32,
+    cell_size: f32,
+    columns: usize,
+    rows: usize,
+    // Index of the sample in each grid cell (cells are small enough to hold at most one)
+    grid: Vec<Option<usize>>,
+    samples: Vec<Vector2<f32>>,
+    active: Vec<usize>,
+}
+
+impl<'a, R: Rng> PoissonDiskSampler<'a, R> {
+    pub fn new(
+        rng: &'a mut R,
+        mins: Vector2<f32>,
+        maxs: Vector2<f32>,
+        spacing: f32,
+    ) -> PoissonDiskSampler<'a, R> {
+        let size = (maxs - mins).sup(&Vector2::zeros());
+        // Increase the spacing if the grid would otherwise be too large.
+        let min_spacing = (size.x * size.y * 2.0 / MAX_POISSON_CELLS as f32).sqrt();
+        let spacing = spacing.max(min_spacing).max(f32::EPSILON);
+        let cell_size = spacing / 2.0f32.sqrt();
+        let columns = (size.x / cell_size).ceil() as usize + 1;
+        let rows = (size.y / cell_size).ceil() as usize + 1;
+        PoissonDiskSampler {
+            rng,
+            mins,
+            maxs,
+            spacing,
+            cell_size,
+            columns,
+            rows,
+            grid: vec![None; columns * rows],
+            samples: Vec::new(),
+            active: Vec::new(),
+        }
+    }
+
+    fn cell_of(&self, pos: &Vector2<f32>) -> (usize, usize) {
+        let offset = (pos - self.mins) / self.cell_size;
+        (
+            (offset.x as usize).min(self.columns - 1),
+            (offset.y as usize).min(self.rows - 1),
+        )
+    }
+
+    fn in_bounds(&self, pos: &Vector2<f32>) -> bool {
+        pos.x >= self.mins.x && pos.x <= self.maxs.x && pos.y >= self.mins.y && pos.y <= self.maxs.y
+    }
+
+    fn is_far_enough(&self, pos: &Vector2<f32>) -> bool {
+        let (column, row) = self.cell_of(pos);
+        for y in row.saturating_sub(2)..(row + 3).min(self.rows) {
+            for x in column.saturating_sub(2)..(column + 3).min(self.columns) {
+                if let Some(i) = self.grid[y * self.columns + x] {
+                    if (self.samples[i] - pos).magnitude_squared() < self.spacing * self.spacing {
+                        return false;
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    fn add_sample(&mut self, pos: Vector2<f32>) -> Vector2<f32> {
+        let (column, row) = self.cell_of(&pos);
+        let index = self.samples.len();
+        self.grid[row * self.columns + column] = Some(index);
+        self.samples.push(pos);
+        self.active.push(index);
+        pos
+    }
+}
+
+impl<'a, R: Rng> Iterator for PoissonDiskSampler<'a, R> {
+    type Item = Vector2<f32>;
+
+    fn next(&mut self) -> Option<Vector2<f32>> {
+        if self.mins.x > self.maxs.x || self.mins.y > self.maxs.y {
+            return None;
+        }
+        if self.samples.is_empty() {
+            let pos = Vector2::new(
+                self.rng.gen_range(self.mins.x..=self.maxs.x),
+                self.rng.gen_range(self.mins.y..=self.maxs.y),
+            );
+            return Some(self.add_sample(pos));
+        }
+
+        while !self.active.is_empty() {
+            let active_index = self.rng.gen_range(0..self.active.len());
+            let center = self.samples[self.active[active_index]];
+            for _ in 0..POISSON_CANDIDATES_PER_SAMPLE {
+                let angle = self.rng.gen_range(0.0..2.0 * PI);
+                let distance = self.rng.gen_range(self.spacing..2.0 * self.spacing);
+                let pos = center + Vector2::new(angle.cos(), angle.sin()) * distance;
+                if self.in_bounds(&pos) && self.is_far_enough(&pos) {
+                    return Some(self.add_sample(pos));
+                }
+            }
+            self.active.swap_remove(active_index);
+        }
+        None
+    }
+}
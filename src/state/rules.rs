@@ -0,0 +1,28 @@
+/// Options that change how a game is played
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct GameRules {
+    /// Whether the predicted path of the current aim is shown while aiming
+    pub trajectory_preview: bool,
+}
+
+impl GameRules {
+    /// Rules for competitive play, without aiming aids
+    pub fn competitive() -> GameRules {
+        GameRules {
+            trajectory_preview: false,
+        }
+    }
+
+    /// Rules for practice and tutorials, with aiming aids
+    pub fn practice() -> GameRules {
+        GameRules {
+            trajectory_preview: true,
+        }
+    }
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        GameRules::competitive()
+    }
+}
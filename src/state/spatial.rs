@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+use ncollide2d::bounding_volume::AABB;
+
+/// Width and height of each grid cell (in game units)
+pub const GRID_CELL_SIZE: f32 = 16.0;
+// Maximum number of cells along either axis that a single query or insertion will cover
+const MAX_CELL_SPAN: i32 = 256;
+
+/// Uniform grid that maps regions of the map to the entities that might occupy them
+#[derive(Clone, Debug, Default)]
+pub struct SpatialGrid {
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    // Entities whose bounds were too large to store efficiently in cells
+    oversized: Vec<usize>,
+}
+
+impl SpatialGrid {
+    pub fn new() -> SpatialGrid {
+        SpatialGrid {
+            cells: HashMap::new(),
+            oversized: Vec::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.oversized.clear();
+    }
+
+    pub fn insert(&mut self, index: usize, bounds: &AABB<f32>) {
+        match cell_range(bounds) {
+            Some((min, max)) => {
+                for x in min.0..=max.0 {
+                    for y in min.1..=max.1 {
+                        self.cells.entry((x, y)).or_default().push(index);
+                    }
+                }
+            }
+            None => self.oversized.push(index),
+        }
+    }
+
+    /// Returns the indices of all entities whose bounds might overlap the given region
+    ///
+    /// The indices are sorted and contain no duplicates.
+    pub fn query(&self, bounds: &AABB<f32>) -> Vec<usize> {
+        let mut result = self.oversized.clone();
+        match cell_range(bounds) {
+            Some((min, max)) => {
+                for x in min.0..=max.0 {
+                    for y in min.1..=max.1 {
+                        if let Some(cell) = self.cells.get(&(x, y)) {
+                            result.extend(cell.iter().cloned());
+                        }
+                    }
+                }
+            }
+            None => result.extend(self.cells.values().flatten().cloned()),
+        }
+        result.sort_unstable();
+        result.dedup();
+        result
+    }
+}
+
+fn cell_coord(value: f32) -> i32 {
+    (value / GRID_CELL_SIZE).floor() as i32
+}
+
+fn cell_range(bounds: &AABB<f32>) -> Option<((i32, i32), (i32, i32))> {
+    let mins = bounds.mins;
+    let maxs = bounds.maxs;
+    if !(mins.x.is_finite() && mins.y.is_finite() && maxs.x.is_finite() && maxs.y.is_finite()) {
+        return None;
+    }
+    let min = (cell_coord(mins.x), cell_coord(mins.y));
+    let max = (cell_coord(maxs.x), cell_coord(maxs.y));
+    // Huge bounds saturate to the ends of the range, so the spans can overflow an i32.
+    let span = |min: i32, max: i32| i64::from(max) - i64::from(min);
+    if span(min.0, max.0) > i64::from(MAX_CELL_SPAN)
+        || span(min.1, max.1) > i64::from(MAX_CELL_SPAN)
+    {
+        return None;
+    }
+    Some((min, max))
+}
@@ -0,0 +1,161 @@
+use std::f32::consts::PI;
+
+use nalgebra::{Isometry2, Point2, Rotation2, UnitQuaternion, Vector2, Vector3};
+use ncollide2d::shape::{Ball, Compound, ConvexPolygon, Polyline, Shape, ShapeHandle};
+
+use crate::state::orbit::Orbit;
+
+/// How a generated map's layout is repeated for each player
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MapSymmetry {
+    /// Entities are placed independently
+    None,
+    /// The map is mirrored across the Y axis (only supported for two players)
+    Mirror,
+    /// The map is rotated around its center once for each player
+    Rotational,
+}
+
+impl MapSymmetry {
+    /// Returns the transforms that map the first player's part of the map to each player's part
+    ///
+    /// The first transform is always the identity. Returns `None` if the symmetry doesn't support
+    /// the given number of players.
+    pub fn transforms(&self, num_players: usize) -> Option<Vec<SymmetryTransform>> {
+        match self {
+            MapSymmetry::None => Some(vec![SymmetryTransform::identity()]),
+            MapSymmetry::Mirror => match num_players {
+                2 => Some(vec![
+                    SymmetryTransform::identity(),
+                    SymmetryTransform {
+                        angle: 0.0,
+                        mirrored: true,
+                    },
+                ]),
+                _ => None,
+            },
+            MapSymmetry::Rotational => Some(
+                (0..num_players.max(1))
+                    .map(|i| SymmetryTransform {
+                        angle: 2.0 * PI * i as f32 / num_players as f32,
+                        mirrored: false,
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// A rotation around the map's center, optionally preceded by a reflection across the Y axis
+#[derive(Clone, Copy, Debug)]
+pub struct SymmetryTransform {
+    pub angle: f32,
+    pub mirrored: bool,
+}
+
+impl SymmetryTransform {
+    pub fn identity() -> SymmetryTransform {
+        SymmetryTransform {
+            angle: 0.0,
+            mirrored: false,
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.angle == 0.0 && !self.mirrored
+    }
+
+    pub fn apply(&self, pos: &Vector2<f32>) -> Vector2<f32> {
+        let reflected = if self.mirrored {
+            Vector2::new(-pos.x, pos.y)
+        } else {
+            *pos
+        };
+        Rotation2::new(self.angle) * reflected
+    }
+
+    /// Transforms the angle of a direction (in radians from the X axis)
+    pub fn apply_angle(&self, angle: f32) -> f32 {
+        let reflected = if self.mirrored { PI - angle } else { angle };
+        reflected + self.angle
+    }
+
+    /// Transforms the rotation of an entity
+    ///
+    /// Entity meshes can't be reflected, so mirrored entities are turned around instead.
+    pub fn apply_rotation(&self, rotation: &UnitQuaternion<f32>) -> UnitQuaternion<f32> {
+        let turn = if self.mirrored { PI } else { 0.0 };
+        UnitQuaternion::from_axis_angle(&Vector3::z_axis(), self.angle + turn) * rotation
+    }
+
+    /// Transforms an orbit, keeping its parent
+    ///
+    /// Mirrored orbits run in the opposite direction.
+    pub fn apply_orbit(&self, orbit: &Orbit) -> Orbit {
+        let (period, phase) = if self.mirrored {
+            (-orbit.period, -orbit.phase)
+        } else {
+            (orbit.period, orbit.phase)
+        };
+        Orbit {
+            center: self.apply(&orbit.center),
+            periapsis_angle: self.apply_angle(orbit.periapsis_angle),
+            period,
+            phase,
+            ..orbit.clone()
+        }
+    }
+
+    /// Transforms the points of an outline, preserving its winding order
+    pub fn apply_outline(&self, outline: &[Point2<f32>]) -> Vec<Point2<f32>> {
+        let mut points: Vec<Point2<f32>> = outline
+            .iter()
+            .map(|p| Point2::from(self.apply(&p.coords)))
+            .collect();
+        if self.mirrored {
+            points.reverse();
+        }
+        points
+    }
+
+    /// Transforms a collision shape (in the entity's local space)
+    ///
+    /// Shapes other than balls, convex polygons, compounds of convex polygons and polylines are
+    /// replaced with a bounding ball.
+    pub fn apply_shape(&self, shape: &dyn Shape<f32>) -> Box<dyn Shape<f32>> {
+        if let Some(ball) = shape.as_shape::<Ball<f32>>() {
+            return Box::new(*ball);
+        }
+        if let Some(polygon) = shape.as_shape::<ConvexPolygon<f32>>() {
+            if let Some(polygon) = ConvexPolygon::try_new(self.apply_outline(polygon.points())) {
+                return Box::new(polygon);
+            }
+        }
+        if let Some(polyline) = shape.as_shape::<Polyline<f32>>() {
+            return Box::new(Polyline::new(self.apply_outline(polyline.points()), None));
+        }
+        if let Some(compound) = shape.as_shape::<Compound<f32>>() {
+            let parts: Option<Vec<(Isometry2<f32>, ShapeHandle<f32>)>> = compound
+                .shapes()
+                .iter()
+                .map(|(delta, part)| {
+                    let points: Vec<Point2<f32>> = part
+                        .as_shape::<ConvexPolygon<f32>>()?
+                        .points()
+                        .iter()
+                        .map(|p| delta * p)
+                        .collect();
+                    let polygon = ConvexPolygon::try_new(self.apply_outline(&points))?;
+                    Some((Isometry2::identity(), ShapeHandle::new(polygon)))
+                })
+                .collect();
+            if let Some(parts) = parts {
+                return Box::new(Compound::new(parts));
+            }
+        }
+        let bounds = shape.local_bounding_sphere();
+        Box::new(Ball::new(
+            bounds.center().coords.magnitude() + bounds.radius(),
+        ))
+    }
+}
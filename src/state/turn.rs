@@ -1,6 +1,8 @@
 #[derive(Clone, Copy, Debug)]
 pub enum GamePhase {
     NotStarted,
+    /// The map editor is active
+    Editing,
     Playing(Turn),
     GameOver,
 }
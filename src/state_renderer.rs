@@ -1,77 +1,400 @@
 use std::cell::RefCell;
 use std::fmt::Debug;
 
-use nalgebra::Matrix4;
+use nalgebra::{Matrix4, Point3, Vector2, Vector3};
 use std::cell::Cell;
 use std::error::Error;
 use std::rc::Rc;
 
 use crate::rendering::context::RenderingContext;
 use crate::rendering::light::SunLight;
-use crate::rendering::line::{BoundLineShader, LineShader, LineWorldContext, PolyLine};
-use crate::rendering::material::{BoundMaterialShader, MaterialShader, MaterialWorldContext};
+use crate::rendering::line::{LineShader, LineStyle, LineWorldContext, ThickLine, ThickLineShader};
+use crate::rendering::material::{MaterialShader, MaterialWorldContext};
 use crate::rendering::mesh::Mesh;
+use crate::rendering::particle::{ParticleBuffer, ParticleShader};
+use crate::rendering::queue::{DrawCommand, DrawItem, QueueShaders, RenderPass, RenderQueue};
+use crate::rendering::scene::Scene;
+use crate::rendering::shader::ShaderBindError;
 use crate::rendering::Rgb;
-use crate::state::{Entity, EntityRenderer, GameState};
+use crate::state::field::GravityField;
+use crate::state::{Entity, EntityRenderer, GameState, MissileTrail, OverlayRenderer};
+
+/// Returns how far in front of the camera a point is
+fn view_depth(world: &GameState, position: &Vector3<f32>) -> f32 {
+    -world
+        .camera
+        .view()
+        .transform_point(&Point3::from(*position))
+        .z
+}
 
 #[derive(Debug)]
 pub struct MeshRenderer<Context: RenderingContext> {
-    mesh: Mesh<Context>,
+    mesh: Rc<Mesh<Context>>,
     renderer: Rc<dyn GameRenderer<Context = Context>>,
 }
 
 impl<Context: RenderingContext> MeshRenderer<Context> {
     pub fn new(renderer: Rc<dyn GameRenderer<Context = Context>>, mesh: Mesh<Context>) -> Self {
-        MeshRenderer { mesh, renderer }
+        MeshRenderer {
+            mesh: Rc::new(mesh),
+            renderer,
+        }
     }
 }
 
 impl<Context: RenderingContext> EntityRenderer for MeshRenderer<Context> {
     fn render(&self, entity: &Entity, world: &GameState) {
-        let context = self.renderer.context();
-        let mat_shader = self.renderer.material_shader();
-        let bound_shader = BoundMaterialShader::new(context, mat_shader, world).unwrap();
-
         let model_transform = entity.transform.to_similarity().to_homogeneous();
-        bound_shader.set_uniform_mat4(
-            self.renderer.material_shader().info.model_transform.index,
-            model_transform,
-        );
-        self.mesh.draw(&bound_shader);
+        let depth = view_depth(world, entity.position());
+        let queue = self.renderer.render_queue();
+        for (index, primitive) in self.mesh.primitives.iter().enumerate() {
+            let pass = if primitive.material.is_blended() {
+                RenderPass::Transparent
+            } else {
+                RenderPass::Opaque
+            };
+            let command = DrawCommand::Primitive {
+                mesh: Rc::clone(&self.mesh),
+                index,
+                model_transform,
+            };
+            queue.submit(DrawItem::new(pass, depth, command));
+        }
     }
 }
 
+/// Draws the meshes of a scene, placing the scene's origin at the entity
+#[derive(Debug)]
+pub struct SceneRenderer<Context: RenderingContext> {
+    /// Each mesh, with its transform relative to the scene
+    meshes: Vec<(Matrix4<f32>, Rc<Mesh<Context>>)>,
+    renderer: Rc<dyn GameRenderer<Context = Context>>,
+}
+
+impl<Context: RenderingContext> SceneRenderer<Context> {
+    pub fn new(renderer: Rc<dyn GameRenderer<Context = Context>>, scene: &Scene<Context>) -> Self {
+        SceneRenderer {
+            meshes: scene.meshes(),
+            renderer,
+        }
+    }
+}
+
+impl<Context: RenderingContext> EntityRenderer for SceneRenderer<Context> {
+    fn render(&self, entity: &Entity, world: &GameState) {
+        let entity_transform = entity.transform.to_similarity().to_homogeneous();
+        let queue = self.renderer.render_queue();
+        for (node_transform, mesh) in self.meshes.iter() {
+            let model_transform = entity_transform * node_transform;
+            let origin = model_transform.transform_point(&Point3::origin());
+            let depth = view_depth(world, &origin.coords);
+            for (index, primitive) in mesh.primitives.iter().enumerate() {
+                let pass = if primitive.material.is_blended() {
+                    RenderPass::Transparent
+                } else {
+                    RenderPass::Opaque
+                };
+                let command = DrawCommand::Primitive {
+                    mesh: Rc::clone(mesh),
+                    index,
+                    model_transform,
+                };
+                queue.submit(DrawItem::new(pass, depth, command));
+            }
+        }
+    }
+}
+
+// Number of ticks over which the oldest parts of a missile trail fade out
+const TRAIL_FADE_TICKS: f32 = 300.0;
+// Alpha of the fully faded parts of a missile trail
+const TRAIL_MIN_ALPHA: f32 = 0.25;
+// Opacity multiplier applied to a trail for each shot fired after it
+const TRAIL_SHOT_DIMMING: f32 = 0.6;
+// Lowest opacity that a trail is dimmed to by later shots
+const TRAIL_MIN_OPACITY: f32 = 0.15;
+const TRAIL_STYLE: LineStyle = LineStyle::solid(2.0);
+
 #[derive(Debug)]
 pub struct MissileTrailRenderer<Context: RenderingContext> {
-    line: RefCell<PolyLine<Context>>,
+    lines: RefCell<Vec<ThickLine<Context>>>,
+    color: Rgb,
     data_version: Cell<usize>,
     renderer: Rc<dyn GameRenderer<Context = Context>>,
 }
 
 impl<Context: RenderingContext> MissileTrailRenderer<Context> {
     pub fn new(renderer: Rc<dyn GameRenderer<Context = Context>>, color: Rgb) -> Result<Self, ()> {
-        let line = PolyLine::new(renderer.context().make_attribute_buffer()?, color);
+        let line = ThickLine::new(renderer.context(), color, TRAIL_STYLE)?;
         Ok(MissileTrailRenderer {
-            line: RefCell::new(line),
+            lines: RefCell::new(vec![line]),
+            color,
             data_version: Cell::new(0),
             renderer,
         })
     }
+
+    /// Returns the alpha of each point in a trail, fading out from the missile's position
+    fn trail_alphas(trail: &MissileTrail) -> Vec<f32> {
+        let newest = trail.positions().len().saturating_sub(1);
+        (0..trail.positions().len())
+            .map(|i| {
+                let fade = ((newest - i) as f32 / TRAIL_FADE_TICKS).min(1.0);
+                1.0 - fade * (1.0 - TRAIL_MIN_ALPHA)
+            })
+            .collect()
+    }
 }
 
 impl<Context: RenderingContext> EntityRenderer for MissileTrailRenderer<Context> {
     fn render(&self, entity: &Entity, world: &GameState) {
         if let Some(ref trail) = entity.missile_trail {
             if trail.data_version() != self.data_version.get() {
-                self.line.borrow_mut().set_positions(trail.positions());
+                let alphas = Self::trail_alphas(trail);
+                let mut lines = self.lines.borrow_mut();
+                let mut start = 0;
+                for (i, segment) in trail.segments().enumerate() {
+                    if i >= lines.len() {
+                        match ThickLine::new(self.renderer.context(), self.color, TRAIL_STYLE) {
+                            Ok(line) => lines.push(line),
+                            Err(_) => break,
+                        }
+                    }
+                    lines[i].set_vertices(segment, &alphas[start..start + segment.len()]);
+                    start += segment.len();
+                }
                 self.data_version.set(trail.data_version());
             }
 
-            let context = self.renderer.context();
-            let line_shader = self.renderer.line_shader();
-            let bound_shader = BoundLineShader::new(context, line_shader, world).unwrap();
+            let later_shots = world.shots_fired().saturating_sub(trail.shot + 1);
+            let opacity = TRAIL_SHOT_DIMMING
+                .powi(later_shots.min(i32::MAX as usize) as i32)
+                .max(TRAIL_MIN_OPACITY);
 
-            self.line.borrow().draw(&bound_shader);
+            let depth = view_depth(world, entity.position());
+            let queue = self.renderer.render_queue();
+            for line in self.lines.borrow_mut().iter_mut() {
+                line.opacity = opacity;
+                let command = DrawCommand::ThickLine(line.clone());
+                queue.submit(DrawItem::new(RenderPass::Transparent, depth, command));
+            }
+        }
+    }
+}
+
+// Number of line segments used to approximate each ring
+const RING_SEGMENTS: usize = 48;
+const RING_STYLE: LineStyle = LineStyle::solid(2.0);
+
+/// Draws a circle of a fixed radius around an entity
+#[derive(Debug)]
+pub struct RingRenderer<Context: RenderingContext> {
+    /// The ring around the origin, which is moved to each entity that it's drawn around
+    line: ThickLine<Context>,
+    renderer: Rc<dyn GameRenderer<Context = Context>>,
+}
+
+impl<Context: RenderingContext> RingRenderer<Context> {
+    pub fn new(
+        renderer: Rc<dyn GameRenderer<Context = Context>>,
+        radius: f32,
+        color: Rgb,
+    ) -> Result<Self, ()> {
+        let mut line = ThickLine::new(renderer.context(), color, RING_STYLE)?;
+        let points: Vec<Vector3<f32>> = (0..=RING_SEGMENTS)
+            .map(|i| {
+                let angle = 2.0 * std::f32::consts::PI * (i as f32) / (RING_SEGMENTS as f32);
+                Vector3::new(angle.cos(), angle.sin(), 0.0) * radius
+            })
+            .collect();
+        line.set_positions(&points);
+        Ok(RingRenderer { line, renderer })
+    }
+}
+
+impl<Context: RenderingContext> EntityRenderer for RingRenderer<Context> {
+    fn render(&self, entity: &Entity, world: &GameState) {
+        let center = *entity.position();
+        let mut line = self.line.clone();
+        line.model_transform = Matrix4::new_translation(&center);
+        let command = DrawCommand::ThickLine(line);
+        self.renderer.render_queue().submit(DrawItem::new(
+            RenderPass::Opaque,
+            view_depth(world, &center),
+            command,
+        ));
+    }
+}
+
+/// Renders an entity with several renderers in sequence
+#[derive(Debug)]
+pub struct CompositeRenderer {
+    renderers: Vec<Rc<dyn EntityRenderer>>,
+}
+
+impl CompositeRenderer {
+    pub fn new(renderers: Vec<Rc<dyn EntityRenderer>>) -> Self {
+        CompositeRenderer { renderers }
+    }
+}
+
+impl EntityRenderer for CompositeRenderer {
+    fn render(&self, entity: &Entity, world: &GameState) {
+        for renderer in self.renderers.iter() {
+            renderer.render(entity, world);
+        }
+    }
+}
+
+// Number of gravity samples along the visible height of the map when drawing contours
+const CONTOUR_SAMPLE_ROWS: f32 = 48.0;
+// Number of arrows along the visible height of the map when drawing the vector field
+const VECTOR_SAMPLE_ROWS: f32 = 20.0;
+// Number of equipotential lines to draw across the visible range of potentials
+const CONTOUR_LEVELS: usize = 16;
+// Length of the longest arrow, relative to the spacing between arrows
+const ARROW_LENGTH: f32 = 0.9;
+// Length of each side of an arrow head, relative to the arrow's length
+const ARROW_HEAD_SIZE: f32 = 0.3;
+const CONTOUR_COLOR: (f32, f32, f32) = (0.2, 0.4, 0.8);
+const VECTOR_COLOR: (f32, f32, f32) = (0.3, 0.8, 0.3);
+const OVERLAY_STYLE: LineStyle = LineStyle::solid(1.5);
+
+// The gravity version and visible bounds that the overlay's lines were built for
+type OverlayKey = (u64, Vector2<f32>, Vector2<f32>);
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GravityOverlayMode {
+    /// Lines of equal gravitational potential
+    Contours,
+    /// Arrows showing the direction and relative strength of gravity
+    Vectors,
+}
+
+/// Visualizes the gravitational field over the visible part of the map
+#[derive(Debug)]
+pub struct GravityOverlayRenderer<Context: RenderingContext> {
+    mode: GravityOverlayMode,
+    lines: RefCell<Vec<ThickLine<Context>>>,
+    num_lines: Cell<usize>,
+    sampled: Cell<Option<OverlayKey>>,
+    renderer: Rc<dyn GameRenderer<Context = Context>>,
+}
+
+impl<Context: RenderingContext> GravityOverlayRenderer<Context> {
+    pub fn new(
+        renderer: Rc<dyn GameRenderer<Context = Context>>,
+        mode: GravityOverlayMode,
+    ) -> Self {
+        GravityOverlayRenderer {
+            mode,
+            lines: RefCell::new(Vec::new()),
+            num_lines: Cell::new(0),
+            sampled: Cell::new(None),
+            renderer,
+        }
+    }
+
+    pub fn mode(&self) -> GravityOverlayMode {
+        self.mode
+    }
+
+    fn sample_field(
+        &self,
+        world: &GameState,
+        mins: Vector2<f32>,
+        maxs: Vector2<f32>,
+    ) -> GravityField {
+        let rows = match self.mode {
+            GravityOverlayMode::Contours => CONTOUR_SAMPLE_ROWS,
+            GravityOverlayMode::Vectors => VECTOR_SAMPLE_ROWS,
+        };
+        world.sample_gravity(mins, maxs, (maxs.y - mins.y) / rows)
+    }
+
+    fn contour_lines(field: &GravityField) -> Vec<Vec<Vector3<f32>>> {
+        let (min, max) = match field.potential_range() {
+            Some(range) => range,
+            None => return Vec::new(),
+        };
+        // Potential grows with the cube of distance, so space the levels evenly by its cube root.
+        let (min, max) = (min.cbrt(), max.cbrt());
+        let step = (max - min) / (CONTOUR_LEVELS + 1) as f32;
+        (1..=CONTOUR_LEVELS)
+            .flat_map(|i| field.contour((min + step * i as f32).powi(3)))
+            .map(|curve| curve.iter().map(|p| Vector3::new(p.x, p.y, 0.0)).collect())
+            .collect()
+    }
+
+    fn vector_lines(field: &GravityField) -> Vec<Vec<Vector3<f32>>> {
+        let max_acceleration = field.max_acceleration();
+        if max_acceleration <= 0.0 {
+            return Vec::new();
+        }
+        let mut lines = Vec::with_capacity(field.columns() * field.rows());
+        for row in 0..field.rows() {
+            for column in 0..field.columns() {
+                let acceleration = field.acceleration(column, row);
+                // Scale by the square root so weak regions are still visible.
+                let strength = (acceleration.magnitude() / max_acceleration).sqrt();
+                let direction = match acceleration.try_normalize(f32::EPSILON) {
+                    Some(direction) => direction,
+                    None => continue,
+                };
+                let length = strength * ARROW_LENGTH * field.spacing();
+                let center = field.position(column, row);
+                let tail = center - direction * length * 0.5;
+                let head = center + direction * length * 0.5;
+                let side = Vector2::new(-direction.y, direction.x);
+                let barb = length * ARROW_HEAD_SIZE;
+                let left = head - (direction - side) * barb;
+                let right = head - (direction + side) * barb;
+                lines.push(
+                    [tail, head, left, head, right]
+                        .iter()
+                        .map(|p| Vector3::new(p.x, p.y, 0.0))
+                        .collect(),
+                );
+            }
+        }
+        lines
+    }
+
+    fn update_lines(&self, field: &GravityField) {
+        let (points, color) = match self.mode {
+            GravityOverlayMode::Contours => (Self::contour_lines(field), CONTOUR_COLOR),
+            GravityOverlayMode::Vectors => (Self::vector_lines(field), VECTOR_COLOR),
+        };
+        let mut lines = self.lines.borrow_mut();
+        let mut num_lines = 0;
+        for line_points in points.iter() {
+            if num_lines >= lines.len() {
+                match ThickLine::new(self.renderer.context(), color.into(), OVERLAY_STYLE) {
+                    Ok(line) => lines.push(line),
+                    Err(_) => break,
+                }
+            }
+            lines[num_lines].set_positions(line_points);
+            num_lines += 1;
+        }
+        self.num_lines.set(num_lines);
+    }
+}
+
+impl<Context: RenderingContext> OverlayRenderer for GravityOverlayRenderer<Context> {
+    fn render(&self, world: &GameState) {
+        let (mins, maxs) = world.camera.visible_bounds();
+        let key = (world.gravity_version(), mins, maxs);
+        if self.sampled.get() != Some(key) {
+            self.update_lines(&self.sample_field(world, mins, maxs));
+            self.sampled.set(Some(key));
+        }
+
+        let queue = self.renderer.render_queue();
+        for line in self.lines.borrow().iter().take(self.num_lines.get()) {
+            let command = DrawCommand::ThickLine(line.clone());
+            queue.submit(DrawItem::new(RenderPass::Overlay, 0.0, command));
         }
     }
 }
@@ -81,8 +404,167 @@ pub trait GameRenderer: Debug {
 
     fn context(&self) -> &Self::Context;
     fn material_shader(&self) -> &MaterialShader<Self::Context>;
+    /// Returns the material shader for instanced draws, if the context supports them
+    fn instanced_material_shader(&self) -> Option<&MaterialShader<Self::Context>>;
     fn line_shader(&self) -> &LineShader<Self::Context>;
-    fn render(&self, state: &mut GameState) -> Result<(), Box<dyn Error>>;
+    fn thick_line_shader(&self) -> &ThickLineShader<Self::Context>;
+    fn particle_shader(&self) -> &ParticleShader<Self::Context>;
+    /// Returns the queue that renderers submit the frame's draw items to
+    fn render_queue(&self) -> &RenderQueue<Self::Context>;
+
+    /// Gets the drawing area ready for a frame, matching the camera's aspect ratio to it and
+    /// clearing it
+    fn begin_frame(&self, state: &mut GameState);
+
+    /// Finishes a frame once everything has been drawn
+    fn end_frame(&self) {}
+
+    /// Draws everything in the game
+    fn render(&self, state: &mut GameState) -> Result<(), Box<dyn Error>> {
+        self.begin_frame(state);
+        for entity in state.iter_entities() {
+            if let Some(ref renderer) = entity.renderer {
+                renderer.render(entity, state);
+            }
+        }
+        if let Some(ref particles) = state.particle_renderer {
+            particles.render(state);
+        }
+        if let Some(ref preview) = state.preview_renderer {
+            preview.render(state);
+        }
+        if let Some(ref overlay) = state.overlay {
+            overlay.render(state);
+        }
+        let result = self.draw_queue(state);
+        self.end_frame();
+        Ok(result?)
+    }
+
+    /// Sorts the draw items that have been submitted, then draws them
+    fn draw_queue(&self, world: &GameState) -> Result<(), ShaderBindError> {
+        let shaders = QueueShaders {
+            material: self.material_shader(),
+            instanced_material: self.instanced_material_shader(),
+            thick_line: self.thick_line_shader(),
+            particle: self.particle_shader(),
+        };
+        let queue = self.render_queue();
+        queue.sort();
+        queue.execute(self.context(), &shaders, world)
+    }
+}
+
+// Alpha of the end of the trajectory preview
+const PREVIEW_MIN_ALPHA: f32 = 0.1;
+const PREVIEW_STYLE: LineStyle = LineStyle::dashed(2.0, 8.0, 6.0);
+
+/// Draws the predicted path of the current player's aim, fading out toward its end
+#[derive(Debug)]
+pub struct TrajectoryPreviewRenderer<Context: RenderingContext> {
+    lines: RefCell<Vec<ThickLine<Context>>>,
+    num_lines: Cell<usize>,
+    // The path and color that the lines were built from
+    path: RefCell<Option<(Vec<Vector3<f32>>, Rgb)>>,
+    renderer: Rc<dyn GameRenderer<Context = Context>>,
+}
+
+impl<Context: RenderingContext> TrajectoryPreviewRenderer<Context> {
+    pub fn new(renderer: Rc<dyn GameRenderer<Context = Context>>) -> Self {
+        TrajectoryPreviewRenderer {
+            lines: RefCell::new(Vec::new()),
+            num_lines: Cell::new(0),
+            path: RefCell::new(None),
+            renderer,
+        }
+    }
+
+    fn update_lines(&self, trail: &MissileTrail, color: Rgb) {
+        let last = trail.positions().len().max(2) - 1;
+        let alphas: Vec<f32> = (0..trail.positions().len())
+            .map(|i| 1.0 - (i as f32 / last as f32) * (1.0 - PREVIEW_MIN_ALPHA))
+            .collect();
+        let mut lines = self.lines.borrow_mut();
+        let mut num_lines = 0;
+        let mut start = 0;
+        for segment in trail.segments() {
+            if num_lines >= lines.len() {
+                match ThickLine::new(self.renderer.context(), color, PREVIEW_STYLE) {
+                    Ok(line) => lines.push(line),
+                    Err(_) => break,
+                }
+            }
+            let line = &mut lines[num_lines];
+            line.color = color;
+            line.set_vertices(segment, &alphas[start..start + segment.len()]);
+            num_lines += 1;
+            start += segment.len();
+        }
+        self.num_lines.set(num_lines);
+    }
+}
+
+impl<Context: RenderingContext> OverlayRenderer for TrajectoryPreviewRenderer<Context> {
+    fn render(&self, world: &GameState) {
+        let trail = match world.trajectory_preview() {
+            Some(trail) => trail,
+            None => return,
+        };
+        let color = world
+            .players()
+            .get(trail.player_id)
+            .map(|p| p.color)
+            .unwrap_or_else(|| Rgb::new(1.0, 1.0, 1.0));
+        let changed = match *self.path.borrow() {
+            Some((ref positions, path_color)) => {
+                positions.as_slice() != trail.positions() || path_color != color
+            }
+            None => true,
+        };
+        if changed {
+            self.update_lines(trail, color);
+            *self.path.borrow_mut() = Some((trail.positions().to_vec(), color));
+        }
+
+        let queue = self.renderer.render_queue();
+        for line in self.lines.borrow().iter().take(self.num_lines.get()) {
+            let command = DrawCommand::ThickLine(line.clone());
+            queue.submit(DrawItem::new(RenderPass::Overlay, 0.0, command));
+        }
+    }
+}
+
+/// Draws the game's particles
+#[derive(Debug)]
+pub struct ParticleRenderer<Context: RenderingContext> {
+    buffer: RefCell<ParticleBuffer<Context>>,
+    renderer: Rc<dyn GameRenderer<Context = Context>>,
+}
+
+impl<Context: RenderingContext> ParticleRenderer<Context> {
+    pub fn new(renderer: Rc<dyn GameRenderer<Context = Context>>) -> Result<Self, ()> {
+        let buffer = ParticleBuffer::new(renderer.context())?;
+        Ok(ParticleRenderer {
+            buffer: RefCell::new(buffer),
+            renderer,
+        })
+    }
+}
+
+impl<Context: RenderingContext> OverlayRenderer for ParticleRenderer<Context> {
+    fn render(&self, world: &GameState) {
+        if world.particles.is_empty() {
+            return;
+        }
+        // Particles move every frame (between ticks), so the buffer is always refilled.
+        let mut buffer = self.buffer.borrow_mut();
+        buffer.set_particles(&world.particles);
+
+        let command = DrawCommand::Particles(buffer.clone());
+        self.renderer
+            .render_queue()
+            .submit(DrawItem::new(RenderPass::Overlay, 0.0, command));
+    }
 }
 
 impl MaterialWorldContext for GameState {
@@ -112,3 +594,44 @@ impl LineWorldContext for GameState {
         self.camera.view().into()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::glue::recording::game_renderer::RecordingRenderer;
+    use crate::glue::recording::{Command, RecordingContext};
+
+    fn count_uploads(context: &RecordingContext) -> usize {
+        context
+            .commands()
+            .iter()
+            .filter(|command| matches!(command, Command::SetBufferData { .. }))
+            .count()
+    }
+
+    #[test]
+    fn gravity_overlay_resamples_only_when_bodies_change() {
+        let context = Rc::new(RecordingContext::new(400, 300));
+        let renderer = Rc::new(RecordingRenderer::new(Rc::clone(&context)).unwrap());
+        let mut state = GameState::new(Box::new(|_| None));
+        let mut planet = Entity::new(Vector3::new(5.0, 0.0, 0.0));
+        planet.mass = 1000.0;
+        state.add_entity(planet);
+        let overlay = GravityOverlayRenderer::new(renderer.clone(), GravityOverlayMode::Vectors);
+        state.overlay = Some(Rc::new(overlay));
+
+        renderer.render(&mut state).unwrap();
+        assert!(count_uploads(&context) > 0);
+
+        context.clear_commands();
+        renderer.render(&mut state).unwrap();
+        assert_eq!(count_uploads(&context), 0);
+        assert!(!context.draw_calls().is_empty());
+
+        state.entities[0].transform.position = Vector3::new(-5.0, 0.0, 0.0);
+        state.rebuild_spatial_index();
+        context.clear_commands();
+        renderer.render(&mut state).unwrap();
+        assert!(count_uploads(&context) > 0);
+    }
+}
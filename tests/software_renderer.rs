@@ -0,0 +1,63 @@
+use std::rc::Rc;
+
+use image::RgbaImage;
+use nalgebra::Vector3;
+
+use gravity_wars::glue::software::game_renderer::SoftwareRenderer;
+use gravity_wars::glue::software::SoftwareContext;
+use gravity_wars::rendering::{Rgb, Rgba};
+use gravity_wars::state::mapgen::SphereRendererFactory;
+use gravity_wars::state::{Entity, GameState};
+use gravity_wars::state_renderer::{GameRenderer, RingRenderer};
+
+const SIZE: u32 = 80;
+
+// Renders a sphere at (-5, 0) and a ring at (5, 0), both with a radius of 3, in a view that spans
+// -10 to 10 on each axis
+fn render_scene() -> RgbaImage {
+    let context = Rc::new(SoftwareContext::new(SIZE, SIZE));
+    let renderer: Rc<dyn GameRenderer<Context = SoftwareContext>> =
+        Rc::new(SoftwareRenderer::new(Rc::clone(&context)).unwrap());
+    let mut state = GameState::new(Box::new(|_| None));
+    state.camera.log_scale = 1.0;
+
+    let spheres = SphereRendererFactory::new(&renderer).unwrap();
+    let mut sphere = Entity::new(Vector3::new(-5.0, 0.0, 0.0));
+    sphere.transform.scale = 3.0;
+    sphere.renderer = Some(spheres.make_renderer(Rgba::new(0.2, 0.4, 1.0, 1.0)));
+    state.add_entity(sphere);
+
+    let ring = RingRenderer::new(Rc::clone(&renderer), 3.0, Rgb::new(1.0, 0.0, 0.0)).unwrap();
+    let mut ringed = Entity::new(Vector3::new(5.0, 0.0, 0.0));
+    ringed.renderer = Some(Rc::new(ring));
+    state.add_entity(ringed);
+
+    renderer.render(&mut state).unwrap();
+    context.image()
+}
+
+// Converts a world position to the pixel that it lands on
+fn pixel(image: &RgbaImage, x: f32, y: f32) -> [u8; 4] {
+    let to_pixel = |v: f32| ((v + 10.0) / 20.0 * SIZE as f32) as u32;
+    image.get_pixel(to_pixel(x), SIZE - 1 - to_pixel(y)).0
+}
+
+#[test]
+fn renders_sphere() {
+    let image = render_scene();
+    let [r, g, b, _] = pixel(&image, -5.0, 0.0);
+    assert!(b > 0 && b > r && b >= g, "{:?}", (r, g, b));
+    assert_eq!(pixel(&image, -5.0, 4.0), [0, 0, 0, 255]);
+}
+
+#[test]
+fn renders_thick_line() {
+    let image = render_scene();
+    let [r, g, b, _] = pixel(&image, 8.0, 0.0);
+    assert!(r > 128 && g == 0 && b == 0, "{:?}", (r, g, b));
+    let [r, g, b, _] = pixel(&image, 5.0, -3.0);
+    assert!(r > 128 && g == 0 && b == 0, "{:?}", (r, g, b));
+    // The ring is hollow.
+    assert_eq!(pixel(&image, 5.0, 0.0), [0, 0, 0, 255]);
+    assert_eq!(pixel(&image, 0.0, 8.0), [0, 0, 0, 255]);
+}